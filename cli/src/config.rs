@@ -0,0 +1,37 @@
+use clap::Subcommand;
+use utils::error::Result;
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    #[command(about = "Print the JSON Schema of the config file")]
+    Schema {},
+    #[command(about = "Check the config file and report every problem in it")]
+    Validate {
+        #[arg(help = "config file path, defaults to the global --config", long)]
+        config: Option<String>,
+        #[arg(help = "Also report keys that oocana doesn't know", long)]
+        strict: bool,
+    },
+}
+
+pub fn config_action(action: &ConfigAction, default_config: &str) -> Result<()> {
+    match action {
+        ConfigAction::Schema {} => {
+            let schema = utils::config::json_schema();
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
+        ConfigAction::Validate { config, strict } => {
+            let config = config.as_deref().unwrap_or(default_config);
+            let problems = utils::config::validate_config(Some(config), *strict);
+            if problems.is_empty() {
+                println!("config {config} is valid");
+                return Ok(());
+            }
+            for problem in &problems {
+                eprintln!("{problem}");
+            }
+            Err(format!("config {config} has {} problem(s)", problems.len()).into())
+        }
+    }
+}
@@ -2,7 +2,13 @@ use super::parser;
 use layer::BindPath;
 use utils::config;
 
-use std::{env::temp_dir, io::BufRead, path::PathBuf};
+use std::{
+    collections::HashMap,
+    env::temp_dir,
+    io::BufRead,
+    path::{Path, PathBuf},
+};
+use utils::error::Result;
 
 fn get_env_file() -> Option<String> {
     std::env::var("OOCANA_ENV_FILE").ok()
@@ -12,6 +18,11 @@ pub fn temp_root() -> String {
     std::env::var("OOCANA_TEMP_ROOT").unwrap_or_else(|_| temp_dir().to_string_lossy().to_string())
 }
 
+/// Value parser of session id args, rejects ids that aren't safe as a directory name.
+pub fn parse_session_id(id: &str) -> Result<String> {
+    id.parse::<job::SessionId>().map(String::from)
+}
+
 pub fn config() -> String {
     std::env::var("OOCANA_CONFIG").unwrap_or_else(|_| "~/.oocana/config".to_string())
 }
@@ -35,6 +46,36 @@ pub fn find_env_file(file: &Option<String>) -> Option<String> {
     None
 }
 
+/// Parse `--env KEY=VALUE` entries. A later entry for the same key wins.
+pub fn parse_env_vars(entries: &[String]) -> Result<HashMap<String, String>> {
+    entries
+        .iter()
+        .map(|entry| match entry.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+            _ => Err(format!("Invalid --env entry {entry:?}, expected KEY=VALUE").into()),
+        })
+        .collect()
+}
+
+/// Read an `--inputs-file` into the JSON string `--inputs` takes. YAML is picked by the `.yaml`/`.yml` extension.
+pub fn load_inputs_file(file: &str) -> Result<String> {
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read inputs file {file}: {e}"))?;
+
+    let is_yaml = Path::new(file)
+        .extension()
+        .is_some_and(|ext| ext == "yaml" || ext == "yml");
+    let value: serde_json::Value = if is_yaml {
+        serde_yaml::from_str(&content)
+            .map_err(|e| format!("Invalid YAML in inputs file {file}: {e}"))?
+    } else {
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid JSON in inputs file {file}: {e}"))?
+    };
+
+    Ok(value.to_string())
+}
+
 fn find_bind_path_file() -> Option<String> {
     std::env::var("OOCANA_BIND_PATH_FILE").ok()
 }
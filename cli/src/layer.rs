@@ -1,5 +1,7 @@
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::fun::arg::parse_search_paths;
 
 use crate::fun::arg::{find_env_file, load_bind_paths};
 use clap::Subcommand;
@@ -38,6 +40,16 @@ pub enum LayerAction {
             long
         )]
         env_file: Option<String>,
+        #[arg(
+            help = "lockfile path, pin the dependency set installed by the package bootstrap script. the lockfile is bound read-only into the layer and its path is passed to bootstrap as OOCANA_LOCKFILE env variable",
+            long
+        )]
+        lockfile: Option<String>,
+        #[arg(
+            help = "OCI image reference, the package layer is built on top of the image's root filesystem. The image is pulled with docker and its digest is recorded in the layer, a layer built from another digest is recreated.",
+            long
+        )]
+        from_image: Option<String>,
     },
     #[command(about = "create external package layer")]
     CreateExternal {
@@ -132,6 +144,85 @@ pub enum LayerAction {
     List {},
     #[command(about = "delete all package layer")]
     DeleteAll {},
+    #[command(about = "delete package layers that none of the given flows use")]
+    Prune {
+        #[arg(
+            help = "flow whose packages are in use, accept multiple input. example: --flow <flow_path> --flow <flow_path>",
+            long = "flow",
+            required = true
+        )]
+        flows: Vec<String>,
+        #[arg(
+            help = "Paths to search for blocks. Repeat the flag or use commas. Fallback to config.",
+            long,
+            alias = "block-search-paths",
+            value_delimiter = ','
+        )]
+        search_paths: Vec<String>,
+        #[arg(
+            help = "package paths that are never pruned, accept multiple input. Fallback to config run.exclude_packages.",
+            long
+        )]
+        exclude_packages: Vec<String>,
+        #[arg(help = "only print the package layers that would be deleted", long)]
+        dry_run: bool,
+        #[arg(
+            help = "only prune layers created more than this many days ago. layers without a creation time are kept",
+            long
+        )]
+        older_than_days: Option<u64>,
+    },
+}
+
+/// Packages `flows` use, subflows and slots included, as canonical paths.
+pub(crate) fn packages_in_use(
+    flows: &[String],
+    search_paths: Option<Vec<PathBuf>>,
+) -> Result<HashSet<PathBuf>> {
+    let mut in_use = HashSet::new();
+    for flow in flows {
+        let packages = runtime::get_packages_recursive(runtime::GetPackageArgs {
+            block: flow,
+            block_reader: manifest_meta::BlockResolver::new(),
+            path_finder: manifest_reader::path_finder::BlockPathFinder::new(
+                std::env::current_dir()?,
+                search_paths.clone(),
+            ),
+            nodes: None,
+        })?;
+        in_use.extend(packages.into_keys().map(|package| canonical(&package)));
+    }
+    Ok(in_use)
+}
+
+/// Layers and flows may refer to a package by different paths, compare the canonical ones.
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Package layers to delete in a prune: not used by any flow, not excluded and old enough.
+/// `in_use` and `excluded` are canonical paths.
+pub(crate) fn prunable_package_layers(
+    layers: &[layer::PackageLayerInfo],
+    in_use: &HashSet<PathBuf>,
+    excluded: &HashSet<PathBuf>,
+    older_than_secs: Option<u64>,
+    now_secs: u64,
+) -> Vec<PathBuf> {
+    layers
+        .iter()
+        .filter(|info| {
+            let path = canonical(&info.path);
+            !in_use.contains(&path) && !excluded.contains(&path)
+        })
+        .filter(|info| {
+            older_than_secs.is_none_or(|older_than| {
+                info.created_at
+                    .is_some_and(|created_at| now_secs.saturating_sub(created_at) >= older_than)
+            })
+        })
+        .map(|info| info.path.clone())
+        .collect()
 }
 
 /// Get layer status, checking external layer store first and falling back to package layer store.
@@ -237,6 +328,8 @@ pub fn layer_action(action: &LayerAction) -> Result<()> {
             bind_path_file,
             retain_env_keys,
             env_file,
+            lockfile,
+            from_image,
         } => {
             if let Some(reason) = package_layer_skip_reason(std::path::Path::new(package)) {
                 return Err(Error::from(format!(
@@ -255,7 +348,31 @@ pub fn layer_action(action: &LayerAction) -> Result<()> {
 
             let env_file = find_env_file(env_file);
 
-            layer::get_or_create_package_layer(package, &bind_path_arg, &envs, &env_file)?;
+            match (from_image, lockfile) {
+                (Some(image), lockfile) => {
+                    layer::create_package_layer_from_image(
+                        package,
+                        &layer::DockerImageSource,
+                        image,
+                        lockfile.as_deref().map(std::path::Path::new),
+                        &bind_path_arg,
+                        &envs,
+                        &env_file,
+                    )?;
+                }
+                (None, Some(lockfile)) => {
+                    layer::create_package_layer_from_lockfile(
+                        package,
+                        lockfile,
+                        &bind_path_arg,
+                        &envs,
+                        &env_file,
+                    )?;
+                }
+                (None, None) => {
+                    layer::get_or_create_package_layer(package, &bind_path_arg, &envs, &env_file)?;
+                }
+            }
         }
         LayerAction::CreateExternal {
             package_name,
@@ -475,6 +592,45 @@ pub fn layer_action(action: &LayerAction) -> Result<()> {
             );
             layer::delete_all_layer_data()?;
         }
+        LayerAction::Prune {
+            flows,
+            search_paths,
+            exclude_packages,
+            dry_run,
+            older_than_days,
+        } => {
+            let in_use = packages_in_use(flows, parse_search_paths(search_paths))?;
+
+            let excluded: HashSet<PathBuf> = if exclude_packages.is_empty() {
+                utils::config::exclude_packages().unwrap_or_default()
+            } else {
+                exclude_packages.to_owned()
+            }
+            .into_iter()
+            .map(|package| canonical(Path::new(&package)))
+            .collect();
+
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let prunable = prunable_package_layers(
+                &layer::list_package_layers_detailed()?,
+                &in_use,
+                &excluded,
+                older_than_days.map(|days| days * 24 * 60 * 60),
+                now,
+            );
+
+            for package in prunable {
+                if *dry_run {
+                    println!("would prune: {package:?}");
+                } else {
+                    info!("prune package layer {package:?}");
+                    layer::delete_package_layer(&package)?;
+                    println!("pruned: {package:?}");
+                }
+            }
+        }
     }
 
     Ok(())
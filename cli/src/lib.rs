@@ -1,10 +1,17 @@
 mod cache;
+mod config;
 mod fun;
 mod layer;
 mod query;
+mod validate;
 
 use cache::CacheAction;
-use fun::arg::{config, find_env_file, load_bind_paths, parse_search_paths, temp_root};
+use fun::arg::{
+    config, find_env_file, load_bind_paths, load_inputs_file, parse_env_vars, parse_search_paths,
+    parse_session_id, temp_root,
+};
+use manifest_meta::BlockResolver;
+use manifest_reader::path_finder::BlockPathFinder;
 use one_shot::one_shot::{BlockArgs, run_block};
 use std::{collections::HashSet, path::PathBuf};
 
@@ -31,6 +38,12 @@ pub struct Cli {
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// One JSON line per output on stdout.
+    JsonlStream,
+}
+
 #[derive(Subcommand, Debug)]
 // TODO: consider boxing large fields to reduce enum size
 #[allow(clippy::large_enum_variant)]
@@ -50,6 +63,12 @@ enum Commands {
             long
         )]
         broker: Option<String>,
+        #[arg(
+            help = "Retry connecting to the broker this many times when it isn't up yet, waiting 0.5s and doubling the wait after every attempt. Only the first connection is retried. Default is 5.",
+            long,
+            default_value_t = 5
+        )]
+        broker_connect_retries: u32,
         #[arg(
             help = "Paths to search for packages. Repeat the flag or use commas. Fallback to config/current flow block.",
             long,
@@ -57,7 +76,7 @@ enum Commands {
             value_delimiter = ','
         )]
         search_paths: Vec<String>,
-        #[arg(help = "id to mark this execution session. If not provided, a UUID will be randomly generated different value as the default value for that run.", long, default_value_t = Uuid::new_v4().to_string())]
+        #[arg(help = "id to mark this execution session. If not provided, a UUID will be randomly generated different value as the default value for that run.", long, default_value_t = Uuid::new_v4().to_string(), value_parser = parse_session_id)]
         session: String,
         #[arg(help = "Enable reporter.", long, num_args =0..=1, require_equals=true, default_missing_value = "true")]
         reporter: Option<bool>,
@@ -75,6 +94,11 @@ enum Commands {
         wait_for_client: bool,
         #[arg(help = "Use previous result cache if exist.", long)]
         use_cache: bool,
+        #[arg(
+            help = "Report the cached outputs of nodes skipped for the cache as BlockOutput messages marked from_cache, so a fresh consumer sees every output.",
+            long
+        )]
+        replay_cached_outputs: bool,
         #[arg(
             help = "Stop the flow after the listed nodes are finished. Repeat the flag or use commas.",
             long,
@@ -82,10 +106,47 @@ enum Commands {
         )]
         nodes: Vec<String>,
         #[arg(
-            help = "Values for the input handles value. It's used to fulfill a block's inputs definition. Format is {\"inputHandleName\": <VALUE>} where the first key is the handle name, and the first-level value is a key-value pair.",
+            help = "Run the nodes whose id matches this regex, e.g. '^extract_', along with their upstream nodes like --nodes. Combines with --nodes.",
+            long
+        )]
+        node_filter_regex: Option<String>,
+        #[arg(
+            help = "Record the inputs the node with this id runs with to <session dir>/<node>.record.json, the last run wins. Replay it with --replay-block.",
+            long,
+            value_name = "NODE"
+        )]
+        record_block: Option<String>,
+        #[arg(
+            help = "Run only the block of the node recorded in this file by --record-block, with the recorded inputs. The flow must still contain the node, only task nodes can be replayed.",
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["inputs", "inputs_file", "nodes", "node_filter_regex"]
+        )]
+        replay_block: Option<String>,
+        #[arg(
+            help = "Resume the interrupted session with this id: the flow nodes it completed are skipped and their outputs come from the values it saved. A flow changed since then runs from the start with a warning.",
+            long,
+            value_name = "SESSION_ID",
+            value_parser = parse_session_id,
+            conflicts_with_all = ["nodes", "node_filter_regex", "replay_block"]
+        )]
+        resume: Option<String>,
+        #[arg(
+            help = "Record the flow nodes this session completes, so --resume can continue it once it's interrupted. A resumed session records its progress too. Progress of sessions older than a week is removed.",
+            long
+        )]
+        resumable: bool,
+        #[arg(
+            help = "Values for the input handles value. It's used to fulfill a block's inputs definition. Format is {\"inputHandleName\": <VALUE>} where the first key is the handle name, and the first-level value is a key-value pair. A value {\"$ref\": \"file.json#/a/b\"} is replaced by the value at JSON pointer /a/b in file.json.",
             long
         )]
         inputs: Option<String>,
+        #[arg(
+            help = "Read the --inputs value from a .json, .yaml or .yml file instead.",
+            long,
+            conflicts_with = "inputs"
+        )]
+        inputs_file: Option<String>,
         #[arg(
             help = "Values for the flow nodes' input handle value. It's used when a block has flow node inputs. Format is {\"node_id\": {\"inputHandleName\": <VALUE>}}. First key is node id, the first level value is a key-value pair, and the next level's value is input values",
             long
@@ -125,12 +186,23 @@ enum Commands {
         )]
         env_file: Option<String>,
         #[arg(
-            help = "bind paths, format src=<source_path>,dst=<target_path>,[ro|rw],[recursive|nonrecursive] (rw,nonrecursive is default value), accept multiple input. example: --bind-paths src=<source_path>,dst=<target_path>,rw/ro,recursive/nonrecursive --bind-paths src=<source_path>,dst=<target_path>,rw/ro,recursive/nonrecursive",
+            help = "Set an env variable on every executor as KEY=VALUE, e.g. --env DEBUG=1. Wins over the same key in the env file and in retained env variables. Repeat the flag for more variables.",
+            long = "env",
+            value_name = "KEY=VALUE"
+        )]
+        env: Vec<String>,
+        #[arg(
+            help = "Pass env file values to executors in a JSON file only the current user can read, deleted when the session ends. Executors get the file path in OOCANA_SECRETS_FILE instead of the values in their environment.",
+            long
+        )]
+        secrets_via_file: bool,
+        #[arg(
+            help = "bind paths, format src=<source_path>,dst=<target_path>,[ro|rw],[recursive|nonrecursive],[exclude=<pattern>:<pattern>] (rw,nonrecursive is default value, exclude leaves matching subpaths like node_modules out of the bind), or type=tmpfs,dst=<target_path>,[size=<size>] for in-memory scratch space, accept multiple input. example: --bind-paths src=<source_path>,dst=<target_path>,rw/ro,recursive/nonrecursive --bind-paths src=<source_path>,dst=<target_path>,rw/ro,recursive/nonrecursive",
             long
         )]
         bind_paths: Option<Vec<String>>,
         #[arg(
-            help = "a file path contains multiple bind paths. The file format is src=<source_path>,dst=<target_path>,[ro|rw],[recursive|nonrecursive] (rw,nonrecursive is default value) line by line, if not provided, it will be found in OOCANA_BIND_PATH_FILE env variable",
+            help = "a file path contains multiple bind paths. The file format is src=<source_path>,dst=<target_path>,[ro|rw],[recursive|nonrecursive],[exclude=<pattern>:<pattern>] (rw,nonrecursive is default value) line by line, if not provided, it will be found in OOCANA_BIND_PATH_FILE env variable",
             long
         )]
         bind_path_file: Option<String>,
@@ -139,6 +211,16 @@ enum Commands {
             long
         )]
         dry_run: bool,
+        #[arg(
+            help = "Resolve every node's block without running the flow, print the resolved manifest path, package name, version and layer status of each node as JSON, then exit.",
+            long
+        )]
+        resolve_only: bool,
+        #[arg(
+            help = "Write the resolved injection store of the flow and its subflows as JSON to this path before running, also with --dry-run or --resolve-only: the packages nodes are injected into, with their version, the injected nodes' absolute and relative entries and the injection scripts.",
+            long
+        )]
+        dump_injection_store: Option<String>,
         #[arg(help = "If true, oocana will forward report messages to console", long)]
         report_to_console: bool,
         #[arg(
@@ -156,11 +238,163 @@ enum Commands {
             long
         )]
         remote_block_timeout: Option<u64>,
+        #[arg(
+            help = "Max bytes kept for a single executor stdout/stderr line, longer lines are truncated in logs. Default is 65536 (64KB).",
+            long
+        )]
+        max_log_line_bytes: Option<usize>,
+        #[arg(
+            help = "Max stdout/stderr bytes kept per executor in total, later lines are dropped from logs. No cap by default.",
+            long
+        )]
+        max_executor_log_bytes: Option<usize>,
+        #[arg(
+            help = "Also write each executor's stdout/stderr to its own <executor>-<identifier>.log file in the session log dir.",
+            long
+        )]
+        per_executor_logs: bool,
+        #[arg(
+            help = "Also report executor stdout/stderr lines as ExecutorLog messages. Off by default, blocks already report their logs through the SDK.",
+            long
+        )]
+        report_executor_logs: bool,
+        #[arg(
+            help = "Append every reporter message as a JSON line to events.jsonl in the session log dir. Works with or without --reporter, the broker still gets the messages when it is enabled.",
+            long
+        )]
+        report_to_file: bool,
+        #[arg(
+            help = "Max number of blocks running at the same time across the whole flow, including subflows. Extra ready nodes wait in queue. Unlimited by default.",
+            long,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        max_concurrency: Option<u32>,
+        #[arg(
+            help = "Max nesting depth of jobs. A job nested this deep fails, a subflow with the chain of flows that led there, e.g. a subflow that runs itself forever. Default is 50.",
+            long,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        max_flow_depth: Option<u32>,
+        #[arg(
+            help = "Max number of sessions running at the same time on this host, counted across oocana processes. A session beyond it is rejected as busy. Unlimited by default.",
+            long,
+            value_parser = clap::value_parser!(u32).range(1..)
+        )]
+        max_concurrent_sessions: Option<u32>,
+        #[arg(
+            help = "Milliseconds to keep delivering outputs that blocks already emitted after the session is cancelled by SIGINT/SIGTERM, before the blocks are torn down. Running blocks get a soft cancel and can still finish in this window, no new block starts. Disabled by default.",
+            long
+        )]
+        abort_grace_outputs: Option<u64>,
+        #[arg(
+            help = "Write the block's outputs to this file as a JSON object when the session finishes.",
+            long
+        )]
+        output_file: Option<String>,
+        #[arg(
+            help = "Only capture these output handles in --output-file. Repeat the flag or use commas. Downstream nodes still receive every output.",
+            long,
+            value_delimiter = ','
+        )]
+        output_only: Vec<String>,
+        #[arg(
+            help = "jsonl-stream writes every output of the block to stdout as a JSON line as soon as it's produced, for piping into another tool. Console logs go to stderr then. The stream ends when oocana exits.",
+            long
+        )]
+        output_format: Option<OutputFormat>,
+        #[arg(
+            help = "Mount package data directories read-only for blocks running in a package layer. Ignored when the layer feature is disabled.",
+            long
+        )]
+        readonly_package_data: bool,
+        #[arg(
+            help = "Fail before running when a connection joins output and input handles whose json_schema types are incompatible. Handles without a type accept anything.",
+            long
+        )]
+        strict_handle_types: bool,
+        #[arg(
+            help = "Resolve connections whose node ids or handle names only match ignoring case, warning about each of them. Without it such connections are dropped.",
+            long
+        )]
+        case_insensitive_handles: bool,
+        #[arg(
+            help = "On SIGTERM, stop starting new nodes and give running blocks this many seconds to finish before cancelling. Without it SIGTERM cancels right away, SIGINT always does.",
+            long
+        )]
+        shutdown_grace_secs: Option<u64>,
+        #[arg(
+            help = "Save the node input values of running flows to their cache every this many seconds, so a crashed run can go on from the last checkpoint with --use-cache.",
+            long,
+            value_name = "SECS",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        checkpoint_interval: Option<u64>,
+        #[arg(
+            help = "Seconds an executor process has to start before it's treated as timed out. Default is 5.",
+            long
+        )]
+        executor_spawn_timeout_secs: Option<u64>,
+        #[arg(
+            help = "Seconds a started executor has to report ready, counted from its spawn, before it's treated as failed to initialize. The error has the last lines of its stderr. Default is 30.",
+            long
+        )]
+        executor_ready_timeout_secs: Option<u64>,
+        #[arg(
+            help = "Seconds a block waits for an executor to pick it up before it's sent again. Default is 3.",
+            long
+        )]
+        listener_timeout_secs: Option<u64>,
+        #[arg(
+            help = "Store and recover flow caches under this directory instead of the configured cache_root (oocana_dir by default).",
+            long
+        )]
+        cache_root: Option<String>,
+        #[arg(
+            help = "Restart an executor that crashes (non-zero exit) up to this many times and send its running blocks again. Default is 0, no restart.",
+            long,
+            default_value_t = 0
+        )]
+        executor_restart_attempts: u32,
+        #[arg(
+            help = "Fail when a node input is wired (by value or connection) to a handle its block doesn't define. Without it such inputs are ignored with a warning.",
+            long
+        )]
+        fail_on_unused_inputs: bool,
+        #[arg(
+            help = "Fail the session when an input of the block has no value, no default, isn't nullable and (for a flow) is connected to a node. Without it the block runs and the input stays unset.",
+            long
+        )]
+        inputs_required_check: bool,
+        #[arg(
+            help = "Also write warnings (missing inputs, nodes that won't run, ...) to this file, one JSON object per line with the message, target and structured fields.",
+            long
+        )]
+        warn_as_json: Option<String>,
+        #[arg(
+            help = "Reuse the outputs of task blocks that ran before with the same block, entry file, executor and inputs instead of running them again. Entries are stored under pkg-data-root and removed after 7 days without use. Blocks with `cacheable: false` always run.",
+            long
+        )]
+        output_cache: bool,
+        #[arg(
+            help = "Extra argument appended to every executor command, e.g. --executor-arg=--plugin-dir=/path. Repeat the flag for more arguments.",
+            long,
+            allow_hyphen_values = true
+        )]
+        executor_arg: Vec<String>,
+        #[arg(
+            help = "Cap the total run time of a node id across the session as NODE=DURATION, e.g. fetch=120s. Durations take ms/s/m/h, plain numbers are seconds. Once spent, further runs of the node are deferred. Repeat the flag for more nodes.",
+            long
+        )]
+        time_budget: Vec<String>,
     },
     Cache {
         #[command(subcommand)]
         action: CacheAction,
     },
+    Config {
+        #[command(subcommand)]
+        action: config::ConfigAction,
+    },
     Query {
         #[command(subcommand)]
         action: query::QueryAction,
@@ -174,6 +408,22 @@ enum Commands {
         #[command(subcommand)]
         action: layer::LayerAction,
     },
+    #[command(about = "Check a flow's connections and blocks without running it")]
+    Validate {
+        #[arg(
+            help = "Absolute Path to the Oocana Block Manifest file or a directory with flow.oo.yaml."
+        )]
+        block: String,
+        #[arg(
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
+            long,
+            alias = "block-search-paths",
+            value_delimiter = ','
+        )]
+        search_paths: Vec<String>,
+        #[arg(help = "Print the report as JSON.", long)]
+        json: bool,
+    },
 }
 
 pub fn cli_match() -> Result<()> {
@@ -186,12 +436,17 @@ pub fn cli_match() -> Result<()> {
             session,
             verbose,
             report_to_console,
+            warn_as_json,
+            output_format,
             ..
         } => utils::logger::setup_logging(LogParams {
-            sub_dir: Some(format!("sessions/{session}")),
+            sub_dir: Some(utils::logger::session_sub_dir(session)),
             log_name: "oocana",
             output_to_console: *verbose,
             capture_stdout_stderr_target: *report_to_console,
+            // stdout only carries the outputs
+            console_to_stderr: *output_format == Some(OutputFormat::JsonlStream),
+            warn_json: warn_as_json.as_ref().map(PathBuf::from),
         })?,
         Commands::PackageLayer { action } => {
             utils::logger::setup_logging({
@@ -209,6 +464,8 @@ pub fn cli_match() -> Result<()> {
                         layer::LayerAction::Create { .. }
                             | layer::LayerAction::CreateExternal { .. }
                     ),
+                    console_to_stderr: false,
+                    warn_json: None,
                 }
             })?
         }
@@ -217,13 +474,19 @@ pub fn cli_match() -> Result<()> {
                 sub_dir: Some("query"),
                 log_name: match action {
                     query::QueryAction::Upstream { .. } => "upstream",
+                    query::QueryAction::Plan { .. } => "plan",
                     query::QueryAction::Service { .. } => "service",
                     query::QueryAction::Package { .. } => "package",
                     query::QueryAction::NodesInputs { .. } => "nodes-inputs",
                     query::QueryAction::Inputs { .. } => "inputs",
+                    query::QueryAction::Graph { .. } => "graph",
+                    query::QueryAction::BlockSchema { .. } => "block-schema",
+                    query::QueryAction::Timings { .. } => "timings",
                 },
                 output_to_console: false,
                 capture_stdout_stderr_target: false,
+                console_to_stderr: false,
+                warn_json: None,
             }
         })?,
         Commands::Cache { .. } => utils::logger::setup_logging({
@@ -232,10 +495,37 @@ pub fn cli_match() -> Result<()> {
                 log_name: "action",
                 output_to_console: false,
                 capture_stdout_stderr_target: false,
+                console_to_stderr: false,
+                warn_json: None,
+            }
+        })?,
+        Commands::Config { .. } => utils::logger::setup_logging({
+            LogParams {
+                sub_dir: Some("config-action"),
+                log_name: "action",
+                output_to_console: false,
+                capture_stdout_stderr_target: false,
+                console_to_stderr: false,
+                warn_json: None,
+            }
+        })?,
+        Commands::Validate { .. } => utils::logger::setup_logging({
+            LogParams {
+                sub_dir: Some("validate"),
+                log_name: "validate",
+                output_to_console: false,
+                capture_stdout_stderr_target: false,
+                console_to_stderr: false,
+                warn_json: None,
             }
         })?,
     };
 
+    // validate has to run on a config that fails to load
+    if let Commands::Config { action } = command {
+        return config::config_action(action, &cli.config);
+    }
+
     let app_config = utils::config::load_config(Some(&cli.config))?;
     debug!(
         "config {:?} command args: {command:#?} in version: {VERSION}",
@@ -245,35 +535,101 @@ pub fn cli_match() -> Result<()> {
         Commands::Run {
             block,
             broker,
+            broker_connect_retries,
             search_paths,
             session,
             reporter,
             debug,
             wait_for_client,
             use_cache,
+            replay_cached_outputs,
             nodes,
+            node_filter_regex,
+            record_block,
+            replay_block,
+            resume,
+            resumable,
             nodes_inputs,
             inputs,
+            inputs_file,
             exclude_packages,
             default_package,
             bind_paths,
             session_dir: session_path,
             retain_env_keys,
             env_file,
+            env,
+            secrets_via_file,
             bind_path_file,
             verbose: _verbose,
             temp_root,
             dry_run,
+            resolve_only,
+            dump_injection_store,
             pkg_data_root,
             project_data,
             report_to_console,
             remote_block_url,
             connector_base_url,
             remote_block_timeout,
+            max_log_line_bytes,
+            max_executor_log_bytes,
+            per_executor_logs,
+            report_executor_logs,
+            report_to_file,
+            max_concurrency,
+            max_flow_depth,
+            max_concurrent_sessions,
+            abort_grace_outputs,
+            output_file,
+            output_only,
+            output_format,
+            readonly_package_data,
+            strict_handle_types,
+            case_insensitive_handles,
+            shutdown_grace_secs,
+            checkpoint_interval,
+            executor_spawn_timeout_secs,
+            executor_ready_timeout_secs,
+            listener_timeout_secs,
+            cache_root,
+            executor_restart_attempts,
+            fail_on_unused_inputs,
+            inputs_required_check,
+            warn_as_json: _,
+            output_cache,
+            executor_arg,
+            time_budget,
         } => {
             let bind_paths = load_bind_paths(bind_paths, bind_path_file);
             let search_paths = parse_search_paths(search_paths);
             let env_file = find_env_file(env_file);
+            let env_vars = parse_env_vars(env)?;
+            let inputs = match inputs_file {
+                Some(file) => Some(load_inputs_file(file)?),
+                None => inputs.to_owned(),
+            };
+            if let Some(cache_root) = cache_root {
+                utils::config::set_cache_root(cache_root);
+            }
+
+            if let Some(path) = dump_injection_store {
+                // resolved the way the run resolves the flow
+                let injections = runtime::resolve::resolve_injections(runtime::GetPackageArgs {
+                    block,
+                    block_reader: BlockResolver::new()
+                        .with_strict_handle_types(*strict_handle_types)
+                        .with_fail_on_unused_inputs(*fail_on_unused_inputs)
+                        .with_case_insensitive_handles(*case_insensitive_handles),
+                    path_finder: BlockPathFinder::new(
+                        std::env::current_dir()?,
+                        search_paths.clone(),
+                    ),
+                    nodes: (!nodes.is_empty()).then(|| nodes.iter().cloned().collect()),
+                })?;
+                std::fs::write(path, serde_json::to_string_pretty(&injections)?)?;
+                tracing::info!("injection store written to {path}");
+            }
 
             if *dry_run {
                 // print the parameters
@@ -282,6 +638,16 @@ pub fn cli_match() -> Result<()> {
 
                 println!("dry_run is enabled, exiting without execution.");
 
+                return Ok(());
+            } else if *resolve_only {
+                let resolved = runtime::resolve::resolve_nodes(runtime::GetPackageArgs {
+                    block,
+                    block_reader: BlockResolver::new(),
+                    path_finder: BlockPathFinder::new(std::env::current_dir()?, search_paths),
+                    nodes: (!nodes.is_empty()).then(|| nodes.iter().cloned().collect()),
+                })?;
+                println!("{}", serde_json::to_string(&resolved)?);
+
                 return Ok(());
             } else {
                 tracing::debug!(
@@ -294,14 +660,21 @@ pub fn cli_match() -> Result<()> {
             run_block(BlockArgs {
                 block_path: block,
                 broker_address: broker.clone().unwrap_or(app_config.run.broker),
+                broker_connect_retries: broker_connect_retries.to_owned(),
                 search_paths,
                 session: session.to_owned(),
                 reporter_enable: reporter.unwrap_or(app_config.run.reporter.unwrap_or_default()),
                 debug: debug.unwrap_or(app_config.run.debug.unwrap_or_default()),
                 wait_for_client: wait_for_client.to_owned(),
                 use_cache: use_cache.to_owned(),
+                replay_cached_outputs: replay_cached_outputs.to_owned(),
                 nodes: (!nodes.is_empty()).then(|| nodes.iter().cloned().collect::<HashSet<_>>()),
-                inputs: inputs.to_owned(),
+                node_filter_regex: node_filter_regex.to_owned(),
+                record_block: record_block.to_owned(),
+                replay_block: replay_block.to_owned(),
+                resume: resume.to_owned(),
+                resumable: resumable.to_owned(),
+                inputs,
                 nodes_inputs: nodes_inputs.to_owned(),
                 default_package: default_package.to_owned(),
                 exclude_packages: (!exclude_packages.is_empty())
@@ -312,6 +685,8 @@ pub fn cli_match() -> Result<()> {
                 retain_env_keys: (!retain_env_keys.is_empty())
                     .then_some(retain_env_keys.to_owned()),
                 env_file,
+                env_vars,
+                secrets_via_file: secrets_via_file.to_owned(),
                 temp_root: temp_root.to_owned(),
                 project_data: &PathBuf::from(project_data),
                 pkg_data_root: &PathBuf::from(pkg_data_root),
@@ -319,17 +694,54 @@ pub fn cli_match() -> Result<()> {
                 remote_block_url: remote_block_url.to_owned(),
                 connector_base_url: connector_base_url.to_owned(),
                 remote_block_timeout: remote_block_timeout.to_owned(),
+                max_log_line_bytes: max_log_line_bytes.to_owned(),
+                max_executor_log_bytes: max_executor_log_bytes.to_owned(),
+                per_executor_logs: per_executor_logs.to_owned(),
+                report_executor_logs: report_executor_logs.to_owned(),
+                report_to_file: report_to_file.to_owned(),
+                max_concurrency: max_concurrency.to_owned(),
+                max_flow_depth: max_flow_depth.to_owned(),
+                max_concurrent_sessions: max_concurrent_sessions.to_owned(),
+                abort_grace_outputs: abort_grace_outputs.to_owned(),
+                output_file: output_file.to_owned(),
+                output_only: (!output_only.is_empty())
+                    .then(|| output_only.iter().cloned().collect::<HashSet<_>>()),
+                output_stream: *output_format == Some(OutputFormat::JsonlStream),
+                readonly_package_data: readonly_package_data.to_owned(),
+                strict_handle_types: strict_handle_types.to_owned(),
+                case_insensitive_handles: case_insensitive_handles.to_owned(),
+                shutdown_grace_secs: shutdown_grace_secs.to_owned(),
+                checkpoint_interval_secs: checkpoint_interval.to_owned(),
+                executor_spawn_timeout_secs: executor_spawn_timeout_secs.to_owned(),
+                executor_ready_timeout_secs: executor_ready_timeout_secs.to_owned(),
+                listener_timeout_secs: listener_timeout_secs.to_owned(),
+                executor_restart_attempts: executor_restart_attempts.to_owned(),
+                fail_on_unused_inputs: fail_on_unused_inputs.to_owned(),
+                inputs_required_check: inputs_required_check.to_owned(),
+                output_cache: output_cache.to_owned(),
+                executor_args: executor_arg.to_owned(),
+                time_budget: time_budget.to_owned(),
             })?
         }
         Commands::Cache { action } => {
             cache::cache_action(action)?;
         }
+        Commands::Config { .. } => {
+            return Err("config is handled before loading the config".into());
+        }
         Commands::Query { action } => {
             query::query(action)?;
         }
         Commands::PackageLayer { action } => {
             layer::layer_action(action)?;
         }
+        Commands::Validate {
+            block,
+            search_paths,
+            json,
+        } => {
+            validate::validate(block, search_paths, *json)?;
+        }
     }
 
     Ok(())
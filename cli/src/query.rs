@@ -1,9 +1,10 @@
 use crate::fun::arg::parse_search_paths;
 use clap::Subcommand;
-use manifest_meta::{BlockResolver, read_flow_or_block};
+use manifest_meta::{Block, BlockResolver, HandleName, read_flow_or_block};
 use manifest_reader::path_finder::BlockPathFinder;
 use one_shot::one_shot::{UpstreamArgs, find_upstream};
-use std::collections::HashSet;
+use runtime::node_timings::{NODE_TIMINGS_FILE, NodeTiming, read_node_timings};
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::io::Write;
 use utils::error::Result;
@@ -24,7 +25,7 @@ pub enum QueryAction {
         )]
         nodes: Vec<String>,
         #[arg(
-            help = "Paths to search for blocks. Repeat the flag or use commas. Fallback to config/current flow block.",
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
             long,
             alias = "block-search-paths",
             value_delimiter = ','
@@ -32,12 +33,57 @@ pub enum QueryAction {
         search_paths: Vec<String>,
         #[arg(help = "Use previous result cache if exist.", long)]
         use_cache: bool,
+        #[arg(
+            help = "Follow dependencies per output handle, so nodes that only feed unused outputs of a subflow are left out when the subflow input they feed already has a value.",
+            long
+        )]
+        by_output_handle: bool,
+        #[arg(
+            help = "Also list the value nodes feeding the nodes in whole. They don't run but provide inputs.",
+            long
+        )]
+        include_value_nodes: bool,
+    },
+    #[command(
+        about = "print which nodes a run with --nodes would start, wait for and stop at, in run order, as JSON"
+    )]
+    Plan {
+        #[arg(
+            help = "Absolute Path to the Oocana Block Manifest file or a directory with flow.oo.yaml."
+        )]
+        block: String,
+        #[arg(
+            help = "Stop the flow after the listed nodes are finished. Repeat the flag or use commas.",
+            long,
+            required = true,
+            value_delimiter = ','
+        )]
+        nodes: Vec<String>,
+        #[arg(
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
+            long,
+            alias = "block-search-paths",
+            value_delimiter = ','
+        )]
+        search_paths: Vec<String>,
+        #[arg(help = "Use previous result cache if exist.", long)]
+        use_cache: bool,
+        #[arg(
+            help = "Follow dependencies per output handle, so nodes that only feed unused outputs of a subflow are left out when the subflow input they feed already has a value.",
+            long
+        )]
+        by_output_handle: bool,
+        #[arg(
+            help = "output file path (JSON format), if not provided, it will print to stdout",
+            long
+        )]
+        output: Option<String>,
     },
     #[command(about = "get package layers from a flow block")]
     Package {
         block: String,
         #[arg(
-            help = "Paths to search for blocks. Repeat the flag or use commas. Fallback to config/current flow block.",
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
             long,
             alias = "block-search-paths",
             value_delimiter = ','
@@ -53,7 +99,7 @@ pub enum QueryAction {
         #[arg(help = "path to the block, it can be a directory or file path.")]
         path: String,
         #[arg(
-            help = "Paths to search for blocks. Repeat the flag or use commas. Fallback to config/current flow block.",
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
             long,
             alias = "block-search-paths",
             value_delimiter = ','
@@ -64,6 +110,11 @@ pub enum QueryAction {
             long
         )]
         output: Option<String>,
+        #[arg(
+            help = "Print only the JSON result to stdout, also when --output is given.",
+            long
+        )]
+        json: bool,
     },
     #[command(about = "query flow block's all start inputs(no connection)")]
     NodesInputs {
@@ -77,7 +128,7 @@ pub enum QueryAction {
         )]
         input_types: Vec<String>,
         #[arg(
-            help = "Paths to search for blocks. Repeat the flag or use commas. Fallback to config/current flow block.",
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
             long,
             alias = "block-search-paths",
             value_delimiter = ','
@@ -88,6 +139,11 @@ pub enum QueryAction {
             long
         )]
         output: Option<String>,
+        #[arg(
+            help = "Print only the JSON result to stdout, also when --output is given.",
+            long
+        )]
+        json: bool,
     },
     #[command(
         about = "get services from a flow block. will output service struct line by line, output will be like: 'service: {json-style service struct}'"
@@ -98,12 +154,55 @@ pub enum QueryAction {
         )]
         block: String,
         #[arg(
-            help = "Paths to search for blocks. Repeat the flag or use commas. Fallback to config/current flow block.",
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
+            long,
+            alias = "block-search-paths",
+            value_delimiter = ','
+        )]
+        search_paths: Vec<String>,
+    },
+    #[command(about = "print a flow's resolved node graph in Graphviz DOT format")]
+    Graph {
+        #[arg(
+            help = "Absolute Path to the Oocana Block Manifest file or a directory with flow.oo.yaml."
+        )]
+        block: String,
+        #[arg(
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
             long,
             alias = "block-search-paths",
             value_delimiter = ','
         )]
         search_paths: Vec<String>,
+        #[arg(
+            help = "output file path (DOT format), if not provided, it will print to stdout",
+            long
+        )]
+        output: Option<String>,
+    },
+    #[command(
+        about = "print a block's interface (inputs, outputs, their types and defaults) as JSON, for editor tooling"
+    )]
+    BlockSchema {
+        #[arg(help = "path to the block, it can be a directory or file path.")]
+        block: String,
+        #[arg(
+            help = "Paths to search for blocks. Repeat the flag or use commas. Relative paths are resolved against the flow block's directory. Fallback to config/current flow block.",
+            long,
+            alias = "block-search-paths",
+            value_delimiter = ','
+        )]
+        search_paths: Vec<String>,
+        #[arg(
+            help = "output file path (JSON format), if not provided, it will print to stdout",
+            long
+        )]
+        output: Option<String>,
+    },
+    #[command(about = "print how long every node of a finished session took, slowest first")]
+    Timings {
+        #[arg(help = "id of the session, same as `oocana run --session`.", long, value_parser = crate::fun::arg::parse_session_id)]
+        session: String,
     },
 }
 
@@ -132,6 +231,18 @@ fn write_json_output(
     Ok(())
 }
 
+/// `--json` keeps stdout to the JSON document alone, so it can be piped. The file is still written
+/// when `--output` is given.
+fn write_json_stdout(output: &Option<String>, json_result: &str) -> Result<()> {
+    if let Some(output) = output {
+        let mut file = std::fs::File::create(output)?;
+        write!(file, "{json_result}")?;
+        file.flush()?;
+    }
+    println!("{json_result}");
+    Ok(())
+}
+
 pub fn query(action: &QueryAction) -> Result<()> {
     match action {
         QueryAction::Upstream {
@@ -139,12 +250,16 @@ pub fn query(action: &QueryAction) -> Result<()> {
             nodes,
             search_paths,
             use_cache,
+            by_output_handle,
+            include_value_nodes,
         } => {
             let (r, w, whole) = find_upstream(UpstreamArgs {
                 block_path: block,
                 search_paths: parse_search_paths(search_paths),
                 use_cache: use_cache.to_owned(),
                 nodes: Some(nodes.iter().cloned().collect::<HashSet<String>>()),
+                by_output_handle: by_output_handle.to_owned(),
+                include_value_nodes: include_value_nodes.to_owned(),
             })?;
             println!(
                 "run:{}\nwaiting:{}\nwhole:{}",
@@ -153,6 +268,27 @@ pub fn query(action: &QueryAction) -> Result<()> {
                 whole.join(",")
             );
         }
+        QueryAction::Plan {
+            block,
+            nodes,
+            search_paths,
+            use_cache,
+            by_output_handle,
+            output,
+        } => {
+            let (block_reader, path_finder) = query_context(search_paths)?;
+            let plan = runtime::plan_nodes(runtime::FindUpstreamArgs {
+                block_name: block,
+                block_reader,
+                path_finder,
+                use_cache: *use_cache,
+                nodes: Some(nodes.iter().cloned().collect::<HashSet<String>>()),
+                by_output_handle: *by_output_handle,
+                include_value_nodes: false,
+            })?;
+            let json_result = serde_json::to_string(&plan)?;
+            write_json_output(output, &json_result, "node plan written to file")?;
+        }
         QueryAction::Package {
             block,
             search_paths,
@@ -174,19 +310,25 @@ pub fn query(action: &QueryAction) -> Result<()> {
             path,
             search_paths,
             output,
+            json,
         } => {
             let (mut block_reader, mut block_path_finder) = query_context(search_paths)?;
             let block_or_flow =
                 read_flow_or_block(path, &mut block_reader, &mut block_path_finder)?;
             let inputs = block_or_flow.inputs_def();
             let json_result = serde_json::to_string(&inputs)?;
-            write_json_output(output, &json_result, "inputs written to file")?;
+            if *json {
+                write_json_stdout(output, &json_result)?;
+            } else {
+                write_json_output(output, &json_result, "inputs written to file")?;
+            }
         }
         QueryAction::NodesInputs {
             flow,
             search_paths,
             input_types: _, // todo: support input types filter
             output,
+            json,
         } => {
             let (mut block_reader, mut block_path_finder) = query_context(search_paths)?;
             let block_or_flow =
@@ -195,10 +337,16 @@ pub fn query(action: &QueryAction) -> Result<()> {
                 manifest_meta::Block::Flow(flow) => {
                     let input = flow.read().unwrap().query_nodes_inputs();
                     let json_result = serde_json::to_string(&input)?;
-                    write_json_output(output, &json_result, "blank input written to file")?;
+                    if *json {
+                        write_json_stdout(output, &json_result)?;
+                    } else {
+                        write_json_output(output, &json_result, "blank input written to file")?;
+                    }
                 }
                 _ => {
-                    println!("block is not flow");
+                    if !*json {
+                        println!("block is not flow");
+                    }
                     return Err(utils::error::Error::new(
                         "Block is not a flow, cannot get blank input.",
                     ));
@@ -225,6 +373,212 @@ pub fn query(action: &QueryAction) -> Result<()> {
                 }
             }
         }
+        QueryAction::Graph {
+            block,
+            search_paths,
+            output,
+        } => {
+            let (mut block_reader, mut block_path_finder) = query_context(search_paths)?;
+            let block_or_flow =
+                read_flow_or_block(block, &mut block_reader, &mut block_path_finder)?;
+            match block_or_flow {
+                manifest_meta::Block::Flow(flow) => {
+                    let dot = flow.read().unwrap().to_dot();
+                    write_json_output(output, &dot, "graph written to file")?;
+                }
+                _ => {
+                    return Err(utils::error::Error::new(
+                        "Block is not a flow, cannot draw its graph.",
+                    ));
+                }
+            }
+        }
+        QueryAction::BlockSchema {
+            block,
+            search_paths,
+            output,
+        } => {
+            let (mut block_reader, mut block_path_finder) = query_context(search_paths)?;
+            let block = read_flow_or_block(block, &mut block_reader, &mut block_path_finder)?;
+            let json_result = serde_json::to_string(&block_schema(&block))?;
+            write_json_output(output, &json_result, "block schema written to file")?;
+        }
+        QueryAction::Timings { session } => {
+            let timings_file = utils::logger::logger_root()
+                .join(utils::logger::session_sub_dir(session))
+                .join(NODE_TIMINGS_FILE);
+            let timings = read_node_timings(&timings_file)?;
+            print!("{}", format_timings(&timings));
+        }
     }
     Ok(())
 }
+
+/// Normalized interface of any block type. Handles are sorted by name, `type` is the json_schema
+/// type (string or array) and `default` is only present when the input has a value.
+pub(crate) fn block_schema(block: &Block) -> serde_json::Value {
+    let (kind, description, outputs_def, additional_inputs, additional_outputs) = match block {
+        Block::Task(task) => (
+            "task",
+            task.description.clone(),
+            task.outputs_def.clone(),
+            task.additional_inputs,
+            task.additional_outputs,
+        ),
+        Block::Flow(flow) => {
+            let flow = flow.read().unwrap();
+            (
+                "flow",
+                flow.description.clone(),
+                flow.outputs_def.clone(),
+                false,
+                false,
+            )
+        }
+        Block::Slot(slot) => ("slot", None, slot.outputs_def.clone(), false, false),
+        Block::Service(service) => ("service", None, service.outputs_def.clone(), false, false),
+        Block::Condition(condition) => (
+            "condition",
+            condition.description.clone(),
+            None,
+            false,
+            false,
+        ),
+    };
+
+    let mut inputs: Vec<_> = block
+        .inputs_def()
+        .unwrap_or_default()
+        .into_values()
+        .collect();
+    inputs.sort_by(|a, b| a.handle.as_str().cmp(b.handle.as_str()));
+    let inputs: Vec<_> = inputs
+        .into_iter()
+        .map(|input| {
+            let mut handle = handle_schema(
+                &input.handle,
+                &input.description,
+                &input.json_schema,
+                input.nullable,
+                input.deprecated.is_some(),
+            );
+            if let Some(value) = input.value {
+                handle["default"] = value.unwrap_or(serde_json::Value::Null);
+            }
+            handle
+        })
+        .collect();
+
+    let outputs: Vec<_> = match block {
+        Block::Condition(condition) => condition
+            .cases
+            .iter()
+            .map(|case| (&case.handle, &case.description))
+            .chain(
+                condition
+                    .default
+                    .iter()
+                    .map(|default| (&default.handle, &default.description)),
+            )
+            .map(|(handle, description)| handle_schema(handle, description, &None, None, false))
+            .collect(),
+        _ => {
+            let mut outputs: Vec<_> = outputs_def.unwrap_or_default().into_values().collect();
+            outputs.sort_by(|a, b| a.handle.as_str().cmp(b.handle.as_str()));
+            outputs
+                .iter()
+                .map(|output| {
+                    handle_schema(
+                        &output.handle,
+                        &output.description,
+                        &output.json_schema,
+                        output.nullable,
+                        output.deprecated.is_some(),
+                    )
+                })
+                .collect()
+        }
+    };
+
+    serde_json::json!({
+        "kind": kind,
+        "description": description,
+        "inputs": inputs,
+        "outputs": outputs,
+        "additional_inputs": additional_inputs,
+        "additional_outputs": additional_outputs,
+    })
+}
+
+fn handle_schema(
+    handle: &HandleName,
+    description: &Option<String>,
+    json_schema: &Option<serde_json::Value>,
+    nullable: Option<bool>,
+    deprecated: bool,
+) -> serde_json::Value {
+    serde_json::json!({
+        "handle": handle.as_str(),
+        "description": description,
+        "type": json_schema.as_ref().and_then(|schema| schema.get("type")),
+        "json_schema": json_schema,
+        "nullable": nullable.unwrap_or(false),
+        "deprecated": deprecated,
+    })
+}
+
+/// One row per node, runs of the same node are summed up. Sorted by total duration, slowest first.
+pub(crate) fn format_timings(timings: &[NodeTiming]) -> String {
+    struct Row<'a> {
+        node_id: &'a str,
+        flow: &'a str,
+        runs: usize,
+        failed: usize,
+        total_ms: u64,
+        max_ms: u64,
+    }
+
+    let mut rows: HashMap<(&str, &str), Row> = HashMap::new();
+    for timing in timings {
+        let row = rows
+            .entry((timing.flow.as_str(), timing.node_id.as_str()))
+            .or_insert(Row {
+                node_id: timing.node_id.as_str(),
+                flow: timing.flow.as_str(),
+                runs: 0,
+                failed: 0,
+                total_ms: 0,
+                max_ms: 0,
+            });
+        row.runs += 1;
+        row.failed += usize::from(!timing.success);
+        row.total_ms += timing.duration_ms;
+        row.max_ms = row.max_ms.max(timing.duration_ms);
+    }
+
+    let mut rows: Vec<Row> = rows.into_values().collect();
+    rows.sort_by(|a, b| {
+        b.total_ms
+            .cmp(&a.total_ms)
+            .then_with(|| a.node_id.cmp(b.node_id))
+            .then_with(|| a.flow.cmp(b.flow))
+    });
+
+    let node_width = rows
+        .iter()
+        .map(|row| row.node_id.len())
+        .chain(["NODE".len()])
+        .max()
+        .unwrap_or_default();
+    let mut table = format!(
+        "{:<node_width$}  {:>10}  {:>10}  {:>4}  {:>6}  FLOW\n",
+        "NODE", "TOTAL(ms)", "MAX(ms)", "RUNS", "FAILED"
+    );
+    for row in rows {
+        table.push_str(&format!(
+            "{:<node_width$}  {:>10}  {:>10}  {:>4}  {:>6}  {}\n",
+            row.node_id, row.total_ms, row.max_ms, row.runs, row.failed, row.flow
+        ));
+    }
+    table
+}
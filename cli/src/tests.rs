@@ -20,6 +20,8 @@ fn run_command_parses_all_flags() {
         "tests/fixtures/connector-flow.oo.yaml",
         "--broker",
         "127.0.0.1:47688",
+        "--broker-connect-retries",
+        "3",
         "--search-paths",
         "/tmp/a,/tmp/b",
         "--session",
@@ -29,8 +31,14 @@ fn run_command_parses_all_flags() {
         "--debug=false",
         "--wait-for-client",
         "--use-cache",
+        "--replay-cached-outputs",
         "--nodes",
         "node-a,node-b",
+        "--node-filter-regex",
+        "^extract_",
+        "--record-block",
+        "node-a",
+        "--resumable",
         "--inputs",
         "{\"input\":1}",
         "--nodes-inputs",
@@ -51,11 +59,15 @@ fn run_command_parses_all_flags() {
         "FOO,BAR",
         "--env-file",
         ".env.test",
+        "--secrets-via-file",
         "--bind-paths",
         "src=/src,dst=/dst,ro,recursive",
         "--bind-path-file",
         "/tmp/binds.txt",
         "--dry-run",
+        "--resolve-only",
+        "--dump-injection-store",
+        "/tmp/injection.json",
         "--report-to-console",
         "--remote-block-url",
         "https://remote.example",
@@ -63,12 +75,66 @@ fn run_command_parses_all_flags() {
         "https://connector.example",
         "--remote-block-timeout",
         "42",
+        "--max-log-line-bytes",
+        "1024",
+        "--max-executor-log-bytes",
+        "1048576",
+        "--per-executor-logs",
+        "--report-executor-logs",
+        "--report-to-file",
+        "--max-concurrency",
+        "4",
+        "--max-flow-depth",
+        "16",
+        "--max-concurrent-sessions",
+        "8",
+        "--abort-grace-outputs",
+        "500",
+        "--output-file",
+        "/tmp/outputs.json",
+        "--output-only",
+        "left,right",
+        "--output-only",
+        "extra",
+        "--output-format",
+        "jsonl-stream",
+        "--readonly-package-data",
+        "--strict-handle-types",
+        "--case-insensitive-handles",
+        "--shutdown-grace-secs",
+        "30",
+        "--checkpoint-interval",
+        "60",
+        "--executor-spawn-timeout-secs",
+        "20",
+        "--executor-ready-timeout-secs",
+        "45",
+        "--listener-timeout-secs",
+        "7",
+        "--cache-root",
+        "/tmp/oocana-cache",
+        "--executor-restart-attempts",
+        "2",
+        "--fail-on-unused-inputs",
+        "--inputs-required-check",
+        "--warn-as-json",
+        "/tmp/warnings.ndjson",
+        "--output-cache",
+        "--executor-arg",
+        "--plugin-dir",
+        "--executor-arg=/plugins",
+        "--time-budget",
+        "fetch=120s",
+        "--env",
+        "DEBUG=1",
+        "--env=GREETING=a=b",
     ]);
 
     match cli.command {
         Commands::Run {
             block,
             broker,
+            broker_connect_retries,
             search_paths,
             session,
             reporter,
@@ -76,8 +142,15 @@ fn run_command_parses_all_flags() {
             debug,
             wait_for_client,
             use_cache,
+            replay_cached_outputs,
             nodes,
+            node_filter_regex,
+            record_block,
+            replay_block,
+            resume,
+            resumable,
             inputs,
+            inputs_file,
             nodes_inputs,
             default_package,
             exclude_packages,
@@ -87,17 +160,50 @@ fn run_command_parses_all_flags() {
             temp_root,
             retain_env_keys,
             env_file,
+            env,
+            secrets_via_file,
             bind_paths,
             bind_path_file,
             dry_run,
+            resolve_only,
+            dump_injection_store,
             report_to_console,
             remote_block_url,
             connector_base_url,
             remote_block_timeout,
+            max_log_line_bytes,
+            max_executor_log_bytes,
+            per_executor_logs,
+            report_executor_logs,
+            report_to_file,
+            max_concurrency,
+            max_flow_depth,
+            max_concurrent_sessions,
+            abort_grace_outputs,
+            output_file,
+            output_only,
+            output_format,
+            readonly_package_data,
+            strict_handle_types,
+            case_insensitive_handles,
+            shutdown_grace_secs,
+            checkpoint_interval,
+            executor_spawn_timeout_secs,
+            executor_ready_timeout_secs,
+            listener_timeout_secs,
+            cache_root,
+            executor_restart_attempts,
+            fail_on_unused_inputs,
+            inputs_required_check,
+            warn_as_json,
+            output_cache,
+            executor_arg,
+            time_budget,
         } => {
             assert_eq!(cli.config, "/tmp/oocana.toml");
             assert_eq!(block, "tests/fixtures/connector-flow.oo.yaml");
             assert_eq!(broker.as_deref(), Some("127.0.0.1:47688"));
+            assert_eq!(broker_connect_retries, 3);
             assert_eq!(search_paths, vec!["/tmp/a", "/tmp/b"]);
             assert_eq!(session, "session-123");
             assert_eq!(reporter, Some(false));
@@ -105,8 +211,15 @@ fn run_command_parses_all_flags() {
             assert_eq!(debug, Some(false));
             assert!(wait_for_client);
             assert!(use_cache);
+            assert!(replay_cached_outputs);
             assert_eq!(nodes, vec!["node-a", "node-b"]);
+            assert_eq!(node_filter_regex.as_deref(), Some("^extract_"));
+            assert_eq!(record_block.as_deref(), Some("node-a"));
+            assert_eq!(replay_block, None);
+            assert_eq!(resume, None);
+            assert!(resumable);
             assert_eq!(inputs.as_deref(), Some("{\"input\":1}"));
+            assert_eq!(inputs_file, None);
             assert_eq!(nodes_inputs.as_deref(), Some("{\"node-a\":{\"input\":1}}"));
             assert_eq!(default_package.as_deref(), Some("/pkg/default"));
             assert_eq!(exclude_packages, vec!["/pkg/a", "/pkg/b"]);
@@ -116,12 +229,15 @@ fn run_command_parses_all_flags() {
             assert_eq!(temp_root, "/tmp/temp-root");
             assert_eq!(retain_env_keys, vec!["FOO", "BAR"]);
             assert_eq!(env_file.as_deref(), Some(".env.test"));
+            assert!(secrets_via_file);
             assert_eq!(
                 bind_paths,
                 Some(vec!["src=/src,dst=/dst,ro,recursive".to_string()])
             );
             assert_eq!(bind_path_file.as_deref(), Some("/tmp/binds.txt"));
             assert!(dry_run);
+            assert!(resolve_only);
+            assert_eq!(dump_injection_store.as_deref(), Some("/tmp/injection.json"));
             assert!(report_to_console);
             assert_eq!(remote_block_url.as_deref(), Some("https://remote.example"));
             assert_eq!(
@@ -129,6 +245,35 @@ fn run_command_parses_all_flags() {
                 Some("https://connector.example")
             );
             assert_eq!(remote_block_timeout, Some(42));
+            assert_eq!(max_log_line_bytes, Some(1024));
+            assert_eq!(max_executor_log_bytes, Some(1048576));
+            assert!(per_executor_logs);
+            assert!(report_executor_logs);
+            assert!(report_to_file);
+            assert_eq!(max_concurrency, Some(4));
+            assert_eq!(max_flow_depth, Some(16));
+            assert_eq!(max_concurrent_sessions, Some(8));
+            assert_eq!(abort_grace_outputs, Some(500));
+            assert_eq!(output_file.as_deref(), Some("/tmp/outputs.json"));
+            assert_eq!(output_only, vec!["left", "right", "extra"]);
+            assert_eq!(output_format, Some(OutputFormat::JsonlStream));
+            assert!(readonly_package_data);
+            assert!(strict_handle_types);
+            assert!(case_insensitive_handles);
+            assert_eq!(shutdown_grace_secs, Some(30));
+            assert_eq!(checkpoint_interval, Some(60));
+            assert_eq!(executor_spawn_timeout_secs, Some(20));
+            assert_eq!(executor_ready_timeout_secs, Some(45));
+            assert_eq!(listener_timeout_secs, Some(7));
+            assert_eq!(cache_root.as_deref(), Some("/tmp/oocana-cache"));
+            assert_eq!(executor_restart_attempts, 2);
+            assert!(fail_on_unused_inputs);
+            assert!(inputs_required_check);
+            assert_eq!(warn_as_json.as_deref(), Some("/tmp/warnings.ndjson"));
+            assert!(output_cache);
+            assert_eq!(executor_arg, vec!["--plugin-dir", "/plugins"]);
+            assert_eq!(time_budget, vec!["fetch=120s"]);
+            assert_eq!(env, vec!["DEBUG=1", "GREETING=a=b"]);
         }
         other => panic!("expected run command, got {other:?}"),
     }
@@ -173,6 +318,8 @@ fn query_upstream_parses_all_flags() {
         "--search-paths",
         "/tmp/a,/tmp/b",
         "--use-cache",
+        "--by-output-handle",
+        "--include-value-nodes",
     ]);
 
     match cli.command {
@@ -183,13 +330,16 @@ fn query_upstream_parses_all_flags() {
                     nodes,
                     search_paths,
                     use_cache,
-                    ..
+                    by_output_handle,
+                    include_value_nodes,
                 },
         } => {
             assert_eq!(block, "examples/base");
             assert_eq!(nodes, vec!["node-a", "node-b"]);
             assert_eq!(search_paths, vec!["/tmp/a", "/tmp/b"]);
             assert!(use_cache);
+            assert!(by_output_handle);
+            assert!(include_value_nodes);
         }
         other => panic!("expected query upstream command, got {other:?}"),
     }
@@ -231,6 +381,7 @@ fn query_other_subcommands_parse() {
         "/tmp/a,/tmp/b",
         "--output",
         "/tmp/inputs.json",
+        "--json",
     ]);
     match inputs.command {
         Commands::Query {
@@ -239,11 +390,13 @@ fn query_other_subcommands_parse() {
                     path,
                     search_paths,
                     output,
+                    json,
                 },
         } => {
             assert_eq!(path, "examples/base");
             assert_eq!(search_paths, vec!["/tmp/a", "/tmp/b"]);
             assert_eq!(output.as_deref(), Some("/tmp/inputs.json"));
+            assert!(json);
         }
         other => panic!("expected query inputs command, got {other:?}"),
     }
@@ -268,16 +421,52 @@ fn query_other_subcommands_parse() {
                     input_types,
                     search_paths,
                     output,
+                    json,
                 },
         } => {
             assert_eq!(flow, "examples/base");
             assert_eq!(input_types, vec!["absence", "nullable"]);
             assert_eq!(search_paths, vec!["/tmp/c", "/tmp/d"]);
             assert_eq!(output.as_deref(), Some("/tmp/nodes-inputs.json"));
+            assert!(!json);
         }
         other => panic!("expected query nodes-inputs command, got {other:?}"),
     }
 
+    let plan = parse_cli(&[
+        "oocana",
+        "query",
+        "plan",
+        "examples/base",
+        "--nodes",
+        "a,b",
+        "--use-cache",
+        "--by-output-handle",
+        "--output",
+        "/tmp/plan.json",
+    ]);
+    match plan.command {
+        Commands::Query {
+            action:
+                query::QueryAction::Plan {
+                    block,
+                    nodes,
+                    search_paths,
+                    use_cache,
+                    by_output_handle,
+                    output,
+                },
+        } => {
+            assert_eq!(block, "examples/base");
+            assert_eq!(nodes, vec!["a", "b"]);
+            assert!(search_paths.is_empty());
+            assert!(use_cache);
+            assert!(by_output_handle);
+            assert_eq!(output.as_deref(), Some("/tmp/plan.json"));
+        }
+        other => panic!("expected query plan command, got {other:?}"),
+    }
+
     let service = parse_cli(&[
         "oocana",
         "query",
@@ -299,6 +488,142 @@ fn query_other_subcommands_parse() {
         }
         other => panic!("expected query service command, got {other:?}"),
     }
+
+    let graph = parse_cli(&[
+        "oocana",
+        "query",
+        "graph",
+        "examples/base",
+        "--output",
+        "/tmp/flow.dot",
+    ]);
+    match graph.command {
+        Commands::Query {
+            action:
+                query::QueryAction::Graph {
+                    block,
+                    search_paths,
+                    output,
+                },
+        } => {
+            assert_eq!(block, "examples/base");
+            assert!(search_paths.is_empty());
+            assert_eq!(output.as_deref(), Some("/tmp/flow.dot"));
+        }
+        other => panic!("expected query graph command, got {other:?}"),
+    }
+
+    let block_schema = parse_cli(&[
+        "oocana",
+        "query",
+        "block-schema",
+        "examples/base",
+        "--output",
+        "/tmp/schema.json",
+    ]);
+    match block_schema.command {
+        Commands::Query {
+            action:
+                query::QueryAction::BlockSchema {
+                    block,
+                    search_paths,
+                    output,
+                },
+        } => {
+            assert_eq!(block, "examples/base");
+            assert!(search_paths.is_empty());
+            assert_eq!(output.as_deref(), Some("/tmp/schema.json"));
+        }
+        other => panic!("expected query block-schema command, got {other:?}"),
+    }
+
+    let timings = parse_cli(&["oocana", "query", "timings", "--session", "session-123"]);
+    match timings.command {
+        Commands::Query {
+            action: query::QueryAction::Timings { session },
+        } => assert_eq!(session, "session-123"),
+        other => panic!("expected query timings command, got {other:?}"),
+    }
+}
+
+#[test]
+fn block_schema_lists_task_handles_with_types() {
+    let task = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../manifest_meta/tests/fixtures/schema-task/task.oo.yaml");
+    let mut path_finder =
+        manifest_reader::path_finder::BlockPathFinder::new(task.parent().unwrap(), None);
+    let block = manifest_meta::read_flow_or_block(
+        task.to_str().unwrap(),
+        &mut manifest_meta::BlockResolver::new(),
+        &mut path_finder,
+    )
+    .unwrap();
+
+    let schema = query::block_schema(&block);
+    assert_eq!(schema["kind"], "task");
+    assert_eq!(schema["description"], "resize an image");
+    assert_eq!(schema["additional_inputs"], true);
+    assert_eq!(schema["additional_outputs"], false);
+    assert_eq!(
+        schema["inputs"],
+        serde_json::json!([
+            {
+                "handle": "image",
+                "description": "path of the image",
+                "type": "string",
+                "json_schema": {"type": "string", "contentMediaType": "oomol/image"},
+                "nullable": true,
+                "deprecated": false,
+                // nullable inputs default to null
+                "default": null,
+            },
+            {
+                "handle": "width",
+                "description": "target width in pixels",
+                "type": "integer",
+                "json_schema": {"type": "integer"},
+                "nullable": false,
+                "deprecated": false,
+                "default": 128,
+            },
+        ])
+    );
+    assert_eq!(schema["outputs"][0]["handle"], "resized");
+    assert_eq!(schema["outputs"][0]["type"], "string");
+    assert_eq!(
+        schema["outputs"][0]["description"],
+        "path of the resized image"
+    );
+}
+
+#[test]
+fn format_timings_sums_runs_slowest_first() {
+    use runtime::node_timings::NodeTiming;
+
+    let timing = |node_id: &str, duration_ms, success| NodeTiming {
+        node_id: manifest_meta::NodeId::new(node_id.to_owned()),
+        flow: "/flows/main.oo.yaml".to_owned(),
+        duration_ms,
+        success,
+    };
+    let table = query::format_timings(&[
+        timing("fast", 5, true),
+        timing("loop", 30, true),
+        timing("loop", 40, false),
+        timing("slow", 50, true),
+    ]);
+
+    assert_eq!(
+        table,
+        [
+            "NODE   TOTAL(ms)     MAX(ms)  RUNS  FAILED  FLOW",
+            "loop          70          40     2       1  /flows/main.oo.yaml",
+            "slow          50          50     1       0  /flows/main.oo.yaml",
+            "fast           5           5     1       0  /flows/main.oo.yaml",
+            "",
+        ]
+        .join("\n")
+    );
 }
 
 #[test]
@@ -313,6 +638,36 @@ fn cache_subcommand_parses() {
     }
 }
 
+#[test]
+fn config_schema_subcommand_parses() {
+    let cli = parse_cli(&["oocana", "config", "schema"]);
+
+    match cli.command {
+        Commands::Config {
+            action: config::ConfigAction::Schema {},
+        } => {}
+        other => panic!("expected config schema command, got {other:?}"),
+    }
+
+    let cli = parse_cli(&[
+        "oocana",
+        "config",
+        "validate",
+        "--config",
+        "/tmp/oocana.toml",
+        "--strict",
+    ]);
+    match cli.command {
+        Commands::Config {
+            action: config::ConfigAction::Validate { config, strict },
+        } => {
+            assert_eq!(config.as_deref(), Some("/tmp/oocana.toml"));
+            assert!(strict);
+        }
+        other => panic!("expected config validate command, got {other:?}"),
+    }
+}
+
 #[test]
 fn package_layer_subcommands_parse() {
     let create = parse_cli(&[
@@ -330,6 +685,10 @@ fn package_layer_subcommands_parse() {
         "BAR",
         "--env-file",
         ".env.layer",
+        "--lockfile",
+        "/pkg/path/requirements.lock",
+        "--from-image",
+        "python:3.12-slim",
     ]);
     match create.command {
         Commands::PackageLayer {
@@ -340,6 +699,8 @@ fn package_layer_subcommands_parse() {
                     bind_path_file,
                     retain_env_keys,
                     env_file,
+                    lockfile,
+                    from_image,
                 },
         } => {
             assert_eq!(package, "/pkg/path");
@@ -353,6 +714,8 @@ fn package_layer_subcommands_parse() {
                 Some(vec!["FOO".to_string(), "BAR".to_string()])
             );
             assert_eq!(env_file.as_deref(), Some(".env.layer"));
+            assert_eq!(lockfile.as_deref(), Some("/pkg/path/requirements.lock"));
+            assert_eq!(from_image.as_deref(), Some("python:3.12-slim"));
         }
         other => panic!("expected package-layer create command, got {other:?}"),
     }
@@ -561,6 +924,108 @@ fn package_layer_subcommands_parse() {
     }
 }
 
+#[test]
+fn package_layer_prune_parses() {
+    let prune = parse_cli(&[
+        "oocana",
+        "package-layer",
+        "prune",
+        "--flow",
+        "/flows/a",
+        "--flow",
+        "/flows/b",
+        "--exclude-packages",
+        "/pkg/keep",
+        "--dry-run",
+        "--older-than-days",
+        "30",
+    ]);
+    match prune.command {
+        Commands::PackageLayer {
+            action:
+                layer::LayerAction::Prune {
+                    flows,
+                    exclude_packages,
+                    dry_run,
+                    older_than_days,
+                    ..
+                },
+        } => {
+            assert_eq!(flows, vec!["/flows/a", "/flows/b"]);
+            assert_eq!(exclude_packages, vec!["/pkg/keep"]);
+            assert!(dry_run);
+            assert_eq!(older_than_days, Some(30));
+        }
+        other => panic!("expected package-layer prune command, got {other:?}"),
+    }
+
+    assert!(Cli::try_parse_from(["oocana", "package-layer", "prune"]).is_err());
+}
+
+fn layer_info(path: impl Into<PathBuf>, created_at: Option<u64>) -> ::layer::PackageLayerInfo {
+    ::layer::PackageLayerInfo {
+        path: path.into(),
+        status: ::layer::PackageLayerStatus::Exist,
+        package_size_bytes: None,
+        created_at,
+        package_name: None,
+        package_version: None,
+    }
+}
+
+#[test]
+fn prune_keeps_used_excluded_and_recent_layers() {
+    let info = |path: &str, created_at| layer_info(path, created_at);
+    let layers = vec![
+        info("/pkg/used", Some(0)),
+        info("/pkg/kept", Some(0)),
+        info("/pkg/old", Some(0)),
+        info("/pkg/recent", Some(950)),
+        info("/pkg/unknown-age", None),
+    ];
+    let in_use = HashSet::from([PathBuf::from("/pkg/used")]);
+    let excluded = HashSet::from([PathBuf::from("/pkg/kept")]);
+
+    assert_eq!(
+        layer::prunable_package_layers(&layers, &in_use, &excluded, Some(100), 1000),
+        vec![PathBuf::from("/pkg/old")]
+    );
+    assert_eq!(
+        layer::prunable_package_layers(&layers, &in_use, &excluded, None, 1000),
+        vec![
+            PathBuf::from("/pkg/old"),
+            PathBuf::from("/pkg/recent"),
+            PathBuf::from("/pkg/unknown-age"),
+        ]
+    );
+}
+
+#[test]
+fn prune_keeps_packages_only_a_subflow_uses() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..");
+    let fixtures = root.join("tests/fixtures");
+    let flow = fixtures.join("query-nested-packages");
+    let in_use = layer::packages_in_use(
+        &[flow.to_string_lossy().to_string()],
+        Some(vec![fixtures.clone()]),
+    )
+    .unwrap();
+
+    let info = |path: PathBuf| layer_info(path, Some(0));
+    // demo is only used two subflows deep, slotted only fills a slot, the layers list them by a
+    // path that isn't canonical
+    let layers = vec![
+        info(fixtures.join("@connector/demo")),
+        info(fixtures.join("@connector/../@connector/slotted")),
+        info(PathBuf::from("/pkg/unused")),
+    ];
+
+    assert_eq!(
+        layer::prunable_package_layers(&layers, &in_use, &HashSet::new(), None, 1000),
+        vec![PathBuf::from("/pkg/unused")]
+    );
+}
+
 #[test]
 fn query_upstream_requires_nodes() {
     let err = Cli::try_parse_from(["oocana", "query", "upstream", "examples/base"])
@@ -586,3 +1051,163 @@ fn parse_alias_for_block_search_paths() {
         other => panic!("expected query upstream command, got {other:?}"),
     }
 }
+
+#[test]
+fn run_rejects_inputs_with_inputs_file() {
+    let err = Cli::try_parse_from([
+        "oocana",
+        "run",
+        "examples/base",
+        "--inputs",
+        "{}",
+        "--inputs-file",
+        "inputs.json",
+    ])
+    .expect_err("--inputs and --inputs-file should conflict");
+    let rendered = err.to_string();
+    assert!(rendered.contains("--inputs-file"));
+    assert!(rendered.contains("cannot be used with"));
+}
+
+#[test]
+fn run_rejects_replay_block_with_inputs() {
+    let err = Cli::try_parse_from([
+        "oocana",
+        "run",
+        "examples/base",
+        "--replay-block",
+        "node-a.record.json",
+        "--inputs",
+        "{}",
+    ])
+    .expect_err("--replay-block brings its own inputs");
+    assert!(err.to_string().contains("cannot be used with"));
+}
+
+#[test]
+fn run_rejects_session_id_with_path_separators() {
+    let err = Cli::try_parse_from([
+        "oocana",
+        "run",
+        "examples/base",
+        "--session",
+        "/tmp/flows/base",
+    ])
+    .expect_err("a path is not a session id");
+    assert!(err.to_string().contains("must not contain '/'"), "{err}");
+}
+
+#[test]
+fn run_rejects_resume_with_nodes() {
+    let err = Cli::try_parse_from([
+        "oocana",
+        "run",
+        "examples/base",
+        "--resume",
+        "session-123",
+        "--nodes",
+        "node-a",
+    ])
+    .expect_err("--resume picks the nodes to run");
+    assert!(err.to_string().contains("cannot be used with"));
+}
+
+#[test]
+fn load_inputs_file_reads_json_and_yaml() {
+    let dir = std::env::temp_dir().join(format!("oocana-inputs-file-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let json = dir.join("inputs.json");
+    std::fs::write(&json, r#"{"count": 1, "name": "a"}"#).unwrap();
+    let yaml = dir.join("inputs.yml");
+    std::fs::write(&yaml, "count: 1\nname: a\n").unwrap();
+
+    let expected = serde_json::json!({"count": 1, "name": "a"});
+    for file in [&json, &yaml] {
+        let inputs = load_inputs_file(file.to_str().unwrap()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&inputs).unwrap(),
+            expected
+        );
+    }
+
+    let broken = dir.join("broken.yaml");
+    std::fs::write(&broken, "count: [1").unwrap();
+    let err = load_inputs_file(broken.to_str().unwrap()).unwrap_err();
+    assert!(err.to_string().contains("Invalid YAML"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parse_env_vars_splits_on_first_equals() {
+    let env_vars = parse_env_vars(&[
+        "DEBUG=1".to_string(),
+        "GREETING=a=b".to_string(),
+        "EMPTY=".to_string(),
+    ])
+    .unwrap();
+    assert_eq!(env_vars["DEBUG"], "1");
+    assert_eq!(env_vars["GREETING"], "a=b");
+    assert_eq!(env_vars["EMPTY"], "");
+
+    for entry in ["NO_VALUE", "=value"] {
+        let err = parse_env_vars(&[entry.to_string()]).unwrap_err();
+        assert!(err.to_string().contains(entry), "{err}");
+    }
+}
+
+#[test]
+fn validate_subcommand_parses() {
+    let cli = parse_cli(&[
+        "oocana",
+        "validate",
+        "examples/base",
+        "--search-paths",
+        "/tmp/a,/tmp/b",
+        "--json",
+    ]);
+
+    match cli.command {
+        Commands::Validate {
+            block,
+            search_paths,
+            json,
+        } => {
+            assert_eq!(block, "examples/base");
+            assert_eq!(search_paths, vec!["/tmp/a", "/tmp/b"]);
+            assert!(json);
+        }
+        other => panic!("expected validate command, got {other:?}"),
+    }
+}
+
+#[test]
+fn validate_json_report_lists_issues() {
+    let flow = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../manifest_meta/tests/fixtures/deprecated-handles");
+    let mut path_finder = manifest_reader::path_finder::BlockPathFinder::new(&flow, None);
+    let issues = manifest_meta::validate_flow_or_block(
+        flow.to_str().unwrap(),
+        &mut manifest_meta::BlockResolver::new(),
+        &mut path_finder,
+    );
+
+    let warnings: Vec<_> = issues
+        .into_iter()
+        .filter(|issue| issue.kind == manifest_meta::IssueKind::DeprecatedHandle)
+        .collect();
+
+    let report = validate::report_json(&warnings, 0);
+    assert_eq!(report["valid"], true);
+    assert_eq!(report["issues"].as_array().unwrap().len(), 2);
+    assert_eq!(
+        report["issues"][0],
+        serde_json::json!({
+            "level": "warning",
+            "kind": "deprecated-handle",
+            "node_id": null,
+            "message": "node(consumer) connects to deprecated input handle(legacy_in): use in1 instead",
+        })
+    );
+}
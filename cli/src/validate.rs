@@ -0,0 +1,51 @@
+use crate::fun::arg::parse_search_paths;
+use manifest_meta::{BlockResolver, IssueLevel, ValidationIssue, validate_flow_or_block};
+use manifest_reader::path_finder::BlockPathFinder;
+use std::env;
+use utils::error::Result;
+
+pub fn validate(block: &str, search_paths: &[String], json: bool) -> Result<()> {
+    let search_paths = parse_search_paths(search_paths);
+    let mut path_finder = BlockPathFinder::new(env::current_dir()?, search_paths);
+    let issues = validate_flow_or_block(block, &mut BlockResolver::new(), &mut path_finder);
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.level() == IssueLevel::Error)
+        .count();
+
+    if json {
+        println!("{}", report_json(&issues, errors));
+    } else {
+        for issue in &issues {
+            println!(
+                "{}[{}]: {}",
+                issue.level().as_str(),
+                issue.kind.as_str(),
+                issue.message
+            );
+        }
+    }
+
+    if errors > 0 {
+        return Err(format!("{block} has {errors} error(s)").into());
+    }
+    Ok(())
+}
+
+pub(crate) fn report_json(issues: &[ValidationIssue], errors: usize) -> serde_json::Value {
+    let issues: Vec<_> = issues
+        .iter()
+        .map(|issue| {
+            serde_json::json!({
+                "level": issue.level().as_str(),
+                "kind": issue.kind.as_str(),
+                "node_id": issue.node_id.as_ref().map(|id| id.as_str()),
+                "message": issue.message,
+            })
+        })
+        .collect();
+    serde_json::json!({
+        "valid": errors == 0,
+        "issues": issues,
+    })
+}
@@ -0,0 +1,7 @@
+def echo(inputs, context):
+    print(f"service echo: {inputs['text']}")
+    return {"text": inputs["text"]}
+
+
+def main(service):
+    service.register_block_handler("echo", echo)
@@ -1,9 +1,10 @@
 mod scope;
 pub use scope::RuntimeScope;
-use std::{collections::HashMap, sync::Arc};
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
 use manifest_meta::{HandleName, NodeId};
 use serde::{Deserialize, Serialize};
+use utils::error::{Error, Result};
 use utils::output::OutputValue;
 
 #[derive(
@@ -16,7 +17,6 @@ use utils::output::OutputValue;
     Hash,
     derive_more::Display,
     derive_more::From,
-    derive_more::FromStr,
     derive_more::Deref,
     derive_more::Constructor,
     derive_more::Into,
@@ -27,6 +27,21 @@ impl SessionId {
     pub fn random() -> Self {
         Self(uuid::Uuid::new_v4().to_string())
     }
+
+    /// A session id given by the user, rejected unless it's safe as a file name.
+    pub fn try_new(id: impl Into<String>) -> Result<Self> {
+        let id = id.into();
+        validate_id("session id", &id)?;
+        Ok(Self(id))
+    }
+}
+
+impl FromStr for SessionId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::try_new(s)
+    }
 }
 
 #[derive(
@@ -39,7 +54,6 @@ impl SessionId {
     Hash,
     derive_more::Display,
     derive_more::From,
-    derive_more::FromStr,
     derive_more::Deref,
     derive_more::Constructor,
     derive_more::Into,
@@ -50,6 +64,39 @@ impl JobId {
     pub fn random() -> Self {
         Self(uuid::Uuid::new_v4().to_string())
     }
+
+    /// A job id given by the user, rejected unless it's safe as a file name.
+    pub fn try_new(id: impl Into<String>) -> Result<Self> {
+        let id = id.into();
+        validate_id("job id", &id)?;
+        Ok(Self(id))
+    }
+}
+
+impl FromStr for JobId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::try_new(s)
+    }
+}
+
+/// Ids name log and session directories, so they must be a single non-empty path component:
+/// no path separators, no control characters and not `.` or `..`.
+fn validate_id(kind: &str, id: &str) -> Result<()> {
+    if id.is_empty() {
+        return Err(format!("{kind} must not be empty").into());
+    }
+    if id == "." || id == ".." {
+        return Err(format!("{kind} {id:?} is not a valid file name").into());
+    }
+    if let Some(c) = id
+        .chars()
+        .find(|c| matches!(c, '/' | '\\') || c.is_control())
+    {
+        return Err(format!("{kind} {id:?} must not contain {c:?}").into());
+    }
+    Ok(())
 }
 
 pub type BlockInputs = HashMap<HandleName, Arc<OutputValue>>;
@@ -61,6 +108,17 @@ pub struct BlockJobStackLevel {
     pub node_id: NodeId,
 }
 
+/// Readable location of the stacked levels: the node ids from the outermost flow joined with `/`,
+/// e.g. `subflow/task`. Flow paths are left out, the flow of a level is the subflow of the node
+/// before it. A `/` or `%` in a node id is percent-encoded so the path splits back unambiguously.
+pub fn stack_path(stacks: &[BlockJobStackLevel]) -> String {
+    stacks
+        .iter()
+        .map(|level| level.node_id.replace('%', "%25").replace('/', "%2F"))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
 #[derive(Debug, Clone)]
 pub struct BlockJobStacks(Arc<Vec<BlockJobStackLevel>>);
 
@@ -97,3 +155,41 @@ impl BlockJobStacks {
         self.0.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ids_must_be_file_name_safe() {
+        assert_eq!(
+            "session-123".parse::<SessionId>().unwrap(),
+            SessionId::new("session-123".to_owned())
+        );
+        assert!(SessionId::try_new(SessionId::random().to_string()).is_ok());
+        assert!(JobId::try_new("job_1.retry").is_ok());
+
+        for id in ["", ".", "..", "a/b", "/tmp/flow", "a\\b", "a\nb", "a\0b"] {
+            let err = SessionId::try_new(id).unwrap_err().to_string();
+            assert!(err.starts_with("session id"), "{id:?}: {err}");
+            assert!(id.parse::<JobId>().is_err(), "{id:?}");
+        }
+    }
+
+    #[test]
+    fn stack_path_joins_node_ids() {
+        let stacks = BlockJobStacks::new()
+            .stack(
+                JobId::random(),
+                "/pkg/flows/main/flow.oo.yaml".to_owned(),
+                NodeId::new("sub/1".to_owned()),
+            )
+            .stack(
+                JobId::random(),
+                "/pkg/subflows/sub/subflow.oo.yaml".to_owned(),
+                NodeId::new("100%".to_owned()),
+            );
+        assert_eq!(stack_path(stacks.vec()), "sub%2F1/100%25");
+        assert_eq!(stack_path(&[]), "");
+    }
+}
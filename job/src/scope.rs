@@ -18,6 +18,8 @@ pub struct RuntimeScope {
     pub node_id: Option<NodeId>,
     pub is_inject: bool,
     pub enable_layer: bool,
+    /// mount data_dir read-only in the layer. Without layer the blocks access data_dir directly, so it is ignored.
+    pub data_dir_readonly: bool,
 }
 
 impl RuntimeScope {
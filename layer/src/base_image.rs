@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use utils::error::{Error, Result};
+
+use crate::cli::exec;
+use crate::layer::{create_layer, delete_layer, list_layers, run_script_unmerge};
+use crate::ovmlayer::BindPath;
+
+static IMAGE_LAYER_PREFIX: &str = "image";
+
+/// OCI image a package layer is built on. The digest pins the image content, a package layer
+/// recorded with another digest is stale.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BaseImage {
+    pub reference: String,
+    pub digest: String,
+}
+
+impl BaseImage {
+    /// Layers are named by digest, package layers built on the same image share one.
+    fn layer_name(&self) -> String {
+        format!(
+            "{IMAGE_LAYER_PREFIX}_{}",
+            utils::calculate_short_hash(&self.digest, 32)
+        )
+    }
+}
+
+/// Where base images come from.
+pub trait ImageSource {
+    /// Make the image available locally and return its content digest.
+    fn pull(&self, reference: &str) -> Result<String>;
+    /// Write the image's root filesystem as a tar archive to `dest`.
+    fn export_rootfs(&self, reference: &str, dest: &Path) -> Result<()>;
+}
+
+/// Pulls images with the docker CLI.
+pub struct DockerImageSource;
+
+impl ImageSource for DockerImageSource {
+    fn pull(&self, reference: &str) -> Result<String> {
+        let mut pull = Command::new("docker");
+        pull.args(["pull", "--quiet", reference]);
+        exec(pull)?;
+
+        let mut inspect = Command::new("docker");
+        inspect.args(["image", "inspect", "--format", "{{.Id}}", reference]);
+        let output = exec(inspect)?;
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_owned())
+    }
+
+    fn export_rootfs(&self, reference: &str, dest: &Path) -> Result<()> {
+        let mut create = Command::new("docker");
+        create.args(["create", reference]);
+        let output = exec(create)?;
+        let container = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+
+        let mut export = Command::new("docker");
+        export
+            .args(["export", "--output"])
+            .arg(dest)
+            .arg(&container);
+        let result = exec(export).map(|_| ());
+
+        let mut remove = Command::new("docker");
+        remove.args(["rm", &container]);
+        if let Err(e) = exec(remove) {
+            tracing::warn!("failed to remove container {container}: {e}");
+        }
+        result
+    }
+}
+
+pub fn resolve_base_image(source: &dyn ImageSource, reference: &str) -> Result<BaseImage> {
+    let digest = source
+        .pull(reference)
+        .map_err(|e| Error::from(format!("Cannot obtain base image {reference}: {e}")))?;
+    if digest.is_empty() {
+        return Err(Error::from(format!(
+            "Cannot obtain base image {reference}: no digest reported"
+        )));
+    }
+    Ok(BaseImage {
+        reference: reference.to_owned(),
+        digest,
+    })
+}
+
+/// The layer holding the image's root filesystem, created on first use.
+#[instrument(skip_all)]
+pub(crate) fn base_image_layer(source: &dyn ImageSource, image: &BaseImage) -> Result<String> {
+    let layer = image.layer_name();
+    if list_layers(None)?.contains(&layer) {
+        return Ok(layer);
+    }
+
+    tracing::info!("creating layer {layer} from image {}", image.reference);
+    let rootfs = std::env::temp_dir().join(format!("{layer}.tar"));
+    let result = source
+        .export_rootfs(&image.reference, &rootfs)
+        .and_then(|_| extract_rootfs(&layer, &rootfs));
+    let _ = std::fs::remove_file(&rootfs);
+    result.map(|_| layer)
+}
+
+fn extract_rootfs(layer: &str, rootfs: &Path) -> Result<()> {
+    create_layer(layer)?;
+    let rootfs = rootfs.to_string_lossy().to_string();
+    let result = run_script_unmerge(
+        &[layer.to_owned()],
+        &[BindPath::new(&rootfs, &rootfs, true, false)],
+        &None,
+        &format!("tar -xf '{rootfs}' -C /"),
+        &HashMap::new(),
+        &None,
+    );
+    if result.is_err() {
+        let _ = delete_layer(layer);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockImageSource {
+        digest: Option<&'static str>,
+    }
+
+    impl ImageSource for MockImageSource {
+        fn pull(&self, reference: &str) -> Result<String> {
+            self.digest
+                .map(str::to_owned)
+                .ok_or_else(|| Error::from(format!("manifest for {reference} not found")))
+        }
+
+        fn export_rootfs(&self, _reference: &str, _dest: &Path) -> Result<()> {
+            unreachable!("resolving an image doesn't export it")
+        }
+    }
+
+    #[test]
+    fn test_resolve_base_image_records_digest() {
+        let image = resolve_base_image(
+            &MockImageSource {
+                digest: Some("sha256:0123abcd"),
+            },
+            "python:3.12-slim",
+        )
+        .unwrap();
+        assert_eq!(
+            image,
+            BaseImage {
+                reference: "python:3.12-slim".to_owned(),
+                digest: "sha256:0123abcd".to_owned(),
+            }
+        );
+        assert!(image.layer_name().starts_with("image_"));
+    }
+
+    #[test]
+    fn test_resolve_missing_base_image() {
+        let err = resolve_base_image(&MockImageSource { digest: None }, "missing:latest")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Cannot obtain base image missing:latest"));
+        assert!(err.contains("not found"));
+    }
+}
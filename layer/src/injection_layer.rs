@@ -12,10 +12,46 @@ pub struct InjectionLayer {
     pub package_path: String,
     pub package_version: String,
     pub layer_name: String,
+    /// Set once the scripts ran successfully in the layer, see [`injection_hash`].
+    #[serde(default)]
+    pub injection_hash: Option<String>,
 }
 
 static INJECTION_LAYER_PREFIX: &str = "injection";
 
+/// Identifies what an injection layer contains, a layer injected with the same hash is reused as is.
+pub fn injection_hash(
+    package_path: &str,
+    package_version: &str,
+    scripts: &[String],
+    nodes: &[String],
+) -> String {
+    let mut nodes = nodes.to_vec();
+    nodes.sort();
+    let key = serde_json::json!([package_path, package_version, scripts, nodes]);
+    utils::calculate_short_hash(&key.to_string(), 16)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InjectionPlan {
+    /// The layer is already injected with the same hash, only merge it.
+    Reuse(String),
+    /// Run the scripts in a new layer, the stale layer (if any) has to be deleted first.
+    Inject { stale_layer: Option<String> },
+}
+
+pub fn plan_injection(existing: Option<&InjectionLayer>, hash: &str) -> InjectionPlan {
+    match existing {
+        Some(layer) if layer.injection_hash.as_deref() == Some(hash) => {
+            InjectionPlan::Reuse(layer.layer_name.clone())
+        }
+        Some(layer) => InjectionPlan::Inject {
+            stale_layer: Some(layer.layer_name.clone()),
+        },
+        None => InjectionPlan::Inject { stale_layer: None },
+    }
+}
+
 impl InjectionLayer {
     pub fn new(
         flow_path: String,
@@ -34,18 +70,10 @@ impl InjectionLayer {
             package_path,
             package_version,
             layer_name: injection_layer_name.to_owned(),
+            injection_hash: None,
         }
     }
 
-    pub fn is_equal_scripts(&self, scripts: Vec<String>) -> bool {
-        let set_a = self
-            .scripts
-            .iter()
-            .collect::<std::collections::HashSet<_>>();
-        let set_b = scripts.iter().collect::<std::collections::HashSet<_>>();
-        set_a == set_b
-    }
-
     pub fn save_to_store(&self) -> Result<()> {
         let file = injection_store_path()?;
         let mut store = load_injection_store()?;
@@ -60,3 +88,77 @@ impl InjectionLayer {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn injected_layer(hash: Option<&str>) -> InjectionLayer {
+        InjectionLayer {
+            flow_path: "/flows/main.oo.yaml".to_string(),
+            scripts: vec!["pip install foo".to_string()],
+            package_path: "/pkgs/foo".to_string(),
+            package_version: "1.0.0".to_string(),
+            layer_name: "injection-abc".to_string(),
+            injection_hash: hash.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn identical_injection_reuses_the_layer() {
+        let scripts = vec!["pip install foo".to_string()];
+        let first = injection_hash("/pkgs/foo", "1.0.0", &scripts, &["a/main.py".to_string()]);
+        // the next session computes the same hash
+        let second = injection_hash("/pkgs/foo", "1.0.0", &scripts, &["a/main.py".to_string()]);
+        assert_eq!(first, second);
+
+        assert_eq!(
+            plan_injection(Some(&injected_layer(Some(&first))), &second),
+            InjectionPlan::Reuse("injection-abc".to_string())
+        );
+    }
+
+    #[test]
+    fn changed_injection_replaces_the_layer() {
+        let scripts = vec!["pip install foo".to_string()];
+        let nodes = vec!["a/main.py".to_string(), "b/main.py".to_string()];
+        let hash = injection_hash("/pkgs/foo", "1.0.0", &scripts, &nodes);
+
+        let reordered = vec!["b/main.py".to_string(), "a/main.py".to_string()];
+        assert_eq!(
+            hash,
+            injection_hash("/pkgs/foo", "1.0.0", &scripts, &reordered)
+        );
+        for changed in [
+            injection_hash("/pkgs/bar", "1.0.0", &scripts, &nodes),
+            injection_hash("/pkgs/foo", "1.0.1", &scripts, &nodes),
+            injection_hash(
+                "/pkgs/foo",
+                "1.0.0",
+                &["pip install bar".to_string()],
+                &nodes,
+            ),
+            injection_hash("/pkgs/foo", "1.0.0", &scripts, &nodes[..1]),
+        ] {
+            assert_ne!(hash, changed);
+            assert_eq!(
+                plan_injection(Some(&injected_layer(Some(&hash))), &changed),
+                InjectionPlan::Inject {
+                    stale_layer: Some("injection-abc".to_string())
+                }
+            );
+        }
+
+        // scripts failed or the layer was created before hashes were recorded
+        assert_eq!(
+            plan_injection(Some(&injected_layer(None)), &hash),
+            InjectionPlan::Inject {
+                stale_layer: Some("injection-abc".to_string())
+            }
+        );
+        assert_eq!(
+            plan_injection(None, &hash),
+            InjectionPlan::Inject { stale_layer: None }
+        );
+    }
+}
@@ -1,3 +1,4 @@
+mod base_image;
 mod cli;
 mod external_layer_store;
 mod injection_layer;
@@ -11,15 +12,20 @@ mod runtime_layer;
 
 use std::process::Command;
 
+pub use base_image::{BaseImage, DockerImageSource, ImageSource, resolve_base_image};
 pub use external_layer_store::{
     ExternalLayerStatus, ExternalLayerStore, create_external_layer, delete_external_layer,
     external_layer_status, get_external_layer, list_external_layers, load_external_store,
 };
-pub use ovmlayer::BindPath;
-pub use package_layer::{import_package_layer, move_package_layer};
+pub use ovmlayer::{BindPath, MountType};
+pub use package_layer::{
+    ImportSummary, import_package_layer, import_package_layers, move_package_layer,
+};
 pub use package_store::{
-    PackageLayerStatus, delete_all_layer_data, delete_package_layer, get_or_create_package_layer,
-    list_package_layers, package_layer_status,
+    PackageLayerInfo, PackageLayerStatus, create_package_layer_from_image,
+    create_package_layer_from_lockfile, delete_all_layer_data, delete_package_layer,
+    get_or_create_package_layer, list_package_layers, list_package_layers_detailed, lockfile_hash,
+    package_layer_status,
 };
 pub use runtime_layer::{InjectionParams, RuntimeLayer, create_runtime_layer};
 
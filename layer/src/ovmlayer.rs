@@ -1,5 +1,5 @@
 use core::str;
-use std::{collections::HashMap, fmt, process::Command};
+use std::{collections::HashMap, fmt, path::Path, process::Command};
 use users::get_current_uid;
 
 pub fn is_root() -> bool {
@@ -101,10 +101,14 @@ pub fn unmerge_cmd(merge_point: &str) -> Command {
 
 #[derive(Debug, Clone)]
 pub struct BindPath {
+    /// Host path, empty for a tmpfs mount.
     pub src: String,
     pub dst: String,
     pub permission: Permission,
     pub bind_option: BindOption,
+    /// Paths under `src`, relative to it, that are left out of the bind, e.g. `node_modules`.
+    pub exclude: Vec<String>,
+    pub mount_type: MountType,
 }
 
 impl BindPath {
@@ -124,8 +128,98 @@ impl BindPath {
             dst: dst.to_string(),
             permission,
             bind_option,
+            exclude: vec![],
+            mount_type: MountType::Bind,
         }
     }
+
+    pub fn with_exclude(mut self, exclude: Vec<String>) -> Self {
+        self.exclude = exclude;
+        self
+    }
+
+    /// Scratch space at `dst` that lives in memory, not backed by any host path. `size` takes
+    /// a `k`, `m` or `g` suffix like `512M`, without it the mount gets ovmlayer's default size.
+    pub fn tmpfs(dst: &str, size: Option<&str>) -> Self {
+        BindPath {
+            src: String::new(),
+            dst: dst.to_string(),
+            permission: Permission::ReadWrite,
+            bind_option: BindOption::NonRecursive,
+            exclude: vec![],
+            mount_type: MountType::Tmpfs {
+                size: size.map(|size| size.to_string()),
+            },
+        }
+    }
+
+    pub fn is_tmpfs(&self) -> bool {
+        matches!(self.mount_type, MountType::Tmpfs { .. })
+    }
+
+    /// Whether what gets mounted is there, a tmpfs mount needs nothing on the host.
+    pub fn source_exists(&self) -> bool {
+        self.is_tmpfs() || std::fs::metadata(&self.src).is_ok()
+    }
+
+    /// The mounts passed to ovmlayer for this path. ovmlayer can't leave paths out of a bind, so
+    /// a bind with `exclude` becomes one bind per entry of `src` that isn't excluded, going down
+    /// into the directories an excluded path is nested in.
+    pub fn mounts(&self) -> Vec<BindPath> {
+        if self.exclude.is_empty() || !Path::new(&self.src).is_dir() {
+            return vec![self.clone().with_exclude(vec![])];
+        }
+        let entries = match std::fs::read_dir(&self.src) {
+            Ok(entries) => entries,
+            Err(e) => {
+                tracing::warn!("failed to read {} to exclude paths from it: {e}", self.src);
+                return vec![self.clone().with_exclude(vec![])];
+            }
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+
+        let mut mounts = vec![];
+        for name in names {
+            let mut nested = vec![];
+            let mut excluded = false;
+            for pattern in &self.exclude {
+                let pattern = pattern.trim_matches('/');
+                match pattern.split_once('/') {
+                    Some((dir, rest)) if dir == name => nested.push(rest.to_string()),
+                    None if pattern == name => excluded = true,
+                    _ => {}
+                }
+            }
+            if excluded {
+                continue;
+            }
+            let child = BindPath {
+                src: Path::new(&self.src)
+                    .join(&name)
+                    .to_string_lossy()
+                    .to_string(),
+                dst: Path::new(&self.dst)
+                    .join(&name)
+                    .to_string_lossy()
+                    .to_string(),
+                exclude: nested,
+                ..self.clone()
+            };
+            mounts.extend(child.mounts());
+        }
+        mounts
+    }
+}
+
+fn is_valid_tmpfs_size(size: &str) -> bool {
+    let digits = size.trim_end_matches(['k', 'K', 'm', 'M', 'g', 'G']);
+    size.len() - digits.len() <= 1
+        && !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit())
 }
 
 impl TryFrom<&str> for BindPath {
@@ -138,8 +232,21 @@ impl TryFrom<&str> for BindPath {
         let mut dst = None;
         let mut readonly = None;
         let mut recursive = None;
+        let mut exclude = vec![];
+        let mut mount_type = None;
+        let mut size = None;
         for part in &parts {
-            if let Some(stripped) = part.strip_prefix("src=") {
+            if let Some(stripped) = part.strip_prefix("type=") {
+                if mount_type.is_some() || !matches!(stripped, "bind" | "tmpfs") {
+                    return Err(format!("Invalid BindPath format: {path}"));
+                }
+                mount_type = Some(stripped);
+            } else if let Some(stripped) = part.strip_prefix("size=") {
+                if !is_valid_tmpfs_size(stripped) {
+                    return Err(format!("Invalid tmpfs size {stripped} in BindPath: {path}"));
+                }
+                size = Some(stripped);
+            } else if let Some(stripped) = part.strip_prefix("src=") {
                 src = stripped.to_string().into();
             } else if let Some(stripped) = part.strip_prefix("dst=") {
                 dst = stripped.to_string().into();
@@ -151,18 +258,43 @@ impl TryFrom<&str> for BindPath {
                 recursive = Some(true);
             } else if *part == "nonrecursive" && recursive.is_none() {
                 recursive = Some(false);
+            } else if let Some(stripped) = part.strip_prefix("exclude=") {
+                exclude.extend(
+                    stripped
+                        .split(':')
+                        .filter(|pattern| !pattern.is_empty())
+                        .map(|pattern| pattern.to_string()),
+                );
             } else {
                 return Err(format!("Invalid BindPath format: {path}"));
             }
         }
 
+        if mount_type == Some("tmpfs") {
+            return match (src, dst) {
+                (None, Some(dst)) if exclude.is_empty() => Ok(BindPath::tmpfs(&dst, size)),
+                (None, Some(_)) => Err(format!(
+                    "Invalid BindPath format: {path}. tmpfs mount can't exclude paths"
+                )),
+                (Some(_), _) => Err(format!(
+                    "Invalid BindPath format: {path}. tmpfs mount takes no src"
+                )),
+                (None, None) => Err(format!("Invalid BindPath format: {path}. Missing dst")),
+            };
+        }
+        if size.is_some() {
+            return Err(format!(
+                "Invalid BindPath format: {path}. size is only for tmpfs mount"
+            ));
+        }
+
         if let (Some(src), Some(dst), readonly, recursive) = (
             src,
             dst,
             readonly.unwrap_or(false),
             recursive.unwrap_or(false),
         ) {
-            Ok(BindPath::new(&src, &dst, readonly, recursive))
+            Ok(BindPath::new(&src, &dst, readonly, recursive).with_exclude(exclude))
         } else {
             Err(format!(
                 "Invalid BindPath format: {path}. Missing src or dst"
@@ -173,14 +305,33 @@ impl TryFrom<&str> for BindPath {
 
 impl fmt::Display for BindPath {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let MountType::Tmpfs { size } = &self.mount_type {
+            write!(f, "type=tmpfs,dst={}", self.dst)?;
+            if let Some(size) = size {
+                write!(f, ",size={size}")?;
+            }
+            return Ok(());
+        }
         write!(
             f,
             "type=bind,src={},dst={},{},{}",
             self.src, self.dst, self.permission, self.bind_option
-        )
+        )?;
+        if !self.exclude.is_empty() {
+            write!(f, ",exclude={}", self.exclude.join(":"))?;
+        }
+        Ok(())
     }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum MountType {
+    /// A host path bound into the layer.
+    Bind,
+    /// An in-memory filesystem, gone when the command exits.
+    Tmpfs { size: Option<String> },
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Permission {
     Readonly,
@@ -235,7 +386,7 @@ pub fn run_cmd(
     let mut binding = ovmlayer_bin();
     let mut options = vec![format!("run"), format!("--all-devices")];
 
-    for bind_path in mount_paths {
+    for bind_path in mount_paths.iter().flat_map(BindPath::mounts) {
         options.push(format!("--mount={bind_path}"));
     }
 
@@ -324,6 +475,48 @@ mod tests {
         assert_eq!(bind_path.bind_option, BindOption::NonRecursive);
     }
 
+    #[test]
+    fn test_bind_path_with_exclude() {
+        let path = "src=/pkg,dst=/pkg,ro,recursive,exclude=node_modules:.git";
+        let bind_path = BindPath::try_from(path).unwrap();
+        assert_eq!(bind_path.exclude, vec!["node_modules", ".git"]);
+        assert_eq!(
+            bind_path.to_string(),
+            "type=bind,src=/pkg,dst=/pkg,ro,recursive,exclude=node_modules:.git"
+        );
+        assert!(BindPath::try_from("type=tmpfs,dst=/scratch,exclude=cache").is_err());
+    }
+
+    #[test]
+    fn test_run_cmd_mounts_what_exclude_leaves() {
+        let src = std::env::temp_dir().join(format!("oocana-bind-exclude-{}", std::process::id()));
+        for dir in ["node_modules/lodash", "src/vendor", "src/lib", ".git"] {
+            std::fs::create_dir_all(src.join(dir)).unwrap();
+        }
+        std::fs::write(src.join("package.json"), "{}").unwrap();
+        let src_str = src.to_string_lossy().to_string();
+        let bind_path = BindPath::new(&src_str, "/pkg", true, true).with_exclude(vec![
+            "node_modules".to_string(),
+            ".git".to_string(),
+            "src/vendor".to_string(),
+        ]);
+
+        let cmd = run_cmd("merge_point", &[bind_path], &None, &HashMap::new(), &None);
+        let mounts: Vec<String> = cmd
+            .get_args()
+            .filter_map(|arg| arg.to_str()?.strip_prefix("--mount=").map(str::to_string))
+            .collect();
+        std::fs::remove_dir_all(&src).unwrap();
+
+        assert_eq!(
+            mounts,
+            vec![
+                format!("type=bind,src={src_str}/package.json,dst=/pkg/package.json,ro,recursive"),
+                format!("type=bind,src={src_str}/src/lib,dst=/pkg/src/lib,ro,recursive"),
+            ]
+        );
+    }
+
     #[test]
     fn test_bind_path_invalid() {
         let path = "src=/tmp,dst=/tmp,invalid_option";
@@ -345,6 +538,32 @@ mod tests {
         assert!(bind_path.is_err());
     }
 
+    #[test]
+    fn test_bind_path_tmpfs() {
+        let bind_path = BindPath::try_from("type=tmpfs,size=512M,dst=/scratch").unwrap();
+        assert_eq!(bind_path.dst, "/scratch");
+        assert_eq!(
+            bind_path.mount_type,
+            MountType::Tmpfs {
+                size: Some("512M".to_string())
+            }
+        );
+        assert!(bind_path.source_exists());
+        assert_eq!(bind_path.to_string(), "type=tmpfs,dst=/scratch,size=512M");
+
+        let bind_path = BindPath::try_from("type=tmpfs,dst=/scratch").unwrap();
+        assert_eq!(bind_path.mount_type, MountType::Tmpfs { size: None });
+
+        let bind_path = BindPath::try_from("type=bind,src=/tmp,dst=/tmp").unwrap();
+        assert_eq!(bind_path.mount_type, MountType::Bind);
+
+        assert!(BindPath::try_from("type=tmpfs,src=/tmp,dst=/scratch").is_err());
+        assert!(BindPath::try_from("type=tmpfs,size=512M").is_err());
+        assert!(BindPath::try_from("type=tmpfs,size=lots,dst=/scratch").is_err());
+        assert!(BindPath::try_from("src=/tmp,dst=/tmp,size=512M").is_err());
+        assert!(BindPath::try_from("type=overlay,dst=/scratch").is_err());
+    }
+
     #[test]
     fn test_bind_path_display() {
         let bind_path = BindPath::new("/tmp", "/tmp", true, true);
@@ -353,4 +572,28 @@ mod tests {
             "type=bind,src=/tmp,dst=/tmp,ro,recursive"
         );
     }
+
+    #[test]
+    fn test_run_cmd_env_file_values_stay_off_the_command_line() {
+        let cmd = run_cmd(
+            "merge_point",
+            &[],
+            &None,
+            &HashMap::new(),
+            &Some("/tmp/executor.env".to_string()),
+        );
+        let args: Vec<String> = cmd
+            .get_args()
+            .map(|arg| arg.to_string_lossy().to_string())
+            .collect();
+
+        assert!(args.contains(&"--env-file=/tmp/executor.env".to_string()));
+        let envs: Vec<&String> = args
+            .iter()
+            .zip(args.iter().skip(1))
+            .filter(|(flag, _)| *flag == "--env")
+            .map(|(_, value)| value)
+            .collect();
+        assert_eq!(envs, vec!["DEBIAN_FRONTEND=noninteractive"]);
+    }
 }
@@ -1,14 +1,21 @@
 use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::fs::{File, metadata};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::base_image::BaseImage;
 use crate::cli::exec;
 use crate::layer::{
     create_random_layer, export_layers, import_layer, list_layers, move_layer, run_script_unmerge,
 };
 use crate::ovmlayer::{BindPath, cp_to_layer};
-use crate::package_store::{add_import_package, get_package_layer, remove_package_from_store};
+use crate::package_store::{
+    PackageLayerStatus, add_import_package, get_package_layer, package_layer_status,
+    remove_package_from_store,
+};
 use manifest_reader::reader::read_package_identity;
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
@@ -47,6 +54,15 @@ pub struct PackageLayer {
     pub bootstrap: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub bootstrap_layer: Option<String>,
+    /// sha256 of the lockfile the layer was built from, see `create_package_layer_from_lockfile`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lockfile_hash: Option<String>,
+    /// Image the layer is built on, see `create_package_layer_from_image`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_image: Option<BaseImage>,
+    /// Unix seconds when the layer was created, missing for layers created by older versions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<u64>,
     pub package_path: PathBuf,
 }
 
@@ -98,7 +114,7 @@ impl PackageLayer {
         }
 
         for bind_path in bind_paths {
-            if metadata(&bind_path.src).is_err() {
+            if !bind_path.source_exists() {
                 tracing::warn!("passing bind paths {:?} is not exist", bind_path.src);
                 continue;
             }
@@ -136,6 +152,12 @@ impl PackageLayer {
             source_layer,
             bootstrap,
             bootstrap_layer,
+            lockfile_hash: None,
+            base_image: None,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|d| d.as_secs()),
             package_path,
         })
     }
@@ -254,6 +276,81 @@ fi
     Ok(())
 }
 
+/// Upper bound of concurrent imports in [`import_package_layers`], every import spawns
+/// `ovmlayer` processes.
+const MAX_IMPORT_WORKERS: usize = 4;
+
+#[derive(Debug, Default)]
+pub struct ImportSummary {
+    pub succeeded: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, String)>,
+    /// Export dirs whose package layer already exists in the store.
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Import exported package layers in parallel. Each export dir is imported to the package path
+/// it was exported from. A failed import doesn't stop the others.
+pub fn import_package_layers(paths: &[PathBuf]) -> ImportSummary {
+    let workers = std::thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(MAX_IMPORT_WORKERS)
+        .min(paths.len());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(paths.len()));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(export_dir) = paths.get(index) else {
+                        break;
+                    };
+                    let result = import_exported_package_layer(export_dir);
+                    results.lock().unwrap().push((export_dir.clone(), result));
+                }
+            });
+        }
+    });
+
+    let mut summary = ImportSummary::default();
+    for (export_dir, result) in results.into_inner().unwrap() {
+        match result {
+            Ok(true) => summary.succeeded.push(export_dir),
+            Ok(false) => summary.skipped.push(export_dir),
+            Err(e) => {
+                tracing::warn!(
+                    "failed to import package layer {}: {e}",
+                    export_dir.display()
+                );
+                summary.failed.push((export_dir, e.to_string()));
+            }
+        }
+    }
+    summary
+}
+
+/// `Ok(false)` when the package layer is already in the store.
+fn import_exported_package_layer(export_dir: &Path) -> Result<bool> {
+    let package_file_path = export_dir.join(PACKAGE_FILENAME);
+    let package_file = File::open(&package_file_path)
+        .map_err(|e| format!("Failed to open {}: {e}", package_file_path.display()))?;
+    let package: PackageLayer = serde_json::from_reader(std::io::BufReader::new(package_file))?;
+
+    if matches!(
+        package_layer_status(&package.package_path),
+        Ok(PackageLayerStatus::Exist)
+    ) {
+        return Ok(false);
+    }
+
+    import_package_layer(
+        &package.package_path.to_string_lossy(),
+        &export_dir.to_string_lossy(),
+    )?;
+    Ok(true)
+}
+
 fn migrate_package_store_to_external(package: &PackageLayer) -> Result<()> {
     let package_name = package
         .name
@@ -461,6 +558,66 @@ mod tests {
         assert_eq!(diff.len(), 0);
     }
 
+    #[test]
+    fn test_import_package_layers_collects_every_failure() {
+        use super::*;
+
+        let temp_root = std::env::temp_dir().join(crate::layer::random_name("import_layers_test"));
+        let missing_dir = temp_root.join("missing");
+        let incomplete_dir = temp_root.join("incomplete");
+        std::fs::create_dir_all(&incomplete_dir).unwrap();
+        let package = PackageLayer {
+            package_path: temp_root.join("package"),
+            ..Default::default()
+        };
+        std::fs::write(
+            incomplete_dir.join(PACKAGE_FILENAME),
+            serde_json::to_string(&package).unwrap(),
+        )
+        .unwrap();
+
+        let summary = import_package_layers(&[missing_dir.clone(), incomplete_dir.clone()]);
+        let _ = std::fs::remove_dir_all(&temp_root);
+
+        assert!(summary.succeeded.is_empty());
+        assert!(summary.skipped.is_empty());
+        let mut failed: Vec<PathBuf> = summary.failed.into_iter().map(|(dir, _)| dir).collect();
+        failed.sort();
+        assert_eq!(failed, vec![incomplete_dir, missing_dir]);
+    }
+
+    #[test]
+    fn test_list_package_layers_detailed_reads_store_metadata() {
+        use super::*;
+
+        let temp_root = std::env::temp_dir().join(crate::layer::random_name("layer_info_test"));
+        let _guard = TestStoreGuard::new(&temp_root);
+        let package_path = temp_root.join("package");
+        std::fs::create_dir_all(package_path.join("src")).unwrap();
+        std::fs::write(package_path.join("src").join("main.py"), "print(1)\n").unwrap();
+        add_import_package(&PackageLayer {
+            name: Some("demo".to_string()),
+            version: Some("1.0.0".to_string()),
+            created_at: Some(1_700_000_000),
+            package_path: package_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let infos = crate::package_store::list_package_layers_detailed().unwrap();
+        let _ = std::fs::remove_dir_all(&temp_root);
+
+        assert_eq!(infos.len(), 1);
+        let info = &infos[0];
+        assert_eq!(info.path, package_path);
+        // no package file, the layer can't be matched to a package version
+        assert_eq!(info.status, PackageLayerStatus::NotInStore);
+        assert_eq!(info.package_size_bytes, Some(9));
+        assert_eq!(info.created_at, Some(1_700_000_000));
+        assert_eq!(info.package_name.as_deref(), Some("demo"));
+        assert_eq!(info.package_version.as_deref(), Some("1.0.0"));
+    }
+
     #[test]
     fn test_package_layers_skip_empty_names() {
         use super::*;
@@ -472,6 +629,9 @@ mod tests {
             source_layer: "".to_string(),
             bootstrap: None,
             bootstrap_layer: Some("bootstrap".to_string()),
+            lockfile_hash: None,
+            base_image: None,
+            created_at: None,
             package_path: PathBuf::from("/tmp/package"),
         };
 
@@ -568,6 +728,9 @@ mod tests {
             source_layer: "layer_source".to_string(),
             bootstrap: None,
             bootstrap_layer: Some("layer_bootstrap".to_string()),
+            lockfile_hash: None,
+            base_image: None,
+            created_at: None,
             package_path: package_path.clone(),
         };
 
@@ -621,6 +784,9 @@ mod tests {
             source_layer: new_source_layer.clone(),
             bootstrap: None,
             bootstrap_layer: Some(new_bootstrap_layer.clone()),
+            lockfile_hash: None,
+            base_image: None,
+            created_at: None,
             package_path: package_path.clone(),
         };
         let existing_external_package = PackageLayer {
@@ -630,6 +796,9 @@ mod tests {
             source_layer: old_source_layer.clone(),
             bootstrap: None,
             bootstrap_layer: Some(old_bootstrap_layer.clone()),
+            lockfile_hash: None,
+            base_image: None,
+            created_at: None,
             package_path: PathBuf::from("/tmp/old-import-external-conflict"),
         };
 
@@ -696,6 +865,9 @@ mod tests {
             source_layer: "layer_source".to_string(),
             bootstrap: None,
             bootstrap_layer: Some("layer_bootstrap".to_string()),
+            lockfile_hash: None,
+            base_image: None,
+            created_at: None,
             package_path: package_path.clone(),
         };
 
@@ -761,6 +933,9 @@ mod tests {
             source_layer: "new_layer_source".to_string(),
             bootstrap: None,
             bootstrap_layer: Some("new_layer_bootstrap".to_string()),
+            lockfile_hash: None,
+            base_image: None,
+            created_at: None,
             package_path: package_path.clone(),
         };
         let existing_external_package = PackageLayer {
@@ -770,6 +945,9 @@ mod tests {
             source_layer: "old_layer_source".to_string(),
             bootstrap: None,
             bootstrap_layer: Some("old_layer_bootstrap".to_string()),
+            lockfile_hash: None,
+            base_image: None,
+            created_at: None,
             package_path: PathBuf::from("/tmp/old-import-external-restore"),
         };
 
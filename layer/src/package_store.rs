@@ -1,3 +1,4 @@
+use crate::base_image::{BaseImage, ImageSource, base_image_layer, resolve_base_image};
 use crate::injection_store::load_injection_store;
 use crate::layer;
 use crate::ovmlayer::{BindPath, LayerType};
@@ -10,7 +11,8 @@ use manifest_reader::reader::read_package;
 use serde::{Deserialize, Serialize};
 use std::env;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::{collections::HashMap, fs};
 use utils::{
     config,
@@ -18,6 +20,7 @@ use utils::{
 };
 
 static PACKAGE_STORE: &str = "package_store.json";
+const LOCKFILE_ENV_KEY: &str = "OOCANA_LOCKFILE";
 
 struct Defer<F: FnOnce()>(Option<F>);
 impl<F: FnOnce()> Drop for Defer<F> {
@@ -39,7 +42,7 @@ fn package_meta<P: AsRef<Path>>(dir: P) -> Result<Package> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 pub enum PackageLayerStatus {
     NotInStore,
     Exist,
@@ -87,7 +90,124 @@ pub fn get_or_create_package_layer<P: AsRef<Path>>(
     envs: &HashMap<String, String>,
     env_file: &Option<String>,
 ) -> Result<PackageLayer> {
-    let package_path = package_path.as_ref();
+    get_or_create_package_layer_inner(package_path.as_ref(), None, None, bind_path, envs, env_file)
+}
+
+/// sha256 of the lockfile content.
+pub fn lockfile_hash<P: AsRef<Path>>(lockfile: P) -> Result<String> {
+    let lockfile = lockfile.as_ref();
+    let content = fs::read_to_string(lockfile).map_err(|e| {
+        Error::new(&format!(
+            "Failed to read lockfile {}: {e}",
+            lockfile.display()
+        ))
+    })?;
+    Ok(utils::calculate_short_hash(&content, 64))
+}
+
+/// Like `get_or_create_package_layer`, but the bootstrap script runs against a pinned lockfile.
+/// The lockfile is bound read-only into the layer and its path is exposed to bootstrap as
+/// `OOCANA_LOCKFILE`. The lockfile hash is recorded in the package layer, an existing layer
+/// built from a different lockfile is treated as stale and recreated.
+pub fn create_package_layer_from_lockfile<P: AsRef<Path>, L: AsRef<Path>>(
+    package_path: P,
+    lockfile: L,
+    bind_path: &[BindPath],
+    envs: &HashMap<String, String>,
+    env_file: &Option<String>,
+) -> Result<PackageLayer> {
+    let mut bind_path = bind_path.to_vec();
+    let mut envs = envs.clone();
+    let hash = pin_lockfile(lockfile.as_ref(), &mut bind_path, &mut envs)?;
+
+    get_or_create_package_layer_inner(
+        package_path.as_ref(),
+        Some(hash),
+        None,
+        &bind_path,
+        &envs,
+        env_file,
+    )
+}
+
+/// Like `get_or_create_package_layer`, but the package is installed on top of the root
+/// filesystem of an OCI image, optionally against a pinned lockfile. The image digest is recorded
+/// in the package layer, a layer built from another digest is treated as stale and recreated.
+/// `source` pulls the image, usually [`DockerImageSource`](crate::DockerImageSource).
+pub fn create_package_layer_from_image<P: AsRef<Path>>(
+    package_path: P,
+    source: &dyn ImageSource,
+    image: &str,
+    lockfile: Option<&Path>,
+    bind_path: &[BindPath],
+    envs: &HashMap<String, String>,
+    env_file: &Option<String>,
+) -> Result<PackageLayer> {
+    let base_image = resolve_base_image(source, image)?;
+    let mut bind_path = bind_path.to_vec();
+    let mut envs = envs.clone();
+    let hash = lockfile
+        .map(|lockfile| pin_lockfile(lockfile, &mut bind_path, &mut envs))
+        .transpose()?;
+
+    get_or_create_package_layer_inner(
+        package_path.as_ref(),
+        hash,
+        Some((base_image, source)),
+        &bind_path,
+        &envs,
+        env_file,
+    )
+}
+
+/// Bind the lockfile into the layer and point bootstrap to it, returns the lockfile hash.
+fn pin_lockfile(
+    lockfile: &Path,
+    bind_path: &mut Vec<BindPath>,
+    envs: &mut HashMap<String, String>,
+) -> Result<String> {
+    if !lockfile.is_file() {
+        return Err(Error::new(&format!(
+            "lockfile not found: {}",
+            lockfile.display()
+        )));
+    }
+    let lockfile = lockfile.canonicalize()?;
+    let hash = lockfile_hash(&lockfile)?;
+
+    let lockfile_str = lockfile.to_string_lossy().to_string();
+    bind_path.push(BindPath::new(&lockfile_str, &lockfile_str, true, false));
+    envs.insert(LOCKFILE_ENV_KEY.to_string(), lockfile_str);
+    Ok(hash)
+}
+
+/// Whether a stored layer was built from the same package version and pins. Pins that weren't
+/// asked for aren't compared.
+fn layer_matches(
+    layer: &PackageLayer,
+    version: &Option<String>,
+    lockfile_hash: &Option<String>,
+    base_image: &Option<BaseImage>,
+) -> bool {
+    layer.version == *version
+        && (lockfile_hash.is_none() || layer.lockfile_hash == *lockfile_hash)
+        && base_image.as_ref().is_none_or(|image| {
+            layer
+                .base_image
+                .as_ref()
+                .is_some_and(|base| base.digest == image.digest)
+        })
+}
+
+fn get_or_create_package_layer_inner(
+    package_path: &Path,
+    lockfile_hash: Option<String>,
+    base_image: Option<(BaseImage, &dyn ImageSource)>,
+    bind_path: &[BindPath],
+    envs: &HashMap<String, String>,
+    env_file: &Option<String>,
+) -> Result<PackageLayer> {
+    let (base_image, image_source) = base_image.unzip();
     let pkg = package_meta(package_path)?;
     let version = pkg.version;
     let bootstrap = pkg.scripts.and_then(|s| s.bootstrap);
@@ -109,7 +229,7 @@ pub fn get_or_create_package_layer<P: AsRef<Path>>(
     let store = load_package_store()?;
 
     if let Some(p) = store.packages.get(&key) {
-        if p.version == version && p.validate().is_ok() {
+        if layer_matches(p, &version, &lockfile_hash, &base_image) && p.validate().is_ok() {
             return Ok(p.clone());
         }
     }
@@ -121,15 +241,24 @@ pub fn get_or_create_package_layer<P: AsRef<Path>>(
         version
     );
 
-    let layer = PackageLayer::create(
+    let base_layers = base_image
+        .as_ref()
+        .zip(image_source)
+        .map(|(image, source)| base_image_layer(source, image))
+        .transpose()?
+        .map(|layer| vec![layer]);
+
+    let mut layer = PackageLayer::create(
         version,
-        None,
+        base_layers,
         bootstrap,
         bind_path,
         package_path.to_path_buf(),
         envs,
         env_file,
     )?;
+    layer.lockfile_hash = lockfile_hash;
+    layer.base_image = base_image;
 
     // avoid race condition, just reload and save
     let mut store = load_package_store()?;
@@ -218,6 +347,49 @@ pub fn list_package_layers() -> Result<Vec<PackageLayer>> {
     Ok(layers)
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageLayerInfo {
+    pub path: PathBuf,
+    pub status: PackageLayerStatus,
+    /// Size of the package directory on the host the layer was created from, not of the layer
+    /// itself, `None` when the directory is gone.
+    pub package_size_bytes: Option<u64>,
+    /// Unix seconds, `None` for layers created before it was recorded.
+    pub created_at: Option<u64>,
+    pub package_name: Option<String>,
+    pub package_version: Option<String>,
+}
+
+/// Like [`list_package_layers`], with the status and metadata a layer listing shows.
+pub fn list_package_layers_detailed() -> Result<Vec<PackageLayerInfo>> {
+    let mut infos: Vec<PackageLayerInfo> = list_package_layers()?
+        .into_iter()
+        .map(|layer| PackageLayerInfo {
+            status: package_layer_status(&layer.package_path)
+                .unwrap_or(PackageLayerStatus::NotInStore),
+            package_size_bytes: dir_size(&layer.package_path).ok(),
+            created_at: layer.created_at,
+            package_name: layer.name,
+            package_version: layer.version,
+            path: layer.package_path,
+        })
+        .collect();
+    infos.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(infos)
+}
+
+fn dir_size(path: &Path) -> std::io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut size = 0;
+    for entry in fs::read_dir(path)? {
+        size += dir_size(&entry?.path())?;
+    }
+    Ok(size)
+}
+
 pub fn get_package_layer<P: AsRef<Path>>(package_path: P) -> Result<Option<PackageLayer>> {
     let package_path = package_path.as_ref();
     let store = load_package_store()?;
@@ -292,6 +464,9 @@ pub fn load_package_store() -> Result<PackageLayerStore> {
 }
 
 pub fn add_import_package(pkg: &PackageLayer) -> Result<()> {
+    // imports can run in parallel, load and save of the store must not interleave
+    static STORE_UPDATE: Mutex<()> = Mutex::new(());
+    let _guard = STORE_UPDATE.lock()?;
     let mut store = load_package_store()?;
 
     store
@@ -387,6 +562,94 @@ pub struct PackageLayerStore {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_lockfile_hash() {
+        let lockfile = std::env::temp_dir().join(crate::layer::random_name("lockfile_hash"));
+        fs::write(&lockfile, "requests==2.31.0\n").unwrap();
+        let hash = lockfile_hash(&lockfile).unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(hash, utils::calculate_short_hash("requests==2.31.0\n", 64));
+        fs::remove_file(lockfile).unwrap();
+    }
+
+    #[test]
+    fn test_create_from_missing_lockfile() {
+        let r = create_package_layer_from_lockfile(
+            "/tmp/not-a-package",
+            "/tmp/oocana-missing.lock",
+            &[],
+            &HashMap::new(),
+            &None,
+        );
+        assert!(r.unwrap_err().to_string().contains("lockfile not found"));
+    }
+
+    struct UnreachableRegistry;
+
+    impl ImageSource for UnreachableRegistry {
+        fn pull(&self, reference: &str) -> Result<String> {
+            Err(Error::new(&format!("{reference}: registry unreachable")))
+        }
+
+        fn export_rootfs(&self, _reference: &str, _dest: &Path) -> Result<()> {
+            unreachable!("an image that can't be pulled isn't exported")
+        }
+    }
+
+    #[test]
+    fn test_create_from_unobtainable_image() {
+        let r = create_package_layer_from_image(
+            "/tmp/not-a-package",
+            &UnreachableRegistry,
+            "python:3.12-slim",
+            None,
+            &[],
+            &HashMap::new(),
+            &None,
+        );
+        let err = r.unwrap_err().to_string();
+        assert!(err.contains("Cannot obtain base image python:3.12-slim"));
+        assert!(err.contains("registry unreachable"));
+    }
+
+    #[test]
+    fn test_layer_matches_base_image_digest() {
+        let image = |digest: &str| BaseImage {
+            reference: "python:3.12-slim".to_owned(),
+            digest: digest.to_owned(),
+        };
+        let layer = PackageLayer {
+            version: Some("1.0.0".to_owned()),
+            base_image: Some(image("sha256:aaa")),
+            ..Default::default()
+        };
+        let version = Some("1.0.0".to_owned());
+
+        assert!(layer_matches(
+            &layer,
+            &version,
+            &None,
+            &Some(image("sha256:aaa"))
+        ));
+        assert!(!layer_matches(
+            &layer,
+            &version,
+            &None,
+            &Some(image("sha256:bbb"))
+        ));
+        assert!(layer_matches(&layer, &version, &None, &None));
+        assert!(!layer_matches(
+            &PackageLayer::default(),
+            &None,
+            &None,
+            &Some(image("sha256:aaa"))
+        ));
+
+        let stored: PackageLayer =
+            serde_json::from_str(&serde_json::to_string(&layer).unwrap()).unwrap();
+        assert_eq!(stored.base_image, Some(image("sha256:aaa")));
+    }
+
     #[test]
     fn test_clean_layers() {
         let r = clean_layer_not_in_store();
@@ -1,5 +1,5 @@
 use crate::external_layer_store::get_external_layer;
-use crate::injection_layer::InjectionLayer;
+use crate::injection_layer::{InjectionLayer, InjectionPlan, injection_hash, plan_injection};
 use crate::injection_store::get_injection_layer;
 use crate::layer::{
     create_tmp_layer, delete_layer, merge_layer, random_merge_point, random_name, unmerge,
@@ -102,6 +102,8 @@ pub struct InjectionParams<'a> {
     pub package_version: &'a str,
     pub scripts: &'a Vec<String>,
     pub flow_path: &'a str,
+    /// Entries of the nodes injected into the package, relative to the package.
+    pub nodes: &'a [String],
 }
 
 fn create_runtime_layer_from_package_layer(layer: &PackageLayer) -> Result<RuntimeLayer> {
@@ -127,7 +129,7 @@ impl RuntimeLayer {
 
     pub fn add_bind_paths(&mut self, bind_paths: &[BindPath]) {
         for b in bind_paths {
-            if metadata(&b.src).is_ok() {
+            if b.source_exists() {
                 self.extra_bind_paths.push(b.clone());
             } else {
                 warn!("add_bind_paths skip paths {:?} which does not exist", b.src);
@@ -142,11 +144,22 @@ impl RuntimeLayer {
         script: &str,
         envs: &HashMap<String, String>,
         env_file: &Option<String>,
+    ) -> Command {
+        let mut cmd = self.shell_command(envs, env_file);
+        cmd.arg(script);
+        cmd
+    }
+
+    /// [`Self::run_command`] without the script, the next argument is the script the shell runs.
+    pub fn shell_command(
+        &self,
+        envs: &HashMap<String, String>,
+        env_file: &Option<String>,
     ) -> Command {
         let mut bind_paths: Vec<BindPath> = vec![];
 
         for b in &self.extra_bind_paths {
-            if metadata(&b.src).is_ok() {
+            if b.source_exists() {
                 bind_paths.push(b.clone());
             } else {
                 warn!("bind paths {:?} is not exist", b.src);
@@ -154,15 +167,13 @@ impl RuntimeLayer {
         }
 
         let work_dir = self.package_path.to_string_lossy().to_string();
-        let mut cmd = ovmlayer::run_cmd(
+        ovmlayer::run_cmd(
             &self.merge_point,
             &bind_paths,
             &Some(work_dir),
             envs,
             env_file,
-        );
-        cmd.arg(script);
-        cmd
+        )
     }
 
     #[instrument(skip_all)]
@@ -201,6 +212,8 @@ impl RuntimeLayer {
         })
     }
 
+    /// Inject the scripts into a layer kept across sessions. A layer already injected with the
+    /// same package, version, scripts and nodes is merged without running the scripts again.
     #[instrument(skip_all)]
     pub fn inject_runtime_layer(&mut self, params: InjectionParams) -> Result<()> {
         let InjectionParams {
@@ -208,56 +221,61 @@ impl RuntimeLayer {
             package_version,
             scripts,
             flow_path,
+            nodes,
         } = params;
 
-        let injection_layer_name =
-            if let Some(injection_layer) = get_injection_layer(flow_path, package_path) {
-                if injection_layer.package_version == package_version
-                    && injection_layer.is_equal_scripts(scripts.clone())
-                {
-                    debug!(
-                        "injection layer {} is matched, skip creation step",
-                        injection_layer.layer_name
-                    );
-                    injection_layer.layer_name.clone()
-                } else {
-                    delete_layer(&injection_layer.layer_name)?;
-                    let new_injection_layer = InjectionLayer::new(
-                        flow_path.to_owned(),
-                        scripts.clone(),
-                        package_path.to_owned(),
-                        package_version.to_owned(),
-                    );
-                    new_injection_layer.save_to_store()?;
-                    info!(
-                        "injection layer {} is not matched, delete old and create {}",
-                        injection_layer.layer_name, new_injection_layer.layer_name
-                    );
-                    new_injection_layer.layer_name.clone()
+        let hash = injection_hash(package_path, package_version, scripts, nodes);
+        let existing = get_injection_layer(flow_path, package_path);
+
+        match plan_injection(existing.as_ref(), &hash) {
+            InjectionPlan::Reuse(layer_name) => {
+                info!("injection layer {layer_name} is already injected, skip injection");
+                self.merge_extra_layer(layer_name)
+            }
+            InjectionPlan::Inject { stale_layer } => {
+                if let Some(stale_layer) = stale_layer {
+                    info!("injection layer {stale_layer} is outdated, delete it");
+                    delete_layer(&stale_layer)?;
                 }
-            } else {
-                let new_injection_layer = InjectionLayer::new(
+                let mut injection_layer = InjectionLayer::new(
                     flow_path.to_owned(),
                     scripts.clone(),
                     package_path.to_owned(),
                     package_version.to_owned(),
                 );
-                new_injection_layer.save_to_store()?;
-                info!(
-                    "injection layer not exist, create {}",
-                    new_injection_layer.layer_name
-                );
-                new_injection_layer.layer_name.clone()
-            };
+                // saved before running the scripts, so a failed injection is cleaned up next time
+                injection_layer.save_to_store()?;
+                info!("create injection layer {}", injection_layer.layer_name);
 
-        self.run_injection_scripts(
-            injection_layer_name,
-            scripts.clone(),
-            package_path,
-            &HashMap::default(), // TODO: implement envs for injection runtime layer
-            &None,               // TODO: implement env_file for injection runtime layer
-        )?;
+                self.run_injection_scripts(
+                    injection_layer.layer_name.clone(),
+                    scripts.clone(),
+                    package_path,
+                    &HashMap::default(), // TODO: implement envs for injection runtime layer
+                    &None,               // TODO: implement env_file for injection runtime layer
+                )?;
+
+                injection_layer.injection_hash = Some(hash);
+                injection_layer.save_to_store()
+            }
+        }
+    }
+
+    /// Put the extra layer below the tmp layer, reusing the current merge point name.
+    fn merge_extra_layer(&mut self, extra_layer: String) -> Result<()> {
+        let mut layers = self.layers.clone().unwrap_or_default();
+        if let Some(ref bootstrap_layer) = self.bootstrap_layer {
+            layers.push(bootstrap_layer.clone());
+        }
+        layers.push(extra_layer.clone());
+        layers.push(self.tmp_layer.clone());
 
+        unmerge(&self.merge_point)?;
+        merge_layer(&layers, &self.merge_point)?;
+
+        self.extra_layers
+            .get_or_insert_with(Vec::new)
+            .push(extra_layer);
         Ok(())
     }
 
@@ -355,17 +373,7 @@ impl RuntimeLayer {
         unmerge(&script_run_merge_point)?;
 
         // unmerge 旧的 merge point，把 extra layer 塞到 tmp_layer 前面，重新用这个 merge point 名称。
-        unmerge(&self.merge_point)?;
-        script_runtime_layers.push(self.tmp_layer.clone());
-        merge_layer(&script_runtime_layers, &self.merge_point)?;
-
-        if let Some(ref mut extra_layers) = self.extra_layers {
-            extra_layers.push(extra_layer);
-        } else {
-            self.extra_layers = Some(vec![extra_layer]);
-        }
-
-        Ok(())
+        self.merge_extra_layer(extra_layer)
     }
 }
 
@@ -102,6 +102,23 @@ mod tests {
         assert_eq!(status, PackageLayerStatus::Exist);
     }
 
+    #[test]
+    fn test_package_layer_from_lockfile() {
+        let d = dirname().join("data").join("simple");
+        let lockfile = d.join("requirements.lock");
+        let r = create_package_layer_from_lockfile(&d, &lockfile, &[], &HashMap::new(), &None);
+        assert!(r.is_ok(), "Error: {:?}", r.unwrap_err());
+
+        let package_layer = r.unwrap();
+        assert_eq!(
+            package_layer.lockfile_hash,
+            Some(lockfile_hash(&lockfile).unwrap())
+        );
+
+        let result = delete_package_layer(d);
+        assert!(result.is_ok(), "Error: {:?}", result.unwrap_err());
+    }
+
     #[test]
     fn test_validate_package() {
         let d = dirname().join("data").join("simple");
@@ -87,4 +87,34 @@ mod tests {
 
         std::fs::remove_dir_all(&work_dir.parent().unwrap()).expect("remove dir failed");
     }
+
+    #[test]
+    fn test_tmpfs_mount_appears_at_destination() {
+        let d = dirname().join("data").join("simple");
+        let pkg_meta = find_package_file(&d)
+            .and_then(|path| read_package(&path).ok())
+            .expect("package.oo.yaml not found");
+        let mut runtime_layer = create_runtime_layer(
+            d.to_str().unwrap(),
+            &[],
+            &HashMap::new(),
+            &None,
+            pkg_meta.name.as_deref(),
+            pkg_meta.version.as_deref(),
+        )
+        .expect("create runtime layer failed");
+
+        runtime_layer.add_bind_paths(&[BindPath::tmpfs("/scratch", Some("16M"))]);
+
+        let exec_string = convert_to_script(&vec!["stat", "-f", "-c", "%T", "/scratch"]);
+        let mut cmd = runtime_layer.run_command(&exec_string, &HashMap::new(), &None);
+
+        let mut log = temp_dir();
+        log.push("ovmlayer.log");
+        cmd.env("OVMLAYER_LOG", log.to_str().unwrap());
+
+        let out = cmd.output().expect("run command failed");
+        assert!(out.status.success(), "{out:?}");
+        assert_eq!(String::from_utf8(out.stdout).unwrap().trim(), "tmpfs");
+    }
 }
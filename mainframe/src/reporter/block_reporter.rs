@@ -1,10 +1,11 @@
 use std::collections::HashMap;
 
-use job::{BlockInputs, BlockJobStacks, JobId};
+use job::{BlockInputs, BlockJobStackLevel, BlockJobStacks, JobId};
 use manifest_meta::JsonValue;
 use serde_json::Value;
 
 use super::{ReporterMessage, ReporterTx};
+use crate::scheduler::ErrorKind;
 
 pub struct BlockReporterTx {
     job_id: JobId,
@@ -39,19 +40,46 @@ impl BlockReporterTx {
         });
     }
 
-    pub fn finished(&self, result: Option<HashMap<String, JsonValue>>, error: Option<String>) {
+    pub fn finished(
+        &self,
+        result: Option<HashMap<String, JsonValue>>,
+        error: Option<String>,
+        error_kind: Option<ErrorKind>,
+    ) {
+        self.finished_with_exit_code(result, error, error_kind, None);
+    }
+
+    /// `exit_code` is set when the block died with its executor process.
+    pub fn finished_with_exit_code(
+        &self,
+        result: Option<HashMap<String, JsonValue>>,
+        error: Option<String>,
+        error_kind: Option<ErrorKind>,
+        exit_code: Option<i32>,
+    ) {
         self.tx.send(ReporterMessage::BlockFinished {
             session_id: &self.tx.session_id,
             job_id: &self.job_id,
             block_path: &self.block_path,
             stacks: self.stacks.vec(),
             error,
+            error_kind,
+            exit_code,
             result,
             finish_at: ReporterMessage::now(),
         });
     }
 
     pub fn output(&self, result: &JsonValue, handle: &str) {
+        self.send_output(result, handle, false);
+    }
+
+    /// Report an output the block produced in an earlier run, the block itself didn't run.
+    pub fn cached_output(&self, result: &JsonValue, handle: &str) {
+        self.send_output(result, handle, true);
+    }
+
+    fn send_output(&self, result: &JsonValue, handle: &str, from_cache: bool) {
         self.tx.send(ReporterMessage::BlockOutput {
             session_id: &self.tx.session_id,
             job_id: &self.job_id,
@@ -59,6 +87,7 @@ impl BlockReporterTx {
             stacks: self.stacks.vec(),
             output: result,
             handle,
+            from_cache,
         });
     }
 
@@ -94,13 +123,26 @@ impl BlockReporterTx {
         });
     }
 
-    pub fn error(&self, error: &str) {
+    pub fn error(&self, error: &str, error_kind: ErrorKind) {
         self.tx.send(ReporterMessage::BlockError {
             session_id: &self.tx.session_id,
             job_id: &self.job_id,
             block_path: &self.block_path,
             stacks: self.stacks.vec(),
             error,
+            error_kind,
+        });
+    }
+
+    pub fn executor_restarted(&self, executor_name: &str, code: i32, attempt: u32) {
+        self.tx.send(ReporterMessage::BlockExecutorRestarted {
+            session_id: &self.tx.session_id,
+            job_id: &self.job_id,
+            block_path: &self.block_path,
+            stacks: self.stacks.vec(),
+            executor_name,
+            code,
+            attempt,
         });
     }
 
@@ -141,14 +183,13 @@ impl BlockReporterTx {
             };
             let mut merged = local_stacks;
             merged.extend(remote_stacks);
-            obj.insert("stacks".into(), Value::Array(merged));
-        }
-
-        match serde_json::to_vec(&item) {
-            Ok(bytes) => self.tx.send_raw(bytes),
-            Err(e) => {
-                tracing::warn!("forward_remote_log: failed to serialize event: {e}");
+            let merged = Value::Array(merged);
+            if let Ok(levels) = serde_json::from_value::<Vec<BlockJobStackLevel>>(merged.clone()) {
+                obj.insert("stack_path".into(), Value::String(job::stack_path(&levels)));
             }
+            obj.insert("stacks".into(), merged);
         }
+
+        self.tx.send_json(item);
     }
 }
@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 use crate::reporter::ErrorDetail;
 
@@ -13,8 +15,12 @@ pub struct FlowReporterTx {
     stacks: BlockJobStacks,
     tx: ReporterTx,
     flow_type: FlowType,
+    // highest milestone already reported, milestones never go backwards
+    milestone: AtomicU8,
 }
 
+const PROGRESS_MILESTONES: [u8; 3] = [25, 50, 75];
+
 enum FlowType {
     Flow,
     Subflow,
@@ -74,6 +80,7 @@ impl FlowReporterTx {
             stacks,
             tx,
             flow_type,
+            milestone: AtomicU8::new(0),
         }
     }
 
@@ -105,7 +112,13 @@ impl FlowReporterTx {
         }
     }
 
-    pub fn will_run_nodes(&self, start: &Vec<String>, mid: &Vec<String>, end: &Vec<String>) {
+    pub fn will_run_nodes(
+        &self,
+        start: &Vec<String>,
+        mid: &Vec<String>,
+        end: &Vec<String>,
+        descriptions: &HashMap<NodeId, String>,
+    ) {
         if matches!(self.flow_type, FlowType::Flow) {
             self.tx.send(ReporterMessage::FlowNodesWillRun {
                 session_id: &self.tx.session_id,
@@ -115,10 +128,46 @@ impl FlowReporterTx {
                 mid_nodes: mid,
                 start_nodes: start,
                 end_nodes: end,
+                node_descriptions: descriptions,
             });
         }
     }
 
+    /// The node won't run, the inputs it feeds were recovered from the cache.
+    pub fn node_cache_hit(&self, node_id: &NodeId) {
+        self.tx.send(ReporterMessage::NodeCacheHit {
+            session_id: &self.tx.session_id,
+            job_id: &self.job_id,
+            flow_path: &self.path,
+            stacks: self.stacks.vec(),
+            node_id,
+        });
+    }
+
+    /// The `wait_all` node didn't run, `unfinished` are the upstream nodes that never finished.
+    pub fn wait_all_node_skipped(&self, node_id: &NodeId, unfinished: &Vec<NodeId>) {
+        self.tx.send(ReporterMessage::WaitAllNodeSkipped {
+            session_id: &self.tx.session_id,
+            job_id: &self.job_id,
+            flow_path: &self.path,
+            stacks: self.stacks.vec(),
+            node_id,
+            unfinished_nodes: unfinished,
+        });
+    }
+
+    /// The node spent its time budget, `held_runs` of its runs never started.
+    pub fn node_held_by_time_budget(&self, node_id: &NodeId, held_runs: usize) {
+        self.tx.send(ReporterMessage::NodeHeldByTimeBudget {
+            session_id: &self.tx.session_id,
+            job_id: &self.job_id,
+            flow_path: &self.path,
+            stacks: self.stacks.vec(),
+            node_id,
+            held_runs,
+        });
+    }
+
     pub fn forward_previews(&self, node_id: NodeId, payload: &serde_json::Value) {
         if matches!(self.flow_type, FlowType::Subflow | FlowType::SlotFlow) {
             self.tx.send(ReporterMessage::BlockPreview {
@@ -177,9 +226,37 @@ impl FlowReporterTx {
             FlowType::Flow => {}
             FlowType::SlotFlow => {}
         }
+        self.progress_milestones(progress);
+    }
+
+    fn progress_milestones(&self, progress: f32) {
+        for milestone in PROGRESS_MILESTONES {
+            if progress < milestone as f32 {
+                break;
+            }
+            let previous = self.milestone.fetch_max(milestone, Ordering::Relaxed);
+            if previous < milestone {
+                self.tx.send(ReporterMessage::FlowProgressMilestone {
+                    session_id: &self.tx.session_id,
+                    job_id: &self.job_id,
+                    flow_path: &self.path,
+                    stacks: self.stacks.vec(),
+                    milestone,
+                });
+            }
+        }
     }
 
     pub fn output(&self, value: Arc<OutputValue>, handle: &str) {
+        self.send_output(value, handle, false);
+    }
+
+    /// Report an output the subflow produced in an earlier run, the subflow itself didn't run.
+    pub fn cached_output(&self, value: Arc<OutputValue>, handle: &str) {
+        self.send_output(value, handle, true);
+    }
+
+    fn send_output(&self, value: Arc<OutputValue>, handle: &str, from_cache: bool) {
         match self.flow_type {
             FlowType::Subflow => self.tx.send(ReporterMessage::SubflowBlockOutput {
                 session_id: &self.tx.session_id,
@@ -188,6 +265,7 @@ impl FlowReporterTx {
                 stacks: self.stacks.vec(),
                 output: value,
                 handle,
+                from_cache,
             }),
             FlowType::Flow => {}
             FlowType::SlotFlow => self.tx.send(ReporterMessage::SlotflowOutput {
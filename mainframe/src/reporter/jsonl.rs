@@ -0,0 +1,128 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use tracing::warn;
+
+use super::ReporterTxImpl;
+use crate::MessageData;
+
+/// File name of the event log in the session log dir.
+pub const EVENTS_FILE: &str = "events.jsonl";
+
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+struct EventsWriter {
+    file: BufWriter<File>,
+    last_flush: Instant,
+}
+
+/// Appends every reporter message as one JSON line to a file. Lines are buffered and flushed at
+/// most a second apart and on disconnect. A failed write is logged and the session goes on.
+pub struct JsonLinesReporterTx {
+    path: PathBuf,
+    writer: Mutex<EventsWriter>,
+}
+
+impl JsonLinesReporterTx {
+    pub fn open(path: PathBuf) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            writer: Mutex::new(EventsWriter {
+                file: BufWriter::new(file),
+                last_flush: Instant::now(),
+            }),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[async_trait]
+impl ReporterTxImpl for JsonLinesReporterTx {
+    async fn send(&self, data: MessageData) {
+        let mut writer = self.writer.lock().unwrap();
+        let mut result = writer
+            .file
+            .write_all(&data)
+            .and_then(|_| writer.file.write_all(b"\n"));
+        if result.is_ok() && writer.last_flush.elapsed() >= FLUSH_INTERVAL {
+            result = writer.file.flush();
+            writer.last_flush = Instant::now();
+        }
+        if let Err(e) = result {
+            warn!("failed to write event log {}: {e}", self.path.display());
+        }
+    }
+
+    async fn disconnect(&self) {
+        if let Err(e) = self.writer.lock().unwrap().file.flush() {
+            warn!("failed to flush event log {}: {e}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter::{self, SseReporterRx, TeeReporterTx};
+    use job::SessionId;
+
+    fn read_types(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let message: serde_json::Value = serde_json::from_str(line).unwrap();
+                message["type"].as_str().unwrap().to_owned()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn tee_writes_every_message_to_both_sinks() {
+        let dir = std::env::temp_dir().join(format!("oocana-events-{}", SessionId::random()));
+        let first = JsonLinesReporterTx::open(dir.join("first").join(EVENTS_FILE)).unwrap();
+        let second = JsonLinesReporterTx::open(dir.join("second").join(EVENTS_FILE)).unwrap();
+        let (reporter, reporter_rx) = reporter::create(
+            SessionId::random(),
+            Some(TeeReporterTx::new(Some(first), Some(second))),
+            None::<SseReporterRx>,
+        );
+        let handle = reporter_rx.event_loop();
+
+        reporter.session_started("flow", false, false);
+        reporter.session_finished("flow", &None, &None, false, false);
+        reporter.abort();
+        handle.await.unwrap();
+
+        for sink in ["first", "second"] {
+            assert_eq!(
+                read_types(&dir.join(sink).join(EVENTS_FILE)),
+                vec!["SessionStarted", "SessionFinished"]
+            );
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn write_errors_are_logged_not_fatal() {
+        let events = JsonLinesReporterTx::open(PathBuf::from("/dev/full")).unwrap();
+        // larger than the write buffer, so it hits the device right away
+        events.send(vec![b'x'; 64 * 1024]).await;
+        events.send(b"{}".to_vec()).await;
+        events.disconnect().await;
+    }
+}
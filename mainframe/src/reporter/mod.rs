@@ -3,26 +3,33 @@ use flume::{Receiver, Sender};
 use serde::Serialize;
 use std::{
     collections::HashMap,
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::{SystemTime, UNIX_EPOCH},
 };
 use tracing::{info, warn};
 use utils::output::OutputValue;
 
 use crate::MessageData;
+use crate::scheduler::ErrorKind;
 use job::{BlockInputs, BlockJobStackLevel, BlockJobStacks, JobId, SessionId};
 use manifest_meta::{JsonValue, NodeId};
 
 mod block_reporter;
 mod flow_reporter;
+mod jsonl;
+mod sse;
 pub use block_reporter::BlockReporterTx;
 pub use flow_reporter::FlowReporterTx;
+pub use jsonl::{EVENTS_FILE, JsonLinesReporterTx};
+pub use sse::{SseOptions, SseOverflow, SseReporterRx, SseReporterTx, bind_sse};
 
 #[derive(Serialize, Debug, Clone)]
 pub struct ErrorDetail {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub message: Option<String>,
     pub stack: Vec<BlockJobStackLevel>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ErrorKind>,
 }
 
 #[derive(Serialize, Debug, Clone)]
@@ -72,6 +79,40 @@ pub enum ReporterMessage<'a> {
         start_nodes: &'a Vec<String>, // 马上就会运行的 nodes
         mid_nodes: &'a Vec<String>,   // 之后会运行的 nodes，但不是最终运行的 nodes
         end_nodes: &'a Vec<String>,   // 最后想要最终运行的 node
+        node_descriptions: &'a HashMap<NodeId, String>, // 上面 nodes 中有 description 的节点
+    },
+    FlowProgressMilestone {
+        session_id: &'a str,
+        job_id: &'a str,
+        flow_path: &'a Option<String>,
+        stacks: &'a Vec<BlockJobStackLevel>,
+        milestone: u8,
+    },
+    // 使用指定 nodes 时，上游节点因为下游的输入已在缓存中而不运行
+    NodeCacheHit {
+        session_id: &'a str,
+        job_id: &'a str,
+        flow_path: &'a Option<String>,
+        stacks: &'a Vec<BlockJobStackLevel>,
+        node_id: &'a NodeId,
+    },
+    // wait_all 节点因为有上游节点没有完成而没有运行
+    WaitAllNodeSkipped {
+        session_id: &'a str,
+        job_id: &'a str,
+        flow_path: &'a Option<String>,
+        stacks: &'a Vec<BlockJobStackLevel>,
+        node_id: &'a NodeId,
+        unfinished_nodes: &'a Vec<NodeId>,
+    },
+    // 节点的时间预算已用完，流程结束时仍有没有运行的次数
+    NodeHeldByTimeBudget {
+        session_id: &'a str,
+        job_id: &'a str,
+        flow_path: &'a Option<String>,
+        stacks: &'a Vec<BlockJobStackLevel>,
+        node_id: &'a NodeId,
+        held_runs: usize,
     },
     SubflowBlockStarted {
         session_id: &'a str,
@@ -132,6 +173,9 @@ pub enum ReporterMessage<'a> {
         stacks: &'a Vec<BlockJobStackLevel>,
         output: Arc<OutputValue>,
         handle: &'a str,
+        /// the node was skipped for the cache, this is the value it produced before
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        from_cache: bool,
     },
     BlockStarted {
         session_id: &'a str,
@@ -152,6 +196,11 @@ pub enum ReporterMessage<'a> {
         result: Option<HashMap<String, JsonValue>>,
         #[serde(skip_serializing_if = "Option::is_none")]
         error: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error_kind: Option<ErrorKind>,
+        /// exit code of the executor process when the block died with it
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
         finish_at: u128,
     },
     BlockOutput {
@@ -162,6 +211,9 @@ pub enum ReporterMessage<'a> {
         stacks: &'a Vec<BlockJobStackLevel>,
         output: &'a JsonValue,
         handle: &'a str,
+        /// the node was skipped for the cache, this is the value it produced before
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        from_cache: bool,
     },
     BlockOutputs {
         session_id: &'a str,
@@ -197,10 +249,61 @@ pub enum ReporterMessage<'a> {
         block_path: &'a Option<String>,
         stacks: &'a Vec<BlockJobStackLevel>,
         error: &'a str,
+        error_kind: ErrorKind,
+    },
+    // block 所在的 executor 崩溃后被重启，block 会重新发送给新的 executor
+    BlockExecutorRestarted {
+        session_id: &'a str,
+        job_id: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        block_path: &'a Option<String>,
+        stacks: &'a Vec<BlockJobStackLevel>,
+        executor_name: &'a str,
+        code: i32,
+        attempt: u32,
+    },
+    // executor 进程自身的 stdout/stderr，只有开启 --report-executor-logs 时才会发送
+    ExecutorLog {
+        session_id: &'a str,
+        executor_name: &'a str,
+        identifier: &'a str,
+        log: &'a str,
+        stdio: &'a str,
     },
 }
 
 impl ReporterMessage<'_> {
+    /// The stacks of the job the message is about, `None` for session and executor messages.
+    pub fn stacks(&self) -> Option<&Vec<BlockJobStackLevel>> {
+        match self {
+            ReporterMessage::FlowStarted { stacks, .. }
+            | ReporterMessage::FlowFinished { stacks, .. }
+            | ReporterMessage::FlowNodesWillRun { stacks, .. }
+            | ReporterMessage::FlowProgressMilestone { stacks, .. }
+            | ReporterMessage::NodeCacheHit { stacks, .. }
+            | ReporterMessage::WaitAllNodeSkipped { stacks, .. }
+            | ReporterMessage::NodeHeldByTimeBudget { stacks, .. }
+            | ReporterMessage::SubflowBlockStarted { stacks, .. }
+            | ReporterMessage::BlockProgress { stacks, .. }
+            | ReporterMessage::SubflowBlockFinished { stacks, .. }
+            | ReporterMessage::SlotflowStarted { stacks, .. }
+            | ReporterMessage::SlotflowFinished { stacks, .. }
+            | ReporterMessage::SlotflowOutput { stacks, .. }
+            | ReporterMessage::SubflowBlockOutput { stacks, .. }
+            | ReporterMessage::BlockStarted { stacks, .. }
+            | ReporterMessage::BlockFinished { stacks, .. }
+            | ReporterMessage::BlockOutput { stacks, .. }
+            | ReporterMessage::BlockOutputs { stacks, .. }
+            | ReporterMessage::BlockPreview { stacks, .. }
+            | ReporterMessage::BlockLog { stacks, .. }
+            | ReporterMessage::BlockError { stacks, .. }
+            | ReporterMessage::BlockExecutorRestarted { stacks, .. } => Some(stacks),
+            ReporterMessage::SessionStarted { .. }
+            | ReporterMessage::SessionFinished { .. }
+            | ReporterMessage::ExecutorLog { .. } => None,
+        }
+    }
+
     pub fn now() -> u128 {
         SystemTime::now()
             .duration_since(UNIX_EPOCH)
@@ -211,6 +314,7 @@ impl ReporterMessage<'_> {
 
 enum Command {
     Report(MessageData),
+    Flush(Sender<()>),
     Abort,
 }
 
@@ -224,13 +328,90 @@ pub trait ReporterRxImpl {
     fn event_loop(self) -> tokio::task::JoinHandle<()>;
 }
 
+/// A sink that isn't configured for this session.
+#[async_trait]
+impl<T: ReporterTxImpl + Send + Sync> ReporterTxImpl for Option<T> {
+    async fn send(&self, data: MessageData) {
+        if let Some(tx) = self {
+            tx.send(data).await;
+        }
+    }
+
+    async fn disconnect(&self) {
+        if let Some(tx) = self {
+            tx.disconnect().await;
+        }
+    }
+}
+
+/// Sends every message to both sinks, e.g. the broker and an event log file.
+pub struct TeeReporterTx<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> TeeReporterTx<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+}
+
+#[async_trait]
+impl<A, B> ReporterTxImpl for TeeReporterTx<A, B>
+where
+    A: ReporterTxImpl + Send + Sync,
+    B: ReporterTxImpl + Send + Sync,
+{
+    async fn send(&self, data: MessageData) {
+        self.first.send(data.clone()).await;
+        self.second.send(data).await;
+    }
+
+    async fn disconnect(&self) {
+        self.first.disconnect().await;
+        self.second.disconnect().await;
+    }
+}
+
+/// Returns false for messages that should not be reported.
+pub type ReporterFilter = Arc<dyn Fn(&ReporterMessage) -> bool + Send + Sync>;
+
+/// What goes on the wire: the message followed by the `seq` of its session. Messages with
+/// `stacks` also get them as a `stack_path` string.
+#[derive(Serialize)]
+struct Envelope<'a, 'b> {
+    #[serde(flatten)]
+    message: &'a ReporterMessage<'b>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stack_path: Option<String>,
+    seq: u64,
+}
+
 #[derive(Clone)]
 pub struct ReporterTx {
     session_id: SessionId,
     tx: Option<Sender<Command>>,
+    filter: Option<ReporterFilter>,
+    /// `seq` of the next message. Each session gets its own counter, held while the message is
+    /// queued so `seq` follows delivery order.
+    seq: Arc<Mutex<u64>>,
+}
+
+impl std::fmt::Debug for ReporterTx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReporterTx")
+            .field("session_id", &self.session_id)
+            .finish_non_exhaustive()
+    }
 }
 
 impl ReporterTx {
+    /// Drop messages rejected by `filter` before they are serialized. `send_json` is not filtered.
+    pub fn with_filter(mut self, filter: ReporterFilter) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
     pub fn session_started(&self, path: &str, partial: bool, cache: bool) {
         self.send(ReporterMessage::SessionStarted {
             session_id: &self.session_id,
@@ -260,18 +441,44 @@ impl ReporterTx {
         });
     }
 
+    pub fn executor_log(&self, executor_name: &str, identifier: &str, log: &str, stdio: &str) {
+        self.send(ReporterMessage::ExecutorLog {
+            session_id: &self.session_id,
+            executor_name,
+            identifier,
+            log,
+            stdio,
+        });
+    }
+
     pub fn send(&self, data: ReporterMessage) {
-        let payload = serde_json::to_vec(&data).unwrap();
-        if let Some(tx) = self.tx.as_ref() {
-            if let Err(e) = tx.send(Command::Report(payload)) {
-                warn!("Reporter send failed: {e}");
-            }
-        } else {
-            info!("[Reporter] {}", serde_json::to_string(&data).unwrap());
+        if self.filter.as_ref().is_some_and(|filter| !filter(&data)) {
+            return;
         }
+        self.queue(|seq| {
+            serde_json::to_vec(&Envelope {
+                message: &data,
+                stack_path: data.stacks().map(|stacks| job::stack_path(stacks)),
+                seq,
+            })
+            .unwrap()
+        });
+    }
+
+    /// Send an already built message object, `seq` is added to it.
+    pub fn send_json(&self, mut message: serde_json::Value) {
+        self.queue(|seq| {
+            if let Some(obj) = message.as_object_mut() {
+                obj.insert("seq".into(), seq.into());
+            }
+            serde_json::to_vec(&message).unwrap()
+        });
     }
 
-    pub fn send_raw(&self, payload: Vec<u8>) {
+    fn queue(&self, payload: impl FnOnce(u64) -> Vec<u8>) {
+        let mut seq = self.seq.lock().unwrap();
+        let payload = payload(*seq);
+        *seq += 1;
         if let Some(tx) = self.tx.as_ref() {
             if let Err(e) = tx.send(Command::Report(payload)) {
                 warn!("Reporter send failed: {e}");
@@ -299,6 +506,18 @@ impl ReporterTx {
         FlowReporterTx::new(job_id, flow_path, stacks, self.clone())
     }
 
+    /// Wait until every message enqueued before this call has been handed to the transport.
+    pub async fn flush(&self) {
+        if let Some(tx) = self.tx.as_ref() {
+            let (ack_tx, ack_rx) = flume::bounded(1);
+            if let Err(e) = tx.send(Command::Flush(ack_tx)) {
+                warn!("Reporter send flush failed: {e}");
+                return;
+            }
+            let _ = ack_rx.recv_async().await;
+        }
+    }
+
     pub fn abort(&self) {
         if let Some(tx) = self.tx.as_ref() {
             if let Err(e) = tx.send(Command::Abort) {
@@ -341,6 +560,9 @@ where
                                 impl_tx.send(data).await;
                             }
                         }
+                        Ok(Command::Flush(ack)) => {
+                            let _ = ack.send(());
+                        }
                         Ok(Command::Abort) => {
                             if let Some(tx) = impl_tx {
                                 tx.disconnect().await;
@@ -378,6 +600,8 @@ where
             ReporterTx {
                 session_id,
                 tx: Some(tx),
+                filter: None,
+                seq: Default::default(),
             },
             ReporterRx {
                 impl_tx,
@@ -390,6 +614,8 @@ where
             ReporterTx {
                 session_id,
                 tx: None,
+                filter: None,
+                seq: Default::default(),
             },
             ReporterRx {
                 impl_tx,
@@ -399,3 +625,225 @@ where
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tokio::time::{Duration, sleep};
+
+    struct SlowReporterTx {
+        delivered: Arc<Mutex<Vec<serde_json::Value>>>,
+    }
+
+    #[async_trait]
+    impl ReporterTxImpl for SlowReporterTx {
+        async fn send(&self, data: MessageData) {
+            sleep(Duration::from_millis(20)).await;
+            let message = serde_json::from_slice(&data).unwrap();
+            self.delivered.lock().unwrap().push(message);
+        }
+
+        async fn disconnect(&self) {}
+    }
+
+    struct NoopReporterRx;
+
+    impl ReporterRxImpl for NoopReporterRx {
+        fn event_loop(self) -> tokio::task::JoinHandle<()> {
+            tokio::spawn(async {})
+        }
+    }
+
+    #[tokio::test]
+    async fn flush_waits_for_session_finished_delivery() {
+        let delivered = Arc::new(Mutex::new(vec![]));
+        let (reporter, reporter_rx) = create(
+            SessionId::random(),
+            Some(SlowReporterTx {
+                delivered: Arc::clone(&delivered),
+            }),
+            Some(NoopReporterRx),
+        );
+        let handle = reporter_rx.event_loop();
+
+        reporter.session_started("flow", false, false);
+        reporter.session_finished("flow", &None, &None, false, false);
+        reporter.flush().await;
+
+        {
+            let delivered = delivered.lock().unwrap();
+            assert_eq!(delivered.len(), 2);
+            assert_eq!(delivered[1]["type"], "SessionFinished");
+        }
+
+        reporter.abort();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn filter_drops_rejected_messages() {
+        let delivered = Arc::new(Mutex::new(vec![]));
+        let (reporter, reporter_rx) = create(
+            SessionId::random(),
+            Some(SlowReporterTx {
+                delivered: Arc::clone(&delivered),
+            }),
+            Some(NoopReporterRx),
+        );
+        let reporter = reporter.with_filter(Arc::new(|message| {
+            !matches!(
+                message,
+                ReporterMessage::BlockLog {
+                    stdio: "stderr",
+                    ..
+                }
+            )
+        }));
+        let handle = reporter_rx.event_loop();
+
+        let job_id = JobId::random();
+        let block_reporter = reporter.block(job_id, None, BlockJobStacks::new());
+        block_reporter.log("to stdout", "stdout");
+        block_reporter.log("to stderr", "stderr");
+        reporter.flush().await;
+
+        {
+            let delivered = delivered.lock().unwrap();
+            assert_eq!(delivered.len(), 1);
+            assert_eq!(delivered[0]["log"], "to stdout");
+        }
+
+        reporter.abort();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn flow_progress_milestones_fire_once_and_forward_only() {
+        let delivered = Arc::new(Mutex::new(vec![]));
+        let (reporter, reporter_rx) = create(
+            SessionId::random(),
+            Some(SlowReporterTx {
+                delivered: Arc::clone(&delivered),
+            }),
+            Some(NoopReporterRx),
+        );
+        let handle = reporter_rx.event_loop();
+
+        let flow_reporter = reporter.flow(
+            JobId::random(),
+            Some("subflow.oo.yaml".to_owned()),
+            BlockJobStacks::new(),
+        );
+        for progress in [10.0, 55.0, 40.0, 60.0, 80.0, 95.0] {
+            flow_reporter.progress(progress);
+        }
+        reporter.flush().await;
+
+        {
+            let delivered = delivered.lock().unwrap();
+            let milestones: Vec<_> = delivered
+                .iter()
+                .filter(|m| m["type"] == "FlowProgressMilestone")
+                .map(|m| m["milestone"].as_u64().unwrap())
+                .collect();
+            assert_eq!(milestones, vec![25, 50, 75]);
+
+            let progress_count = delivered
+                .iter()
+                .filter(|m| m["type"] == "BlockProgress")
+                .count();
+            assert_eq!(progress_count, 6);
+        }
+
+        reporter.abort();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn seq_counts_up_per_session() {
+        let delivered = Arc::new(Mutex::new(vec![]));
+        let (first, first_rx) = create(
+            SessionId::random(),
+            Some(SlowReporterTx {
+                delivered: Arc::clone(&delivered),
+            }),
+            Some(NoopReporterRx),
+        );
+        let (second, second_rx) = create(
+            SessionId::random(),
+            Some(SlowReporterTx {
+                delivered: Arc::clone(&delivered),
+            }),
+            Some(NoopReporterRx),
+        );
+        let first_handle = first_rx.event_loop();
+        let second_handle = second_rx.event_loop();
+
+        first.session_started("flow", false, false);
+        second.session_started("flow", false, false);
+        let block_reporter = first.block(JobId::random(), None, BlockJobStacks::new());
+        block_reporter.log("line", "stdout");
+        block_reporter.forward_remote_log(serde_json::json!({"type": "BlockLog", "seq": 99}));
+        first.flush().await;
+        second.flush().await;
+
+        {
+            let delivered = delivered.lock().unwrap();
+            let seqs = |session: &SessionId| {
+                delivered
+                    .iter()
+                    .filter(|m| m["session_id"] == session.to_string())
+                    .map(|m| m["seq"].as_u64().unwrap())
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(seqs(&first.session_id), vec![0, 1, 2]);
+            assert_eq!(seqs(&second.session_id), vec![0]);
+        }
+
+        first.abort();
+        second.abort();
+        first_handle.await.unwrap();
+        second_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn flush_without_transport_returns_immediately() {
+        let (reporter, _reporter_rx) =
+            create::<SlowReporterTx, NoopReporterRx>(SessionId::random(), None, None);
+        reporter.flush().await;
+    }
+
+    #[test]
+    fn stack_path_follows_the_stacked_levels() {
+        let stacks = BlockJobStacks::new()
+            .stack(
+                JobId::random(),
+                "main.oo.yaml".to_owned(),
+                NodeId::new("sub".to_owned()),
+            )
+            .stack(
+                JobId::random(),
+                "sub.oo.yaml".to_owned(),
+                NodeId::new("task".to_owned()),
+            );
+        let message = ReporterMessage::BlockLog {
+            session_id: "session",
+            job_id: "job",
+            block_path: &None,
+            stacks: stacks.vec(),
+            log: "hello",
+            stdio: "stdout",
+        };
+        let value = serde_json::to_value(Envelope {
+            message: &message,
+            stack_path: message.stacks().map(|stacks| job::stack_path(stacks)),
+            seq: 0,
+        })
+        .unwrap();
+
+        assert_eq!(value["stack_path"], "sub/task");
+        assert_eq!(value["stacks"].as_array().unwrap().len(), 2);
+        assert_eq!(value["stacks"][1]["node_id"], "task");
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    collections::HashMap,
+    io,
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use async_trait::async_trait;
+use flume::{Receiver, Sender, TrySendError};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+    task::JoinSet,
+};
+use tracing::{info, warn};
+
+use super::{ReporterRxImpl, ReporterTxImpl};
+use crate::MessageData;
+
+const DEFAULT_SSE_BUFFER: usize = 1024;
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+
+/// What to do when a slow SSE client's buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SseOverflow {
+    /// Drop the oldest buffered event of that client.
+    DropOldest,
+    /// Wait until the client has room again. A slow client slows down the whole reporter.
+    Block,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SseOptions {
+    /// Events buffered per client.
+    pub buffer: usize,
+    pub overflow: SseOverflow,
+}
+
+impl Default for SseOptions {
+    fn default() -> Self {
+        Self {
+            buffer: DEFAULT_SSE_BUFFER,
+            overflow: SseOverflow::DropOldest,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SseClient {
+    tx: Sender<Arc<str>>,
+    // kept on the sender side so DropOldest can pop the oldest event
+    rx: Receiver<Arc<str>>,
+}
+
+type Clients = Arc<Mutex<HashMap<u64, SseClient>>>;
+
+/// Pushes every reporter message as a `data:` event to the connected SSE clients.
+pub struct SseReporterTx {
+    clients: Clients,
+    overflow: SseOverflow,
+    shutdown_tx: watch::Sender<bool>,
+}
+
+#[async_trait]
+impl ReporterTxImpl for SseReporterTx {
+    async fn send(&self, data: MessageData) {
+        let event: Arc<str> = format!("data: {}\n\n", String::from_utf8_lossy(&data)).into();
+        // don't hold the lock across await
+        let clients: Vec<SseClient> = self.clients.lock().unwrap().values().cloned().collect();
+
+        for SseClient { tx, rx } in clients {
+            match self.overflow {
+                SseOverflow::DropOldest => {
+                    let mut event = event.clone();
+                    while let Err(TrySendError::Full(e)) = tx.try_send(event) {
+                        let _ = rx.try_recv();
+                        event = e;
+                    }
+                }
+                SseOverflow::Block => {
+                    // a disconnected client must not keep the channel alive
+                    drop(rx);
+                    let _ = tx.send_async(event.clone()).await;
+                }
+            }
+        }
+    }
+
+    async fn disconnect(&self) {
+        let _ = self.shutdown_tx.send(true);
+    }
+}
+
+/// Accepts SSE clients until the paired [`SseReporterTx`] disconnects, then flushes the
+/// buffered events to every client before closing.
+pub struct SseReporterRx {
+    listener: TcpListener,
+    clients: Clients,
+    buffer: usize,
+    shutdown_rx: watch::Receiver<bool>,
+}
+
+impl SseReporterRx {
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+impl ReporterRxImpl for SseReporterRx {
+    fn event_loop(self) -> tokio::task::JoinHandle<()> {
+        let Self {
+            listener,
+            clients,
+            buffer,
+            mut shutdown_rx,
+        } = self;
+
+        tokio::spawn(async move {
+            let next_id = AtomicU64::new(0);
+            let mut handlers = JoinSet::new();
+
+            loop {
+                tokio::select! {
+                    _ = shutdown_rx.changed() => break,
+                    conn = listener.accept() => {
+                        let (stream, peer) = match conn {
+                            Ok(conn) => conn,
+                            Err(e) => {
+                                warn!("SSE reporter accept failed: {e}");
+                                continue;
+                            }
+                        };
+
+                        let id = next_id.fetch_add(1, Ordering::Relaxed);
+                        let (tx, rx) = flume::bounded(buffer.max(1));
+                        clients.lock().unwrap().insert(
+                            id,
+                            SseClient {
+                                tx,
+                                rx: rx.clone(),
+                            },
+                        );
+                        info!("SSE reporter client {peer} connected");
+
+                        let clients = Arc::clone(&clients);
+                        let shutdown_rx = shutdown_rx.clone();
+                        handlers.spawn(async move {
+                            if let Err(e) = serve_client(stream, &rx, shutdown_rx).await {
+                                info!("SSE reporter client {peer} disconnected: {e}");
+                            }
+                            clients.lock().unwrap().remove(&id);
+                        });
+                    }
+                }
+            }
+
+            while handlers.join_next().await.is_some() {}
+            info!("SSE reporter shutting down");
+        })
+    }
+}
+
+async fn serve_client(
+    mut stream: TcpStream,
+    rx: &Receiver<Arc<str>>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) -> io::Result<()> {
+    read_request_head(&mut stream).await?;
+    stream
+        .write_all(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n",
+        )
+        .await?;
+
+    loop {
+        tokio::select! {
+            event = rx.recv_async() => match event {
+                Ok(event) => stream.write_all(event.as_bytes()).await?,
+                Err(_) => break,
+            },
+            _ = shutdown_rx.changed() => {
+                while let Ok(event) = rx.try_recv() {
+                    stream.write_all(event.as_bytes()).await?;
+                }
+                break;
+            }
+        }
+    }
+
+    stream.shutdown().await
+}
+
+/// The request itself is ignored, every path gets the event stream.
+async fn read_request_head(stream: &mut TcpStream) -> io::Result<()> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.len() > MAX_REQUEST_HEAD_BYTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "request head too large",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Bind an SSE endpoint on `addr`, pass the returned pair to [`super::create`].
+pub async fn bind_sse(
+    addr: SocketAddr,
+    options: SseOptions,
+) -> io::Result<(SseReporterTx, SseReporterRx)> {
+    let listener = TcpListener::bind(addr).await?;
+    let clients: Clients = Arc::new(Mutex::new(HashMap::new()));
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+    Ok((
+        SseReporterTx {
+            clients: Arc::clone(&clients),
+            overflow: options.overflow,
+            shutdown_tx,
+        },
+        SseReporterRx {
+            listener,
+            clients,
+            buffer: options.buffer,
+            shutdown_rx,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reporter;
+    use job::SessionId;
+    use tokio::time::{Duration, timeout};
+
+    async fn read_until(stream: &mut TcpStream, needle: &str) -> String {
+        let mut received = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !String::from_utf8_lossy(&received).contains(needle) {
+            let n = timeout(Duration::from_secs(5), stream.read(&mut buf))
+                .await
+                .expect("timed out waiting for SSE data")
+                .unwrap();
+            assert!(n > 0, "stream closed before {needle:?} arrived");
+            received.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8_lossy(&received).into_owned()
+    }
+
+    #[tokio::test]
+    async fn sse_client_receives_reporter_messages() {
+        let (impl_tx, impl_rx) = bind_sse("127.0.0.1:0".parse().unwrap(), SseOptions::default())
+            .await
+            .unwrap();
+        let addr = impl_rx.local_addr().unwrap();
+        let (reporter, reporter_rx) =
+            reporter::create(SessionId::random(), Some(impl_tx), Some(impl_rx));
+        let handle = reporter_rx.event_loop();
+
+        let mut client = TcpStream::connect(addr).await.unwrap();
+        client
+            .write_all(b"GET /events HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let head = read_until(&mut client, "\r\n\r\n").await;
+        assert!(head.contains("text/event-stream"));
+
+        reporter.session_started("flow", false, false);
+        reporter.session_finished("flow", &None, &None, false, false);
+        reporter.abort();
+
+        let events = read_until(&mut client, "SessionFinished").await;
+        assert!(events.contains("data: {\"type\":\"SessionStarted\""));
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn slow_client_drops_oldest_events() {
+        let (impl_tx, _impl_rx) = bind_sse(
+            "127.0.0.1:0".parse().unwrap(),
+            SseOptions {
+                buffer: 2,
+                overflow: SseOverflow::DropOldest,
+            },
+        )
+        .await
+        .unwrap();
+
+        let (tx, rx) = flume::bounded(2);
+        impl_tx
+            .clients
+            .lock()
+            .unwrap()
+            .insert(0, SseClient { tx, rx: rx.clone() });
+
+        for i in 0..3 {
+            impl_tx.send(format!("{i}").into_bytes()).await;
+        }
+
+        let queued: Vec<_> = rx.try_iter().map(|e| e.to_string()).collect();
+        assert_eq!(queued, vec!["data: 1\n\n", "data: 2\n\n"]);
+    }
+}
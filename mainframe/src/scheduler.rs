@@ -6,11 +6,15 @@ use manifest_reader::reader::read_package;
 use port_check::free_local_ipv4_port_in_range;
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     default,
-    path::PathBuf,
+    ffi::OsString,
+    path::{Path, PathBuf},
     process,
-    sync::{Arc, RwLock},
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicUsize, Ordering},
+    },
     vec,
 };
 use utils::calculate_short_hash;
@@ -21,14 +25,29 @@ use manifest_meta::{
     HandleName, InjectionStore, InjectionTarget, InputDefPatchMap, InputHandles, JsonValue, NodeId,
     OutputHandles, ServiceExecutorOptions, TaskBlockExecutor,
 };
-use tokio::io::AsyncBufReadExt;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWriteExt};
 
 use tokio::process::Command as tokioCommand;
+use tokio::sync::watch;
 use tracing::{debug, error, info, instrument, warn};
 
 use utils::error::{Error, Result};
 
 use crate::MessageData;
+use crate::reporter::ReporterTx;
+
+/// Default cap for a single executor stdout/stderr line kept in logs.
+pub const DEFAULT_MAX_LOG_LINE_BYTES: usize = 64 * 1024;
+/// How long an executor process has to start.
+pub const DEFAULT_EXECUTOR_SPAWN_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+/// How long a started executor has to send `ExecutorReady`, counted from its spawn.
+pub const DEFAULT_EXECUTOR_READY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+/// How long a block waits for its executor to pick it up before it's sent again.
+pub const DEFAULT_LISTENER_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// An output whose serialized JSON is larger than this many bytes is sent as
+/// [`ReceiveMessage::BlockOutputChunk`] pieces of at most this size instead of one `BlockOutput`.
+pub const OUTPUT_CHUNK_THRESHOLD: usize = 1024 * 1024;
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct RunBlockRequest {
@@ -132,9 +151,27 @@ pub struct OutputOptions {
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct OutputTarget {
     pub to_node: Option<Vec<ToNodeInput>>,
+    /// Node inputs addressed by a node id pattern, on top of `to_node`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_node_pattern: Option<Vec<ToNodeInputPattern>>,
     pub to_flow: Option<Vec<ToFlowOutput>>,
 }
 
+impl OutputTarget {
+    /// Whether the output goes to this node input, listed in `to_node` or matched by a pattern.
+    pub fn targets_node(&self, node_id: &NodeId, input_handle: &HandleName) -> bool {
+        self.to_node.as_ref().is_some_and(|to_nodes| {
+            to_nodes
+                .iter()
+                .any(|to| &to.node_id == node_id && &to.input_handle == input_handle)
+        }) || self.to_node_pattern.as_ref().is_some_and(|patterns| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches(node_id, input_handle))
+        })
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct ToFlowOutput {
     pub output_handle: HandleName,
@@ -146,6 +183,61 @@ pub struct ToNodeInput {
     pub input_handle: HandleName,
 }
 
+/// The `input_handle` of every node whose id matches `node_id_glob`, e.g. `worker-*`. The glob
+/// only knows `*` (any run of characters, possibly empty) and `?` (exactly one character), every
+/// other character matches itself.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ToNodeInputPattern {
+    pub node_id_glob: String,
+    pub input_handle: HandleName,
+}
+
+impl ToNodeInputPattern {
+    pub fn matches(&self, node_id: &NodeId, input_handle: &HandleName) -> bool {
+        &self.input_handle == input_handle && glob_match(&self.node_id_glob, node_id)
+    }
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // let the last `*` take one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// What a block error is about, sent next to the message so tooling doesn't have to parse it.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Timeout,
+    /// the executor running the block crashed or couldn't start.
+    ExecutorExit,
+    /// the block itself failed, e.g. the user code threw.
+    UserError,
+    /// oocana couldn't run the block, e.g. a missing configuration.
+    SchedulerError,
+    Cancelled,
+    /// the connection to the broker was lost, nothing more arrives for the running blocks.
+    BrokerDisconnected,
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(tag = "type")]
 pub enum ReceiveMessage {
@@ -162,6 +254,18 @@ pub enum ReceiveMessage {
         /// if not None, it means this output is only to specific nodes. None means all nodes.
         options: Option<OutputOptions>,
     },
+    /// One piece of a large output. The `data` of the `chunk_total` pieces of a handle, joined in
+    /// `chunk_index` order, is the output's JSON. The flow passes the output on once all arrived.
+    BlockOutputChunk {
+        session_id: SessionId,
+        job_id: JobId,
+        handle: HandleName,
+        chunk_index: usize,
+        chunk_total: usize,
+        data: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        options: Option<OutputOptions>,
+    },
     BlockOutputs {
         session_id: SessionId,
         job_id: JobId,
@@ -171,6 +275,8 @@ pub enum ReceiveMessage {
         session_id: SessionId,
         job_id: JobId,
         error: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error_kind: Option<ErrorKind>,
     },
     BlockProgress {
         session_id: SessionId,
@@ -183,14 +289,28 @@ pub enum ReceiveMessage {
         job_id: JobId,
         result: Option<HashMap<HandleName, JsonValue>>,
         error: Option<String>,
+        /// unset when the executor reports the error, which means [`ErrorKind::UserError`].
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error_kind: Option<ErrorKind>,
+        /// exit code of the executor process, set when the block died because it exited.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit_code: Option<i32>,
     },
     ExecutorReady {
         session_id: SessionId,
         executor_name: String,
         package: Option<String>,
         identifier: Option<String>,
+        /// pid of the executor process, a ready with another pid means the executor was replaced.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pid: Option<u32>,
     },
     BlockRequest(BlockRequest),
+    /// Sent by a controller to abort one running task job, the rest of the flow goes on.
+    AbortJob {
+        session_id: SessionId,
+        job_id: JobId,
+    },
     // --- 以下消息，是通过 scheduler 发送给 subscriber 的消息，而不是 mqtt 消息 --- //
     ExecutorTimeout {
         session_id: SessionId,
@@ -204,6 +324,14 @@ pub enum ReceiveMessage {
         code: i32,
         reason: Option<String>,
     },
+    ExecutorRestart {
+        session_id: SessionId,
+        job_id: JobId,
+        executor_name: String,
+        code: i32,
+        /// starts at 1 for the first restart of this executor.
+        attempt: u32,
+    },
     // --- 以下消息，是其他信息发送的 --- //
     ListenerTimeout {
         session_id: SessionId,
@@ -216,14 +344,17 @@ impl ReceiveMessage {
         match self {
             ReceiveMessage::BlockReady { session_id, .. } => session_id,
             ReceiveMessage::BlockOutput { session_id, .. } => session_id,
+            ReceiveMessage::BlockOutputChunk { session_id, .. } => session_id,
             ReceiveMessage::BlockOutputs { session_id, .. } => session_id,
             ReceiveMessage::BlockError { session_id, .. } => session_id,
             ReceiveMessage::BlockProgress { session_id, .. } => session_id,
             ReceiveMessage::BlockFinished { session_id, .. } => session_id,
             ReceiveMessage::BlockRequest(block) => block.session_id(),
+            ReceiveMessage::AbortJob { session_id, .. } => session_id,
             ReceiveMessage::ExecutorReady { session_id, .. } => session_id,
             ReceiveMessage::ExecutorExit { session_id, .. } => session_id,
             ReceiveMessage::ExecutorTimeout { session_id, .. } => session_id,
+            ReceiveMessage::ExecutorRestart { session_id, .. } => session_id,
             ReceiveMessage::ListenerTimeout { session_id, .. } => session_id,
         }
     }
@@ -232,14 +363,17 @@ impl ReceiveMessage {
         match self {
             ReceiveMessage::BlockReady { job_id, .. } => Some(job_id),
             ReceiveMessage::BlockOutput { job_id, .. } => Some(job_id),
+            ReceiveMessage::BlockOutputChunk { job_id, .. } => Some(job_id),
             ReceiveMessage::BlockOutputs { job_id, .. } => Some(job_id),
             ReceiveMessage::BlockError { job_id, .. } => Some(job_id),
             ReceiveMessage::BlockProgress { job_id, .. } => Some(job_id),
             ReceiveMessage::BlockFinished { job_id, .. } => Some(job_id),
             ReceiveMessage::BlockRequest(block) => Some(block.job_id()),
+            ReceiveMessage::AbortJob { job_id, .. } => Some(job_id),
             ReceiveMessage::ExecutorReady { .. } => None,
             ReceiveMessage::ExecutorExit { .. } => None,
             ReceiveMessage::ExecutorTimeout { .. } => None,
+            ReceiveMessage::ExecutorRestart { job_id, .. } => Some(job_id),
             ReceiveMessage::ListenerTimeout { job_id, .. } => Some(job_id),
         }
     }
@@ -282,6 +416,11 @@ pub enum ExecutePayload<'a> {
         service_hash: String,
         identifier: &'a str,
     },
+    /// Ask a running block to shut down gracefully, it's hard aborted later if it doesn't finish.
+    SoftCancel {
+        session_id: &'a SessionId,
+        job_id: &'a JobId,
+    },
 }
 
 // job 和 session id 在发送给 executor 时，是必须的。添加这两个函数是为了保证这两个字段不缺失。
@@ -291,6 +430,7 @@ impl ExecutePayload<'_> {
             ExecutePayload::BlockInputs { job_id, .. } => job_id,
             ExecutePayload::BlockPayload { job_id, .. } => job_id,
             ExecutePayload::ServiceBlockPayload { job_id, .. } => job_id,
+            ExecutePayload::SoftCancel { job_id, .. } => job_id,
         }
     }
 
@@ -299,6 +439,7 @@ impl ExecutePayload<'_> {
             ExecutePayload::BlockInputs { session_id, .. } => session_id,
             ExecutePayload::BlockPayload { session_id, .. } => session_id,
             ExecutePayload::ServiceBlockPayload { session_id, .. } => session_id,
+            ExecutePayload::SoftCancel { session_id, .. } => session_id,
         }
     }
 }
@@ -316,6 +457,7 @@ pub trait SchedulerTxImpl {
         data: MessageData,
     );
     async fn run_service_block(&self, executor_name: &str, data: MessageData);
+    async fn soft_cancel(&self, executor_name: &str, data: MessageData);
     async fn disconnect(&self);
 }
 
@@ -378,8 +520,22 @@ enum SchedulerCommand {
         executor: String,
         package: Option<String>,
         identifier: Option<String>,
+        /// the timeout that passed.
+        timeout: std::time::Duration,
+        /// set when the executor process is alive but never reported ready, with the last lines
+        /// of its stderr.
+        stderr_tail: Option<Vec<String>>,
     },
     ReceiveMessage(MessageData),
+    ServiceHealthChecked {
+        job_id: JobId,
+        executor_name: String,
+        /// executor map name of the service executor
+        service: String,
+        data: MessageData,
+        error: Option<String>,
+    },
+    SoftCancelRunningBlocks,
     Abort,
 }
 
@@ -387,12 +543,29 @@ enum SchedulerCommand {
 pub struct ExecutorState {
     spawn_state: ExecutorSpawnState,
     pid: Option<u32>,
+    /// `ovmlayer run` of the executor's layer up to the script, to run more commands in the layer
+    /// while the executor is alive. `None` for executors that run on the host.
+    layer_shell: Option<Vec<OsString>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 struct RunningBlock {
     executor_name: String,
     identifier: String,
+    /// what is needed to send the block again after its executor is restarted, service blocks
+    /// are not resubmitted.
+    resubmit: Option<ResubmitBlock>,
+}
+
+#[derive(Debug, Clone)]
+struct ResubmitBlock {
+    dir: String,
+    stacks: Vec<BlockJobStackLevel>,
+    outputs: Option<OutputHandles>,
+    executor: TaskBlockExecutor,
+    injection_store: Option<InjectionStore>,
+    scope: RuntimeScope,
+    flow_path: Option<String>,
 }
 
 fn executor_name_matches(running_executor: &str, exited_executor: &str) -> bool {
@@ -411,6 +584,71 @@ fn lifecycle_identifier_matches(event_identifier: Option<&str>, running_identifi
     }
 }
 
+/// Run `command` in `dir` until it exits with 0, at most for `timeout`. With a `layer_shell` the
+/// command runs in the layer the service executor runs in, see [`ExecutorState::layer_shell`].
+async fn wait_until_healthy(
+    command: &str,
+    dir: &str,
+    layer_shell: Option<&[OsString]>,
+    timeout: std::time::Duration,
+    interval: std::time::Duration,
+) -> std::result::Result<(), String> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let mut check = match layer_shell {
+            Some([shell, args @ ..]) => {
+                let mut check = tokioCommand::new(shell);
+                check.args(args).arg(format!(
+                    "{} && {command}",
+                    layer::convert_to_script(&vec!["cd", dir])
+                ));
+                check
+            }
+            _ => {
+                let mut check = tokioCommand::new("sh");
+                check.arg("-c").arg(command).current_dir(dir);
+                check
+            }
+        };
+        let status = tokio::time::timeout_at(
+            deadline,
+            check
+                .stdout(process::Stdio::null())
+                .stderr(process::Stdio::null())
+                .kill_on_drop(true)
+                .status(),
+        )
+        .await;
+        if let Ok(Ok(status)) = &status {
+            if status.success() {
+                return Ok(());
+            }
+        }
+        if status.is_err() || tokio::time::Instant::now() + interval >= deadline {
+            return Err(format!(
+                "service health check `{command}` didn't pass within {}s",
+                timeout.as_secs_f32()
+            ));
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// A subscriber whose receiver is gone belongs to a job that already ended, drop it instead of
+/// failing the event loop.
+fn send_to_subscriber(
+    subscribers: &mut HashMap<JobId, Sender<ReceiveMessage>>,
+    job_id: &JobId,
+    message: ReceiveMessage,
+) {
+    if let Some(sender) = subscribers.get(job_id) {
+        if let Err(e) = sender.send(message) {
+            warn!("Scheduler send message to subscriber {job_id} failed, removing: {e}");
+            subscribers.remove(job_id);
+        }
+    }
+}
+
 fn executor_map_name_from_parts(executor: &str, identifier: Option<&str>) -> String {
     let executor_name = executor.strip_suffix("-executor").unwrap_or(executor);
     match identifier {
@@ -436,6 +674,9 @@ pub struct SchedulerTx {
     default_package: Option<String>,
     exclude_packages: Option<Vec<String>>,
     data_dir: String,
+    /// set once the transport stops delivering messages, see [`SchedulerTx::transport_closed`].
+    transport_closed: watch::Receiver<bool>,
+    restart_policy: ExecutorRestartPolicy,
 }
 
 pub struct BlockResponseParams {
@@ -580,6 +821,7 @@ impl SchedulerTx {
                             node_id: scope.node_id().clone(),
                             enable_layer: false,
                             is_inject: scope.is_inject(),
+                            data_dir_readonly: scope.data_dir_readonly,
                         },
                         None => RuntimeScope {
                             session_id: scope.session_id.clone(),
@@ -590,6 +832,7 @@ impl SchedulerTx {
                             node_id: scope.node_id().clone(),
                             enable_layer: false,
                             is_inject: scope.is_inject(),
+                            data_dir_readonly: scope.data_dir_readonly,
                         },
                     }
                 } else {
@@ -676,11 +919,34 @@ impl SchedulerTx {
         }
     }
 
+    /// Send a soft cancel to every block running in an executor.
+    pub fn soft_cancel_running_blocks(&self) {
+        if let Err(e) = self.tx.send(SchedulerCommand::SoftCancelRunningBlocks) {
+            warn!("Scheduler send soft cancel failed: {e}");
+        }
+    }
+
     pub fn abort(&self) {
         if let Err(e) = self.tx.send(SchedulerCommand::Abort) {
             warn!("Scheduler send abort failed: {e}");
         }
     }
+
+    /// Whether a crashed executor is restarted, a restarted executor asks for the inputs of its
+    /// running blocks again.
+    pub fn restarts_executors(&self) -> bool {
+        self.restart_policy.max_attempts > 0
+    }
+
+    /// Resolves once the transport stopped delivering messages, e.g. the broker went away.
+    /// Blocks still running then never report back.
+    pub async fn transport_closed(&self) {
+        let mut closed = self.transport_closed.clone();
+        if closed.wait_for(|closed| *closed).await.is_err() {
+            // the event loop never ran, there's no transport to lose
+            std::future::pending::<()>().await;
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -695,11 +961,130 @@ where
     executor_payload: ExecutorParameters,
     tx: Sender<SchedulerCommand>,
     rx: Receiver<SchedulerCommand>,
+    transport_closed: watch::Sender<bool>,
+}
+
+/// Arguments of the `<executor>-executor` command. The layer command historically passes
+/// `--package` before the debug parameters, `extra_args` always go last.
+fn executor_args(
+    executor_payload: &ExecutorParameters,
+    tmp_dir: &str,
+    identifier: &str,
+    package: &str,
+    debug_parameters: &[String],
+    in_layer: bool,
+) -> Vec<String> {
+    let mut args = vec![
+        "--session-id".to_owned(),
+        executor_payload.session_id.to_string(),
+        "--address".to_owned(),
+        executor_payload.addr.clone(),
+        "--session-dir".to_owned(),
+        executor_payload.session_dir.clone(),
+        "--tmp-dir".to_owned(),
+        tmp_dir.to_owned(),
+    ];
+
+    if !identifier.is_empty() {
+        args.push("--identifier".to_owned());
+        args.push(identifier.to_owned());
+    }
+
+    let package_args = ["--package".to_owned(), package.to_owned()];
+    if in_layer {
+        args.extend(package_args);
+        args.extend_from_slice(debug_parameters);
+    } else {
+        args.extend_from_slice(debug_parameters);
+        args.extend(package_args);
+    }
+
+    args.extend(executor_payload.extra_args.iter().cloned());
+    args
+}
+
+/// Envs of an executor: the passed through process envs, `--env` values, then the env file
+/// values for keys not set yet. The keys oocana sets for the executor go in last, nothing above
+/// replaces them.
+fn executor_envs(
+    executor_payload: &ExecutorParameters,
+    scope: &RuntimeScope,
+) -> HashMap<String, String> {
+    let mut envs: HashMap<String, String> = std::env::vars()
+        .filter(|(key, _)| {
+            key.starts_with("OOMOL_") || executor_payload.pass_through_env_keys.contains(key)
+        })
+        .collect();
+
+    tracing::debug!("pass through these env keys: {:?}", envs.keys());
+
+    envs.extend(executor_payload.env_vars.clone());
+    // the secrets file replaces the env file, only its path goes into the environment
+    match &executor_payload.secrets_file {
+        Some(secrets_file) => {
+            envs.insert(
+                utils::env::SECRETS_FILE_ENV_KEY.to_owned(),
+                secrets_file.to_string_lossy().to_string(),
+            );
+        }
+        // merged here rather than passed to ovmlayer, so both branches resolve keys the same way
+        None => utils::env::merge_envs(&mut envs, &executor_payload.env_file),
+    }
+
+    envs.insert("IS_FORKED".to_string(), "1".to_string());
+    envs.insert("OOCANA_PKG_DIR".to_string(), scope.data_dir.clone());
+    envs
+}
+
+/// Wraps an executor script in a layer, with the env file ovmlayer reads the envs from.
+type LayerCommand<'a> = &'a dyn Fn(&str, &Option<String>) -> process::Command;
+
+/// The command that starts an executor with `args` and `envs`. In a layer, `layer_command` wraps
+/// the executor script in ovmlayer, which gets the envs through a private env file created in
+/// `env_file_dir`. The file has to live until the executor started.
+fn executor_command(
+    executor_bin: &str,
+    args: &[String],
+    envs: HashMap<String, String>,
+    layer_command: Option<LayerCommand<'_>>,
+    env_file_dir: &Path,
+) -> Result<(process::Command, Option<utils::env::PrivateEnvFile>)> {
+    let (mut command, env_file) = match layer_command {
+        Some(layer_command) => {
+            let mut exec_form_cmd: Vec<&str> = vec![executor_bin];
+            exec_form_cmd.extend(args.iter().map(String::as_str));
+            let script_str = layer::convert_to_script(&exec_form_cmd);
+
+            // ovmlayer takes envs as arguments, which every user can read in `ps`, the values
+            // (secrets from env files and `--env` included) go through a file only we can read
+            let env_file = utils::env::PrivateEnvFile::create(
+                env_file_dir.join(format!("{executor_bin}-{}.env", rand::random::<u32>())),
+                &envs,
+            )?;
+            let command = layer_command(
+                &script_str,
+                &Some(env_file.path().to_string_lossy().to_string()),
+            );
+            (command, Some(env_file))
+        }
+        None => {
+            let mut cmd = process::Command::new(executor_bin);
+            cmd.args(args);
+            (cmd, None)
+        }
+    };
+
+    command
+        .envs(envs)
+        .stdin(process::Stdio::null())
+        .stdout(process::Stdio::piped())
+        .stderr(process::Stdio::piped());
+    Ok((command, env_file))
 }
 
 fn spawn_executor(
     executor: &str,
-    layer: Option<RuntimeLayer>,
+    mut layer: Option<RuntimeLayer>,
     scope: &RuntimeScope,
     executor_map: Arc<RwLock<HashMap<String, ExecutorState>>>,
     executor_payload: ExecutorParameters,
@@ -721,20 +1106,32 @@ fn spawn_executor(
         ExecutorState {
             spawn_state: ExecutorSpawnState::Spawned,
             pid: None,
+            layer_shell: None,
         },
     );
     drop(write_map);
 
     let ExecutorParameters {
-        session_id,
-        addr,
-        session_dir,
-        pass_through_env_keys,
+        session_id: _session_id,
+        addr: _addr,
+        session_dir: _session_dir,
+        pass_through_env_keys: _pass_through_env_keys,
         bind_paths: _bind_paths,
-        env_file,
+        env_file: _env_file,
+        env_vars: _env_vars,
+        secrets_file,
         tmp_dir,
         debug,
         wait_for_client,
+        max_log_line_bytes,
+        max_log_bytes,
+        per_executor_log_dir,
+        executor_log_reporter,
+        spawn_timeout,
+        ready_timeout,
+        listener_timeout: _listener_timeout,
+        restart_policy: _restart_policy,
+        extra_args: _extra_args,
     } = &executor_payload;
 
     // 后面加 -executor 尾缀是一种隐式约定。例如：如果 executor 是 "python"，那么实际上会执行 python-executor。
@@ -798,44 +1195,22 @@ fn spawn_executor(
         vec![]
     };
 
-    let mut envs: HashMap<String, String> = std::env::vars()
-        .filter(|(key, _)| key.starts_with("OOMOL_") || pass_through_env_keys.contains(key))
-        .collect();
-
-    envs.insert("IS_FORKED".to_string(), "1".to_string());
-
-    tracing::debug!("pass through these env keys: {:?}", envs.keys());
-
-    let mut command = if let Some(ref pkg_layer) = layer {
-        let package_path_str = pkg_layer.package_path.to_string_lossy();
-
-        envs.insert("OOCANA_PKG_DIR".to_string(), scope.data_dir.clone());
-
-        let mut exec_form_cmd: Vec<&str> = vec![
-            &executor_bin,
-            "--session-id",
-            session_id,
-            "--address",
-            addr,
-            "--session-dir",
-            session_dir,
-            "--tmp-dir",
-            tmp_dir.as_str(),
-        ];
-
-        if !identifier.is_empty() {
-            exec_form_cmd.push("--identifier");
-            exec_form_cmd.push(&identifier);
-        }
-
-        exec_form_cmd.push("--package");
-        exec_form_cmd.push(&scope_package);
-
-        for p in debug_parameters.iter() {
-            exec_form_cmd.push(p);
-        }
+    let mut envs = executor_envs(&executor_payload, scope);
+    if let (Some(secrets_file), Some(pkg_layer)) = (secrets_file, layer.as_mut()) {
+        let path = secrets_file.to_string_lossy().to_string();
+        pkg_layer.add_bind_paths(&[BindPath::new(&path, &path, true, false)]);
+    }
 
-        executor_package = Some(package_path_str.to_string());
+    let args = executor_args(
+        &executor_payload,
+        &tmp_dir,
+        &identifier,
+        &scope_package,
+        &debug_parameters,
+        layer.is_some(),
+    );
+    let (command, layer_env_file) = if let Some(ref pkg_layer) = layer {
+        executor_package = Some(pkg_layer.package_path.to_string_lossy().to_string());
 
         let log_filename = format!("ovmlayer-{executor_bin}-{identifier}.log");
 
@@ -845,126 +1220,107 @@ fn spawn_executor(
             log_dir.join(&log_filename).to_string_lossy().to_string(),
         );
 
-        let script_str = layer::convert_to_script(&exec_form_cmd);
-
-        pkg_layer.run_command(&script_str, &envs, env_file)
+        let run_in_layer = |script: &str, env_file: &Option<String>| {
+            pkg_layer.run_command(script, &HashMap::new(), env_file)
+        };
+        executor_command(
+            &executor_bin,
+            &args,
+            envs,
+            Some(&run_in_layer),
+            &tmp_pkg_dir,
+        )?
     } else {
-        envs.insert("OOCANA_PKG_DIR".to_string(), scope.data_dir.clone());
-        for (key, value) in utils::env::load_env_from_file(env_file) {
-            if envs.contains_key(&key) {
-                // TODO: consider whether to skip the env key or not.
-                warn!("env key {} is already in envs, skip", key);
-            } else {
-                envs.insert(key.to_owned(), value.to_owned());
-            }
-        }
-
-        let mut args = vec![
-            "--session-id",
-            session_id,
-            "--address",
-            addr,
-            "--session-dir",
-            session_dir,
-            "--tmp-dir",
-            tmp_dir.as_str(),
-        ];
-
-        if !identifier.is_empty() {
-            args.push("--identifier");
-            args.push(&identifier);
-        }
-
-        for p in debug_parameters.iter() {
-            args.push(p);
-        }
-
-        args.push("--package");
-        args.push(&scope_package);
-
-        let mut cmd = process::Command::new(&executor_bin);
-        cmd.args(args);
-        cmd
+        executor_command(&executor_bin, &args, envs, None, &tmp_pkg_dir)?
     };
 
-    command
-        .envs(envs)
-        .stdin(process::Stdio::null())
-        .stdout(process::Stdio::piped())
-        .stderr(process::Stdio::piped());
-
     info!("spawn executor: {:?}", command);
     let mut tokio_command = tokioCommand::from(command);
 
     let child = tokio_command.spawn();
 
-    let executor_map_clone = executor_map.clone();
-    let txx = tx.clone();
-
+    let stderr_tail = OutputTail::default();
     let executor_bin_clone = executor_bin.clone();
-    let executor_map_name_clone = executor_map_name.clone();
     let identifier_for_timeout = identifier.clone();
-    tokio::spawn(async move {
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-        {
-            let read_map = executor_map_clone.read().unwrap();
-            let executor_state = read_map
-                .get(&executor_map_name_clone)
-                .cloned()
-                .unwrap_or_default();
-            if executor_state.spawn_state != ExecutorSpawnState::Spawned {
-                return;
-            }
-            if let Err(e) = txx.send(SchedulerCommand::SpawnExecutorTimeout {
-                executor: executor_bin_clone,
-                package: executor_package,
-                identifier: Some(identifier_for_timeout),
-            }) {
-                warn!("Scheduler send spawn executor timeout failed: {e}");
-            }
-        }
-    });
+    spawn_ready_timer(
+        executor_map.clone(),
+        executor_map_name.clone(),
+        *spawn_timeout,
+        *ready_timeout,
+        stderr_tail.clone(),
+        tx.clone(),
+        move |timeout, stderr_tail| SchedulerCommand::SpawnExecutorTimeout {
+            executor: executor_bin_clone.clone(),
+            package: executor_package.clone(),
+            identifier: Some(identifier_for_timeout.clone()),
+            timeout,
+            stderr_tail,
+        },
+    );
 
     match child {
         Ok(mut ch) => {
             let pid = ch.id();
+            let layer_shell = layer.as_ref().map(|layer| {
+                let shell = layer.shell_command(&HashMap::new(), &None);
+                std::iter::once(shell.get_program())
+                    .chain(shell.get_args())
+                    .map(ToOwned::to_owned)
+                    .collect()
+            });
             let mut map = executor_map.write().unwrap();
             map.insert(
                 executor_map_name.clone(),
                 ExecutorState {
                     spawn_state: ExecutorSpawnState::Spawned,
                     pid,
+                    layer_shell,
                 },
             );
             drop(map);
 
+            let log_file = per_executor_log_dir
+                .as_ref()
+                .and_then(|dir| open_executor_log_file(dir, &executor_bin, &identifier));
+
+            let log_source = |stdio| {
+                executor_log_reporter
+                    .as_ref()
+                    .map(|reporter| ExecutorLogSource {
+                        reporter: reporter.clone(),
+                        executor_name: executor_bin.clone(),
+                        identifier: identifier.clone(),
+                        stdio,
+                    })
+            };
+
+            let budget = max_log_bytes.map(OutputBudget::new);
+
             if let Some(stdout) = ch.stdout.take() {
-                let mut reader = tokio::io::BufReader::new(stdout).lines();
-                let executor_bin_clone = executor_bin.clone();
-                let identifier_clone = identifier.clone();
-                tokio::spawn(async move {
-                    while let Ok(Some(line)) = reader.next_line().await {
-                        debug!(
-                            "{} ({}) stdout: {}",
-                            executor_bin_clone, identifier_clone, line
-                        );
-                    }
-                });
+                spawn_output_reader(
+                    stdout,
+                    *max_log_line_bytes,
+                    budget.clone(),
+                    format!("{executor_bin} ({identifier}) stdout"),
+                    log_file
+                        .as_ref()
+                        .and_then(|f| f.try_clone().ok())
+                        .map(tokio::fs::File::from_std),
+                    log_source("stdout"),
+                    None,
+                );
             }
 
             if let Some(stderr) = ch.stderr.take() {
-                let mut reader = tokio::io::BufReader::new(stderr).lines();
-                let executor_bin_clone = executor_bin.clone();
-                let identifier_clone = identifier.clone();
-
-                tokio::spawn(async move {
-                    while let Ok(Some(line)) = reader.next_line().await {
-                        debug!(
-                            "{} ({}) stderr: {}",
-                            executor_bin_clone, identifier_clone, line
-                        );
-                    }
-                });
+                spawn_output_reader(
+                    stderr,
+                    *max_log_line_bytes,
+                    budget,
+                    format!("{executor_bin} ({identifier}) stderr"),
+                    log_file.map(tokio::fs::File::from_std),
+                    log_source("stderr"),
+                    Some(stderr_tail),
+                );
             }
             let executor_bin_clone = executor_bin;
             let executor_map_clone = executor_map.clone();
@@ -986,6 +1342,7 @@ fn spawn_executor(
                 if let Some(layer) = layer {
                     drop(layer);
                 }
+                drop(layer_env_file);
                 match status {
                     // the time maybe after scheduler shutdown, send to tx will fail
                     Ok(status) => {
@@ -1023,6 +1380,7 @@ fn spawn_executor(
                 ExecutorState {
                     spawn_state: ExecutorSpawnState::None,
                     pid: None,
+                    layer_shell: None,
                 },
             );
             drop(write_map);
@@ -1036,6 +1394,260 @@ fn spawn_executor(
     }
 }
 
+fn executor_log_path(dir: &Path, executor_bin: &str, identifier: &str) -> PathBuf {
+    if identifier.is_empty() {
+        dir.join(format!("{executor_bin}.log"))
+    } else {
+        dir.join(format!("{executor_bin}-{identifier}.log"))
+    }
+}
+
+fn open_executor_log_file(
+    dir: &Path,
+    executor_bin: &str,
+    identifier: &str,
+) -> Option<std::fs::File> {
+    let path = executor_log_path(dir, executor_bin, identifier);
+    std::fs::create_dir_all(dir)
+        .and_then(|_| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+        })
+        .map_err(|e| warn!("Failed to open executor log file {path:?}: {e}"))
+        .ok()
+}
+
+/// Where an executor output stream is reported, see [`ExecutorParameters::executor_log_reporter`].
+struct ExecutorLogSource {
+    reporter: ReporterTx,
+    executor_name: String,
+    identifier: String,
+    stdio: &'static str,
+}
+
+/// Report an executor that isn't ready in time: its process didn't start within `spawn_timeout`,
+/// or it started but didn't send `ExecutorReady` within `ready_timeout`. Both count from the
+/// spawn.
+fn spawn_ready_timer(
+    executor_map: Arc<RwLock<HashMap<String, ExecutorState>>>,
+    executor_map_name: String,
+    spawn_timeout: std::time::Duration,
+    ready_timeout: std::time::Duration,
+    stderr_tail: OutputTail,
+    tx: Sender<SchedulerCommand>,
+    timeout_command: impl Fn(std::time::Duration, Option<Vec<String>>) -> SchedulerCommand
+    + Send
+    + 'static,
+) {
+    let spawned_state = move || {
+        let read_map = executor_map.read().unwrap();
+        read_map
+            .get(&executor_map_name)
+            .cloned()
+            .unwrap_or_default()
+    };
+    tokio::spawn(async move {
+        tokio::time::sleep(spawn_timeout).await;
+        let executor_state = spawned_state();
+        if executor_state.spawn_state != ExecutorSpawnState::Spawned {
+            return;
+        }
+        let command = if executor_state.pid.is_none() {
+            timeout_command(spawn_timeout, None)
+        } else {
+            tokio::time::sleep(ready_timeout.saturating_sub(spawn_timeout)).await;
+            let executor_state = spawned_state();
+            if executor_state.spawn_state != ExecutorSpawnState::Spawned
+                || executor_state.pid.is_none()
+            {
+                return;
+            }
+            // a live process got stuck starting up, its stderr usually tells why
+            timeout_command(ready_timeout.max(spawn_timeout), Some(stderr_tail.lines()))
+        };
+        if let Err(e) = tx.send(command) {
+            warn!("Scheduler send spawn executor timeout failed: {e}");
+        }
+    });
+}
+
+/// Stderr lines of an executor kept to explain a start that never finished.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// The last lines an executor wrote, kept by its output reader.
+#[derive(Debug, Clone, Default)]
+struct OutputTail(Arc<Mutex<VecDeque<String>>>);
+
+impl OutputTail {
+    fn push(&self, line: &str) {
+        let mut lines = self.0.lock().unwrap();
+        if lines.len() == STDERR_TAIL_LINES {
+            lines.pop_front();
+        }
+        lines.push_back(line.to_owned());
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+/// Output bytes one executor may still have captured, shared by its stdout and stderr readers.
+#[derive(Debug, Clone)]
+struct OutputBudget {
+    max: usize,
+    left: Arc<AtomicUsize>,
+}
+
+impl OutputBudget {
+    fn new(max: usize) -> Self {
+        Self {
+            max,
+            left: Arc::new(AtomicUsize::new(max)),
+        }
+    }
+
+    /// Take `len` bytes. Once a line doesn't fit the budget is used up, so no later line is kept
+    /// after a dropped one.
+    fn take(&self, len: usize) -> bool {
+        let mut fits = false;
+        let _ = self
+            .left
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                fits = left >= len;
+                Some(if fits { left - len } else { 0 })
+            });
+        fits
+    }
+}
+
+/// Forward every line of an executor output stream to the session log, to `log_file` and to the
+/// reporter if given. Lines past the `budget` are dropped and counted in a final
+/// `…(truncated N bytes)` line.
+fn spawn_output_reader<R: AsyncRead + Unpin + Send + 'static>(
+    output: R,
+    max_log_line_bytes: usize,
+    budget: Option<OutputBudget>,
+    label: String,
+    log_file: Option<tokio::fs::File>,
+    log_source: Option<ExecutorLogSource>,
+    tail: Option<OutputTail>,
+) -> tokio::task::JoinHandle<()> {
+    let mut reader = tokio::io::BufReader::new(output);
+    let mut sink = OutputSink {
+        label,
+        log_file,
+        log_source,
+    };
+    tokio::spawn(async move {
+        let mut dropped = 0usize;
+        while let Ok(Some(line)) = read_capped_line(&mut reader, max_log_line_bytes).await {
+            // kept even past the budget, the last lines matter most
+            if let Some(tail) = &tail {
+                tail.push(&line);
+            }
+            if let Some(budget) = &budget {
+                // the line ending counts too
+                if !budget.take(line.len() + 1) {
+                    if dropped == 0 {
+                        warn!(
+                            "{}: executor output reached its {} bytes cap, later lines are dropped",
+                            sink.label, budget.max
+                        );
+                    }
+                    dropped += line.len() + 1;
+                    continue;
+                }
+            }
+            sink.write(&line).await;
+        }
+        if dropped > 0 {
+            sink.write(&format!("…(truncated {dropped} bytes)")).await;
+        }
+        if let Some(mut file) = sink.log_file {
+            let _ = file.flush().await;
+        }
+    })
+}
+
+struct OutputSink {
+    label: String,
+    log_file: Option<tokio::fs::File>,
+    log_source: Option<ExecutorLogSource>,
+}
+
+impl OutputSink {
+    async fn write(&mut self, line: &str) {
+        debug!("{}: {}", self.label, line);
+        if let Some(source) = &self.log_source {
+            source.reporter.executor_log(
+                &source.executor_name,
+                &source.identifier,
+                line,
+                source.stdio,
+            );
+        }
+        if let Some(file) = self.log_file.as_mut() {
+            if let Err(e) = file.write_all(format!("{line}\n").as_bytes()).await {
+                warn!("{}: failed to write executor log file: {e}", self.label);
+                self.log_file = None;
+            }
+        }
+    }
+}
+
+/// Read one line from `reader` without its line ending. At most `max_bytes` of the line are kept,
+/// the rest is discarded while reading so a huge line is never fully buffered, and a
+/// `…(truncated N bytes)` marker is appended instead. Returns `None` at EOF.
+async fn read_capped_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    max_bytes: usize,
+) -> std::io::Result<Option<String>> {
+    let mut line = Vec::new();
+    let mut truncated = 0usize;
+    let mut read_any = false;
+
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            if !read_any {
+                return Ok(None);
+            }
+            break;
+        }
+        read_any = true;
+
+        let newline = available.iter().position(|b| *b == b'\n');
+        let chunk = &available[..newline.unwrap_or(available.len())];
+        let keep = max_bytes.saturating_sub(line.len()).min(chunk.len());
+        line.extend_from_slice(&chunk[..keep]);
+        truncated += chunk.len() - keep;
+
+        let consumed = newline.map(|i| i + 1).unwrap_or(available.len());
+        reader.consume(consumed);
+        if newline.is_some() {
+            break;
+        }
+    }
+
+    if truncated == 0 && line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    let mut line = String::from_utf8_lossy(&line).into_owned();
+    if truncated > 0 {
+        line.push_str(&format!("…(truncated {truncated} bytes)"));
+    }
+    Ok(Some(line))
+}
+
+fn package_data_bind_path(scope: &RuntimeScope) -> BindPath {
+    let pkg_dir = scope.data_dir.as_str();
+    BindPath::new(pkg_dir, pkg_dir, scope.data_dir_readonly, false)
+}
+
 fn query_executor_state(params: ExecutorCheckParams) -> Result<ExecutorCheckResult> {
     let ExecutorCheckParams {
         executor_name,
@@ -1080,6 +1692,13 @@ fn query_executor_state(params: ExecutorCheckParams) -> Result<ExecutorCheckResu
             layer: None,
         });
     } else if !scope.need_layer() {
+        if scope.data_dir_readonly {
+            tracing::info!(
+                "scope {} runs without layer, data dir {} stays writable",
+                scope.identifier(),
+                scope.data_dir
+            );
+        }
         let pkg_dir = PathBuf::from(&scope.data_dir);
         if !pkg_dir.exists() {
             std::fs::create_dir_all(&pkg_dir).unwrap_or_else(|e| {
@@ -1133,12 +1752,7 @@ fn query_executor_state(params: ExecutorCheckParams) -> Result<ExecutorCheckResu
             });
         }
 
-        bind_paths.push(BindPath::new(
-            pkg_dir.to_string_lossy().as_ref(),
-            pkg_dir.to_string_lossy().as_ref(),
-            false,
-            false,
-        ));
+        bind_paths.push(package_data_bind_path(scope));
 
         let path_str = pkg.to_string_lossy().to_string();
         let (package_name, version) = resolve_package_meta(scope, injection_store);
@@ -1155,12 +1769,18 @@ fn query_executor_state(params: ExecutorCheckParams) -> Result<ExecutorCheckResu
             let target = manifest_meta::InjectionTarget::Package(scope.path().to_owned());
             if let Some(meta) = store.get(&target) {
                 let scripts = meta.scripts.clone().unwrap_or_default();
+                let nodes: Vec<String> = meta
+                    .nodes
+                    .iter()
+                    .map(|node| node.relative_entry.to_string_lossy().to_string())
+                    .collect();
 
                 let result = runtime_layer.inject_runtime_layer(InjectionParams {
                     package_version: &meta.package_version,
                     package_path: &path_str,
                     scripts: &scripts,
                     flow_path: flow_path.as_ref().unwrap_or(&"".to_string()),
+                    nodes: &nodes,
                 });
 
                 if let Err(e) = result {
@@ -1202,9 +1822,14 @@ where
             executor_payload,
             impl_tx,
             mut impl_rx,
+            transport_closed,
         } = self;
 
         let mut running_blocks: HashMap<JobId, RunningBlock> = HashMap::new();
+        // service executors whose health check passed, keyed by executor map name
+        let mut healthy_services: HashSet<String> = HashSet::new();
+        // restarts so far, keyed by executor map name
+        let mut restart_attempts: HashMap<String, u32> = HashMap::new();
         let session_id = executor_payload.session_id.clone();
         let tx_clone = tx.clone();
 
@@ -1213,6 +1838,7 @@ where
                 let data = impl_rx.recv().await;
                 // if data is empty, it means the impl_rx is closed.
                 if data.is_empty() {
+                    let _ = transport_closed.send(true);
                     break;
                 }
                 if let Err(e) = tx_clone.send(SchedulerCommand::ReceiveMessage(data)) {
@@ -1238,6 +1864,7 @@ where
                             ReceiveMessage::BlockReady { .. }
                                 | ReceiveMessage::BlockFinished { .. }
                                 | ReceiveMessage::BlockOutput { .. }
+                                | ReceiveMessage::BlockOutputChunk { .. }
                                 | ReceiveMessage::BlockOutputs { .. }
                                 | ReceiveMessage::BlockError { .. }
                         ) {
@@ -1314,6 +1941,7 @@ where
                             RunningBlock {
                                 executor_name: executor_name.clone(),
                                 identifier: scope.identifier(),
+                                resubmit: None,
                             },
                         );
 
@@ -1389,15 +2017,81 @@ where
                                 identifier: &scope.identifier(),
                             })
                             .unwrap();
-                            impl_tx.run_service_block(&executor_name, data).await;
+
+                            let service = executor_map_name_from_parts(
+                                &executor_name,
+                                Some(&scope.identifier()),
+                            );
+                            match service_executor.health_check {
+                                Some(check) if !healthy_services.contains(&service) => {
+                                    let tx = tx.clone();
+                                    let layer_shell = executor_map
+                                        .read()
+                                        .unwrap()
+                                        .get(&service)
+                                        .and_then(|state| state.layer_shell.clone());
+                                    tokio::spawn(async move {
+                                        let error = wait_until_healthy(
+                                            &check.command,
+                                            &dir,
+                                            layer_shell.as_deref(),
+                                            std::time::Duration::from_secs(check.timeout),
+                                            std::time::Duration::from_millis(check.interval),
+                                        )
+                                        .await
+                                        .err();
+                                        if let Err(e) =
+                                            tx.send(SchedulerCommand::ServiceHealthChecked {
+                                                job_id,
+                                                executor_name,
+                                                service,
+                                                data,
+                                                error,
+                                            })
+                                        {
+                                            warn!(
+                                                "Scheduler send service health check failed: {e}"
+                                            );
+                                        }
+                                    });
+                                }
+                                _ => impl_tx.run_service_block(&executor_name, data).await,
+                            }
                         }
                     }
-                    Ok(SchedulerCommand::ExecuteBlock {
+                    Ok(SchedulerCommand::ServiceHealthChecked {
                         job_id,
                         executor_name,
-                        dir,
-                        scope,
-                        stacks,
+                        service,
+                        data,
+                        error,
+                    }) => {
+                        let Some(error) = error else {
+                            healthy_services.insert(service);
+                            impl_tx.run_service_block(&executor_name, data).await;
+                            continue;
+                        };
+
+                        warn!("{error}, job {job_id} is not sent to {executor_name}");
+                        running_blocks.remove(&job_id);
+                        let event = ReceiveMessage::BlockFinished {
+                            session_id: session_id.clone(),
+                            job_id: job_id.clone(),
+                            result: None,
+                            error: Some(error),
+                            error_kind: Some(ErrorKind::Timeout),
+                            exit_code: None,
+                        };
+                        let data = serde_json::to_vec(&event).unwrap();
+                        impl_tx.send_block_event(&session_id, data).await;
+                        send_to_subscriber(&mut subscribers, &job_id, event);
+                    }
+                    Ok(SchedulerCommand::ExecuteBlock {
+                        job_id,
+                        executor_name,
+                        dir,
+                        scope,
+                        stacks,
                         outputs,
                         executor,
                         injection_store,
@@ -1408,6 +2102,15 @@ where
                             RunningBlock {
                                 executor_name: executor_name.clone(),
                                 identifier: scope.identifier(),
+                                resubmit: Some(ResubmitBlock {
+                                    dir: dir.clone(),
+                                    stacks: stacks.clone(),
+                                    outputs: outputs.clone(),
+                                    executor: executor.clone(),
+                                    injection_store: injection_store.clone(),
+                                    scope: scope.clone(),
+                                    flow_path: flow_path.clone(),
+                                }),
                             },
                         );
 
@@ -1485,8 +2188,9 @@ where
                             let tx_clone = tx.clone();
 
                             let session_id_clone = session_id.clone();
+                            let listener_timeout = executor_payload.listener_timeout;
                             _ = tokio::spawn(async move {
-                                tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+                                tokio::time::sleep(listener_timeout).await;
                                 let data = serde_json::to_vec(&ReceiveMessage::ListenerTimeout {
                                     job_id: job_id.clone(),
                                     session_id: session_id_clone.clone(),
@@ -1505,6 +2209,8 @@ where
                         executor,
                         package,
                         identifier,
+                        timeout,
+                        stderr_tail,
                     }) => {
                         let executor_map_name =
                             executor_map_name_from_parts(&executor, identifier.as_deref());
@@ -1536,9 +2242,21 @@ where
                             continue;
                         }
 
-                        let error_message = format!(
-                            "Executor {executor} identifier {identifier:?} for package {package:?} timeout after 5s"
-                        );
+                        let error_message = match stderr_tail {
+                            Some(lines) => {
+                                let mut message = format!(
+                                    "Executor {executor} identifier {identifier:?} for package {package:?} started but failed to initialize, it didn't report ready within {timeout:?}"
+                                );
+                                if !lines.is_empty() {
+                                    message.push_str(". Last stderr lines:\n");
+                                    message.push_str(&lines.join("\n"));
+                                }
+                                message
+                            }
+                            None => format!(
+                                "Executor {executor} identifier {identifier:?} for package {package:?} didn't start within {timeout:?}"
+                            ),
+                        };
                         let finished_jobs = running_blocks
                             .iter()
                             .filter_map(|(job_id, running_block)| {
@@ -1562,16 +2280,12 @@ where
                                 job_id: job_id.clone(),
                                 result: None,
                                 error: Some(error_message.clone()),
+                                error_kind: Some(ErrorKind::Timeout),
+                                exit_code: None,
                             };
                             let data = serde_json::to_vec(&event).unwrap();
                             impl_tx.send_block_event(&session_id, data).await;
-                            if let Some(sender) = subscribers.get(&job_id) {
-                                if let Err(e) = sender.send(event) {
-                                    warn!(
-                                        "Scheduler send executor timeout block finish to subscriber failed: {e}"
-                                    );
-                                }
-                            }
+                            send_to_subscriber(&mut subscribers, &job_id, event);
                         }
 
                         subscribers.retain(|_, sender| {
@@ -1597,6 +2311,94 @@ where
                         let error_message = reason
                             .clone()
                             .unwrap_or(format!("Executor {executor} exit with code {code}"));
+
+                        // a reason means the executor never got to run (spawn or wait failed),
+                        // restarting won't help there.
+                        let executor_map_name =
+                            executor_map_name_from_parts(&executor, identifier.as_deref());
+                        // a restarted service has to pass its health check again
+                        healthy_services.remove(&executor_map_name);
+                        let attempt = restart_attempts
+                            .get(&executor_map_name)
+                            .copied()
+                            .unwrap_or(0)
+                            + 1;
+                        let restartable = code != 0
+                            && reason.is_none()
+                            && attempt <= executor_payload.restart_policy.max_attempts;
+                        let resubmit_jobs = if restartable {
+                            running_blocks
+                                .iter()
+                                .filter_map(|(job_id, running_block)| {
+                                    if !executor_name_matches(
+                                        &running_block.executor_name,
+                                        &executor,
+                                    ) || !lifecycle_identifier_matches(
+                                        identifier.as_deref(),
+                                        &running_block.identifier,
+                                    ) {
+                                        return None;
+                                    }
+                                    running_block.resubmit.clone().map(|resubmit| {
+                                        (
+                                            job_id.clone(),
+                                            running_block.executor_name.clone(),
+                                            resubmit,
+                                        )
+                                    })
+                                })
+                                .collect::<Vec<_>>()
+                        } else {
+                            vec![]
+                        };
+
+                        if !resubmit_jobs.is_empty() {
+                            restart_attempts.insert(executor_map_name.clone(), attempt);
+                            executor_map.write().unwrap().remove(&executor_map_name);
+                            warn!(
+                                "Executor {executor} ({executor_map_name}) exit with code {code}, restart attempt {attempt}/{}",
+                                executor_payload.restart_policy.max_attempts
+                            );
+                        }
+
+                        for (job_id, executor_name, resubmit) in resubmit_jobs {
+                            running_blocks.remove(&job_id);
+                            send_to_subscriber(
+                                &mut subscribers,
+                                &job_id,
+                                ReceiveMessage::ExecutorRestart {
+                                    session_id: session_id.clone(),
+                                    job_id: job_id.clone(),
+                                    executor_name: executor.clone(),
+                                    code,
+                                    attempt,
+                                },
+                            );
+                            // goes through the normal path, which spawns the executor again
+                            let ResubmitBlock {
+                                dir,
+                                stacks,
+                                outputs,
+                                executor: block_executor,
+                                injection_store,
+                                scope,
+                                flow_path,
+                            } = resubmit;
+                            if let Err(e) = tx.send(SchedulerCommand::ExecuteBlock {
+                                job_id,
+                                executor_name,
+                                dir,
+                                stacks,
+                                outputs,
+                                executor: block_executor,
+                                injection_store,
+                                scope,
+                                flow_path,
+                            }) {
+                                warn!("Scheduler resubmit block failed: {e}");
+                            }
+                        }
+
                         let finished_jobs = running_blocks
                             .iter()
                             .filter(|(_, running_block)| {
@@ -1616,16 +2418,13 @@ where
                                 job_id: job_id.clone(),
                                 result: None,
                                 error: Some(error_message.clone()),
+                                error_kind: Some(ErrorKind::ExecutorExit),
+                                // without a reason the process did run and exit with `code`
+                                exit_code: reason.is_none().then_some(code),
                             };
                             let data = serde_json::to_vec(&event).unwrap();
                             impl_tx.send_block_event(&session_id, data).await;
-                            if let Some(sender) = subscribers.get(&job_id) {
-                                if let Err(e) = sender.send(event) {
-                                    warn!(
-                                        "Scheduler send executor exit block finish to subscriber failed: {e}"
-                                    );
-                                }
-                            }
+                            send_to_subscriber(&mut subscribers, &job_id, event);
                         }
                     }
                     Ok(SchedulerCommand::ReceiveMessage(data)) => {
@@ -1637,6 +2436,7 @@ where
                                     package,
                                     session_id,
                                     identifier,
+                                    pid,
                                 } => {
                                     // same as generate_executor_map_name fn logic
                                     let executor_map_name = executor_map_name_from_parts(
@@ -1660,7 +2460,20 @@ where
                                                 false
                                             }
                                             Some(state) => {
+                                                if state.spawn_state == ExecutorSpawnState::Ready
+                                                    && pid.is_some()
+                                                    && state.pid.is_some()
+                                                    && state.pid != pid
+                                                {
+                                                    info!(
+                                                        "Executor {} replaced, pid {:?} -> {:?}",
+                                                        executor_map_name, state.pid, pid
+                                                    );
+                                                }
                                                 state.spawn_state = ExecutorSpawnState::Ready;
+                                                if pid.is_some() {
+                                                    state.pid = pid;
+                                                }
                                                 true
                                             }
                                             None => {
@@ -1668,7 +2481,8 @@ where
                                                     executor_map_name,
                                                     ExecutorState {
                                                         spawn_state: ExecutorSpawnState::Ready,
-                                                        pid: None,
+                                                        pid,
+                                                        layer_shell: None,
                                                     },
                                                 );
                                                 true
@@ -1687,6 +2501,7 @@ where
                                             package: package.clone(),
                                             session_id: session_id.clone(),
                                             identifier: identifier.clone(),
+                                            pid,
                                         }) {
                                             warn!("Scheduler send executor ready to subscriber failed, removing: {e}");
                                             false
@@ -1698,37 +2513,40 @@ where
                                 ReceiveMessage::BlockRequest(request) => {
                                     // Handle block request
                                     let job_id = request.job_id().clone();
-                                    if let Some(sender) = subscribers.get(&job_id) {
-                                        if let Err(e) =
-                                            sender.send(ReceiveMessage::BlockRequest(request))
-                                        {
-                                            warn!(
-                                                "Scheduler send block request to subscriber failed, removing: {e}"
-                                            );
-                                            subscribers.remove(&job_id);
-                                        }
+                                    if subscribers.contains_key(&job_id) {
+                                        send_to_subscriber(
+                                            &mut subscribers,
+                                            &job_id,
+                                            ReceiveMessage::BlockRequest(request),
+                                        );
                                     } else {
                                         warn!("No subscriber for job_id: {:?}", request.job_id());
                                     }
                                 }
                                 _ => {
-                                    if let ReceiveMessage::BlockFinished { job_id, .. } = &msg {
+                                    if let ReceiveMessage::BlockFinished { job_id, .. }
+                                    | ReceiveMessage::AbortJob { job_id, .. } = &msg
+                                    {
                                         running_blocks.remove(job_id);
                                     }
                                     if let Some(job_id) = msg.job_id().cloned() {
-                                        if let Some(sender) = subscribers.get(&job_id) {
-                                            if let Err(e) = sender.send(msg) {
-                                                warn!(
-                                                    "Scheduler send message to subscriber failed, removing: {e}"
-                                                );
-                                                subscribers.remove(&job_id);
-                                            }
-                                        }
+                                        send_to_subscriber(&mut subscribers, &job_id, msg);
                                     }
                                 }
                             }
                         }
                     }
+                    Ok(SchedulerCommand::SoftCancelRunningBlocks) => {
+                        for (job_id, block) in running_blocks.iter() {
+                            info!("soft cancel block {job_id} in {}", block.executor_name);
+                            let data = serde_json::to_vec(&ExecutePayload::SoftCancel {
+                                session_id: &session_id,
+                                job_id,
+                            })
+                            .unwrap();
+                            impl_tx.soft_cancel(&block.executor_name, data).await;
+                        }
+                    }
                     Ok(SchedulerCommand::Abort) => {
                         {
                             // TODO: global service will be kill as well, try to find a better way to handle this
@@ -1788,9 +2606,40 @@ pub struct ExecutorParameters {
     pub pass_through_env_keys: Vec<String>,
     pub bind_paths: Vec<BindPath>,
     pub env_file: Option<String>,
+    /// set on every executor from `--env`, they win over `env_file` values with the same key.
+    pub env_vars: HashMap<String, String>,
+    /// when set, `env_file` values are in this file for executors to read, see
+    /// [`utils::env::SecretsFile`], instead of in their environment.
+    pub secrets_file: Option<PathBuf>,
     pub tmp_dir: PathBuf,
     pub debug: bool,
     pub wait_for_client: bool,
+    /// lines longer than this from executor stdout/stderr are truncated.
+    pub max_log_line_bytes: usize,
+    /// stdout and stderr bytes kept per executor in total, later lines are dropped. `None` keeps
+    /// everything.
+    pub max_log_bytes: Option<usize>,
+    /// when set, each executor's stdout/stderr is also written to its own file in this dir.
+    pub per_executor_log_dir: Option<PathBuf>,
+    /// when set, each executor's stdout/stderr line is also reported as `ExecutorLog`.
+    pub executor_log_reporter: Option<ReporterTx>,
+    /// an executor whose process doesn't start within this time is reported as timed out.
+    pub spawn_timeout: std::time::Duration,
+    /// a started executor that doesn't send `ExecutorReady` within this time, counted from its
+    /// spawn, is reported as failed to initialize with the last lines of its stderr.
+    pub ready_timeout: std::time::Duration,
+    /// a block that no executor picked up within this time is sent to the executor again.
+    pub listener_timeout: std::time::Duration,
+    pub restart_policy: ExecutorRestartPolicy,
+    /// appended to every executor command after the standard args, from `--executor-arg`.
+    pub extra_args: Vec<String>,
+}
+
+/// How often a crashed executor is restarted, its running blocks are sent to the new executor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExecutorRestartPolicy {
+    /// restarts per executor for the whole session, 0 turns restarting off.
+    pub max_attempts: u32,
 }
 
 pub fn create<TT, TR>(
@@ -1806,12 +2655,15 @@ where
     TR: SchedulerRxImpl,
 {
     let (tx, rx) = flume::unbounded();
+    let (transport_closed_tx, transport_closed_rx) = watch::channel(false);
     (
         SchedulerTx {
             tx: tx.clone(),
             default_package,
             exclude_packages,
             data_dir,
+            transport_closed: transport_closed_rx,
+            restart_policy: executor_payload.restart_policy,
         },
         SchedulerRx {
             impl_tx,
@@ -1820,6 +2672,7 @@ where
             executor_payload,
             tx,
             rx,
+            transport_closed: transport_closed_tx,
         },
     )
 }
@@ -1830,7 +2683,7 @@ mod tests {
     use crate::MessageData;
     use async_trait::async_trait;
     use job::SessionId;
-    use std::path::PathBuf;
+    use std::{ffi::OsStr, path::PathBuf};
     use tokio::time::{Duration, timeout};
 
     #[test]
@@ -1850,8 +2703,37 @@ mod tests {
         }));
     }
 
+    #[test]
+    fn node_input_pattern_matches_globs() {
+        let pattern = |glob: &str| ToNodeInputPattern {
+            node_id_glob: glob.to_owned(),
+            input_handle: "input".to_string().into(),
+        };
+        let input: HandleName = "input".to_string().into();
+        let node = |id: &str| -> NodeId { id.to_string().into() };
+
+        assert!(pattern("worker-*").matches(&node("worker-1"), &input));
+        assert!(pattern("worker-*").matches(&node("worker-"), &input));
+        assert!(pattern("worker-?").matches(&node("worker-2"), &input));
+        assert!(!pattern("worker-?").matches(&node("worker-10"), &input));
+        assert!(pattern("*-1*").matches(&node("worker-10"), &input));
+        assert!(!pattern("worker-*").matches(&node("reducer"), &input));
+        assert!(!pattern("worker-*").matches(&node("worker-1"), &"other".to_string().into()));
+        // no character classes, `[` is literal
+        assert!(!pattern("worker-[12]").matches(&node("worker-1"), &input));
+
+        let target: OutputTarget = serde_json::from_str(
+            r#"{"to_node": [{"node_id": "reducer", "input_handle": "input"}], "to_node_pattern": [{"node_id_glob": "worker-*", "input_handle": "input"}]}"#,
+        )
+        .unwrap();
+        assert!(target.targets_node(&node("reducer"), &input));
+        assert!(target.targets_node(&node("worker-3"), &input));
+        assert!(!target.targets_node(&node("mapper"), &input));
+    }
+
     struct CaptureSchedulerTx {
         block_events: Sender<ReceiveMessage>,
+        soft_cancels: Option<Sender<(String, serde_json::Value)>>,
     }
 
     #[async_trait]
@@ -1875,6 +2757,16 @@ mod tests {
 
         async fn run_service_block(&self, _executor_name: &str, _data: MessageData) {}
 
+        async fn soft_cancel(&self, executor_name: &str, data: MessageData) {
+            if let Some(soft_cancels) = &self.soft_cancels {
+                let payload = serde_json::from_slice(&data).unwrap();
+                soft_cancels
+                    .send_async((executor_name.to_string(), payload))
+                    .await
+                    .unwrap();
+            }
+        }
+
         async fn disconnect(&self) {}
     }
 
@@ -1895,12 +2787,146 @@ mod tests {
             pass_through_env_keys: vec![],
             bind_paths: vec![],
             env_file: None,
+            env_vars: HashMap::new(),
+            secrets_file: None,
             tmp_dir: std::env::temp_dir(),
             debug: false,
             wait_for_client: false,
+            max_log_line_bytes: DEFAULT_MAX_LOG_LINE_BYTES,
+            max_log_bytes: None,
+            per_executor_log_dir: None,
+            executor_log_reporter: None,
+            spawn_timeout: DEFAULT_EXECUTOR_SPAWN_TIMEOUT,
+            ready_timeout: DEFAULT_EXECUTOR_READY_TIMEOUT,
+            listener_timeout: DEFAULT_LISTENER_TIMEOUT,
+            restart_policy: ExecutorRestartPolicy::default(),
+            extra_args: vec![],
         }
     }
 
+    #[test]
+    fn executor_args_end_with_extra_args() {
+        let mut payload = test_executor_payload(SessionId::new("s1".to_owned()));
+        payload.extra_args = vec!["--plugin-dir".to_owned(), "/plugins".to_owned()];
+        let debug_parameters = vec!["--debug-port".to_owned(), "5678".to_owned()];
+
+        for in_layer in [false, true] {
+            let args = executor_args(
+                &payload,
+                "/tmp/oocana",
+                "pkg",
+                "/pkg",
+                &debug_parameters,
+                in_layer,
+            );
+            assert_eq!(args[..2], ["--session-id", "s1"]);
+            assert_eq!(args[args.len() - 2..], ["--plugin-dir", "/plugins"]);
+            assert!(args.windows(2).any(|pair| pair == ["--package", "/pkg"]));
+        }
+    }
+
+    #[test]
+    fn executor_command_resolves_envs_the_same_with_and_without_layer() {
+        let session_id = SessionId::new("s1".to_owned());
+        let scope = test_scope(session_id.clone(), "node");
+        let dir = std::env::temp_dir().join(format!("oocana-executor-env-{}", JobId::random()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let env_file = dir.join("executor.env");
+        std::fs::write(&env_file, "SHARED=from_file\nFILE_ONLY=file\nIS_FORKED=0\n").unwrap();
+
+        let mut payload = test_executor_payload(session_id);
+        payload.env_file = Some(env_file.to_string_lossy().to_string());
+        payload.env_vars = HashMap::from([
+            ("SHARED".to_owned(), "from_env".to_owned()),
+            ("OOCANA_PKG_DIR".to_owned(), "/elsewhere".to_owned()),
+        ]);
+        let expected = HashMap::from([
+            ("SHARED".to_owned(), "from_env".to_owned()),
+            ("FILE_ONLY".to_owned(), "file".to_owned()),
+            ("IS_FORKED".to_owned(), "1".to_owned()),
+            ("OOCANA_PKG_DIR".to_owned(), scope.data_dir.clone()),
+        ]);
+        let picked = |envs: HashMap<String, String>| {
+            envs.into_iter()
+                .filter(|(key, _)| expected.contains_key(key))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let args = executor_args(&payload, "/tmp/oocana", "pkg", "/pkg", &[], false);
+        let (command, layer_env_file) = executor_command(
+            "python-executor",
+            &args,
+            executor_envs(&payload, &scope),
+            None,
+            &dir,
+        )
+        .unwrap();
+        assert!(layer_env_file.is_none());
+        assert_eq!(command.get_program(), "python-executor");
+        assert!(command.get_args().eq(args.iter().map(OsStr::new)));
+        let envs = command
+            .get_envs()
+            .filter_map(|(key, value)| {
+                Some((key.to_str()?.to_owned(), value?.to_str()?.to_owned()))
+            })
+            .collect();
+        assert_eq!(picked(envs), expected);
+
+        let args = executor_args(&payload, "/tmp/oocana", "pkg", "/pkg", &[], true);
+        let run_in_layer = |script: &str, env_file: &Option<String>| {
+            let mut cmd = process::Command::new("ovmlayer");
+            cmd.arg(format!("--env-file={}", env_file.as_ref().unwrap()));
+            cmd.arg(script);
+            cmd
+        };
+        let (command, layer_env_file) = executor_command(
+            "python-executor",
+            &args,
+            executor_envs(&payload, &scope),
+            Some(&run_in_layer),
+            &dir,
+        )
+        .unwrap();
+        let layer_env_file = layer_env_file.expect("a layer takes its envs from a file");
+        let command_args: Vec<_> = command.get_args().collect();
+        assert_eq!(
+            command_args[0].to_string_lossy(),
+            format!("--env-file={}", layer_env_file.path().display())
+        );
+        assert!(
+            command_args[1]
+                .to_string_lossy()
+                .starts_with("python-executor")
+        );
+        let envs = utils::env::load_env_from_file(&Some(layer_env_file.path()));
+        assert_eq!(picked(envs), expected);
+
+        // env files can't hold a line break, the layer command fails instead of losing the value
+        payload.env_vars = HashMap::from([("MULTILINE".to_owned(), "a\nb".to_owned())]);
+        let err = executor_command(
+            "python-executor",
+            &args,
+            executor_envs(&payload, &scope),
+            Some(&run_in_layer),
+            &dir,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("MULTILINE"), "{err}");
+        assert!(
+            executor_command(
+                "python-executor",
+                &args,
+                executor_envs(&payload, &scope),
+                None,
+                &dir,
+            )
+            .is_ok()
+        );
+
+        drop(layer_env_file);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
     fn test_scope(session_id: SessionId, node_id: &str) -> RuntimeScope {
         let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
             .join("..")
@@ -1915,182 +2941,336 @@ mod tests {
             node_id: Some(NodeId::from(node_id.to_string())),
             is_inject: false,
             enable_layer: false,
+            data_dir_readonly: false,
+        }
+    }
+
+    /// A scheduler with its event loop running and a ready python executor for `scope`.
+    struct TestScheduler {
+        tx: SchedulerTx,
+        handle: tokio::task::JoinHandle<()>,
+        executor_map: Arc<RwLock<HashMap<String, ExecutorState>>>,
+        block_events: Receiver<ReceiveMessage>,
+        soft_cancels: Receiver<(String, serde_json::Value)>,
+    }
+
+    impl TestScheduler {
+        fn start(executor_payload: ExecutorParameters, scope: &RuntimeScope) -> Self {
+            let (block_event_tx, block_events) = flume::unbounded();
+            let (soft_cancel_tx, soft_cancels) = flume::unbounded();
+            let (tx, scheduler_rx) = create(
+                CaptureSchedulerTx {
+                    block_events: block_event_tx,
+                    soft_cancels: Some(soft_cancel_tx),
+                },
+                PendingSchedulerRx,
+                None,
+                None,
+                executor_payload,
+                scope.data_dir.clone(),
+            );
+
+            scheduler_rx.executor_map.write().unwrap().insert(
+                generate_executor_map_name("python", scope),
+                ExecutorState {
+                    spawn_state: ExecutorSpawnState::Ready,
+                    pid: None,
+                    layer_shell: None,
+                },
+            );
+            let executor_map = scheduler_rx.executor_map.clone();
+            let handle = scheduler_rx.event_loop();
+            Self {
+                tx,
+                handle,
+                executor_map,
+                block_events,
+                soft_cancels,
+            }
+        }
+
+        /// Run a python block as `job_id` in `scope`, returns what its subscriber receives.
+        fn execute_block(&self, job_id: &JobId, scope: &RuntimeScope) -> Receiver<ReceiveMessage> {
+            let executor: TaskBlockExecutor = serde_json::from_value(
+                serde_json::json!({"name":"python","options":{"entry":"main.py"}}),
+            )
+            .unwrap();
+            let (subscriber_tx, subscriber_rx) = flume::unbounded();
+            self.tx.register_subscriber(job_id.clone(), subscriber_tx);
+            self.tx
+                .tx
+                .send(SchedulerCommand::ExecuteBlock {
+                    job_id: job_id.clone(),
+                    executor_name: "python".to_string(),
+                    dir: scope.data_dir.clone(),
+                    stacks: vec![],
+                    outputs: None,
+                    executor,
+                    injection_store: None,
+                    scope: scope.clone(),
+                    flow_path: None,
+                })
+                .unwrap();
+            subscriber_rx
+        }
+
+        fn executor_exit(&self, scope: &RuntimeScope, code: i32) {
+            self.tx
+                .tx
+                .send(SchedulerCommand::ExecutorExit {
+                    executor: "python-executor".to_string(),
+                    identifier: Some(scope.identifier()),
+                    code,
+                    reason: None,
+                })
+                .unwrap();
+        }
+
+        async fn stop(self) {
+            self.tx.abort();
+            self.handle.await.unwrap();
         }
     }
 
     #[tokio::test]
-    async fn executor_exit_sends_block_finished_for_running_block() {
+    async fn listener_timeout_follows_executor_parameters() {
         let session_id = SessionId::random();
         let job_id = JobId::random();
-        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
-            .join("..")
-            .canonicalize()
-            .unwrap();
-        let scope = RuntimeScope {
-            session_id: session_id.clone(),
-            pkg_name: None,
-            data_dir: root.display().to_string(),
-            pkg_root: root.clone(),
-            path: root,
-            node_id: None,
-            is_inject: false,
-            enable_layer: false,
-        };
-        let executor: TaskBlockExecutor = serde_json::from_value(
-            serde_json::json!({"name":"python","options":{"entry":"main.py"}}),
-        )
-        .unwrap();
-        let (block_event_tx, block_event_rx) = flume::unbounded();
-        let (scheduler_tx, scheduler_rx) = create(
-            CaptureSchedulerTx {
-                block_events: block_event_tx,
+        let scope = test_scope(session_id.clone(), "node");
+        let scheduler = TestScheduler::start(
+            ExecutorParameters {
+                listener_timeout: Duration::from_millis(50),
+                ..test_executor_payload(session_id)
             },
-            PendingSchedulerRx,
-            None,
-            None,
-            test_executor_payload(session_id.clone()),
-            scope.data_dir.clone(),
+            &scope,
         );
+        let subscriber_rx = scheduler.execute_block(&job_id, &scope);
 
-        scheduler_rx.executor_map.write().unwrap().insert(
-            generate_executor_map_name("python", &scope),
-            ExecutorState {
-                spawn_state: ExecutorSpawnState::Ready,
-                pid: None,
+        // well before the 3s default
+        let event = timeout(Duration::from_secs(1), subscriber_rx.recv_async())
+            .await
+            .expect("listener timeout should fire after the configured duration")
+            .unwrap();
+        assert!(matches!(
+            event,
+            ReceiveMessage::ListenerTimeout { job_id: ref timeout_job_id, .. } if timeout_job_id == &job_id
+        ));
+
+        scheduler.stop().await;
+    }
+
+    #[tokio::test]
+    async fn executor_crash_restarts_executor_and_resubmits_block() {
+        let session_id = SessionId::random();
+        let job_id = JobId::random();
+        let scope = test_scope(session_id.clone(), "restart");
+        let scheduler = TestScheduler::start(
+            ExecutorParameters {
+                spawn_timeout: Duration::from_millis(100),
+                ready_timeout: Duration::from_millis(100),
+                restart_policy: ExecutorRestartPolicy { max_attempts: 1 },
+                extra_args: vec![],
+                ..test_executor_payload(session_id)
             },
+            &scope,
         );
-        let scheduler_handle = scheduler_rx.event_loop();
+        let subscriber_rx = scheduler.execute_block(&job_id, &scope);
+        scheduler.executor_exit(&scope, 137);
 
-        let (subscriber_tx, subscriber_rx) = flume::unbounded();
-        scheduler_tx.register_subscriber(job_id.clone(), subscriber_tx);
-        scheduler_tx
-            .tx
-            .send(SchedulerCommand::ExecuteBlock {
-                job_id: job_id.clone(),
-                executor_name: "python".to_string(),
-                dir: scope.data_dir.clone(),
-                stacks: vec![],
-                outputs: None,
-                executor,
-                injection_store: None,
-                scope: scope.clone(),
-                flow_path: None,
-            })
-            .unwrap();
-        scheduler_tx
-            .tx
-            .send(SchedulerCommand::ExecutorExit {
-                executor: "python-executor".to_string(),
-                identifier: Some(scope.identifier()),
-                code: 1,
-                reason: None,
-            })
+        let event = timeout(Duration::from_secs(1), subscriber_rx.recv_async())
+            .await
+            .expect("subscriber should be told about the restart")
             .unwrap();
+        assert!(matches!(
+            event,
+            ReceiveMessage::ExecutorRestart {
+                job_id: ref restarted_job_id,
+                code: 137,
+                attempt: 1,
+                ..
+            } if restarted_job_id == &job_id
+        ));
+
+        // the resubmitted block spawns a new executor, which fails or times out in tests, but the
+        // crash itself no longer finishes the block
+        let finished_error = loop {
+            let event = timeout(Duration::from_secs(5), subscriber_rx.recv_async())
+                .await
+                .expect("resubmitted block should finish")
+                .unwrap();
+            if let ReceiveMessage::BlockFinished { error, .. } = event {
+                break error;
+            }
+        };
+        assert_ne!(
+            finished_error.as_deref(),
+            Some("Executor python-executor exit with code 137")
+        );
+
+        scheduler.stop().await;
+    }
+
+    /// Run a block on a ready executor that then exits with `code`, returns the finish the
+    /// subscriber and the broker get for the block.
+    async fn finish_by_executor_exit(code: i32) -> (JobId, ReceiveMessage, ReceiveMessage) {
+        let session_id = SessionId::random();
+        let job_id = JobId::random();
+        let scope = test_scope(session_id.clone(), "node");
+        let scheduler = TestScheduler::start(test_executor_payload(session_id), &scope);
+        let subscriber_rx = scheduler.execute_block(&job_id, &scope);
+        scheduler.executor_exit(&scope, code);
 
         let subscriber_event = timeout(Duration::from_secs(1), subscriber_rx.recv_async())
             .await
             .expect("subscriber should receive synthesized finish")
             .unwrap();
+        let block_event = timeout(Duration::from_secs(1), scheduler.block_events.recv_async())
+            .await
+            .expect("broker block event should receive synthesized finish")
+            .unwrap();
+
+        scheduler.stop().await;
+        (job_id, subscriber_event, block_event)
+    }
+
+    #[tokio::test]
+    async fn executor_exit_sends_block_finished_for_running_block() {
+        let (job_id, subscriber_event, block_event) = finish_by_executor_exit(1).await;
         assert!(matches!(
             subscriber_event,
             ReceiveMessage::BlockFinished {
                 job_id: ref finished_job_id,
                 error: Some(ref error),
+                error_kind: Some(ErrorKind::ExecutorExit),
                 ..
             } if finished_job_id == &job_id
                 && error == "Executor python-executor exit with code 1"
         ));
-
-        let block_event = timeout(Duration::from_secs(1), block_event_rx.recv_async())
-            .await
-            .expect("broker block event should receive synthesized finish")
-            .unwrap();
         assert!(matches!(
             block_event,
             ReceiveMessage::BlockFinished {
                 job_id: ref finished_job_id,
                 error: Some(ref error),
+                error_kind: Some(ErrorKind::ExecutorExit),
                 ..
             } if finished_job_id == &job_id
                 && error == "Executor python-executor exit with code 1"
         ));
+    }
 
-        scheduler_tx.abort();
-        scheduler_handle.await.unwrap();
+    #[tokio::test]
+    async fn executor_exit_code_is_reported_on_the_block() {
+        let (job_id, subscriber_event, block_event) = finish_by_executor_exit(2).await;
+        for event in [subscriber_event, block_event] {
+            assert!(matches!(
+                event,
+                ReceiveMessage::BlockFinished {
+                    job_id: ref finished_job_id,
+                    exit_code: Some(2),
+                    ..
+                } if finished_job_id == &job_id
+            ));
+        }
     }
 
     #[tokio::test]
-    async fn executor_exit_only_finishes_matching_identifier() {
+    async fn dropped_subscriber_does_not_stop_the_event_loop() {
         let session_id = SessionId::random();
-        let matching_job_id = JobId::random();
-        let other_job_id = JobId::random();
-        let matching_scope = test_scope(session_id.clone(), "matching");
-        let other_scope = test_scope(session_id.clone(), "other");
-        let executor: TaskBlockExecutor = serde_json::from_value(
-            serde_json::json!({"name":"python","options":{"entry":"main.py"}}),
-        )
-        .unwrap();
-        let (block_event_tx, block_event_rx) = flume::unbounded();
-        let (scheduler_tx, scheduler_rx) = create(
-            CaptureSchedulerTx {
-                block_events: block_event_tx,
-            },
-            PendingSchedulerRx,
-            None,
-            None,
-            test_executor_payload(session_id.clone()),
-            matching_scope.data_dir.clone(),
-        );
+        let scope = test_scope(session_id.clone(), "node");
+        let scheduler = TestScheduler::start(test_executor_payload(session_id), &scope);
 
-        {
-            let mut executor_map = scheduler_rx.executor_map.write().unwrap();
-            for scope in [&matching_scope, &other_scope] {
-                executor_map.insert(
-                    generate_executor_map_name("python", scope),
-                    ExecutorState {
-                        spawn_state: ExecutorSpawnState::Ready,
-                        pid: None,
-                    },
-                );
-            }
-        }
+        let dropped_job_id = JobId::random();
+        let live_job_id = JobId::random();
+        let dropped_rx = scheduler.execute_block(&dropped_job_id, &scope);
+        let live_rx = scheduler.execute_block(&live_job_id, &scope);
+        drop(dropped_rx);
+        scheduler.executor_exit(&scope, 1);
 
-        let scheduler_handle = scheduler_rx.event_loop();
+        let live_event = timeout(Duration::from_secs(1), live_rx.recv_async())
+            .await
+            .expect("live subscriber should still receive its finish")
+            .unwrap();
+        assert!(matches!(
+            live_event,
+            ReceiveMessage::BlockFinished { ref job_id, .. } if job_id == &live_job_id
+        ));
 
-        let (matching_subscriber_tx, matching_subscriber_rx) = flume::unbounded();
-        let (other_subscriber_tx, other_subscriber_rx) = flume::unbounded();
-        scheduler_tx.register_subscriber(matching_job_id.clone(), matching_subscriber_tx);
-        scheduler_tx.register_subscriber(other_job_id.clone(), other_subscriber_tx);
+        scheduler.stop().await;
+    }
 
-        for (job_id, scope) in [
-            (matching_job_id.clone(), matching_scope.clone()),
-            (other_job_id.clone(), other_scope.clone()),
-        ] {
-            scheduler_tx
-                .tx
-                .send(SchedulerCommand::ExecuteBlock {
-                    job_id,
-                    executor_name: "python".to_string(),
-                    dir: scope.data_dir.clone(),
-                    stacks: vec![],
-                    outputs: None,
-                    executor: executor.clone(),
-                    injection_store: None,
-                    scope,
-                    flow_path: None,
-                })
+    #[tokio::test]
+    async fn soft_cancel_reaches_running_block_which_can_still_finish() {
+        let session_id = SessionId::random();
+        let job_id = JobId::random();
+        let scope = test_scope(session_id.clone(), "node");
+        let scheduler = TestScheduler::start(test_executor_payload(session_id.clone()), &scope);
+        let subscriber_rx = scheduler.execute_block(&job_id, &scope);
+        scheduler.tx.soft_cancel_running_blocks();
+
+        let (executor_name, payload) =
+            timeout(Duration::from_secs(1), scheduler.soft_cancels.recv_async())
+                .await
+                .expect("running block should be soft cancelled")
                 .unwrap();
-        }
+        assert_eq!(executor_name, "python");
+        assert_eq!(payload["type"], "SoftCancel");
+        assert_eq!(payload["job_id"], job_id.to_string());
 
-        scheduler_tx
+        // the block wraps up on its own before any hard abort
+        scheduler
             .tx
-            .send(SchedulerCommand::ExecutorExit {
-                executor: "python-executor".to_string(),
-                identifier: Some(matching_scope.identifier()),
-                code: 1,
-                reason: None,
-            })
+            .tx
+            .send(SchedulerCommand::ReceiveMessage(
+                serde_json::to_vec(&ReceiveMessage::BlockFinished {
+                    session_id: session_id.clone(),
+                    job_id: job_id.clone(),
+                    result: None,
+                    error: None,
+                    error_kind: None,
+                    exit_code: None,
+                })
+                .unwrap(),
+            ))
+            .unwrap();
+        let finished = timeout(Duration::from_secs(1), subscriber_rx.recv_async())
+            .await
+            .expect("subscriber should receive the block finish")
             .unwrap();
+        assert!(matches!(
+            finished,
+            ReceiveMessage::BlockFinished {
+                job_id: ref finished_job_id,
+                error: None,
+                ..
+            } if finished_job_id == &job_id
+        ));
+
+        // a finished block is no longer soft cancelled
+        scheduler.tx.soft_cancel_running_blocks();
+        let soft_cancels = scheduler.soft_cancels.clone();
+        scheduler.stop().await;
+        assert!(soft_cancels.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn executor_exit_only_finishes_matching_identifier() {
+        let session_id = SessionId::random();
+        let matching_job_id = JobId::random();
+        let other_job_id = JobId::random();
+        let matching_scope = test_scope(session_id.clone(), "matching");
+        let other_scope = test_scope(session_id.clone(), "other");
+        let scheduler = TestScheduler::start(test_executor_payload(session_id), &matching_scope);
+        scheduler.executor_map.write().unwrap().insert(
+            generate_executor_map_name("python", &other_scope),
+            ExecutorState {
+                spawn_state: ExecutorSpawnState::Ready,
+                pid: None,
+                layer_shell: None,
+            },
+        );
+        let matching_subscriber_rx = scheduler.execute_block(&matching_job_id, &matching_scope);
+        let other_subscriber_rx = scheduler.execute_block(&other_job_id, &other_scope);
+        scheduler.executor_exit(&matching_scope, 1);
 
         let matching_event = timeout(Duration::from_secs(1), matching_subscriber_rx.recv_async())
             .await
@@ -2104,7 +3284,7 @@ mod tests {
             } if finished_job_id == &matching_job_id
         ));
 
-        let block_event = timeout(Duration::from_secs(1), block_event_rx.recv_async())
+        let block_event = timeout(Duration::from_secs(1), scheduler.block_events.recv_async())
             .await
             .expect("broker block event should receive synthesized finish")
             .unwrap();
@@ -2123,12 +3303,11 @@ mod tests {
             "executor exit should not notify jobs from another identifier"
         );
         assert!(
-            block_event_rx.try_recv().is_err(),
+            scheduler.block_events.try_recv().is_err(),
             "executor exit should emit exactly one block finish event"
         );
 
-        scheduler_tx.abort();
-        scheduler_handle.await.unwrap();
+        scheduler.stop().await;
     }
 
     #[tokio::test]
@@ -2136,54 +3315,25 @@ mod tests {
         let session_id = SessionId::random();
         let job_id = JobId::random();
         let scope = test_scope(session_id.clone(), "late-ready");
-        let executor: TaskBlockExecutor = serde_json::from_value(
-            serde_json::json!({"name":"python","options":{"entry":"main.py"}}),
-        )
-        .unwrap();
-        let (block_event_tx, block_event_rx) = flume::unbounded();
-        let (scheduler_tx, scheduler_rx) = create(
-            CaptureSchedulerTx {
-                block_events: block_event_tx,
-            },
-            PendingSchedulerRx,
-            None,
-            None,
-            test_executor_payload(session_id.clone()),
-            scope.data_dir.clone(),
-        );
-
-        scheduler_rx.executor_map.write().unwrap().insert(
+        let scheduler = TestScheduler::start(test_executor_payload(session_id.clone()), &scope);
+        scheduler.executor_map.write().unwrap().insert(
             generate_executor_map_name("python", &scope),
             ExecutorState {
                 spawn_state: ExecutorSpawnState::Spawned,
                 pid: Some(42),
+                layer_shell: None,
             },
         );
-        let executor_map = scheduler_rx.executor_map.clone();
-        let scheduler_handle = scheduler_rx.event_loop();
-
-        let (subscriber_tx, subscriber_rx) = flume::unbounded();
-        scheduler_tx.register_subscriber(job_id.clone(), subscriber_tx);
-        scheduler_tx
+        let subscriber_rx = scheduler.execute_block(&job_id, &scope);
+        scheduler
             .tx
-            .send(SchedulerCommand::ExecuteBlock {
-                job_id: job_id.clone(),
-                executor_name: "python".to_string(),
-                dir: scope.data_dir.clone(),
-                stacks: vec![],
-                outputs: None,
-                executor,
-                injection_store: None,
-                scope: scope.clone(),
-                flow_path: None,
-            })
-            .unwrap();
-        scheduler_tx
             .tx
             .send(SchedulerCommand::SpawnExecutorTimeout {
                 executor: "python-executor".to_string(),
                 package: None,
                 identifier: Some(scope.identifier()),
+                timeout: DEFAULT_EXECUTOR_SPAWN_TIMEOUT,
+                stderr_tail: None,
             })
             .unwrap();
 
@@ -2199,12 +3349,12 @@ mod tests {
                 ..
             } if finished_job_id == &job_id
                 && error == &format!(
-                    "Executor python-executor identifier {:?} for package None timeout after 5s",
+                    "Executor python-executor identifier {:?} for package None didn't start within 5s",
                     Some(scope.identifier())
                 )
         ));
 
-        let block_event = timeout(Duration::from_secs(1), block_event_rx.recv_async())
+        let block_event = timeout(Duration::from_secs(1), scheduler.block_events.recv_async())
             .await
             .expect("broker block event should receive synthesized timeout finish")
             .unwrap();
@@ -2225,7 +3375,8 @@ mod tests {
             ReceiveMessage::ExecutorTimeout { .. }
         ));
 
-        scheduler_tx
+        scheduler
+            .tx
             .tx
             .send(SchedulerCommand::ReceiveMessage(
                 serde_json::to_vec(&ReceiveMessage::ExecutorReady {
@@ -2233,6 +3384,7 @@ mod tests {
                     executor_name: "python".to_string(),
                     package: None,
                     identifier: Some(scope.identifier()),
+                    pid: None,
                 })
                 .unwrap(),
             ))
@@ -2245,7 +3397,8 @@ mod tests {
             "late executor ready should not be forwarded to subscriber"
         );
         assert_eq!(
-            executor_map
+            scheduler
+                .executor_map
                 .read()
                 .unwrap()
                 .get(&generate_executor_map_name("python", &scope))
@@ -2254,10 +3407,166 @@ mod tests {
             ExecutorSpawnState::TimedOut
         );
 
+        scheduler.stop().await;
+    }
+
+    #[tokio::test]
+    async fn executor_stuck_starting_reports_its_stderr() {
+        let session_id = SessionId::random();
+        let job_id = JobId::random();
+        let scope = test_scope(session_id.clone(), "stuck");
+        let scheduler = TestScheduler::start(test_executor_payload(session_id), &scope);
+
+        // an executor that fails importing its dependencies and hangs
+        let mut child = tokio::process::Command::new("sh")
+            .args([
+                "-c",
+                "echo 'Traceback (most recent call last):' >&2; echo \"ModuleNotFoundError: No module named 'numpy'\" >&2; sleep 30",
+            ])
+            .stderr(process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let executor_map_name = generate_executor_map_name("python", &scope);
+        scheduler.executor_map.write().unwrap().insert(
+            executor_map_name.clone(),
+            ExecutorState {
+                spawn_state: ExecutorSpawnState::Spawned,
+                pid: child.id(),
+                layer_shell: None,
+            },
+        );
+        let stderr_tail = OutputTail::default();
+        spawn_output_reader(
+            child.stderr.take().unwrap(),
+            DEFAULT_MAX_LOG_LINE_BYTES,
+            None,
+            "python-executor (stuck) stderr".to_string(),
+            None,
+            None,
+            Some(stderr_tail.clone()),
+        );
+        let subscriber_rx = scheduler.execute_block(&job_id, &scope);
+        let identifier = scope.identifier();
+        spawn_ready_timer(
+            scheduler.executor_map.clone(),
+            executor_map_name,
+            Duration::from_millis(50),
+            Duration::from_millis(500),
+            stderr_tail,
+            scheduler.tx.tx.clone(),
+            move |timeout, stderr_tail| SchedulerCommand::SpawnExecutorTimeout {
+                executor: "python-executor".to_string(),
+                package: None,
+                identifier: Some(identifier.clone()),
+                timeout,
+                stderr_tail,
+            },
+        );
+
+        let event = timeout(Duration::from_secs(5), subscriber_rx.recv_async())
+            .await
+            .expect("subscriber should receive the failed start")
+            .unwrap();
+        let ReceiveMessage::BlockFinished {
+            error: Some(error), ..
+        } = event
+        else {
+            panic!("expected block finished, got {event:?}");
+        };
+        assert!(
+            error.contains("started but failed to initialize, it didn't report ready within 500ms"),
+            "{error}"
+        );
+        assert!(
+            error.ends_with(
+                "Last stderr lines:\nTraceback (most recent call last):\nModuleNotFoundError: No module named 'numpy'"
+            ),
+            "{error}"
+        );
+
+        scheduler.stop().await;
+    }
+
+    #[tokio::test]
+    async fn second_executor_ready_replaces_the_pid() {
+        let session_id = SessionId::random();
+        let scope = test_scope(session_id.clone(), "replaced");
+        let (block_event_tx, _block_event_rx) = flume::unbounded();
+        let (scheduler_tx, scheduler_rx) = create(
+            CaptureSchedulerTx {
+                block_events: block_event_tx,
+                soft_cancels: None,
+            },
+            PendingSchedulerRx,
+            None,
+            None,
+            test_executor_payload(session_id.clone()),
+            scope.data_dir.clone(),
+        );
+        let executor_map = scheduler_rx.executor_map.clone();
+        let scheduler_handle = scheduler_rx.event_loop();
+
+        let (subscriber_tx, subscriber_rx) = flume::unbounded();
+        scheduler_tx.register_subscriber(JobId::random(), subscriber_tx);
+
+        for pid in [100, 200] {
+            scheduler_tx
+                .tx
+                .send(SchedulerCommand::ReceiveMessage(
+                    serde_json::to_vec(&ReceiveMessage::ExecutorReady {
+                        session_id: session_id.clone(),
+                        executor_name: "python".to_string(),
+                        package: None,
+                        identifier: Some(scope.identifier()),
+                        pid: Some(pid),
+                    })
+                    .unwrap(),
+                ))
+                .unwrap();
+
+            let ready = timeout(Duration::from_secs(1), subscriber_rx.recv_async())
+                .await
+                .expect("subscriber should receive every executor ready")
+                .unwrap();
+            assert!(matches!(
+                ready,
+                ReceiveMessage::ExecutorReady { pid: Some(ready_pid), .. } if ready_pid == pid
+            ));
+            assert_eq!(
+                executor_map
+                    .read()
+                    .unwrap()
+                    .get(&generate_executor_map_name("python", &scope))
+                    .unwrap(),
+                &ExecutorState {
+                    spawn_state: ExecutorSpawnState::Ready,
+                    pid: Some(pid),
+                    layer_shell: None,
+                }
+            );
+        }
+
         scheduler_tx.abort();
         scheduler_handle.await.unwrap();
     }
 
+    #[test]
+    fn package_data_bind_path_follows_readonly_flag() {
+        let mut scope = test_scope(SessionId::random(), "node");
+        let data_dir = scope.data_dir.clone();
+        assert_eq!(
+            package_data_bind_path(&scope).to_string(),
+            format!("type=bind,src={data_dir},dst={data_dir},rw,nonrecursive")
+        );
+
+        scope.data_dir_readonly = true;
+        assert_eq!(
+            package_data_bind_path(&scope).to_string(),
+            format!("type=bind,src={data_dir},dst={data_dir},ro,nonrecursive")
+        );
+    }
+
     #[test]
     fn finished_executor_state_allows_restart() {
         let session_id = SessionId::random();
@@ -2267,6 +3576,7 @@ mod tests {
             ExecutorState {
                 spawn_state: ExecutorSpawnState::Finished,
                 pid: None,
+                layer_shell: None,
             },
         )])));
 
@@ -2282,4 +3592,244 @@ mod tests {
 
         assert_eq!(result.executor_state, ExecutorSpawnState::None);
     }
+
+    #[tokio::test]
+    async fn per_executor_log_file_receives_executor_lines() {
+        let dir = std::env::temp_dir().join(format!("oocana-executor-logs-{}", std::process::id()));
+        let file = open_executor_log_file(&dir, "python-executor", "session-abc").unwrap();
+
+        let output: &[u8] = b"hello\nworld\n";
+        spawn_output_reader(
+            output,
+            DEFAULT_MAX_LOG_LINE_BYTES,
+            None,
+            "python-executor (session-abc) stdout".to_string(),
+            Some(tokio::fs::File::from_std(file)),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let path = dir.join("python-executor-session-abc.log");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello\nworld\n");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn output_tail_keeps_the_last_lines() {
+        let output = (0..25).map(|i| format!("line {i}\n")).collect::<String>();
+        let tail = OutputTail::default();
+        spawn_output_reader(
+            std::io::Cursor::new(output.into_bytes()),
+            DEFAULT_MAX_LOG_LINE_BYTES,
+            Some(OutputBudget::new(0)),
+            "python-executor (session-abc) stderr".to_string(),
+            None,
+            None,
+            Some(tail.clone()),
+        )
+        .await
+        .unwrap();
+
+        let lines = tail.lines();
+        assert_eq!(lines.len(), STDERR_TAIL_LINES);
+        assert_eq!(lines.first().map(String::as_str), Some("line 5"));
+        assert_eq!(lines.last().map(String::as_str), Some("line 24"));
+    }
+
+    #[tokio::test]
+    async fn executor_output_past_the_budget_is_dropped() {
+        let dir =
+            std::env::temp_dir().join(format!("oocana-executor-budget-{}", std::process::id()));
+        let file = open_executor_log_file(&dir, "python-executor", "session-abc").unwrap();
+        let budget = OutputBudget::new(30);
+
+        // a runaway line with no newline, capped to 16 bytes but still over the budget
+        let output = format!("hello\n{}\nafter\n", "x".repeat(100));
+        spawn_output_reader(
+            std::io::Cursor::new(output.into_bytes()),
+            16,
+            Some(budget.clone()),
+            "python-executor (session-abc) stdout".to_string(),
+            Some(tokio::fs::File::from_std(file)),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let path = dir.join("python-executor-session-abc.log");
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "hello\n…(truncated 46 bytes)\n"
+        );
+        // stderr of the same executor shares the used up budget
+        assert!(!budget.take(1));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    struct CaptureReporterTx {
+        delivered: flume::Sender<serde_json::Value>,
+    }
+
+    #[async_trait]
+    impl crate::reporter::ReporterTxImpl for CaptureReporterTx {
+        async fn send(&self, data: MessageData) {
+            let _ = self.delivered.send(serde_json::from_slice(&data).unwrap());
+        }
+
+        async fn disconnect(&self) {}
+    }
+
+    struct NoopReporterRx;
+
+    impl crate::reporter::ReporterRxImpl for NoopReporterRx {
+        fn event_loop(self) -> tokio::task::JoinHandle<()> {
+            tokio::spawn(async {})
+        }
+    }
+
+    #[tokio::test]
+    async fn executor_output_is_reported_as_executor_log() {
+        let (delivered_tx, delivered_rx) = flume::unbounded();
+        let (reporter, reporter_rx) = crate::reporter::create(
+            SessionId::new("session-abc".to_string()),
+            Some(CaptureReporterTx {
+                delivered: delivered_tx,
+            }),
+            Some(NoopReporterRx),
+        );
+        let reporter_handle = reporter_rx.event_loop();
+
+        let output: &[u8] = b"hello\n";
+        spawn_output_reader(
+            output,
+            DEFAULT_MAX_LOG_LINE_BYTES,
+            None,
+            "python-executor (pkg) stderr".to_string(),
+            None,
+            Some(ExecutorLogSource {
+                reporter: reporter.clone(),
+                executor_name: "python-executor".to_string(),
+                identifier: "pkg".to_string(),
+                stdio: "stderr",
+            }),
+            None,
+        )
+        .await
+        .unwrap();
+
+        let message = timeout(Duration::from_secs(1), delivered_rx.recv_async())
+            .await
+            .expect("executor line should be reported")
+            .unwrap();
+        assert_eq!(
+            message,
+            serde_json::json!({
+                "type": "ExecutorLog",
+                "session_id": "session-abc",
+                "executor_name": "python-executor",
+                "identifier": "pkg",
+                "log": "hello",
+                "stdio": "stderr",
+                "seq": 0,
+            })
+        );
+
+        reporter.abort();
+        reporter_handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn read_capped_line_truncates_long_lines() {
+        let long_line = "x".repeat(100);
+        let input = format!("short\r\n{long_line}\nlast");
+        let mut reader = tokio::io::BufReader::with_capacity(8, input.as_bytes());
+
+        assert_eq!(
+            read_capped_line(&mut reader, 16).await.unwrap().as_deref(),
+            Some("short")
+        );
+        assert_eq!(
+            read_capped_line(&mut reader, 16).await.unwrap(),
+            Some(format!("{}…(truncated 84 bytes)", "x".repeat(16)))
+        );
+        assert_eq!(
+            read_capped_line(&mut reader, 16).await.unwrap().as_deref(),
+            Some("last")
+        );
+        assert_eq!(read_capped_line(&mut reader, 16).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn wait_until_healthy_polls_until_the_check_passes() {
+        let dir = std::env::temp_dir().join(format!("oocana-health-check-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ready = dir.join("ready");
+        let _ = std::fs::remove_file(&ready);
+
+        let marker = ready.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            std::fs::write(marker, "").unwrap();
+        });
+
+        let result = wait_until_healthy(
+            "test -f ready",
+            dir.to_str().unwrap(),
+            None,
+            std::time::Duration::from_secs(5),
+            std::time::Duration::from_millis(10),
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn wait_until_healthy_runs_the_check_in_the_layer_shell() {
+        let dir = std::env::temp_dir().join(format!("oocana-layer-health-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("ready"), "").unwrap();
+
+        // stands in for `ovmlayer run ... zsh -i -c`, the shell gets the script as its last arg
+        let shell = ["sh", "-c"].map(OsString::from);
+        let result = wait_until_healthy(
+            "test -f ready",
+            dir.to_str().unwrap(),
+            Some(&shell),
+            std::time::Duration::from_secs(5),
+            std::time::Duration::from_millis(10),
+        )
+        .await;
+        assert_eq!(result, Ok(()));
+
+        let result = wait_until_healthy(
+            "test -f ready",
+            std::env::temp_dir().to_str().unwrap(),
+            Some(&shell),
+            std::time::Duration::from_millis(100),
+            std::time::Duration::from_millis(10),
+        )
+        .await;
+        assert!(result.is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn wait_until_healthy_fails_after_timeout() {
+        let result = wait_until_healthy(
+            "false",
+            std::env::temp_dir().to_str().unwrap(),
+            None,
+            std::time::Duration::from_millis(100),
+            std::time::Duration::from_millis(10),
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err("service health check `false` didn't pass within 0.1s".to_owned())
+        );
+    }
 }
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
 
 use crate::MessageData;
+use crate::scheduler::OUTPUT_CHUNK_THRESHOLD;
 use async_trait::async_trait;
 use flume::{Receiver, Sender};
 use job::{BlockJobStackLevel, JobId, SessionId};
 use manifest_meta::{JsonValue, ServiceExecutorOptions};
+use serde_json::value::RawValue;
 use tokio::sync::oneshot;
 use tracing::{error, warn};
 
@@ -22,7 +24,15 @@ pub enum BlockMessage<'a> {
         job_id: &'a str,
         done: bool,
         handle: &'a str,
-        output: &'a JsonValue,
+        output: &'a RawValue,
+    },
+    BlockOutputChunk {
+        session_id: &'a str,
+        job_id: &'a str,
+        handle: &'a str,
+        chunk_index: usize,
+        chunk_total: usize,
+        data: &'a str,
     },
     BlockError {
         session_id: &'a str,
@@ -84,6 +94,9 @@ impl ReceiveMessage {
 #[async_trait]
 pub trait WorkerTxImpl {
     async fn send(&self, data: MessageData);
+    /// called after the final `BlockFinished` is sent. implementations should flush pending
+    /// messages and make the paired `WorkerRxImpl::recv` return empty data.
+    async fn disconnect(&self);
 }
 
 #[async_trait]
@@ -125,17 +138,38 @@ impl WorkerTx {
         }
     }
 
+    /// Outputs larger than [`OUTPUT_CHUNK_THRESHOLD`] once serialized are sent in chunks.
     pub fn output(&self, output: &JsonValue, handle: &str, done: bool) {
-        self.send(
-            BlockMessage::BlockOutput {
-                session_id: &self.session_id,
-                job_id: &self.job_id,
-                done,
-                handle,
-                output,
-            },
-            false,
-        );
+        // serialized once, the size decides how it's sent
+        let output = serde_json::value::to_raw_value(output).unwrap();
+        let data = output.get();
+        if data.len() > OUTPUT_CHUNK_THRESHOLD {
+            let chunks = split_chunks(data, OUTPUT_CHUNK_THRESHOLD);
+            for (chunk_index, chunk) in chunks.iter().enumerate() {
+                self.send(
+                    BlockMessage::BlockOutputChunk {
+                        session_id: &self.session_id,
+                        job_id: &self.job_id,
+                        handle,
+                        chunk_index,
+                        chunk_total: chunks.len(),
+                        data: chunk,
+                    },
+                    false,
+                );
+            }
+        } else {
+            self.send(
+                BlockMessage::BlockOutput {
+                    session_id: &self.session_id,
+                    job_id: &self.job_id,
+                    done,
+                    handle,
+                    output: &output,
+                },
+                false,
+            );
+        }
         if done {
             self.done(None);
         }
@@ -171,6 +205,22 @@ impl WorkerTx {
     }
 }
 
+/// Split `data` in pieces of at most `size` bytes, on char boundaries.
+fn split_chunks(data: &str, size: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut rest = data;
+    while !rest.is_empty() {
+        let mut end = size.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk);
+        rest = tail;
+    }
+    chunks
+}
+
 #[derive(Debug, Clone)]
 pub struct WorkerRx<TT, TR>
 where
@@ -214,6 +264,7 @@ where
                     Ok(Command::SendMessage(data, done)) => {
                         impl_tx.send(data).await;
                         if done {
+                            impl_tx.disconnect().await;
                             break;
                         }
                     }
@@ -245,6 +296,10 @@ where
         let impl_rx_handle = tokio::spawn(async move {
             loop {
                 let data = impl_rx.recv().await;
+                // if data is empty, it means the impl_rx is closed.
+                if data.is_empty() {
+                    break;
+                }
                 if let Err(e) = tx.send(Command::ReceiveMessage(data)) {
                     warn!("Worker send receive message failed: {e}");
                     break;
@@ -259,6 +314,8 @@ where
 pub struct WorkerRxHandle(tokio::task::JoinHandle<()>, tokio::task::JoinHandle<()>);
 
 impl WorkerRxHandle {
+    /// Resolves after the block sent `BlockFinished` (see `WorkerTx::done`) and the transport
+    /// flushed and closed.
     pub async fn wait(self) {
         _ = self.0.await;
         _ = self.1.await;
@@ -333,3 +390,112 @@ where
         },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::sync::watch;
+    use tokio::time::{Duration, timeout};
+
+    struct CaptureWorkerTx {
+        sent: Sender<serde_json::Value>,
+        closed: watch::Sender<bool>,
+    }
+
+    #[async_trait]
+    impl WorkerTxImpl for CaptureWorkerTx {
+        async fn send(&self, data: MessageData) {
+            let message = serde_json::from_slice(&data).unwrap();
+            self.sent.send_async(message).await.unwrap();
+        }
+
+        async fn disconnect(&self) {
+            let _ = self.closed.send(true);
+        }
+    }
+
+    struct ClosableWorkerRx {
+        closed: watch::Receiver<bool>,
+    }
+
+    #[async_trait]
+    impl WorkerRxImpl for ClosableWorkerRx {
+        async fn recv(&mut self) -> MessageData {
+            let _ = self.closed.wait_for(|closed| *closed).await;
+            MessageData::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn done_flushes_outputs_and_resolves_wait() {
+        let (sent_tx, sent_rx) = flume::unbounded();
+        let (closed_tx, closed_rx) = watch::channel(false);
+        let (tx, rx) = create(
+            SessionId::new("session".to_string()),
+            JobId::new("job".to_string()),
+            CaptureWorkerTx {
+                sent: sent_tx,
+                closed: closed_tx,
+            },
+            ClosableWorkerRx { closed: closed_rx },
+        );
+        let handle = rx.event_loop();
+
+        tx.output(&json!(1), "out", false);
+        tx.done(None);
+
+        timeout(Duration::from_secs(1), handle.wait())
+            .await
+            .expect("event loop should finish after done");
+
+        let messages = sent_rx.drain().collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["type"], "BlockOutput");
+        assert_eq!(messages[1]["type"], "BlockFinished");
+        assert!(messages[1]["error"].is_null());
+    }
+
+    #[test]
+    fn split_chunks_keeps_chars_whole() {
+        assert_eq!(split_chunks("abcdefg", 3), vec!["abc", "def", "g"]);
+        // "é" is two bytes, a chunk never ends in the middle of it
+        assert_eq!(split_chunks("aéb", 2), vec!["a", "é", "b"]);
+    }
+
+    #[tokio::test]
+    async fn large_output_is_sent_in_chunks() {
+        let (sent_tx, sent_rx) = flume::unbounded();
+        let (closed_tx, closed_rx) = watch::channel(false);
+        let (tx, rx) = create(
+            SessionId::new("session".to_string()),
+            JobId::new("job".to_string()),
+            CaptureWorkerTx {
+                sent: sent_tx,
+                closed: closed_tx,
+            },
+            ClosableWorkerRx { closed: closed_rx },
+        );
+        let handle = rx.event_loop();
+
+        let output = json!("x".repeat(OUTPUT_CHUNK_THRESHOLD * 2));
+        tx.output(&output, "out", true);
+
+        timeout(Duration::from_secs(1), handle.wait())
+            .await
+            .expect("event loop should finish after done");
+
+        let messages = sent_rx.drain().collect::<Vec<_>>();
+        assert_eq!(messages.len(), 4);
+        let mut data = String::new();
+        for (index, message) in messages[..3].iter().enumerate() {
+            assert_eq!(message["type"], "BlockOutputChunk");
+            assert_eq!(message["handle"], "out");
+            assert_eq!(message["chunk_index"], index);
+            assert_eq!(message["chunk_total"], 3);
+            data.push_str(message["data"].as_str().unwrap());
+        }
+        assert_eq!(serde_json::from_str::<JsonValue>(&data).unwrap(), output);
+        assert_eq!(messages[3]["type"], "BlockFinished");
+    }
+}
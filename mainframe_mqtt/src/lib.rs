@@ -1,3 +1,6 @@
 pub mod reporter;
 pub mod scheduler;
 pub mod worker;
+
+#[cfg(test)]
+mod test_broker;
@@ -8,7 +8,15 @@ use mainframe::{
 };
 use rumqttc::{AsyncClient, Event, EventLoop, Incoming, MqttOptions, QoS};
 use tokio::sync::watch;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use utils::error::Result;
+
+/// Wait before the first retry of the broker connection, doubled after every failed attempt.
+const BROKER_CONNECT_INTERVAL: Duration = Duration::from_millis(500);
+const BROKER_CONNECT_MAX_INTERVAL: Duration = Duration::from_secs(8);
+/// Reconnects to a broker lost mid-session before the session fails, waiting like the first
+/// connection between them.
+const BROKER_RECONNECT_ATTEMPTS: u32 = 5;
 
 pub struct SchedulerTx {
     session_id: SessionId,
@@ -68,6 +76,15 @@ impl SchedulerTxImpl for SchedulerTx {
             .unwrap();
     }
 
+    async fn soft_cancel(&self, executor: &str, data: MessageData) {
+        let topic = format!("executor/{executor}/soft_cancel");
+
+        self.tx
+            .publish(topic, QoS::AtLeastOnce, false, data)
+            .await
+            .unwrap();
+    }
+
     async fn disconnect(&self) {
         let _ = self.shutdown_tx.send(true);
         let _ = self.tx.disconnect().await;
@@ -76,6 +93,11 @@ impl SchedulerTxImpl for SchedulerTx {
 
 pub struct SchedulerRx {
     rx: EventLoop,
+    client: AsyncClient,
+    /// The session topic, subscribed again after a reconnect that lost the broker session.
+    topic: String,
+    /// Failed reconnects in a row.
+    reconnects: u32,
     shutdown_rx: watch::Receiver<bool>,
 }
 
@@ -84,20 +106,45 @@ impl SchedulerRxImpl for SchedulerRx {
     async fn recv(&mut self) -> MessageData {
         loop {
             match self.rx.poll().await {
-                Ok(notification) => {
-                    if let Event::Incoming(Incoming::Publish(packet)) = notification {
-                        return packet.payload.into();
+                Ok(Event::Incoming(Incoming::Publish(packet))) => {
+                    return packet.payload.into();
+                }
+                // the first connack is taken by `connect`, this is a reconnect
+                Ok(Event::Incoming(Incoming::ConnAck(ack))) => {
+                    info!("scheduler reconnected to broker");
+                    self.reconnects = 0;
+                    if !ack.session_present {
+                        // the event loop isn't polled while this runs, an awaiting subscribe could block forever
+                        if let Err(e) = self.client.try_subscribe(&self.topic, QoS::AtLeastOnce) {
+                            warn!(
+                                "Failed to restore subscription {} after reconnect: {e:?}",
+                                self.topic
+                            );
+                        }
                     }
                 }
+                Ok(_) => {}
                 Err(e) => {
-                    let is_shutdown = self.shutdown_rx.borrow();
-                    if *is_shutdown {
+                    if *self.shutdown_rx.borrow() {
                         info!("scheduler is shutting down");
                         break;
                     }
-                    // TODO: need distinguish between normal error and unrecoverable error
-                    error!("exit because scheduler error: {:?}", e);
-                    std::process::exit(1);
+                    if self.reconnects >= BROKER_RECONNECT_ATTEMPTS {
+                        // the session fails with a broker disconnected error once recv stops delivering
+                        error!(
+                            "scheduler lost the broker: {:?}, gave up after {} reconnects",
+                            e, self.reconnects
+                        );
+                        break;
+                    }
+                    let interval = (BROKER_CONNECT_INTERVAL * 2u32.pow(self.reconnects))
+                        .min(BROKER_CONNECT_MAX_INTERVAL);
+                    self.reconnects += 1;
+                    warn!(
+                        "scheduler lost the broker: {:?}, reconnect {}/{} in {:?}",
+                        e, self.reconnects, BROKER_RECONNECT_ATTEMPTS, interval
+                    );
+                    tokio::time::sleep(interval).await;
                 }
             }
         }
@@ -105,7 +152,12 @@ impl SchedulerRxImpl for SchedulerRx {
     }
 }
 
-pub async fn connect(addr: &SocketAddr, session_id: SessionId) -> (SchedulerTx, SchedulerRx) {
+/// Connect to the broker at `addr`, retrying up to `retries` times while it isn't up yet.
+pub async fn connect(
+    addr: &SocketAddr,
+    session_id: SessionId,
+    retries: u32,
+) -> Result<(SchedulerTx, SchedulerRx)> {
     let mut options = MqttOptions::new(
         format!("oocana-scheduler-{}", &session_id),
         addr.ip().to_string(),
@@ -114,19 +166,153 @@ pub async fn connect(addr: &SocketAddr, session_id: SessionId) -> (SchedulerTx,
     options.set_max_packet_size(268435456, 268435456);
     options.set_keep_alive(Duration::from_secs(60));
 
-    let (tx, rx) = AsyncClient::new(options, 50);
+    let (tx, mut rx) = AsyncClient::new(options, 50);
+    wait_for_broker(&mut rx, addr, retries).await?;
 
     let channel = format!("session/{}", &session_id);
 
     tx.subscribe(&channel, QoS::AtLeastOnce).await.unwrap();
     let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
-    (
+    Ok((
         SchedulerTx {
-            tx,
+            tx: tx.clone(),
             session_id,
             shutdown_tx,
         },
-        SchedulerRx { rx, shutdown_rx },
-    )
+        SchedulerRx {
+            rx,
+            client: tx,
+            topic: channel,
+            reconnects: 0,
+            shutdown_rx,
+        },
+    ))
+}
+
+/// Poll until the broker accepts the connection. Losing the broker mid-session is retried in
+/// [`SchedulerRx::recv`] instead.
+async fn wait_for_broker(rx: &mut EventLoop, addr: &SocketAddr, retries: u32) -> Result<()> {
+    let mut interval = BROKER_CONNECT_INTERVAL;
+    let mut attempt = 0;
+    loop {
+        match rx.poll().await {
+            Ok(Event::Incoming(Incoming::ConnAck(_))) => {
+                info!("connected to broker {addr}");
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                warn!(
+                    "broker {addr} is not reachable: {e}, retry {attempt}/{retries} in {interval:?}"
+                );
+                tokio::time::sleep(interval).await;
+                interval = (interval * 2).min(BROKER_CONNECT_MAX_INTERVAL);
+            }
+            Err(e) => {
+                return Err(format!(
+                    "Failed to connect to broker {addr} after {} attempts: {e}",
+                    attempt + 1
+                )
+                .into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_broker::{accept_and_expect_subscribe, write_packet};
+    use rumqttc::{Packet, Publish};
+    use tokio::{net::TcpListener, time::timeout};
+
+    /// An address nothing listens on, until the test binds it again.
+    async fn unused_addr() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        listener.local_addr().unwrap()
+    }
+
+    #[tokio::test]
+    async fn connect_retries_until_the_broker_is_up() {
+        let addr = unused_addr().await;
+        let connecting =
+            tokio::spawn(async move { connect(&addr, SessionId::new("session".into()), 5).await });
+
+        // the first attempt is refused, the broker comes up during the backoff
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let listener = TcpListener::bind(addr).await.unwrap();
+        let broker =
+            tokio::spawn(
+                async move { accept_and_expect_subscribe(&listener, "session/session").await },
+            );
+
+        let (_tx, mut rx) = timeout(Duration::from_secs(10), connecting)
+            .await
+            .expect("scheduler should connect once the broker is up")
+            .unwrap()
+            .unwrap();
+        let received = tokio::spawn(async move { rx.recv().await });
+        let stream = timeout(Duration::from_secs(5), broker)
+            .await
+            .expect("scheduler should subscribe to its session")
+            .unwrap();
+
+        // recv would keep reconnecting once the broker is gone
+        received.abort();
+        let _ = received.await;
+        drop(stream);
+    }
+
+    #[tokio::test]
+    async fn recv_reconnects_to_a_lost_broker() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let topic = "session/session";
+
+        let broker = tokio::spawn(async move {
+            let stream = accept_and_expect_subscribe(&listener, topic).await;
+            (listener, stream)
+        });
+        let (_tx, mut rx) = connect(&addr, SessionId::new("session".into()), 0)
+            .await
+            .unwrap();
+        // the subscribe is only sent once recv polls the connection
+        let received = tokio::spawn(async move { rx.recv().await });
+        let (listener, first) = timeout(Duration::from_secs(5), broker)
+            .await
+            .expect("scheduler should connect")
+            .unwrap();
+
+        // broker blip, the new session knows nothing about the old subscription
+        drop(first);
+        let mut second = timeout(
+            Duration::from_secs(5),
+            accept_and_expect_subscribe(&listener, topic),
+        )
+        .await
+        .expect("scheduler should reconnect and subscribe again");
+        write_packet(
+            &mut second,
+            Packet::Publish(Publish::new(topic, QoS::AtMostOnce, b"event".to_vec())),
+        )
+        .await;
+
+        let data = timeout(Duration::from_secs(5), received)
+            .await
+            .expect("scheduler should receive messages after the reconnect")
+            .unwrap();
+        assert_eq!(data, b"event".to_vec());
+    }
+
+    #[tokio::test]
+    async fn connect_gives_up_after_the_retries() {
+        let addr = unused_addr().await;
+        let err = match connect(&addr, SessionId::new("session".into()), 1).await {
+            Ok(_) => panic!("nothing listens on {addr}"),
+            Err(e) => e.to_string(),
+        };
+        assert!(err.contains("after 2 attempts"), "{err}");
+    }
 }
@@ -0,0 +1,67 @@
+//! A broker that speaks just enough MQTT for the client tests.
+
+use bytes::BytesMut;
+use rumqttc::{ConnAck, ConnectReturnCode, Packet, QoS, SubAck, SubscribeReasonCode, mqttbytes};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const MAX_PACKET_SIZE: usize = 1024 * 1024;
+
+pub(crate) async fn read_packet(stream: &mut TcpStream, buf: &mut BytesMut) -> Packet {
+    loop {
+        match mqttbytes::v4::read(buf, MAX_PACKET_SIZE) {
+            Ok(packet) => return packet,
+            Err(mqttbytes::Error::InsufficientBytes(_)) => {}
+            Err(e) => panic!("broker failed to read packet: {e:?}"),
+        }
+        let n = stream.read_buf(buf).await.unwrap();
+        assert!(n > 0, "client closed the connection");
+    }
+}
+
+pub(crate) async fn write_packet(stream: &mut TcpStream, packet: Packet) {
+    let mut buf = BytesMut::new();
+    match packet {
+        Packet::ConnAck(ack) => ack.write(&mut buf),
+        Packet::SubAck(ack) => ack.write(&mut buf),
+        Packet::Publish(publish) => publish.write(&mut buf),
+        other => panic!("broker can't write {other:?}"),
+    }
+    .unwrap();
+    stream.write_all(&buf).await.unwrap();
+}
+
+/// Accept a client connection and answer its connect, then expect a subscribe to `topic`.
+pub(crate) async fn accept_and_expect_subscribe(listener: &TcpListener, topic: &str) -> TcpStream {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    let mut buf = BytesMut::new();
+
+    assert!(matches!(
+        read_packet(&mut stream, &mut buf).await,
+        Packet::Connect(_)
+    ));
+    write_packet(
+        &mut stream,
+        Packet::ConnAck(ConnAck::new(ConnectReturnCode::Success, false)),
+    )
+    .await;
+
+    match read_packet(&mut stream, &mut buf).await {
+        Packet::Subscribe(subscribe) => {
+            let paths: Vec<&str> = subscribe.filters.iter().map(|f| f.path.as_str()).collect();
+            assert_eq!(paths, vec![topic]);
+            write_packet(
+                &mut stream,
+                Packet::SubAck(SubAck::new(
+                    subscribe.pkid,
+                    vec![SubscribeReasonCode::Success(QoS::AtLeastOnce)],
+                )),
+            )
+            .await;
+        }
+        other => panic!("expected subscribe to {topic}, got {other:?}"),
+    }
+    stream
+}
@@ -2,7 +2,8 @@ use std::{net::SocketAddr, time::Duration};
 
 use async_trait::async_trait;
 use rumqttc::{AsyncClient, Event, EventLoop, Incoming, MqttOptions, QoS};
-use tracing::warn;
+use tokio::sync::watch;
+use tracing::{info, warn};
 
 use job::{JobId, SessionId};
 use mainframe::{
@@ -13,6 +14,7 @@ use mainframe::{
 pub struct WorkerTx {
     topic: String,
     tx: AsyncClient,
+    shutdown_tx: watch::Sender<bool>,
 }
 
 #[async_trait]
@@ -23,10 +25,32 @@ impl WorkerTxImpl for WorkerTx {
             .await
             .unwrap();
     }
+
+    async fn disconnect(&self) {
+        let _ = self.shutdown_tx.send(true);
+        let _ = self.tx.disconnect().await;
+    }
 }
 
 pub struct WorkerRx {
     rx: EventLoop,
+    client: AsyncClient,
+    /// Topics this worker subscribed to. The broker forgets them with the clean session on a
+    /// reconnect, so they are subscribed again.
+    topics: Vec<String>,
+    connected_before: bool,
+    shutdown_rx: watch::Receiver<bool>,
+}
+
+impl WorkerRx {
+    fn resubscribe(&self) {
+        for topic in &self.topics {
+            // the event loop isn't polled while this runs, an awaiting subscribe could block forever
+            if let Err(e) = self.client.try_subscribe(topic, QoS::AtLeastOnce) {
+                warn!("Failed to restore subscription {topic} after reconnect: {e:?}");
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -34,16 +58,27 @@ impl WorkerRxImpl for WorkerRx {
     async fn recv(&mut self) -> MessageData {
         loop {
             match self.rx.poll().await {
-                Ok(notification) => {
-                    if let Event::Incoming(Incoming::Publish(packet)) = notification {
-                        return packet.payload.into();
+                Ok(Event::Incoming(Incoming::Publish(packet))) => {
+                    return packet.payload.into();
+                }
+                Ok(Event::Incoming(Incoming::ConnAck(ack))) => {
+                    if self.connected_before && !ack.session_present {
+                        info!("worker reconnected to broker, restoring subscriptions");
+                        self.resubscribe();
                     }
+                    self.connected_before = true;
                 }
+                Ok(_) => {}
                 Err(e) => {
+                    if *self.shutdown_rx.borrow() {
+                        info!("worker is shutting down");
+                        break;
+                    }
                     warn!("Cannot connect Oocana Worker to broker. error: {:?}", e);
                 }
             }
         }
+        MessageData::default()
     }
 }
 
@@ -62,18 +97,74 @@ pub async fn connect(
 
     let (tx, rx) = AsyncClient::new(options, 50);
 
-    tx.subscribe(
-        format!("inputs/{}/{}", &session_id, &job_id),
-        QoS::AtLeastOnce,
-    )
-    .await
-    .unwrap();
+    let topics = vec![format!("inputs/{}/{}", &session_id, &job_id)];
+    for topic in &topics {
+        tx.subscribe(topic, QoS::AtLeastOnce).await.unwrap();
+    }
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
     (
         WorkerTx {
-            tx,
+            tx: tx.clone(),
             topic: format!("session/{}", &session_id),
+            shutdown_tx,
+        },
+        WorkerRx {
+            rx,
+            client: tx,
+            topics,
+            connected_before: false,
+            shutdown_rx,
         },
-        WorkerRx { rx },
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_broker::{accept_and_expect_subscribe, write_packet};
+    use rumqttc::{Packet, Publish};
+    use tokio::{net::TcpListener, time::timeout};
+
+    #[tokio::test]
+    async fn reconnect_restores_subscriptions() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let topic = "inputs/session/job";
+
+        let (_tx, mut rx) = connect(
+            addr,
+            SessionId::new("session".to_string()),
+            JobId::new("job".to_string()),
+        )
+        .await;
+        let received = tokio::spawn(async move { rx.recv().await });
+
+        let first = timeout(
+            Duration::from_secs(5),
+            accept_and_expect_subscribe(&listener, topic),
+        )
+        .await
+        .expect("worker should connect");
+        // broker blip, the new session knows nothing about the old subscriptions
+        drop(first);
+
+        let mut second = timeout(
+            Duration::from_secs(5),
+            accept_and_expect_subscribe(&listener, topic),
+        )
+        .await
+        .expect("worker should reconnect and subscribe again");
+        write_packet(
+            &mut second,
+            Packet::Publish(Publish::new(topic, QoS::AtMostOnce, b"inputs".to_vec())),
+        )
+        .await;
+
+        let data = timeout(Duration::from_secs(5), received)
+            .await
+            .expect("worker should receive messages after the reconnect")
+            .unwrap();
+        assert_eq!(data, b"inputs".to_vec());
+    }
+}
@@ -18,6 +18,9 @@ pub struct BlockResolver {
     flow_cache: Option<HashMap<BlockPath, Arc<RwLock<SubflowBlock>>>>,
     task_cache: Option<HashMap<BlockPath, Arc<TaskBlock>>>,
     service_cache: Option<HashMap<BlockPath, Service>>,
+    strict_handle_types: bool,
+    fail_on_unused_inputs: bool,
+    case_insensitive_handles: bool,
 }
 
 impl Default for BlockResolver {
@@ -32,9 +35,44 @@ impl BlockResolver {
             flow_cache: None,
             task_cache: None,
             service_cache: None,
+            strict_handle_types: false,
+            fail_on_unused_inputs: false,
+            case_insensitive_handles: false,
         }
     }
 
+    /// Fail flow resolution when a connection joins handles with incompatible `json_schema` types.
+    pub fn with_strict_handle_types(mut self, strict: bool) -> Self {
+        self.strict_handle_types = strict;
+        self
+    }
+
+    pub fn strict_handle_types(&self) -> bool {
+        self.strict_handle_types
+    }
+
+    /// Fail flow resolution when a node input is wired to a handle its block doesn't define,
+    /// instead of only warning about it.
+    pub fn with_fail_on_unused_inputs(mut self, fail: bool) -> Self {
+        self.fail_on_unused_inputs = fail;
+        self
+    }
+
+    pub fn fail_on_unused_inputs(&self) -> bool {
+        self.fail_on_unused_inputs
+    }
+
+    /// Resolve connections whose node or handle names only match ignoring case, with a warning
+    /// for each of them.
+    pub fn with_case_insensitive_handles(mut self, enabled: bool) -> Self {
+        self.case_insensitive_handles = enabled;
+        self
+    }
+
+    pub fn case_insensitive_handles(&self) -> bool {
+        self.case_insensitive_handles
+    }
+
     pub fn resolve_flow_block(
         &mut self,
         flow_name: &str,
@@ -170,10 +208,11 @@ impl BlockResolver {
         }
 
         if matches!(block_type, BlockType::Service | BlockType::Unknown) {
-            // currently it is not considered, so we can ignore it for now. When we support it, we need to check whether the block_name is flow or service, because a flow can be empty, and all YAML files can be loaded as flow blocks.
             let service_path = finder.find_service_block(block_name);
             if let Ok(service_path) = service_path {
-                match self.read_service_block(&service_path, block_name) {
+                // `pkg::service::block` only names the block by its last segment inside the service manifest
+                let service_block_name = block_name.split("::").last().unwrap();
+                match self.read_service_block(&service_path, service_block_name) {
                     Ok(service) => {
                         return Ok(Block::Service(service));
                     }
@@ -9,6 +9,13 @@ use super::{
     node::{HandleFrom, HandleTo},
 };
 
+/// The input and output handles a node's block defines.
+#[derive(Debug, Clone, Default)]
+pub struct NodeHandles {
+    pub inputs: HashSet<HandleName>,
+    pub outputs: HashSet<HandleName>,
+}
+
 pub struct Connections {
     pub nodes: HashSet<NodeId>,
 
@@ -17,6 +24,12 @@ pub struct Connections {
 
     pub flow_inputs_tos: ConnNodeTos,
     pub flow_outputs_froms: ConnNodeFroms,
+    pub collected_flow_outputs: HashSet<HandleName>,
+
+    case_insensitive: bool,
+    node_handles: HashMap<NodeId, NodeHandles>,
+    /// One message per connection that only matched its node or handle by ignoring case.
+    pub case_mismatches: Vec<String>,
 }
 
 impl Connections {
@@ -28,7 +41,105 @@ impl Connections {
 
             flow_inputs_tos: ConnNodeTos::new(),
             flow_outputs_froms: ConnNodeFroms::new(),
+            collected_flow_outputs: HashSet::new(),
+
+            case_insensitive: false,
+            node_handles: HashMap::new(),
+            case_mismatches: vec![],
+        }
+    }
+
+    /// Match the nodes, flow inputs and node handles connections refer to ignoring case, when
+    /// there's no exact match.
+    pub fn with_case_insensitive_handles(mut self, enabled: bool) -> Self {
+        self.case_insensitive = enabled;
+        self
+    }
+
+    /// The handles of each node, node handles are only matched ignoring case for nodes listed here.
+    pub fn with_node_handles(mut self, node_handles: HashMap<NodeId, NodeHandles>) -> Self {
+        self.node_handles = node_handles;
+        self
+    }
+
+    /// The input handle `node_id` defines for `handle`, `handle` itself when there's no other match.
+    pub fn node_input_handle(&self, node_id: &NodeId, handle: &HandleName) -> HandleName {
+        self.defined_handle(node_id, handle, |handles| &handles.inputs)
+            .unwrap_or_else(|| handle.to_owned())
+    }
+
+    fn defined_handle(
+        &self,
+        node_id: &NodeId,
+        handle: &HandleName,
+        handles: impl Fn(&NodeHandles) -> &HashSet<HandleName>,
+    ) -> Option<HandleName> {
+        if !self.case_insensitive {
+            return None;
         }
+        let defined = handles(self.node_handles.get(node_id)?);
+        if defined.contains(handle) {
+            return None;
+        }
+        match_ignoring_case(handle.as_str(), defined.iter()).cloned()
+    }
+
+    /// The input handle of `node_id` a connection refers to, see [`Self::node_input_handle`].
+    fn find_node_input(&mut self, node_id: &NodeId, handle: &HandleName) -> HandleName {
+        let Some(found) = self.defined_handle(node_id, handle, |handles| &handles.inputs) else {
+            return handle.to_owned();
+        };
+        self.case_mismatches.push(format!(
+            "connection refers to node({node_id}) input handle({found}) as handle({handle})"
+        ));
+        found
+    }
+
+    /// The output handle of `node_id` a connection refers to, `handle` itself when there's no
+    /// other match.
+    fn find_node_output(&mut self, node_id: &NodeId, handle: &HandleName) -> HandleName {
+        let Some(found) = self.defined_handle(node_id, handle, |handles| &handles.outputs) else {
+            return handle.to_owned();
+        };
+        self.case_mismatches.push(format!(
+            "connection refers to node({node_id}) output handle({found}) as handle({handle})"
+        ));
+        found
+    }
+
+    /// The node a connection refers to, `None` when it's not in the flow.
+    fn find_node(&mut self, node_id: &NodeId) -> Option<NodeId> {
+        if self.nodes.contains(node_id) {
+            return Some(node_id.to_owned());
+        }
+        if !self.case_insensitive {
+            return None;
+        }
+        let found = match_ignoring_case(node_id.as_str(), self.nodes.iter())?.to_owned();
+        self.case_mismatches.push(format!(
+            "connection refers to node({found}) as node({node_id})"
+        ));
+        Some(found)
+    }
+
+    /// The flow input a connection refers to, `None` when the flow doesn't define it.
+    fn find_flow_input(
+        &mut self,
+        handle: &HandleName,
+        flow_inputs_def: &Option<HashMap<HandleName, manifest::InputHandle>>,
+    ) -> Option<HandleName> {
+        let def = flow_inputs_def.as_ref()?;
+        if def.contains_key(handle) {
+            return Some(handle.to_owned());
+        }
+        if !self.case_insensitive {
+            return None;
+        }
+        let found = match_ignoring_case(handle.as_str(), def.keys())?.to_owned();
+        self.case_mismatches.push(format!(
+            "connection refers to flow input handle({found}) as handle({handle})"
+        ));
+        Some(found)
     }
 
     /// add node connection from node to slot provider
@@ -67,26 +178,28 @@ impl Connections {
                         continue;
                     }
 
-                    if !self.nodes.contains(&from_node.node_id) {
+                    let Some(from_node_id) = self.find_node(&from_node.node_id) else {
                         tracing::warn!(
                             "ignore slot connection because node({}) is not in runtime nodes or value nodes",
                             from_node.node_id
                         );
                         continue;
-                    }
+                    };
+                    let output_handle =
+                        self.find_node_output(&from_node_id, &from_node.output_handle);
 
                     self.node_inputs_froms.add(
                         subflow_node_id.to_owned(),
                         runtime_handle.clone(),
                         HandleFrom::FromNodeOutput {
-                            node_id: from_node.node_id.to_owned(),
-                            output_handle: from_node.output_handle.to_owned(),
+                            node_id: from_node_id.to_owned(),
+                            output_handle: output_handle.to_owned(),
                         },
                     );
 
                     self.node_outputs_tos.add(
-                        from_node.node_id.to_owned(),
-                        from_node.output_handle.to_owned(),
+                        from_node_id,
+                        output_handle,
                         HandleTo::ToNodeInput {
                             node_id: subflow_node_id.to_owned(),
                             input_handle: runtime_handle.clone(),
@@ -132,22 +245,29 @@ impl Connections {
     pub fn parse_flow_outputs_from(&mut self, outputs_from: Option<Vec<manifest::NodeInputFrom>>) {
         if let Some(outputs_from) = outputs_from {
             for output_from in outputs_from {
+                if output_from.merge == manifest::OutputMerge::Collect {
+                    self.collected_flow_outputs
+                        .insert(output_from.handle.to_owned());
+                }
+
                 if let Some(from_nodes) = output_from.from_node {
                     for from_node in from_nodes {
-                        if !self.nodes.contains(&from_node.node_id) {
+                        let Some(from_node_id) = self.find_node(&from_node.node_id) else {
                             continue;
-                        }
+                        };
+                        let output_handle =
+                            self.find_node_output(&from_node_id, &from_node.output_handle);
 
                         self.flow_outputs_froms.add(
                             output_from.handle.to_owned(),
                             HandleFrom::FromNodeOutput {
-                                node_id: from_node.node_id.to_owned(),
-                                output_handle: from_node.output_handle.to_owned(),
+                                node_id: from_node_id.to_owned(),
+                                output_handle: output_handle.to_owned(),
                             },
                         );
                         self.node_outputs_tos.add(
-                            from_node.node_id.to_owned(),
-                            from_node.output_handle.to_owned(),
+                            from_node_id,
+                            output_handle,
                             HandleTo::ToFlowOutput {
                                 output_handle: output_from.handle.to_owned(),
                             },
@@ -184,13 +304,30 @@ impl Connections {
     ) {
         if let Some(inputs_from) = inputs_from {
             for input_from in inputs_from {
+                let handle = self.find_node_input(node_id, &input_from.handle);
+                // a value is merged from the manifest by its handle, which no longer matches
+                if handle != input_from.handle
+                    && input_from.from_node.is_none()
+                    && input_from.from_flow.is_none()
+                {
+                    if let Some(value) = input_from.value.clone() {
+                        self.node_inputs_froms.add(
+                            node_id.to_owned(),
+                            handle.to_owned(),
+                            HandleFrom::FromValue {
+                                value: Some(value).into(),
+                            },
+                        );
+                    }
+                }
+
                 if let Some(from_nodes) = &input_from.from_node {
                     for from_node in from_nodes {
                         if let Some(value_node) = find_value_node(&from_node.node_id) {
                             if let Some(input) = value_node.get_handle(&from_node.output_handle) {
                                 self.node_inputs_froms.add(
                                     node_id.to_owned(),
-                                    input_from.handle.to_owned(),
+                                    handle.to_owned(),
                                     HandleFrom::FromValue {
                                         value: input.value.clone().into(),
                                     },
@@ -208,28 +345,30 @@ impl Connections {
                             continue;
                         }
 
-                        if !self.nodes.contains(&from_node.node_id) {
+                        let Some(from_node_id) = self.find_node(&from_node.node_id) else {
                             tracing::warn!(
                                 "ignore node connection because node({}) is not in runtime nodes or value nodes",
                                 from_node.node_id
                             );
                             continue;
-                        }
+                        };
+                        let output_handle =
+                            self.find_node_output(&from_node_id, &from_node.output_handle);
 
                         self.node_inputs_froms.add(
                             node_id.to_owned(),
-                            input_from.handle.to_owned(),
+                            handle.to_owned(),
                             HandleFrom::FromNodeOutput {
-                                node_id: from_node.node_id.to_owned(),
-                                output_handle: from_node.output_handle.to_owned(),
+                                node_id: from_node_id.to_owned(),
+                                output_handle: output_handle.to_owned(),
                             },
                         );
                         self.node_outputs_tos.add(
-                            from_node.node_id.to_owned(),
-                            from_node.output_handle.to_owned(),
+                            from_node_id,
+                            output_handle,
                             HandleTo::ToNodeInput {
                                 node_id: node_id.to_owned(),
-                                input_handle: input_from.handle.to_owned(),
+                                input_handle: handle.to_owned(),
                             },
                         );
                     }
@@ -237,22 +376,21 @@ impl Connections {
 
                 if let Some(from_flow) = &input_from.from_flow {
                     for flow_handle in from_flow {
-                        if flow_inputs_def
-                            .as_ref()
-                            .is_some_and(|def| def.contains_key(&flow_handle.input_handle))
+                        if let Some(input_handle) =
+                            self.find_flow_input(&flow_handle.input_handle, flow_inputs_def)
                         {
                             self.node_inputs_froms.add(
                                 node_id.to_owned(),
-                                input_from.handle.to_owned(),
+                                handle.to_owned(),
                                 HandleFrom::FromFlowInput {
-                                    input_handle: flow_handle.input_handle.to_owned(),
+                                    input_handle: input_handle.to_owned(),
                                 },
                             );
                             self.flow_inputs_tos.add(
-                                flow_handle.input_handle.to_owned(),
+                                input_handle,
                                 HandleTo::ToNodeInput {
                                     node_id: node_id.to_owned(),
-                                    input_handle: input_from.handle.to_owned(),
+                                    input_handle: handle.to_owned(),
                                 },
                             );
                         } else {
@@ -268,6 +406,16 @@ impl Connections {
     }
 }
 
+/// The one name in `names` equal to `name` ignoring case, `None` when there's none or several.
+fn match_ignoring_case<'a, T: std::ops::Deref<Target = String>>(
+    name: &str,
+    names: impl Iterator<Item = &'a T>,
+) -> Option<&'a T> {
+    let mut matches = names.filter(|candidate| candidate.eq_ignore_ascii_case(name));
+    let found = matches.next()?;
+    matches.next().is_none().then_some(found)
+}
+
 #[derive(Debug, Clone)]
 pub struct ConnNodeTos {
     node_tos: HashMap<HandleName, Vec<HandleTo>>,
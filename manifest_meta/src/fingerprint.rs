@@ -0,0 +1,85 @@
+use crate::{HandleFrom, HandleSource, HandleTo, HandlesFroms, HandlesTos, SubflowBlock};
+
+impl SubflowBlock {
+    /// Short hash of what cached node inputs depend on: nodes, their blocks, handles and
+    /// connections. Static values and descriptions are left out, editing them keeps the cache.
+    pub fn fingerprint(&self) -> String {
+        let mut lines = vec![];
+
+        for handle in self.inputs_def.iter().flat_map(|def| def.keys()) {
+            lines.push(format!("flow input {handle}"));
+        }
+        for handle in self.outputs_def.iter().flat_map(|def| def.keys()) {
+            lines.push(format!("flow output {handle}"));
+        }
+        push_tos(&mut lines, "flow", &self.flow_inputs_tos);
+        push_froms(&mut lines, &self.flow_outputs_froms);
+
+        for (node_id, node) in &self.nodes {
+            let block = node
+                .block()
+                .path_str()
+                .unwrap_or_else(|| "inline".to_owned());
+            lines.push(format!("node {node_id} block {block}"));
+            for (handle, input) in node.inputs() {
+                let sources = input
+                    .sources
+                    .iter()
+                    .flatten()
+                    .map(|source| match source {
+                        HandleSource::FlowInput { input_handle } => format!("flow.{input_handle}"),
+                        HandleSource::NodeOutput {
+                            node_id,
+                            output_handle,
+                        } => format!("{node_id}.{output_handle}"),
+                    })
+                    .collect::<Vec<_>>();
+                lines.push(format!(
+                    "node {node_id} input {handle} <- {}",
+                    sorted(sources).join(",")
+                ));
+            }
+            if let Some(tos) = node.to() {
+                push_tos(&mut lines, node_id.as_str(), tos);
+            }
+        }
+
+        utils::calculate_short_hash(&sorted(lines).join("\n"), 16)
+    }
+}
+
+fn push_tos(lines: &mut Vec<String>, from: &str, tos: &HandlesTos) {
+    for (handle, targets) in tos {
+        for target in targets {
+            let to = match target {
+                HandleTo::ToNodeInput {
+                    node_id,
+                    input_handle,
+                } => format!("{node_id}.{input_handle}"),
+                HandleTo::ToFlowOutput { output_handle } => format!("flow.{output_handle}"),
+            };
+            lines.push(format!("{from}.{handle} -> {to}"));
+        }
+    }
+}
+
+fn push_froms(lines: &mut Vec<String>, froms: &HandlesFroms) {
+    for (handle, sources) in froms {
+        for source in sources {
+            let from = match source {
+                HandleFrom::FromFlowInput { input_handle } => format!("flow.{input_handle}"),
+                HandleFrom::FromNodeOutput {
+                    node_id,
+                    output_handle,
+                } => format!("{node_id}.{output_handle}"),
+                HandleFrom::FromValue { .. } => "value".to_owned(),
+            };
+            lines.push(format!("{from} -> flow output {handle}"));
+        }
+    }
+}
+
+fn sorted(mut lines: Vec<String>) -> Vec<String> {
+    lines.sort();
+    lines
+}
@@ -30,7 +30,7 @@ use utils::error::Result;
 use crate::{
     HandlesFroms, HandlesTos, Node, NodeId, SlotNode, SubflowNode,
     block_resolver::{BlockResolver, package_path},
-    connections::Connections,
+    connections::{Connections, NodeHandles},
     node::{ConditionNode, ServiceNode, TaskNode},
 };
 
@@ -70,11 +70,17 @@ pub struct SubflowBlock {
     pub flow_inputs_tos: HandlesTos,
     /// Flow outputs from in-flow nodes
     pub flow_outputs_froms: HandlesFroms,
+    /// Flow outputs emitted once as an array of all their values when the flow finishes
+    pub collected_outputs: HashSet<HandleName>,
+    /// Value nodes feeding each node. They are folded into the node inputs and are not in `nodes`.
+    pub value_node_sources: HashMap<NodeId, Vec<NodeId>>,
     pub package_path: Option<PathBuf>,
     pub injection_store: Option<InjectionStore>,
     pub forward_previews: Option<Vec<NodeId>>,
     pub hide_source: bool,
     pub remote_timeout: Option<u64>,
+    /// Connections that only matched their node or handle ignoring case
+    pub case_mismatches: Vec<String>,
 }
 
 #[derive(Hash, PartialEq, Eq, Debug)]
@@ -239,6 +245,73 @@ pub fn generate_node_inputs(
     inputs
 }
 
+/// The handles of the block `node` runs, `None` for nodes whose handles aren't known before the
+/// flow is read.
+fn resolve_node_handles(
+    node: &manifest::Node,
+    block_resolver: &mut BlockResolver,
+    path_finder: &mut BlockPathFinder,
+) -> Result<Option<NodeHandles>> {
+    fn keys<T>(def: &Option<HashMap<HandleName, T>>) -> HashSet<HandleName> {
+        def.iter().flat_map(|def| def.keys().cloned()).collect()
+    }
+
+    let handles = match node {
+        manifest::Node::Subflow(subflow_node) => {
+            let flow = block_resolver.resolve_flow_block(&subflow_node.subflow, path_finder)?;
+            let flow_guard = flow.read().unwrap();
+            NodeHandles {
+                inputs: keys(&flow_guard.inputs_def),
+                outputs: keys(&flow_guard.outputs_def),
+            }
+        }
+        manifest::Node::Task(task_node) => {
+            let task =
+                block_resolver.resolve_task_node_block(task_node.task.clone(), path_finder)?;
+            let mut handles = NodeHandles {
+                inputs: keys(&task.inputs_def),
+                outputs: keys(&task.outputs_def),
+            };
+            if task.additional_inputs {
+                handles.inputs.extend(
+                    task_node
+                        .inputs_def
+                        .iter()
+                        .flatten()
+                        .map(|input| input.handle.to_owned()),
+                );
+            }
+            if task.additional_outputs {
+                handles.outputs.extend(
+                    task_node
+                        .outputs_def
+                        .iter()
+                        .flatten()
+                        .map(|output| output.handle.to_owned()),
+                );
+            }
+            handles
+        }
+        manifest::Node::Service(service_node) => {
+            let service = block_resolver
+                .resolve_service_node_block(service_node.service.to_owned(), path_finder)?;
+            NodeHandles {
+                inputs: keys(&service.inputs_def),
+                outputs: keys(&service.outputs_def),
+            }
+        }
+        manifest::Node::Slot(slot_node) => {
+            let slot = block_resolver.resolve_slot_node_block(slot_node.slot.to_owned())?;
+            NodeHandles {
+                inputs: keys(&slot.inputs_def),
+                outputs: keys(&slot.outputs_def),
+            }
+        }
+        manifest::Node::Condition(_) | manifest::Node::Value(_) => return Ok(None),
+    };
+    Ok(Some(handles))
+}
+
 pub type MergeInputsValue = HashMap<NodeId, HashMap<HandleName, JsonValue>>;
 
 impl SubflowBlock {
@@ -253,11 +326,14 @@ impl SubflowBlock {
             path,
             flow_inputs_tos: HashMap::new(),
             flow_outputs_froms: HashMap::new(),
+            collected_outputs: HashSet::new(),
+            value_node_sources: HashMap::new(),
             package_path: None,
             injection_store: None,
             forward_previews: None,
             hide_source: false,
             remote_timeout: None,
+            case_mismatches: vec![],
         }
     }
 
@@ -308,6 +384,146 @@ impl SubflowBlock {
         inputs
     }
 
+    /// One message per connection that targets a handle marked `deprecated` in its block.
+    pub fn deprecated_connection_warnings(&self) -> Vec<String> {
+        fn describe(deprecation: &manifest::HandleDeprecation) -> String {
+            deprecation
+                .message()
+                .map(|message| format!(": {message}"))
+                .unwrap_or_default()
+        }
+
+        let mut node_ids: Vec<&NodeId> = self.nodes.keys().collect();
+        node_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        let mut warnings = vec![];
+        for node_id in node_ids {
+            let node = &self.nodes[node_id];
+
+            let mut inputs: Vec<&NodeInput> = node
+                .inputs()
+                .values()
+                .filter(|input| input.sources.as_ref().is_some_and(|s| !s.is_empty()))
+                .collect();
+            inputs.sort_by(|a, b| a.def.handle.as_str().cmp(b.def.handle.as_str()));
+            for input in inputs {
+                if let Some(deprecation) =
+                    input.def.deprecated.as_ref().filter(|d| d.is_deprecated())
+                {
+                    warnings.push(format!(
+                        "node({}) connects to deprecated input handle({}){}",
+                        node_id,
+                        input.def.handle,
+                        describe(deprecation)
+                    ));
+                }
+            }
+
+            if let (Some(outputs_def), Some(tos)) = (node.outputs_def(), node.to()) {
+                let mut outputs: Vec<&OutputHandle> = outputs_def
+                    .values()
+                    .filter(|output| tos.get(&output.handle).is_some_and(|t| !t.is_empty()))
+                    .collect();
+                outputs.sort_by(|a, b| a.handle.as_str().cmp(b.handle.as_str()));
+                for output in outputs {
+                    if let Some(deprecation) =
+                        output.deprecated.as_ref().filter(|d| d.is_deprecated())
+                    {
+                        warnings.push(format!(
+                            "node({}) connects from deprecated output handle({}){}",
+                            node_id,
+                            output.handle,
+                            describe(deprecation)
+                        ));
+                    }
+                }
+            }
+        }
+        warnings
+    }
+
+    /// One message per wired node input (by value or connection) whose handle is not in the node's
+    /// inputs, the runtime never delivers those.
+    fn unused_inputs(&self, wired_inputs: &[(NodeId, Vec<HandleName>)]) -> Vec<String> {
+        let mut unused = vec![];
+        for (node_id, handles) in wired_inputs {
+            let Some(node) = self.nodes.get(node_id) else {
+                continue;
+            };
+            for handle in handles {
+                if !node.inputs().contains_key(handle) {
+                    unused.push(format!(
+                        "node({node_id}) input handle({handle}) is not in the node's inputs_def"
+                    ));
+                }
+            }
+        }
+        unused
+    }
+
+    /// One message per node-to-node connection whose output and input `json_schema` types can't
+    /// match. Handles without a declared type accept anything.
+    pub fn handle_type_mismatches(&self) -> Vec<String> {
+        let mut node_ids: Vec<&NodeId> = self.nodes.keys().collect();
+        node_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        let mut mismatches = vec![];
+        for node_id in node_ids {
+            let node = &self.nodes[node_id];
+            let (Some(outputs_def), Some(tos)) = (node.outputs_def(), node.to()) else {
+                continue;
+            };
+
+            let mut handles: Vec<(&HandleName, &Vec<crate::HandleTo>)> = tos.iter().collect();
+            handles.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+            for (output_handle, targets) in handles {
+                let Some(output_types) = outputs_def
+                    .get(output_handle)
+                    .and_then(|def| schema_types(def.json_schema.as_ref()))
+                else {
+                    continue;
+                };
+
+                for target in targets {
+                    let crate::HandleTo::ToNodeInput {
+                        node_id: target_id,
+                        input_handle,
+                    } = target
+                    else {
+                        continue;
+                    };
+                    let Some(input_types) = self
+                        .nodes
+                        .get(target_id)
+                        .and_then(|n| n.inputs().get(input_handle))
+                        .and_then(|input| schema_types(input.def.json_schema.as_ref()))
+                    else {
+                        continue;
+                    };
+
+                    let compatible = output_types.iter().any(|output_type| {
+                        input_types.iter().any(|input_type| {
+                            input_type == output_type
+                                || (*input_type == "number" && *output_type == "integer")
+                        })
+                    });
+                    if !compatible {
+                        mismatches.push(format!(
+                            "node({}) output handle({}) of type {} connects to node({}) input handle({}) of type {}",
+                            node_id,
+                            output_handle,
+                            output_types.join("|"),
+                            target_id,
+                            input_handle,
+                            input_types.join("|")
+                        ));
+                    }
+                }
+            }
+        }
+        mismatches
+    }
+
     pub fn update_node(&mut self, node_id: &NodeId, node: Node) {
         if let Some(existing_node) = self.nodes.get_mut(node_id) {
             *existing_node = node;
@@ -366,12 +582,25 @@ impl SubflowBlock {
             .filter(|node| !node.should_ignore() && !value_nodes_id.contains(node.node_id()))
             .collect();
 
+        let case_insensitive = block_resolver.case_insensitive_handles();
+        let mut node_handles = HashMap::new();
+        if case_insensitive {
+            for node in nodes_in_flow.iter() {
+                if let Some(handles) = resolve_node_handles(node, block_resolver, &mut path_finder)?
+                {
+                    node_handles.insert(node.node_id().to_owned(), handles);
+                }
+            }
+        }
+
         let mut connections = Connections::new(
             nodes_in_flow
                 .iter()
                 .map(|n| n.node_id().to_owned())
                 .collect(),
-        );
+        )
+        .with_case_insensitive_handles(case_insensitive)
+        .with_node_handles(node_handles);
 
         connections.parse_flow_outputs_from(outputs_from);
 
@@ -400,10 +629,43 @@ impl SubflowBlock {
             }
         }
 
+        // handles the flow wires into each node, checked against the resolved inputs later
+        let wired_inputs: Vec<(NodeId, Vec<HandleName>)> = nodes_in_flow
+            .iter()
+            .map(|node| {
+                let handles = node
+                    .inputs_from()
+                    .into_iter()
+                    .flatten()
+                    .map(|input_from| {
+                        connections.node_input_handle(node.node_id(), &input_from.handle)
+                    })
+                    .collect();
+                (node.node_id().to_owned(), handles)
+            })
+            .collect();
+
         let find_node = |node_id: &NodeId| -> Option<&manifest::Node> {
             nodes_in_flow.iter().find(|n| n.node_id() == node_id)
         };
 
+        let value_node_sources: HashMap<NodeId, Vec<NodeId>> = nodes_in_flow
+            .iter()
+            .filter_map(|node| {
+                let mut sources: Vec<NodeId> = node
+                    .inputs_from()
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|input_from| input_from.from_node.iter().flatten())
+                    .map(|from_node| from_node.node_id.to_owned())
+                    .filter(|node_id| value_nodes_id.contains(node_id))
+                    .collect();
+                sources.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+                sources.dedup();
+                (!sources.is_empty()).then(|| (node.node_id().to_owned(), sources))
+            })
+            .collect();
+
         drop(value_nodes);
         drop(value_nodes_id);
 
@@ -799,6 +1061,7 @@ impl SubflowBlock {
                             concurrency: subflow_node.concurrency,
                             scope: running_scope,
                             progress_weight: subflow_node.progress_weight,
+                            wait_all: subflow_node.wait_all,
                             slots: if slot_blocks.is_empty() {
                                 None
                             } else {
@@ -832,8 +1095,10 @@ impl SubflowBlock {
                             timeout: service_node.timeout,
                             block: service,
                             inputs,
+                            retry: service_node.retry.clone(),
                             concurrency: service_node.concurrency,
                             progress_weight: service_node.progress_weight,
+                            wait_all: service_node.wait_all,
                         }),
                     );
                 }
@@ -993,8 +1258,10 @@ impl SubflowBlock {
                             task,
                             inputs,
                             outputs_def: merged_outputs_def,
+                            retry: task_node.retry.clone(),
                             concurrency: task_node.concurrency,
                             progress_weight: task_node.progress_weight,
+                            wait_all: task_node.wait_all,
                         }),
                     );
                 }
@@ -1023,6 +1290,7 @@ impl SubflowBlock {
                             inputs,
                             concurrency: slot_node.concurrency,
                             progress_weight: slot_node.progress_weight,
+                            wait_all: slot_node.wait_all,
                         }),
                     );
                 }
@@ -1058,6 +1326,7 @@ impl SubflowBlock {
                             json_schema: input.json_schema.clone(),
                             nullable: input.nullable,
                             kind: input.kind.clone(),
+                            deprecated: None,
                             _serialize_for_cache: false,
                         })
                     });
@@ -1073,6 +1342,7 @@ impl SubflowBlock {
                             conditions: Arc::new(ConditionBlock::from_manifest(conditions)),
                             concurrency: condition_node.concurrency,
                             progress_weight: condition_node.progress_weight,
+                            wait_all: condition_node.wait_all,
                         }),
                     );
                 }
@@ -1150,7 +1420,7 @@ impl SubflowBlock {
             }
         }
 
-        Ok(Self {
+        let flow = Self {
             description,
             nodes: new_nodes,
             inputs_def,
@@ -1159,6 +1429,8 @@ impl SubflowBlock {
             path: flow_path.clone(),
             flow_inputs_tos: connections.flow_inputs_tos.restore(),
             flow_outputs_froms: connections.flow_outputs_froms.restore(),
+            collected_outputs: connections.collected_flow_outputs,
+            value_node_sources,
             package_path: package_path(&flow_path).ok(),
             injection_store: if injection.is_empty() {
                 None
@@ -1168,7 +1440,43 @@ impl SubflowBlock {
             forward_previews,
             hide_source: false,
             remote_timeout: None,
-        })
+            case_mismatches: connections.case_mismatches,
+        };
+
+        if block_resolver.strict_handle_types() {
+            let mismatches = flow.handle_type_mismatches();
+            if !mismatches.is_empty() {
+                return Err(format!(
+                    "incompatible handle types in flow {}: {}",
+                    flow.path_str,
+                    mismatches.join("; ")
+                )
+                .into());
+            }
+        }
+
+        let unused_inputs = flow.unused_inputs(&wired_inputs);
+        if block_resolver.fail_on_unused_inputs() && !unused_inputs.is_empty() {
+            return Err(format!(
+                "unused inputs in flow {}: {}",
+                flow.path_str,
+                unused_inputs.join("; ")
+            )
+            .into());
+        }
+        for unused in unused_inputs {
+            warn!("{} in flow {}, it is ignored", unused, flow.path_str);
+        }
+
+        for warning in flow.deprecated_connection_warnings() {
+            warn!("{} in flow {}", warning, flow.path_str);
+        }
+
+        for mismatch in &flow.case_mismatches {
+            warn!("{} in flow {}", mismatch, flow.path_str);
+        }
+
+        Ok(flow)
     }
 
     pub fn get_services(&self) -> HashSet<ServiceQueryResult> {
@@ -1210,6 +1518,16 @@ impl SubflowBlock {
     }
 }
 
+/// The `type` keyword of a handle schema, `None` when it's missing so the handle stays permissive.
+fn schema_types(schema: Option<&JsonValue>) -> Option<Vec<&str>> {
+    let types: Vec<&str> = match schema?.get("type")? {
+        JsonValue::String(t) => vec![t.as_str()],
+        JsonValue::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+        _ => return None,
+    };
+    (!types.is_empty()).then_some(types)
+}
+
 fn get_inputs_def_patch(
     node_inputs_from: &Option<Vec<manifest::NodeInputFrom>>,
 ) -> Option<HashMap<HandleName, Vec<InputDefPatch>>> {
@@ -1352,6 +1670,7 @@ mod tests {
             from_flow: None,
             from_node: None,
             serialize_for_cache: false,
+            merge: Default::default(),
         }
     }
 
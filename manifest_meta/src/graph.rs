@@ -0,0 +1,89 @@
+use std::fmt::Write;
+
+use crate::{HandleName, HandleTo, HandlesTos, Node, NodeId, SubflowBlock};
+
+const FLOW_INPUTS_ID: &str = "flow inputs";
+const FLOW_OUTPUTS_ID: &str = "flow outputs";
+
+impl SubflowBlock {
+    /// Render the resolved connections as a Graphviz DOT digraph. Nodes are labeled by node id,
+    /// edges by `output handle -> input handle`. Output is sorted so it's stable between runs.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph flow {\n    rankdir=LR;\n");
+
+        let mut node_ids: Vec<&NodeId> = self.nodes.keys().collect();
+        node_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        if !self.flow_inputs_tos.is_empty() {
+            writeln!(dot, "    {} [shape=plaintext];", quote(FLOW_INPUTS_ID)).unwrap();
+        }
+        for node_id in &node_ids {
+            writeln!(
+                dot,
+                "    {} [shape={}];",
+                quote(node_id.as_str()),
+                node_shape(&self.nodes[*node_id])
+            )
+            .unwrap();
+        }
+        if !self.flow_outputs_froms.is_empty() {
+            writeln!(dot, "    {} [shape=plaintext];", quote(FLOW_OUTPUTS_ID)).unwrap();
+        }
+
+        write_edges(&mut dot, FLOW_INPUTS_ID, &self.flow_inputs_tos);
+        for node_id in node_ids {
+            if let Some(to) = self.nodes[node_id].to() {
+                write_edges(&mut dot, node_id.as_str(), to);
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn node_shape(node: &Node) -> &'static str {
+    match node {
+        Node::Task(_) => "box",
+        Node::Flow(_) => "box3d",
+        Node::Slot(_) => "hexagon",
+        Node::Service(_) => "component",
+        Node::Condition(_) => "diamond",
+    }
+}
+
+fn write_edges(dot: &mut String, from: &str, tos: &HandlesTos) {
+    let mut handles: Vec<(&HandleName, &Vec<HandleTo>)> = tos.iter().collect();
+    handles.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+
+    for (output_handle, targets) in handles {
+        let mut edges: Vec<(&str, &str)> = targets
+            .iter()
+            .map(|target| match target {
+                HandleTo::ToNodeInput {
+                    node_id,
+                    input_handle,
+                } => (node_id.as_str(), input_handle.as_str()),
+                HandleTo::ToFlowOutput { output_handle } => {
+                    (FLOW_OUTPUTS_ID, output_handle.as_str())
+                }
+            })
+            .collect();
+        edges.sort();
+
+        for (to, input_handle) in edges {
+            writeln!(
+                dot,
+                "    {} -> {} [label={}];",
+                quote(from),
+                quote(to),
+                quote(&format!("{output_handle} -> {input_handle}"))
+            )
+            .unwrap();
+        }
+    }
+}
+
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -4,7 +4,8 @@ use manifest_reader::path_finder::{BlockPathFinder, find_flow};
 pub use manifest_reader::{
     JsonValue,
     manifest::{
-        HandleName, InputHandle, NodeId, OutputHandle, ServiceExecutorOptions, TaskBlockExecutor,
+        HandleName, InputHandle, NodeId, OutputHandle, RetryPolicy, ServiceExecutorOptions,
+        TaskBlockExecutor,
     },
 };
 
@@ -48,6 +49,12 @@ pub use block_resolver::BlockResolver;
 use utils::error::Result;
 pub mod flow_resolver;
 
+mod fingerprint;
+mod graph;
+
+mod validate;
+pub use validate::{IssueKind, IssueLevel, ValidationIssue, validate_flow_or_block};
+
 pub fn read_flow_or_block(
     block_name: &str,
     block_reader: &mut BlockResolver,
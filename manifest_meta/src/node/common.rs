@@ -130,6 +130,7 @@ macro_rules! extend_node_common_field {
             pub inputs: HashMap<HandleName, NodeInput>,
             pub concurrency: i32,
             pub progress_weight: f32,
+            pub wait_all: bool,
         }
     };
 }
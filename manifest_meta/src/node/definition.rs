@@ -1,23 +1,31 @@
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
 
 use manifest_reader::manifest::{InputHandles, OutputHandle, OutputHandles};
 
 use crate::condition::ConditionBlock;
-use crate::{Block, HandleName, NodeId, ServiceBlock, SlotBlock, TaskBlock, scope::BlockScope};
+use crate::{
+    Block, HandleName, NodeId, RetryPolicy, ServiceBlock, SlotBlock, TaskBlock, scope::BlockScope,
+};
 
 use crate::extend_node_common_field;
 
-use super::common::{HandlesTos, InputDefPatchMap, NodeInput};
+use super::common::{HandleSource, HandlesTos, InputDefPatchMap, NodeInput};
 use super::subflow::SubflowNode;
 
 extend_node_common_field!(TaskNode {
     task: Arc<TaskBlock>,
     outputs_def: Option<OutputHandles>,
     scope: BlockScope,
+    retry: Option<RetryPolicy>,
 });
 
 extend_node_common_field!(ServiceNode {
-    block: Arc<ServiceBlock>
+    block: Arc<ServiceBlock>,
+    retry: Option<RetryPolicy>,
 });
 
 extend_node_common_field!(SlotNode {
@@ -68,6 +76,29 @@ impl Node {
         }
     }
 
+    /// Whether the node runs once after all of its upstream nodes finished.
+    pub fn wait_all(&self) -> bool {
+        match self {
+            Self::Task(task) => task.wait_all,
+            Self::Flow(flow) => flow.wait_all,
+            Self::Slot(slot) => slot.wait_all,
+            Self::Service(service) => service.wait_all,
+            Self::Condition(condition) => condition.wait_all,
+        }
+    }
+
+    /// Nodes whose outputs feed the node's inputs.
+    pub fn upstream_nodes(&self) -> HashSet<&NodeId> {
+        self.inputs()
+            .values()
+            .flat_map(|input| input.sources.iter().flatten())
+            .filter_map(|source| match source {
+                HandleSource::NodeOutput { node_id, .. } => Some(node_id),
+                HandleSource::FlowInput { .. } => None,
+            })
+            .collect()
+    }
+
     pub fn progress_weight(&self) -> f32 {
         match self {
             Self::Task(task) => task.progress_weight,
@@ -242,6 +273,15 @@ impl Node {
         }
     }
 
+    /// Only task and service nodes can be retried.
+    pub fn retry(&self) -> Option<&RetryPolicy> {
+        match self {
+            Self::Task(task) => task.retry.as_ref(),
+            Self::Service(service) => service.retry.as_ref(),
+            _ => None,
+        }
+    }
+
     pub fn scope(&self) -> BlockScope {
         match self {
             Self::Task(task) => task.scope.clone(),
@@ -18,6 +18,7 @@ pub struct TaskBlock {
     pub package_path: Option<PathBuf>,
     pub hide_source: bool,
     pub remote_timeout: Option<u64>,
+    pub cacheable: bool,
 }
 
 impl TaskBlock {
@@ -63,6 +64,7 @@ impl TaskBlock {
             additional_inputs,
             additional_outputs,
             description,
+            cacheable,
         } = manifest;
 
         Self {
@@ -76,6 +78,7 @@ impl TaskBlock {
             additional_outputs,
             hide_source,
             remote_timeout,
+            cacheable,
         }
     }
 }
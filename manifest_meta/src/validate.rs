@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+
+use manifest_reader::{
+    manifest::{self, InjectionTarget, NodeId, NodeInputFrom},
+    path_finder::BlockPathFinder,
+    reader,
+};
+
+use crate::{Block, BlockResolver, SubflowBlock, read_flow_or_block};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueLevel {
+    Error,
+    Warning,
+}
+
+impl IssueLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueLevel::Error => "error",
+            IssueLevel::Warning => "warning",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    /// The block, or a block used by one of its nodes, can't be read.
+    UnresolvedBlock,
+    /// A connection comes from a node that is not in the flow.
+    MissingNode,
+    /// A connection uses a handle the node or flow doesn't define.
+    HandleNotInDef,
+    /// A connection comes from a flow input the flow doesn't define.
+    MissingFlowInput,
+    /// An input has neither a connection nor a value, so its node never runs.
+    UnsatisfiableInput,
+    /// A node is injected into a package that can't be found.
+    PackageNotFound,
+    /// A connection targets a handle marked `deprecated`.
+    DeprecatedHandle,
+}
+
+impl IssueKind {
+    pub fn level(&self) -> IssueLevel {
+        match self {
+            IssueKind::DeprecatedHandle => IssueLevel::Warning,
+            _ => IssueLevel::Error,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueKind::UnresolvedBlock => "unresolved-block",
+            IssueKind::MissingNode => "missing-node",
+            IssueKind::HandleNotInDef => "handle-not-in-def",
+            IssueKind::MissingFlowInput => "missing-flow-input",
+            IssueKind::UnsatisfiableInput => "unsatisfiable-input",
+            IssueKind::PackageNotFound => "package-not-found",
+            IssueKind::DeprecatedHandle => "deprecated-handle",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationIssue {
+    pub kind: IssueKind,
+    pub node_id: Option<NodeId>,
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(kind: IssueKind, node_id: Option<&NodeId>, message: String) -> Self {
+        Self {
+            kind,
+            node_id: node_id.cloned(),
+            message,
+        }
+    }
+
+    pub fn level(&self) -> IssueLevel {
+        self.kind.level()
+    }
+}
+
+/// Read a flow or block like [`crate::read_flow_or_block`] does, but collect the problems the
+/// reader only logs (and keeps going with) instead of running anything.
+pub fn validate_flow_or_block(
+    block_name: &str,
+    block_reader: &mut BlockResolver,
+    path_finder: &mut BlockPathFinder,
+) -> Vec<ValidationIssue> {
+    let unresolved = |err: utils::error::Error| {
+        vec![ValidationIssue::new(
+            IssueKind::UnresolvedBlock,
+            None,
+            format!("failed to read {block_name}: {err}"),
+        )]
+    };
+
+    let flow = match read_flow_or_block(block_name, block_reader, path_finder) {
+        Ok(Block::Flow(flow)) => flow,
+        Ok(_) => return vec![],
+        Err(err) => return unresolved(err),
+    };
+    let flow = flow.read().unwrap();
+
+    match reader::read_flow(&flow.path) {
+        Ok(manifest) => validate_flow(&manifest, &flow, &path_finder.subflow(&flow.path)),
+        Err(err) => unresolved(err),
+    }
+}
+
+fn validate_flow(
+    manifest: &manifest::SubflowBlock,
+    flow: &SubflowBlock,
+    path_finder: &BlockPathFinder,
+) -> Vec<ValidationIssue> {
+    let value_nodes: HashMap<&NodeId, &manifest::ValueNode> = manifest
+        .nodes
+        .iter()
+        .filter_map(|node| match node {
+            manifest::Node::Value(value_node) if !value_node.ignore => {
+                Some((&value_node.node_id, value_node))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut issues = vec![];
+
+    for output_from in manifest.outputs_from.iter().flatten() {
+        let target = format!("flow output handle({})", output_from.handle);
+        check_from_nodes(&target, None, output_from, flow, &value_nodes, &mut issues);
+    }
+
+    let nodes = manifest
+        .nodes
+        .iter()
+        .filter(|node| !node.should_ignore() && !matches!(node, manifest::Node::Value(_)));
+    for node in nodes {
+        let node_id = node.node_id();
+        let Some(flow_node) = flow.nodes.get(node_id) else {
+            continue;
+        };
+
+        for input_from in node.inputs_from().into_iter().flatten() {
+            let target = format!("node({}) input handle({})", node_id, input_from.handle);
+            if !flow_node.inputs().contains_key(&input_from.handle) {
+                issues.push(ValidationIssue::new(
+                    IssueKind::HandleNotInDef,
+                    Some(node_id),
+                    format!("{target} is not in the node's inputs_def"),
+                ));
+                continue;
+            }
+
+            check_from_nodes(
+                &target,
+                Some(node_id),
+                input_from,
+                flow,
+                &value_nodes,
+                &mut issues,
+            );
+
+            for from_flow in input_from.from_flow.iter().flatten() {
+                let defined = flow
+                    .inputs_def
+                    .as_ref()
+                    .is_some_and(|def| def.contains_key(&from_flow.input_handle));
+                if !defined {
+                    issues.push(ValidationIssue::new(
+                        IssueKind::MissingFlowInput,
+                        Some(node_id),
+                        format!(
+                            "{target} connects from flow input({}) which is not in the flow's inputs_def",
+                            from_flow.input_handle
+                        ),
+                    ));
+                }
+            }
+        }
+
+        if let manifest::Node::Task(task_node) = node {
+            if let Some(InjectionTarget::Package(pkg_name)) =
+                task_node.inject.as_ref().map(|injection| &injection.target)
+            {
+                if path_finder.find_package_file_path(pkg_name).is_err() {
+                    issues.push(ValidationIssue::new(
+                        IssueKind::PackageNotFound,
+                        Some(node_id),
+                        format!(
+                            "node({node_id}) is injected into package {pkg_name} which is not found"
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut node_ids: Vec<&NodeId> = flow.nodes.keys().collect();
+    node_ids.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+    for node_id in node_ids {
+        let mut inputs: Vec<_> = flow.nodes[node_id].inputs().values().collect();
+        inputs.sort_by(|a, b| a.def.handle.as_str().cmp(b.def.handle.as_str()));
+        for input in inputs {
+            // same rule the runtime uses to decide whether a node can start
+            if input.sources.as_ref().is_none_or(|s| s.is_empty()) && !input.value.is_provided() {
+                issues.push(ValidationIssue::new(
+                    IssueKind::UnsatisfiableInput,
+                    Some(node_id),
+                    format!(
+                        "node({}) input handle({}) has no connection and no value, the node won't run",
+                        node_id, input.def.handle
+                    ),
+                ));
+            }
+        }
+    }
+
+    issues.extend(
+        flow.deprecated_connection_warnings()
+            .into_iter()
+            .map(|warning| ValidationIssue::new(IssueKind::DeprecatedHandle, None, warning)),
+    );
+
+    issues
+}
+
+/// The reader drops connections from unknown nodes, so they are checked against the manifest.
+fn check_from_nodes(
+    target: &str,
+    node_id: Option<&NodeId>,
+    input_from: &NodeInputFrom,
+    flow: &SubflowBlock,
+    value_nodes: &HashMap<&NodeId, &manifest::ValueNode>,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    for from_node in input_from.from_node.iter().flatten() {
+        let has_output = if let Some(value_node) = value_nodes.get(&from_node.node_id) {
+            value_node.get_handle(&from_node.output_handle).is_some()
+        } else if let Some(source) = flow.nodes.get(&from_node.node_id) {
+            source
+                .outputs_def()
+                .is_none_or(|def| def.contains_key(&from_node.output_handle))
+        } else {
+            issues.push(ValidationIssue::new(
+                IssueKind::MissingNode,
+                node_id,
+                format!(
+                    "{target} connects from node({}) which is not in the flow",
+                    from_node.node_id
+                ),
+            ));
+            continue;
+        };
+        if !has_output {
+            issues.push(ValidationIssue::new(
+                IssueKind::HandleNotInDef,
+                node_id,
+                format!(
+                    "{target} connects from output handle({}) which node({}) doesn't define",
+                    from_node.output_handle, from_node.node_id
+                ),
+            ));
+        }
+    }
+}
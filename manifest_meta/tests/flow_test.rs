@@ -1,7 +1,10 @@
 #[cfg(test)]
 mod tests {
 
-    use manifest_meta::{BlockResolver, HandleName, NodeId, generate_runtime_handle_name};
+    use manifest_meta::{
+        BlockResolver, HandleName, HandleSource, IssueKind, IssueLevel, JsonValue, NodeId,
+        generate_runtime_handle_name, validate_flow_or_block,
+    };
     use manifest_reader::path_finder::BlockPathFinder;
 
     use std::path::PathBuf;
@@ -271,6 +274,302 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_deprecated_handle_connection_warnings() {
+        let base_dir = test_directory();
+        let mut finder = BlockPathFinder::new(base_dir, None);
+        let mut block_reader = BlockResolver::new();
+
+        let flow_block = block_reader
+            .resolve_flow_block("deprecated-handles", &mut finder)
+            .unwrap();
+        let flow_block = flow_block.read().unwrap();
+
+        // only connections count, a constant value on `producer.legacy_in` is not a connection
+        assert_eq!(
+            flow_block.deprecated_connection_warnings(),
+            vec![
+                "node(consumer) connects to deprecated input handle(legacy_in): use in1 instead",
+                "node(producer) connects from deprecated output handle(legacy_out)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_strict_handle_types_rejects_mismatched_connection() {
+        let base_dir = test_directory();
+        let mut finder = BlockPathFinder::new(base_dir.clone(), None);
+        let mut block_reader = BlockResolver::new();
+        assert!(
+            block_reader
+                .resolve_flow_block("mismatched-types", &mut finder)
+                .is_ok()
+        );
+
+        let mut finder = BlockPathFinder::new(base_dir, None);
+        let mut block_reader = BlockResolver::new().with_strict_handle_types(true);
+        let err = block_reader
+            .resolve_flow_block("mismatched-types", &mut finder)
+            .unwrap_err()
+            .to_string();
+
+        // integer -> number and untyped inputs are accepted, only string -> number is reported
+        assert!(
+            err.contains(
+                "node(producer) output handle(text) of type string connects to node(consumer) input handle(ratio) of type number"
+            ),
+            "{err}"
+        );
+        assert!(!err.contains("handle(count)"), "{err}");
+        assert!(!err.contains("handle(anything)"), "{err}");
+    }
+
+    #[test]
+    fn test_case_insensitive_handles_resolves_mismatched_connection() {
+        let base_dir = test_directory();
+        let producer = NodeId::new("producer".to_owned());
+        let consumer = NodeId::new("consumer".to_owned());
+        let text = HandleName::new("text".to_owned());
+        let greeting = HandleName::new("greeting".to_owned());
+
+        let mut finder = BlockPathFinder::new(base_dir.clone(), None);
+        let flow_block = BlockResolver::new()
+            .resolve_flow_block("case-mismatch", &mut finder)
+            .unwrap();
+        let flow_block = flow_block.read().unwrap();
+        let tos = flow_block.nodes.get(&producer).unwrap().to().unwrap();
+        assert!(!tos.contains_key(&text));
+        let in_sources = &flow_block.nodes.get(&consumer).unwrap().inputs()
+            [&HandleName::new("in".to_owned())]
+            .sources;
+        assert!(in_sources.as_ref().is_none_or(|s| s.is_empty()));
+        assert!(!flow_block.flow_inputs_tos.contains_key(&greeting));
+
+        let mut finder = BlockPathFinder::new(base_dir, None);
+        let flow_block = BlockResolver::new()
+            .with_case_insensitive_handles(true)
+            .resolve_flow_block("case-mismatch", &mut finder)
+            .unwrap();
+        let flow_block = flow_block.read().unwrap();
+        let tos = flow_block.nodes.get(&producer).unwrap().to().unwrap();
+        assert_eq!(tos.len(), 1);
+        assert!(matches!(
+            tos.get(&text).unwrap().as_slice(),
+            [
+                manifest_meta::HandleTo::ToNodeInput { node_id, input_handle },
+                manifest_meta::HandleTo::ToFlowOutput { .. },
+            ] | [
+                manifest_meta::HandleTo::ToFlowOutput { .. },
+                manifest_meta::HandleTo::ToNodeInput { node_id, input_handle },
+            ] if node_id == &consumer && input_handle.as_str() == "in"
+        ));
+        assert!(matches!(
+            flow_block.flow_inputs_tos.get(&greeting).unwrap().as_slice(),
+            [manifest_meta::HandleTo::ToNodeInput { input_handle, .. }] if input_handle.as_str() == "label"
+        ));
+
+        let inputs = flow_block.nodes.get(&consumer).unwrap().inputs();
+        assert!(matches!(
+            inputs[&HandleName::new("in".to_owned())].sources.as_deref(),
+            Some([HandleSource::NodeOutput { node_id, output_handle }])
+                if node_id == &producer && output_handle == &text
+        ));
+        assert!(matches!(
+            inputs[&HandleName::new("label".to_owned())].sources.as_deref(),
+            Some([HandleSource::FlowInput { input_handle }]) if input_handle == &greeting
+        ));
+        assert!(matches!(
+            flow_block.flow_outputs_froms[&HandleName::new("out".to_owned())].as_slice(),
+            [manifest_meta::HandleFrom::FromNodeOutput { output_handle, .. }] if output_handle == &text
+        ));
+
+        let mut warnings = flow_block.case_mismatches.clone();
+        warnings.sort();
+        assert_eq!(
+            warnings,
+            [
+                "connection refers to flow input handle(greeting) as handle(Greeting)",
+                "connection refers to node(consumer) input handle(label) as handle(Label)",
+                "connection refers to node(producer) as node(Producer)",
+                "connection refers to node(producer) output handle(text) as handle(TEXT)",
+                "connection refers to node(producer) output handle(text) as handle(Text)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fail_on_unused_inputs_rejects_undeclared_handles() {
+        let base_dir = test_directory();
+        let mut finder = BlockPathFinder::new(base_dir.clone(), None);
+        let mut block_reader = BlockResolver::new();
+        assert!(
+            block_reader
+                .resolve_flow_block("unused-inputs", &mut finder)
+                .is_ok()
+        );
+
+        let mut finder = BlockPathFinder::new(base_dir, None);
+        let mut block_reader = BlockResolver::new().with_fail_on_unused_inputs(true);
+        let err = block_reader
+            .resolve_flow_block("unused-inputs", &mut finder)
+            .unwrap_err()
+            .to_string();
+
+        assert!(
+            err.contains("node(consumer) input handle(comand) is not in the node's inputs_def"),
+            "{err}"
+        );
+        assert!(
+            err.contains("node(consumer) input handle(extra) is not in the node's inputs_def"),
+            "{err}"
+        );
+        assert!(!err.contains("handle(command)"), "{err}");
+    }
+
+    #[test]
+    fn test_validate_flow_collects_connection_problems() {
+        let flow_path = test_directory().join("invalid-connections");
+        let mut finder = BlockPathFinder::new(test_directory(), None);
+        let mut block_reader = BlockResolver::new();
+
+        let issues =
+            validate_flow_or_block(flow_path.to_str().unwrap(), &mut block_reader, &mut finder);
+        let issues: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|issue| (issue.kind.as_str(), issue.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            issues,
+            vec![
+                (
+                    "missing-node",
+                    "flow output handle(flow_out1) connects from node(ghost) which is not in the flow"
+                ),
+                (
+                    "missing-flow-input",
+                    "node(producer) input handle(in2) connects from flow input(missing_flow_in) which is not in the flow's inputs_def"
+                ),
+                (
+                    "missing-node",
+                    "node(consumer) input handle(in1) connects from node(ghost) which is not in the flow"
+                ),
+                (
+                    "handle-not-in-def",
+                    "node(consumer) input handle(in2) connects from output handle(missing_out) which node(producer) doesn't define"
+                ),
+                (
+                    "handle-not-in-def",
+                    "node(consumer) input handle(not_defined) is not in the node's inputs_def"
+                ),
+                (
+                    "package-not-found",
+                    "node(injected) is injected into package missing-pkg which is not found"
+                ),
+                (
+                    "unsatisfiable-input",
+                    "node(consumer) input handle(in1) has no connection and no value, the node won't run"
+                ),
+                (
+                    "unsatisfiable-input",
+                    "node(producer) input handle(in2) has no connection and no value, the node won't run"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_flow_reports_deprecated_handles_as_warnings() {
+        let flow_path = test_directory().join("deprecated-handles");
+        let mut finder = BlockPathFinder::new(test_directory(), None);
+        let mut block_reader = BlockResolver::new();
+
+        let issues =
+            validate_flow_or_block(flow_path.to_str().unwrap(), &mut block_reader, &mut finder);
+
+        let deprecated: Vec<_> = issues
+            .iter()
+            .filter(|issue| issue.kind == IssueKind::DeprecatedHandle)
+            .collect();
+        assert_eq!(deprecated.len(), 2);
+        assert!(
+            deprecated
+                .iter()
+                .all(|issue| issue.level() == IssueLevel::Warning)
+        );
+    }
+
+    #[test]
+    fn test_flow_to_dot() {
+        let base_dir = test_directory();
+        let mut finder = BlockPathFinder::new(base_dir, None);
+        let mut block_reader = BlockResolver::new();
+
+        let flow_block = block_reader
+            .resolve_flow_block("subflows/serializable-var", &mut finder)
+            .unwrap();
+        let dot = flow_block.read().unwrap().to_dot();
+
+        assert_eq!(
+            dot,
+            r#"digraph flow {
+    rankdir=LR;
+    "node1" [shape=box];
+    "node2" [shape=box];
+    "node3" [shape=box3d];
+    "node1" -> "node2" [label="out -> in"];
+}
+"#
+        );
+
+        let slot_flow = block_reader
+            .resolve_flow_block(
+                test_directory()
+                    .join("subflows/serializable-var/subflow1.oo.yaml")
+                    .to_str()
+                    .unwrap(),
+                &mut finder,
+            )
+            .unwrap();
+        let dot = slot_flow.read().unwrap().to_dot();
+
+        assert!(dot.contains(r#""+slot#1" [shape=hexagon];"#));
+        assert!(dot.contains(r#""flow inputs" -> "+slot#1" [label="input -> input"];"#));
+        assert!(dot.contains(r#""+slot#1" -> "flow outputs" [label="output -> output"];"#));
+    }
+
+    #[test]
+    fn test_flow_fingerprint_follows_structure() {
+        let fingerprint = |name: &str| {
+            let mut finder = BlockPathFinder::new(test_directory(), None);
+            let flow_block = BlockResolver::new()
+                .resolve_flow_block(name, &mut finder)
+                .unwrap();
+            let mut flow = flow_block.write().unwrap();
+            let before_values = flow.fingerprint();
+            flow.merge_input_values(
+                [(
+                    NodeId::from("node1".to_owned()),
+                    [(
+                        HandleName::new("in1".to_owned()),
+                        JsonValue::String("changed".to_owned()),
+                    )]
+                    .into(),
+                )]
+                .into(),
+            );
+            // static values don't affect the cache
+            assert_eq!(before_values, flow.fingerprint());
+            before_values
+        };
+
+        assert_eq!(fingerprint("basic"), fingerprint("basic"));
+        assert_ne!(
+            fingerprint("basic"),
+            fingerprint("subflows/serializable-var")
+        );
+    }
+
     fn test_directory() -> PathBuf {
         PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
     }
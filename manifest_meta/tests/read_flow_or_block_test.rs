@@ -77,6 +77,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_read_package_service_block() {
+        let base_dir = test_directory().join("fixtures");
+        let packages_dir = base_dir.join("packages");
+        let mut finder = BlockPathFinder::new(&base_dir, Some(vec![packages_dir]));
+        let mut block_reader = BlockResolver::new();
+
+        let block =
+            read_flow_or_block("test-pkg::echo::echo", &mut block_reader, &mut finder).unwrap();
+
+        match block {
+            Block::Service(service) => {
+                assert_eq!(service.name, "echo");
+                assert!(service.service_executor.is_some());
+            }
+            _ => panic!("Expected a Service block, found {block:?}"),
+        }
+    }
+
     fn test_directory() -> PathBuf {
         PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
     }
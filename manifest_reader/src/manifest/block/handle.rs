@@ -26,6 +26,8 @@ impl From<&str> for HandleName {
     }
 }
 
+// only lives while a manifest is deserialized, not worth boxing
+#[allow(clippy::large_enum_variant)]
 #[derive(Deserialize, Serialize, Debug, Clone)]
 #[serde(untagged)]
 pub enum MiddleInputHandle {
@@ -46,6 +48,27 @@ pub enum MiddleOutputHandle {
     },
 }
 
+/// `deprecated: true` or `deprecated: "<migration hint>"` on a handle definition.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum HandleDeprecation {
+    Flag(bool),
+    Message(String),
+}
+
+impl HandleDeprecation {
+    pub fn is_deprecated(&self) -> bool {
+        !matches!(self, HandleDeprecation::Flag(false))
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            HandleDeprecation::Message(message) => Some(message),
+            HandleDeprecation::Flag(_) => None,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 struct TempInputHandle {
     pub handle: HandleName,
@@ -62,6 +85,8 @@ struct TempInputHandle {
         with = "::serde_with::rust::double_option"
     )]
     pub value: Option<Option<serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<HandleDeprecation>,
 }
 
 impl From<TempInputHandle> for InputHandle {
@@ -73,6 +98,7 @@ impl From<TempInputHandle> for InputHandle {
             kind,
             nullable,
             value,
+            deprecated,
         } = temp;
         let value = if nullable.is_some_and(|n| n) {
             if value.is_none() { Some(None) } else { value }
@@ -86,6 +112,7 @@ impl From<TempInputHandle> for InputHandle {
             kind,
             nullable,
             value,
+            deprecated,
             remember: false,
             is_additional: false,
             _deserialize_from_cache: false,
@@ -111,6 +138,8 @@ pub struct InputHandle {
         with = "::serde_with::rust::double_option"
     )]
     pub value: Option<Option<serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<HandleDeprecation>,
     #[serde(default, skip_serializing)]
     pub remember: bool,
     /// Indicates whether this handle is an additional handle. This field is set by the manifest reader and is not present in the original manifest.
@@ -131,6 +160,7 @@ impl InputHandle {
             kind: None,
             description: None,
             nullable: None,
+            deprecated: None,
             remember: false,
             is_additional: false,
             _deserialize_from_cache: false,
@@ -164,6 +194,8 @@ pub struct OutputHandle {
     pub kind: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub nullable: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deprecated: Option<HandleDeprecation>,
     /// Indicates whether this handle is an additional handle. This field is generated by the manifest reader not originally defined in the manifest.
     /// additional handle is not defined in block , it is defined in the flow node.
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
@@ -231,6 +263,7 @@ mod tests {
             kind: Some("var1".to_string()),
             description: None,
             nullable: None,
+            deprecated: None,
             is_additional: false,
             _serialize_for_cache: false,
         };
@@ -327,4 +360,20 @@ mod tests {
         assert_eq!(deserialized.handle, HandleName::new("input".to_string()));
         assert_eq!(deserialized.value, Some(None));
     }
+
+    #[test]
+    fn deserialize_deprecated_handle() {
+        use super::*;
+        let input: InputHandle =
+            serde_yaml::from_str("{handle: old, deprecated: use new instead}").unwrap();
+        let deprecated = input.deprecated.unwrap();
+        assert!(deprecated.is_deprecated());
+        assert_eq!(deprecated.message(), Some("use new instead"));
+
+        let output: OutputHandle = serde_yaml::from_str("{handle: old, deprecated: true}").unwrap();
+        assert_eq!(output.deprecated, Some(HandleDeprecation::Flag(true)));
+
+        let output: OutputHandle = serde_yaml::from_str("{handle: new}").unwrap();
+        assert!(output.deprecated.is_none());
+    }
 }
@@ -26,6 +26,8 @@ struct TmpTaskBlock {
     pub additional_inputs: Option<AdditionalObject>,
     #[serde(default)]
     pub additional_outputs: Option<AdditionalObject>,
+    #[serde(default)]
+    pub cacheable: Option<bool>,
 }
 
 impl From<TmpTaskBlock> for TaskBlock {
@@ -45,6 +47,7 @@ impl From<TmpTaskBlock> for TaskBlock {
                 Some(AdditionalObject::Value(_)) => true,
                 None => false,
             },
+            cacheable: tmp.cacheable.unwrap_or(true),
         }
     }
 }
@@ -58,6 +61,9 @@ pub struct TaskBlock {
     pub outputs_def: Option<OutputHandles>,
     pub additional_inputs: bool,
     pub additional_outputs: bool,
+    /// `false` for blocks with side effects or non-deterministic outputs, they are never served
+    /// from the output cache.
+    pub cacheable: bool,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -200,6 +206,7 @@ mod test {
                     kind: None,
                     nullable: None,
                     value: None,
+                    deprecated: None,
                     remember: false,
                     is_additional: false,
                     _deserialize_from_cache: false,
@@ -214,11 +221,13 @@ mod test {
                 json_schema: None,
                 kind: None,
                 nullable: None,
+                deprecated: None,
                 is_additional: false,
                 _serialize_for_cache: false,
             })]),
             additional_inputs: Some(AdditionalObject::Bool(true)),
             additional_outputs: Some(AdditionalObject::Bool(true)),
+            cacheable: None,
         };
 
         let str = serde_json::to_string(&tmp_task_block).unwrap();
@@ -240,7 +249,8 @@ mod test {
                 "inputs_def": [{"handle": "input1", "description": "Input 1"}, {"group": "section"}],
                 "outputs_def": [{"handle": "output1", "description": "Output 1"}],
                 "additional_inputs": true,
-                "additional_outputs": false
+                "additional_outputs": false,
+                "cacheable": false
             }"#;
 
             let result = serde_json::from_str::<TaskBlock>(str);
@@ -257,6 +267,7 @@ mod test {
             assert!(matches!(*block.executor, TaskBlockExecutor::NodeJS(_)));
             assert!(block.additional_inputs);
             assert!(!block.additional_outputs);
+            assert!(!block.cacheable);
         }
 
         // Test with additional_inputs and additional_outputs as objects
@@ -319,6 +330,7 @@ mod test {
             assert!(matches!(*block.executor, TaskBlockExecutor::NodeJS(_)));
             assert!(!block.additional_inputs);
             assert!(!block.additional_outputs);
+            assert!(block.cacheable);
         }
     }
 
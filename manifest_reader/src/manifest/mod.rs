@@ -3,18 +3,18 @@ mod node;
 mod package;
 mod service;
 
-pub use self::block::handle::{HandleName, InputHandle, OutputHandle};
+pub use self::block::handle::{HandleDeprecation, HandleName, InputHandle, OutputHandle};
 
 pub use self::block::{ConditionBlock, ConditionHandleDef, DefaultConditionHandleDef};
 pub use self::block::{InputHandles, OutputHandles};
 pub use self::block::{ServiceBlock, SlotBlock, SubflowBlock, TaskBlock};
 pub use self::block::{SpawnOptions, TaskBlockExecutor};
 pub use self::node::{
-    Injection, InjectionTarget, Node, NodeId, ServiceNode, SlotNode, SlotNodeBlock, SlotProvider,
-    SubflowNode, TaskNode, TaskNodeBlock, ValueNode,
+    Injection, InjectionTarget, Node, NodeId, RetryPolicy, ServiceNode, SlotNode, SlotNodeBlock,
+    SlotProvider, SubflowNode, TaskNode, TaskNodeBlock, ValueNode,
 };
 
-pub use self::node::input_from::{InputDefPatch, NodeInputFrom};
+pub use self::node::input_from::{InputDefPatch, NodeInputFrom, OutputMerge};
 
 pub use self::package::PackageMeta;
-pub use self::service::{Service, ServiceExecutorOptions};
+pub use self::service::{HealthCheck, Service, ServiceExecutorOptions};
@@ -16,6 +16,10 @@ macro_rules! extend_node_common_field {
             pub progress_weight: f32,
             #[serde(default)]
             pub ignore: bool,
+            /// Run once, after every upstream node it reads from finished, instead of on every
+            /// arrival of its inputs.
+            #[serde(default)]
+            pub wait_all: bool,
         }
     };
 }
@@ -27,6 +31,16 @@ pub fn default_concurrency() -> i32 {
 pub fn default_progress_weight() -> f32 {
     1.0
 }
+
+/// Re-run a failed node before its error fails the flow.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Runs in total, the first one included. `1` never retries.
+    pub max_attempts: u32,
+    /// Milliseconds to wait before each retry.
+    #[serde(default)]
+    pub delay: u64,
+}
 #[derive(
     Serialize,
     Deserialize,
@@ -32,6 +32,19 @@ struct TmpNodeInputFrom {
     pub from_node: Option<Vec<NodeHandleFrom>>,
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub serialize_for_cache: bool,
+    #[serde(default)]
+    pub merge: OutputMerge,
+}
+
+/// How a flow output handle with several sources in `outputs_from` merges their values.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMerge {
+    /// Every value is emitted as it arrives, the last one wins.
+    #[default]
+    Last,
+    /// Values are collected and emitted as one array when the flow finishes.
+    Collect,
 }
 
 /// PatchSchema
@@ -82,6 +95,8 @@ pub struct NodeInputFrom {
     /// Indicates whether the input should be serialized for caching purposes (implemented in executor)
     /// Set this to `true` if the input needs to be stored in a cache for reuse. Currently, this is only used for Python Pandas DataFrame inputs.
     pub serialize_for_cache: bool,
+    /// Only used in a flow's `outputs_from`.
+    pub merge: OutputMerge,
 }
 
 impl From<TmpNodeInputFrom> for NodeInputFrom {
@@ -121,6 +136,7 @@ impl From<TmpNodeInputFrom> for NodeInputFrom {
             from_flow: data.from_flow,
             from_node: data.from_node,
             serialize_for_cache: data.serialize_for_cache,
+            merge: data.merge,
         }
     }
 }
@@ -150,6 +166,16 @@ mod tests {
         assert_eq!(data.value, Some(None));
     }
 
+    #[test]
+    fn test_merge() {
+        let data = serde_yaml::from_str::<NodeInputFrom>("handle: output").unwrap();
+        assert_eq!(data.merge, OutputMerge::Last);
+
+        let data =
+            serde_yaml::from_str::<NodeInputFrom>("{handle: output, merge: collect}").unwrap();
+        assert_eq!(data.merge, OutputMerge::Collect);
+    }
+
     #[test]
     fn test_input_from_deserialize() {
         let data = serde_yaml::from_str::<NodeInputFrom>(
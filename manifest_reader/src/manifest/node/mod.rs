@@ -8,7 +8,7 @@ mod task;
 mod value;
 
 pub mod input_from;
-pub use self::common::NodeId;
+pub use self::common::{NodeId, RetryPolicy};
 pub use self::condition::ConditionNode;
 pub use self::definition::Node;
 pub use self::service::ServiceNode;
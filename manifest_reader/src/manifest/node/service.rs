@@ -2,9 +2,12 @@ use serde::Deserialize;
 
 use crate::{extend_node_common_field, manifest::NodeInputFrom};
 
-use super::common::{NodeId, default_concurrency, default_progress_weight};
+use super::common::{NodeId, RetryPolicy, default_concurrency, default_progress_weight};
 
-extend_node_common_field!(ServiceNode { service: String });
+extend_node_common_field!(ServiceNode {
+    service: String,
+    retry: Option<RetryPolicy>,
+});
 
 #[cfg(test)]
 mod tests {
@@ -7,13 +7,14 @@ use crate::{
     path_finder::{BlockValueType, calculate_block_value_type},
 };
 
-use super::common::{NodeId, default_concurrency, default_progress_weight};
+use super::common::{NodeId, RetryPolicy, default_concurrency, default_progress_weight};
 
 extend_node_common_field!(TaskNode {
     task: TaskNodeBlock,
     inject: Option<Injection>,
     inputs_def: Option<Vec<InputHandle>>,
     outputs_def: Option<Vec<OutputHandle>>,
+    retry: Option<RetryPolicy>,
 });
 
 #[derive(Deserialize, Debug, Clone)]
@@ -110,5 +111,26 @@ mod tests {
         assert_eq!(node.node_id, NodeId::from("example_node".to_owned()));
         assert_eq!(node.concurrency, 5);
         assert!(!node.ignore);
+        assert!(node.retry.is_none());
+    }
+
+    #[test]
+    fn test_task_node_retry() {
+        let yaml = r#"
+        task: example_task
+        node_id: example_node
+        retry:
+          max_attempts: 3
+          delay: 500
+        "#;
+
+        let node: TaskNode = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            node.retry,
+            Some(RetryPolicy {
+                max_attempts: 3,
+                delay: 500
+            })
+        );
     }
 }
@@ -17,6 +17,31 @@ pub struct ServiceExecutorOptions {
     #[serde(default)]
     pub stop_at: StopAt,
     pub keep_alive: Option<u64>,
+    /// Checked before the first block is sent to the service executor.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_check: Option<HealthCheck>,
+}
+
+/// A shell command run in the service dir, the service is healthy once it exits with 0. A script
+/// relative to the service dir works too, e.g. `./health.sh`. It runs in the layer of a service
+/// executor that runs in one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HealthCheck {
+    pub command: String,
+    /// Seconds to wait for the service to become healthy.
+    #[serde(default = "default_health_check_timeout")]
+    pub timeout: u64,
+    /// Milliseconds between two runs of the command.
+    #[serde(default = "default_health_check_interval")]
+    pub interval: u64,
+}
+
+fn default_health_check_timeout() -> u64 {
+    30
+}
+
+fn default_health_check_interval() -> u64 {
+    500
 }
 
 impl ServiceExecutorOptions {
@@ -62,6 +87,7 @@ struct TmpServiceExecutorOptions {
     pub start_at: Option<String>,
     pub stop_at: Option<String>,
     pub keep_alive: Option<u64>,
+    pub health_check: Option<HealthCheck>,
 }
 
 impl From<TmpServiceExecutorOptions> for ServiceExecutorOptions {
@@ -98,6 +124,7 @@ impl From<TmpServiceExecutorOptions> for ServiceExecutorOptions {
                 }
             },
             keep_alive: tmp.keep_alive,
+            health_check: tmp.health_check,
         }
     }
 }
@@ -130,6 +157,21 @@ mod tests {
         assert_eq!(executor.keep_alive, Some(100));
     }
 
+    #[test]
+    fn test_executor_options_health_check() {
+        use super::*;
+        let json = r#"{"name":"test","health_check":{"command":"./health.sh","timeout":5}}"#;
+        let executor: ServiceExecutorOptions = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            executor.health_check,
+            Some(HealthCheck {
+                command: "./health.sh".to_string(),
+                timeout: 5,
+                interval: 500,
+            })
+        );
+    }
+
     #[test]
     fn test_executor_options_wrong_alive() {
         use super::*;
@@ -117,10 +117,28 @@ impl BlockPathFinder {
             .map(|p| p.to_path_buf())
             .unwrap_or_else(|| PathBuf::from("."));
 
-        let mut pkg_versions = collect_latest_pkg_version(
-            &working_dir,
-            &Some(self.search_paths.iter().cloned().collect()),
-        );
+        // relative search paths belong to the first flow, nested subflows get them resolved
+        let search_paths = if self.search_paths.iter().any(|path| path.is_relative()) {
+            let resolved: Vec<PathBuf> = self
+                .search_paths
+                .iter()
+                .map(|path| {
+                    let path = working_dir.join(path);
+                    std::path::absolute(&path).unwrap_or(path)
+                })
+                .collect();
+            tracing::debug!(
+                "search paths resolved against {}: {:?}",
+                working_dir.display(),
+                resolved
+            );
+            Arc::new(resolved)
+        } else {
+            Arc::clone(&self.search_paths)
+        };
+
+        let mut pkg_versions =
+            collect_latest_pkg_version(&working_dir, &Some(search_paths.iter().cloned().collect()));
 
         // subflow should be in a/b/c/flows/flow1/flow.oo.yaml. package.oo.yaml is in a/b/c.
         let specified_version_package = flow_path
@@ -139,7 +157,7 @@ impl BlockPathFinder {
         Self {
             base_dir: working_dir,
             cache: HashMap::new(),
-            search_paths: Arc::clone(&self.search_paths),
+            search_paths,
             pkg_version: pkg_versions,
         }
     }
@@ -260,3 +278,39 @@ impl BlockPathFinder {
         Ok(service_path)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_search_paths_resolve_against_the_flow_dir() {
+        let root = std::env::temp_dir().join(format!("oocana-search-paths-{}", std::process::id()));
+        let flow_dir = root.join("project").join("flows").join("main");
+        let pkg_dir = flow_dir.join("packages").join("my-pkg");
+        fs::create_dir_all(&pkg_dir).unwrap();
+        fs::write(flow_dir.join("flow.oo.yaml"), "nodes: []\n").unwrap();
+        fs::write(pkg_dir.join("package.oo.yaml"), "name: my-pkg\n").unwrap();
+
+        // the working dir is elsewhere, the relative path only exists next to the flow
+        let finder = BlockPathFinder::new(
+            std::env::temp_dir(),
+            Some(vec![PathBuf::from("packages"), root.clone()]),
+        );
+        let subflow = finder.subflow(flow_dir.join("flow.oo.yaml"));
+
+        assert_eq!(
+            *subflow.search_paths,
+            vec![flow_dir.join("packages"), root.clone()]
+        );
+        assert_eq!(
+            subflow.find_package_file_path("my-pkg").unwrap(),
+            pkg_dir.join("package.oo.yaml")
+        );
+        // nested subflows keep the paths of the first flow
+        let nested = subflow.subflow(root.join("flow.oo.yaml"));
+        assert_eq!(nested.search_paths, subflow.search_paths);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -121,10 +121,74 @@ pub fn read_manifest_file<T: DeserializeOwned>(file_path: &Path) -> Result<T> {
     // Remove Unicode line separator and paragraph separator before parsing since they will cause serde_yaml to fail
     let s = s.replace("\u{2028}", "").replace("\u{2029}", "");
 
-    let yaml_data: T = serde_yaml::from_str(&s)?;
+    let mut yaml_value: serde_yaml::Value = serde_yaml::from_str(&s)?;
+    interpolate_env(&mut yaml_value)?;
+    let yaml_data: T = serde_yaml::from_value(yaml_value)?;
     Ok(yaml_data)
 }
 
+/// Shell scripts expand their own `${VAR}` when they run, they're never interpolated.
+const VERBATIM_KEYS: &[&str] = &["scripts", "script"];
+
+/// Replace `${VAR}` and `${VAR:-fallback}` in string values with the process environment, except
+/// under [`VERBATIM_KEYS`]. `$${` is kept as a literal `${`.
+fn interpolate_env(value: &mut serde_yaml::Value) -> Result<()> {
+    match value {
+        serde_yaml::Value::String(s) if s.contains("${") => *s = interpolate_str(s)?,
+        serde_yaml::Value::Sequence(seq) => {
+            for item in seq {
+                interpolate_env(item)?;
+            }
+        }
+        serde_yaml::Value::Mapping(map) => {
+            for (key, item) in map.iter_mut() {
+                if key.as_str().is_some_and(|key| VERBATIM_KEYS.contains(&key)) {
+                    continue;
+                }
+                interpolate_env(item)?;
+            }
+        }
+        serde_yaml::Value::Tagged(tagged) => interpolate_env(&mut tagged.value)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn interpolate_str(s: &str) -> Result<String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start..];
+        if let Some(escaped) = after.strip_prefix("$${") {
+            result.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(var) = after.strip_prefix("${") else {
+            result.push('$');
+            rest = &after[1..];
+            continue;
+        };
+        let end = var
+            .find('}')
+            .ok_or_else(|| format!("Unclosed environment variable in {s:?}"))?;
+        let expr = &var[..end];
+        let (name, fallback) = match expr.split_once(":-") {
+            Some((name, fallback)) => (name, Some(fallback)),
+            None => (expr, None),
+        };
+        let resolved = std::env::var(name)
+            .ok()
+            .or_else(|| fallback.map(str::to_owned))
+            .ok_or_else(|| format!("Environment variable {name} is not set"))?;
+        result.push_str(&resolved);
+        rest = &var[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
 /// Metadata read from `.metadata.oo.json` in the package root directory.
 pub struct BlockMetadata {
     pub hide_source: bool,
@@ -79,6 +79,7 @@ mod tests {
             kind: None,
             nullable: None,
             value: None,
+            deprecated: None,
             remember: false,
             is_additional: false,
             _deserialize_from_cache: false,
@@ -103,4 +104,58 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn load_package_with_env_vars() -> Result<()> {
+        std::env::set_var("OOCANA_READER_TEST_PACKAGE", "env-package");
+
+        let package = read_package(fixtures_dir().join("env-package.oo.yaml"))?;
+
+        assert_eq!(package.name, Some("env-package".to_string()));
+        assert_eq!(package.version, Some("2.0.0".to_string()));
+        let scripts = package.scripts.expect("scripts should exist");
+        // scripts are kept as they are, shell variables included
+        assert_eq!(
+            scripts.bootstrap,
+            Some(
+                "pip install -i ${PIP_INDEX_URL:-https://pypi.org/simple} -r ${REQUIREMENTS}"
+                    .to_string()
+            )
+        );
+        let exports = package.exports.expect("exports should exist");
+        assert_eq!(exports["cache"], "${HOME}/.cache");
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_flow_with_env_var_input_values() -> Result<()> {
+        std::env::set_var("OOCANA_READER_TEST_ENDPOINT", "https://example.com/api");
+
+        let flow_block = read_flow_block(&fixtures_dir().join("env-flow.oo.yaml"))?;
+
+        let inputs_from = flow_block.nodes[0]
+            .inputs_from()
+            .expect("inputs_from should exist");
+        let value = |handle: &str| {
+            inputs_from
+                .iter()
+                .find(|input| input.handle == HandleName::new(handle.to_string()))
+                .and_then(|input| input.value.clone().flatten())
+        };
+        assert_eq!(value("endpoint"), Some("https://example.com/api".into()));
+        assert_eq!(value("region"), Some("us-east-1".into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unset_env_var_without_fallback_fails() {
+        let err = read_package(fixtures_dir().join("env-missing.oo.yaml")).unwrap_err();
+
+        assert!(
+            format!("{err:?}").contains("OOCANA_READER_TEST_UNSET_NAME is not set"),
+            "{err:?}"
+        );
+    }
 }
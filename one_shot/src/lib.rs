@@ -1 +1,2 @@
 pub mod one_shot;
+mod session_slots;
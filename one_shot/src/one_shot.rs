@@ -1,15 +1,20 @@
 //! Run flow once and exit.
 
+use crate::session_slots::{acquire_session_slot, session_slots_dir};
 use job::SessionId;
 use mainframe::BindPath;
 use mainframe::scheduler::ExecutorParameters;
-use manifest_meta::BlockResolver;
+use manifest_meta::{BlockResolver, HandleName, NodeId};
 use manifest_reader::path_finder::BlockPathFinder;
-use std::collections::HashSet;
+use runtime::session_progress::{
+    ResumedSession, SESSION_PROGRESS_MAX_AGE, SessionProgress, prune_session_progress,
+    session_progress_dir,
+};
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::{self, metadata};
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
 use std::sync::Arc;
 use tracing::{info, warn};
@@ -47,6 +52,8 @@ pub struct UpstreamArgs<'a> {
     pub search_paths: Option<Vec<PathBuf>>,
     pub use_cache: bool,
     pub nodes: Option<HashSet<String>>,
+    pub by_output_handle: bool,
+    pub include_value_nodes: bool,
 }
 
 // TODO: 从 one_shot 中移除，这里不需要配置很多环境，简单裹一层意义不大。
@@ -56,6 +63,8 @@ pub fn find_upstream(args: UpstreamArgs<'_>) -> Result<(Vec<String>, Vec<String>
         search_paths,
         use_cache,
         nodes,
+        by_output_handle,
+        include_value_nodes,
     } = args;
 
     let block_reader = BlockResolver::new();
@@ -67,6 +76,8 @@ pub fn find_upstream(args: UpstreamArgs<'_>) -> Result<(Vec<String>, Vec<String>
         path_finder: block_path_finder,
         use_cache,
         nodes,
+        by_output_handle,
+        include_value_nodes,
     };
 
     runtime::find_upstream(upstream_args)
@@ -75,13 +86,24 @@ pub fn find_upstream(args: UpstreamArgs<'_>) -> Result<(Vec<String>, Vec<String>
 pub struct BlockArgs<'a> {
     pub block_path: &'a str,
     pub broker_address: String,
+    pub broker_connect_retries: u32,
     pub search_paths: Option<Vec<PathBuf>>,
     pub session: String,
     pub reporter_enable: bool,
     pub debug: bool,
     pub wait_for_client: bool,
     pub use_cache: bool,
+    pub replay_cached_outputs: bool,
     pub nodes: Option<HashSet<String>>,
+    pub node_filter_regex: Option<String>,
+    /// Record the inputs of this node to `<session_dir>/<node>.record.json`.
+    pub record_block: Option<String>,
+    /// Run only the block of a node recorded with `record_block`, with its recorded inputs.
+    pub replay_block: Option<String>,
+    /// Id of an interrupted session to resume, see [`runtime::RunArgs::resume`].
+    pub resume: Option<String>,
+    /// Record the progress of the session so it can be resumed, always on for a resumed session.
+    pub resumable: bool,
     pub inputs: Option<String>,
     pub nodes_inputs: Option<String>,
     pub default_package: Option<String>,
@@ -90,6 +112,8 @@ pub struct BlockArgs<'a> {
     pub bind_paths: Vec<BindPath>,
     pub retain_env_keys: Option<Vec<String>>,
     pub env_file: Option<String>,
+    pub env_vars: HashMap<String, String>,
+    pub secrets_via_file: bool,
     pub temp_root: String,
     pub project_data: &'a PathBuf,
     pub pkg_data_root: &'a PathBuf,
@@ -97,19 +121,53 @@ pub struct BlockArgs<'a> {
     pub remote_block_url: Option<String>,
     pub connector_base_url: Option<String>,
     pub remote_block_timeout: Option<u64>,
+    pub max_log_line_bytes: Option<usize>,
+    pub max_executor_log_bytes: Option<usize>,
+    pub per_executor_logs: bool,
+    pub report_executor_logs: bool,
+    pub report_to_file: bool,
+    pub max_concurrency: Option<u32>,
+    pub max_flow_depth: Option<u32>,
+    pub max_concurrent_sessions: Option<u32>,
+    pub abort_grace_outputs: Option<u64>,
+    pub output_file: Option<String>,
+    pub output_only: Option<HashSet<String>>,
+    /// Stream the block's outputs to stdout as JSON lines, see `RunArgs::output_stream`.
+    pub output_stream: bool,
+    pub readonly_package_data: bool,
+    pub strict_handle_types: bool,
+    pub case_insensitive_handles: bool,
+    pub shutdown_grace_secs: Option<u64>,
+    pub checkpoint_interval_secs: Option<u64>,
+    pub executor_spawn_timeout_secs: Option<u64>,
+    pub executor_ready_timeout_secs: Option<u64>,
+    pub listener_timeout_secs: Option<u64>,
+    pub executor_restart_attempts: u32,
+    pub fail_on_unused_inputs: bool,
+    pub inputs_required_check: bool,
+    pub output_cache: bool,
+    pub executor_args: Vec<String>,
+    pub time_budget: Vec<String>,
 }
 
 async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
     let BlockArgs {
         block_path,
         broker_address,
+        broker_connect_retries,
         search_paths,
         session,
         reporter_enable,
         debug,
         wait_for_client,
         use_cache,
+        replay_cached_outputs,
         nodes,
+        node_filter_regex,
+        record_block,
+        replay_block,
+        resume,
+        resumable,
         inputs,
         nodes_inputs,
         default_package,
@@ -118,6 +176,8 @@ async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
         session_dir,
         retain_env_keys,
         env_file,
+        env_vars,
+        secrets_via_file,
         temp_root,
         project_data,
         pkg_data_root,
@@ -125,8 +185,56 @@ async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
         remote_block_url,
         connector_base_url,
         remote_block_timeout,
+        max_log_line_bytes,
+        max_executor_log_bytes,
+        per_executor_logs,
+        report_executor_logs,
+        report_to_file,
+        max_concurrency,
+        max_flow_depth,
+        max_concurrent_sessions,
+        abort_grace_outputs,
+        output_file,
+        output_only,
+        output_stream,
+        readonly_package_data,
+        strict_handle_types,
+        case_insensitive_handles,
+        shutdown_grace_secs,
+        checkpoint_interval_secs,
+        executor_spawn_timeout_secs,
+        executor_ready_timeout_secs,
+        listener_timeout_secs,
+        executor_restart_attempts,
+        fail_on_unused_inputs,
+        inputs_required_check,
+        output_cache,
+        executor_args,
+        time_budget,
     } = block_args;
+    let time_budgets = runtime::time_budget::TimeBudgets::parse(&time_budget)?;
+    let node_filter = node_filter_regex
+        .map(|pattern| {
+            regex::Regex::new(&pattern)
+                .map_err(|e| format!("Invalid --node-filter-regex {pattern:?}: {e}"))
+        })
+        .transpose()?;
+    let replay_block = replay_block
+        .map(|path| runtime::block_record::BlockRecord::read(Path::new(&path)))
+        .transpose()?;
+    let resume = resume
+        .map(|id| ResumedSession::read(&session_progress_dir(), &SessionId::new(id)))
+        .transpose()?;
     let session_id = SessionId::new(session);
+    // held until the session finishes
+    let _session_slot = match max_concurrent_sessions {
+        Some(max) => {
+            let slot = acquire_session_slot(&session_slots_dir(), max as usize)?;
+            info!("session holds slot {}", slot.path().display());
+            Some(slot)
+        }
+        None => None,
+    };
     tracing::info!("Session start with session id: {}", session_id);
 
     let addr = broker_address.parse::<SocketAddr>().unwrap_or_else(|_| {
@@ -151,7 +259,8 @@ async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
     };
 
     let (_scheduler_impl_tx, _scheduler_impl_rx) =
-        mainframe_mqtt::scheduler::connect(&addr, session_id.to_owned()).await;
+        mainframe_mqtt::scheduler::connect(&addr, session_id.to_owned(), broker_connect_retries)
+            .await?;
 
     let block_path_finder = BlockPathFinder::new(env::current_dir().unwrap(), search_paths);
     let default_pkg_path = if let Some(ref default_pkg) = default_package {
@@ -256,6 +365,39 @@ async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
         false
     };
 
+    // removed when dropped at the end of the session
+    let secrets_file = if secrets_via_file {
+        let values = utils::env::load_env_from_file(&env_file);
+        let path = flow_tmp_dir.join(format!("secrets-{session_id}.json"));
+        Some(utils::env::SecretsFile::create(path, &values)?)
+    } else {
+        None
+    };
+
+    let (broker_reporter_tx, broker_reporter_rx) = if reporter_enable {
+        let (tx, rx) =
+            mainframe_mqtt::reporter::connect(&addr, session_id.to_owned(), report_to_console)
+                .await;
+        (Some(tx), Some(rx))
+    } else {
+        (None, None)
+    };
+    let event_log = if report_to_file {
+        let path = utils::logger::logger_dir().join(mainframe::reporter::EVENTS_FILE);
+        let event_log = mainframe::reporter::JsonLinesReporterTx::open(path.clone())
+            .map_err(|e| format!("Failed to open event log {}: {e}", path.display()))?;
+        Some(event_log)
+    } else {
+        None
+    };
+    let (reporter_tx, reporter_rx) = mainframe::reporter::create(
+        session_id.to_owned(),
+        (broker_reporter_tx.is_some() || event_log.is_some())
+            .then(|| mainframe::reporter::TeeReporterTx::new(broker_reporter_tx, event_log)),
+        broker_reporter_rx,
+    );
+    let reporter_handle = reporter_rx.event_loop();
+
     let (scheduler_tx, scheduler_rx) = mainframe::scheduler::create(
         _scheduler_impl_tx,
         _scheduler_impl_rx,
@@ -270,28 +412,34 @@ async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
             bind_paths,
             pass_through_env_keys: retain_env_keys.unwrap_or_default(),
             env_file: env_file.clone(),
+            env_vars,
+            secrets_file: secrets_file.as_ref().map(|file| file.path().to_owned()),
             tmp_dir: flow_tmp_dir.clone(),
             debug,
             wait_for_client,
+            max_log_line_bytes: max_log_line_bytes
+                .unwrap_or(mainframe::scheduler::DEFAULT_MAX_LOG_LINE_BYTES),
+            max_log_bytes: max_executor_log_bytes,
+            per_executor_log_dir: per_executor_logs.then(utils::logger::logger_dir),
+            executor_log_reporter: report_executor_logs.then(|| reporter_tx.clone()),
+            spawn_timeout: executor_spawn_timeout_secs
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(mainframe::scheduler::DEFAULT_EXECUTOR_SPAWN_TIMEOUT),
+            ready_timeout: executor_ready_timeout_secs
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(mainframe::scheduler::DEFAULT_EXECUTOR_READY_TIMEOUT),
+            listener_timeout: listener_timeout_secs
+                .map(std::time::Duration::from_secs)
+                .unwrap_or(mainframe::scheduler::DEFAULT_LISTENER_TIMEOUT),
+            restart_policy: mainframe::scheduler::ExecutorRestartPolicy {
+                max_attempts: executor_restart_attempts,
+            },
+            extra_args: executor_args,
         },
         project_data.to_string_lossy().to_string(),
     );
     let scheduler_handle = scheduler_rx.event_loop();
 
-    let (reporter_tx, reporter_rx) = if reporter_enable {
-        let (_reporter_impl_tx, _reporter_impl_rx) =
-            mainframe_mqtt::reporter::connect(&addr, session_id.to_owned(), report_to_console)
-                .await;
-        mainframe::reporter::create(
-            session_id.to_owned(),
-            Some(_reporter_impl_tx),
-            Some(_reporter_impl_rx),
-        )
-    } else {
-        mainframe::reporter::create(session_id.to_owned(), None, None)
-    };
-    let reporter_handle = reporter_rx.event_loop();
-
     let (delay_abort_tx, delay_abort_rx) = runtime::delay_abort::delay_abort();
     // delay to collect rest loggings
     let delay_abort_handle = delay_abort_rx.run();
@@ -315,10 +463,38 @@ async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
         delay_abort_tx,
         reporter: reporter_tx.clone(),
         use_cache,
+        replay_cached_outputs,
+        output_cache: output_cache.then(|| {
+            let cache = runtime::output_cache::OutputCache::new(pkg_data_root);
+            cache.prune(runtime::output_cache::OUTPUT_CACHE_MAX_AGE);
+            cache
+        }),
         remote_task_config,
+        concurrency_limit: max_concurrency
+            .map(|n| Arc::new(tokio::sync::Semaphore::new(n as usize))),
+        node_timings: Default::default(),
+        time_budgets,
+        drain: Default::default(),
+        max_flow_depth: max_flow_depth
+            .map(|depth| depth as usize)
+            .unwrap_or(runtime::shared::DEFAULT_MAX_FLOW_DEPTH),
+        block_recorder: record_block.map(|node_id| {
+            let path = Path::new(&session_dir).join(format!("{node_id}.record.json"));
+            runtime::block_record::BlockRecorder::new(NodeId::new(node_id), path)
+        }),
+        checkpoint_interval: checkpoint_interval_secs.map(std::time::Duration::from_secs),
+        session_progress: (resumable || resume.is_some()).then(|| {
+            let dir = session_progress_dir();
+            prune_session_progress(&dir, SESSION_PROGRESS_MAX_AGE);
+            SessionProgress::new(&dir, &session_id)
+        }),
     });
+    let node_timings = shared.node_timings.clone();
 
-    let block_reader = BlockResolver::new();
+    let block_reader = BlockResolver::new()
+        .with_strict_handle_types(strict_handle_types)
+        .with_fail_on_unused_inputs(fail_on_unused_inputs)
+        .with_case_insensitive_handles(case_insensitive_handles);
 
     let result = runtime::run(runtime::RunArgs {
         shared,
@@ -327,13 +503,26 @@ async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
         path_finder: block_path_finder,
         job_id: None,
         nodes,
+        node_filter,
+        replay_block,
+        resume,
         inputs,
+        inputs_required_check,
         nodes_inputs,
         default_package_path: current_package_path.map(|p| p.to_owned()),
         pkg_data_root,
         project_data,
         in_layer: run_in_layer,
         vault_client,
+        cancel: None,
+        abort_grace_outputs: abort_grace_outputs.map(std::time::Duration::from_millis),
+        output_file: output_file.map(PathBuf::from),
+        output_only: output_only.map(|handles| handles.into_iter().map(HandleName::new).collect()),
+        output_stream: output_stream
+            .then(|| Box::new(std::io::stdout()) as Box<dyn std::io::Write + Send>),
+        readonly_package_data,
+        drain: None,
+        shutdown_grace: shutdown_grace_secs.map(std::time::Duration::from_secs),
     })
     .await;
 
@@ -348,6 +537,12 @@ async fn run_block_async(block_args: BlockArgs<'_>) -> Result<()> {
     _ = scheduler_handle.await;
     _ = reporter_handle.await;
 
+    let node_timings_file =
+        utils::logger::logger_dir().join(runtime::node_timings::NODE_TIMINGS_FILE);
+    if let Err(err) = node_timings.write(&node_timings_file) {
+        warn!("Failed to write node timings at {node_timings_file:?}: {err}");
+    }
+
     tracing::info!(
         "Session finished with session id {} result: {:?}",
         session_id,
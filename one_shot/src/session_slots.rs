@@ -0,0 +1,71 @@
+//! Cap on the sessions running at once on a host, for `--max-concurrent-sessions`.
+
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+use fs2::FileExt;
+use utils::error::Result;
+
+/// One of the slots, held by a running session. The lock is released when the slot is dropped or
+/// the process exits, a crashed session doesn't keep its slot.
+#[derive(Debug)]
+pub struct SessionSlot {
+    _file: File,
+    path: PathBuf,
+}
+
+impl SessionSlot {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Directory shared by the sessions of a host.
+pub fn session_slots_dir() -> PathBuf {
+    utils::config::oocana_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("session-slots")
+}
+
+/// Take a free slot out of `max`, a new session is rejected as busy when all of them are held.
+pub fn acquire_session_slot(dir: &Path, max: usize) -> Result<SessionSlot> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create session slots dir {}: {e}", dir.display()))?;
+    for slot in 0..max {
+        let path = dir.join(format!("slot-{slot}.lock"));
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| format!("Failed to open session slot {}: {e}", path.display()))?;
+        if file.try_lock_exclusive().is_ok() {
+            return Ok(SessionSlot { _file: file, path });
+        }
+    }
+    Err(format!("Server busy: {max} sessions are already running, try again later").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_beyond_the_limit_is_rejected() {
+        let dir = std::env::temp_dir().join(format!("oocana-session-slots-{}", std::process::id()));
+
+        let first = acquire_session_slot(&dir, 2).unwrap();
+        let second = acquire_session_slot(&dir, 2).unwrap();
+        assert_ne!(first.path(), second.path());
+
+        let err = acquire_session_slot(&dir, 2).unwrap_err().to_string();
+        assert!(err.contains("Server busy"), "{err}");
+
+        drop(first);
+        let third = acquire_session_slot(&dir, 2).unwrap();
+        assert_ne!(third.path(), second.path());
+
+        drop((second, third));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
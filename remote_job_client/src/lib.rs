@@ -1,11 +1,16 @@
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
+use std::sync::Arc;
 use std::time::Duration;
 use thiserror::Error;
 
+pub use tokio_util::sync::CancellationToken;
+
 #[cfg(feature = "mock")]
 pub mod mock;
+#[cfg(feature = "webhook")]
+pub mod webhook;
 
 type JsonMap = Map<String, Value>;
 
@@ -20,6 +25,10 @@ pub enum TaskClientError {
     },
     #[error("task {task_id} failed: {message}")]
     TaskFailed { task_id: String, message: String },
+    #[error("task {task_id} was cancelled")]
+    Cancelled { task_id: String },
+    #[error("invalid task request: {field} is empty")]
+    InvalidRequest { field: &'static str },
 }
 
 pub type Result<T> = std::result::Result<T, TaskClientError>;
@@ -47,6 +56,45 @@ pub struct RemoteJobClient {
 }
 
 const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+const CREATE_ATTEMPTS: u32 = 3;
+const CREATE_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Called with the task progress, see [`RunTaskOptions::on_progress`].
+pub type ProgressCallback = Arc<dyn Fn(f64) + Send + Sync>;
+
+/// Options for [`RemoteJobClient::run_task`].
+#[derive(Clone)]
+pub struct RunTaskOptions {
+    pub poll_interval: Duration,
+    /// When fired, the remote task is cancelled and `run_task` returns
+    /// [`TaskClientError::Cancelled`].
+    pub cancel: Option<CancellationToken>,
+    /// Invoked after a poll whenever the task progress changes. The callback runs
+    /// synchronously on the polling task's executor, so keep it cheap.
+    pub on_progress: Option<ProgressCallback>,
+}
+
+impl std::fmt::Debug for RunTaskOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RunTaskOptions")
+            .field("poll_interval", &self.poll_interval)
+            .field("cancel", &self.cancel)
+            .field("on_progress", &self.on_progress.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+impl Default for RunTaskOptions {
+    fn default() -> Self {
+        Self {
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            cancel: None,
+            on_progress: None,
+        }
+    }
+}
 
 impl RemoteJobClient {
     pub fn new(base_url: impl Into<String>) -> Self {
@@ -66,9 +114,40 @@ impl RemoteJobClient {
         self
     }
 
+    /// Create a task. Transient failures are retried with the same `Idempotency-Key`, so the
+    /// server creates the task at most once. The key is [`CreateTaskRequest::idempotency_key`]
+    /// or a random one per call.
     pub async fn create_remote_job(&self, payload: &CreateTaskRequest) -> Result<String> {
+        payload.validate()?;
         let url = format!("{}/v3/users/me/tasks", self.base_url);
-        let req = self.client.post(url).json(payload);
+        let key = payload
+            .idempotency_key
+            .clone()
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+        let mut attempt = 1;
+        loop {
+            match self.try_create_remote_job(&url, &key, payload).await {
+                Err(err) if attempt < CREATE_ATTEMPTS && is_transient(&err) => {
+                    tokio::time::sleep(CREATE_RETRY_DELAY * attempt).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn try_create_remote_job(
+        &self,
+        url: &str,
+        key: &str,
+        payload: &CreateTaskRequest,
+    ) -> Result<String> {
+        let req = self
+            .client
+            .post(url)
+            .header(IDEMPOTENCY_KEY_HEADER, key)
+            .json(payload);
         let resp = self.with_auth(req).send().await?;
         let resp = ensure_success(resp).await?;
         let body: CreateTaskResponse = resp.json().await?;
@@ -111,6 +190,120 @@ impl RemoteJobClient {
         Ok(items.into_iter().map(unstringify_values).collect())
     }
 
+    pub async fn cancel_task(&self, task_id: &str) -> Result<()> {
+        let url = format!("{}/v3/users/me/tasks/{task_id}", self.base_url);
+        let req = self.client.delete(url);
+        let resp = self.with_auth(req).send().await?;
+        ensure_success(resp).await?;
+        Ok(())
+    }
+
+    /// Create a task and poll it until it reaches a terminal state, then return its result.
+    pub async fn run_task(
+        &self,
+        payload: &CreateTaskRequest,
+        options: RunTaskOptions,
+    ) -> Result<TaskResult> {
+        let task_id = self.create_remote_job(payload).await?;
+        self.poll_task(task_id, options).await
+    }
+
+    /// Create a task that posts a [`TaskWebhook`] to `callback_url` when it finishes, returns
+    /// its id.
+    pub async fn create_task_with_callback(
+        &self,
+        payload: &CreateTaskRequest,
+        callback_url: &str,
+    ) -> Result<String> {
+        let payload = payload.clone().with_callback_url(callback_url);
+        self.create_remote_job(&payload).await
+    }
+
+    /// Like [`run_task`](Self::run_task), but waits for the task to report its completion to
+    /// `receiver`. Anyone can post to the receiver, so a webhook only triggers a check of the
+    /// task's detail. Polling only starts when the webhook doesn't arrive within
+    /// `webhook_timeout`, so [`RunTaskOptions::on_progress`] is only called from then on.
+    #[cfg(feature = "webhook")]
+    pub async fn run_task_with_webhook(
+        &self,
+        payload: &CreateTaskRequest,
+        receiver: &webhook::WebhookReceiver,
+        webhook_timeout: Duration,
+        options: RunTaskOptions,
+    ) -> Result<TaskResult> {
+        let task_id = self
+            .create_task_with_callback(payload, &receiver.callback_url())
+            .await?;
+        let cancel = options.cancel.clone().unwrap_or_default();
+
+        let event = tokio::select! {
+            _ = cancel.cancelled() => return Err(self.cancelled(task_id).await),
+            event = receiver.wait(&task_id, webhook_timeout) => event,
+        };
+        if event.is_some() {
+            let detail = self.get_task_detail(&task_id).await?;
+            if let Some(result) = self
+                .finished_task(&task_id, detail.status, detail.failed_message)
+                .await
+            {
+                return result;
+            }
+        }
+        self.poll_task(task_id, options).await
+    }
+
+    async fn poll_task(&self, task_id: String, options: RunTaskOptions) -> Result<TaskResult> {
+        let cancel = options.cancel.unwrap_or_default();
+        let mut last_progress: Option<f64> = None;
+
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => return Err(self.cancelled(task_id).await),
+                _ = tokio::time::sleep(options.poll_interval) => {}
+            }
+
+            let detail = self.get_task_detail(&task_id).await?;
+            if last_progress != Some(detail.progress) {
+                last_progress = Some(detail.progress);
+                if let Some(on_progress) = &options.on_progress {
+                    on_progress(detail.progress);
+                }
+            }
+            if let Some(result) = self
+                .finished_task(&task_id, detail.status, detail.failed_message)
+                .await
+            {
+                return result;
+            }
+        }
+    }
+
+    /// Cancel the remote task once the caller cancelled the run. The run is cancelled either way,
+    /// a failed cancel request is only logged.
+    async fn cancelled(&self, task_id: String) -> TaskClientError {
+        if let Err(e) = self.cancel_task(&task_id).await {
+            tracing::warn!("failed to cancel remote task {task_id}: {e}");
+        }
+        TaskClientError::Cancelled { task_id }
+    }
+
+    /// The outcome of a task in `status`, `None` while it hasn't finished.
+    async fn finished_task(
+        &self,
+        task_id: &str,
+        status: TaskStatus,
+        failed_message: Option<String>,
+    ) -> Option<Result<TaskResult>> {
+        match status {
+            TaskStatus::Success => Some(self.get_task_result(task_id).await),
+            TaskStatus::Failed => Some(Err(TaskClientError::TaskFailed {
+                task_id: task_id.to_string(),
+                message: failed_message.unwrap_or_else(|| "unknown error".to_string()),
+            })),
+            _ => None,
+        }
+    }
+
     fn with_auth(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
         match &self.auth {
             Auth::None => req,
@@ -136,6 +329,12 @@ pub struct CreateTaskRequest {
     pub block_name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub input_values: Option<JsonMap>,
+    /// Sent as the `Idempotency-Key` header, a random key is used when `None`.
+    #[serde(skip)]
+    pub idempotency_key: Option<String>,
+    /// URL the server posts a [`TaskWebhook`] to when the task finishes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_url: Option<String>,
 }
 
 impl CreateTaskRequest {
@@ -151,11 +350,36 @@ impl CreateTaskRequest {
             package_version,
             block_name,
             input_values,
+            idempotency_key: None,
+            callback_url: None,
+        }
+    }
+
+    pub fn with_idempotency_key(mut self, key: impl Into<String>) -> Self {
+        self.idempotency_key = Some(key.into());
+        self
+    }
+
+    pub fn with_callback_url(mut self, url: impl Into<String>) -> Self {
+        self.callback_url = Some(url.into());
+        self
+    }
+
+    /// Reject requests the server would refuse with an opaque 4xx.
+    pub fn validate(&self) -> Result<()> {
+        let required = [
+            ("packageName", &self.package_name),
+            ("packageVersion", &self.package_version),
+            ("blockName", &self.block_name),
+        ];
+        match required.iter().find(|(_, value)| value.trim().is_empty()) {
+            Some((field, _)) => Err(TaskClientError::InvalidRequest { field }),
+            None => Ok(()),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum TaskStatus {
     Queued,
@@ -172,6 +396,35 @@ pub struct TaskDetail {
     pub status: TaskStatus,
     pub progress: f64,
     pub failed_message: Option<String>,
+    /// Epoch seconds.
+    #[serde(default)]
+    pub start_time: Option<f64>,
+    /// Epoch seconds.
+    #[serde(default)]
+    pub end_time: Option<f64>,
+}
+
+impl TaskDetail {
+    /// `end_time - start_time`, clamped to zero on clock skew. `None` if either is missing.
+    pub fn elapsed(&self) -> Option<Duration> {
+        let (start, end) = (self.start_time?, self.end_time?);
+        Some(Duration::try_from_secs_f64(end - start).unwrap_or(Duration::ZERO))
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self.status, TaskStatus::Success | TaskStatus::Failed)
+    }
+}
+
+/// Body of the request a task with a `callbackUrl` sends when it finishes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskWebhook {
+    #[serde(rename = "taskID")]
+    pub task_id: String,
+    pub status: TaskStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_message: Option<String>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -221,6 +474,15 @@ struct ApiErrorMessage {
     message: Option<String>,
 }
 
+/// Failures where the request may not have reached the server, or the server may recover.
+fn is_transient(err: &TaskClientError) -> bool {
+    match err {
+        TaskClientError::Http(err) => err.is_connect() || err.is_timeout(),
+        TaskClientError::ApiStatus { status, .. } => status.is_server_error(),
+        _ => false,
+    }
+}
+
 async fn ensure_success(resp: reqwest::Response) -> Result<reqwest::Response> {
     let status = resp.status();
     if status.is_success() {
@@ -254,6 +516,42 @@ mod tests {
         assert_eq!(value["packageName"], "@oomol/pkg");
         assert_eq!(value["packageVersion"], "1.0.0");
         assert_eq!(value["blockName"], "main");
+        assert!(value.get("idempotencyKey").is_none());
+        assert!(value.get("callbackUrl").is_none());
+
+        let payload = CreateTaskRequest::new(
+            "@oomol/pkg".to_string(),
+            "1.0.0".to_string(),
+            "main".to_string(),
+            None,
+        )
+        .with_callback_url("http://127.0.0.1:8080/webhook");
+        let value = serde_json::to_value(payload).expect("payload should serialize");
+        assert_eq!(value["callbackUrl"], "http://127.0.0.1:8080/webhook");
+    }
+
+    #[tokio::test]
+    async fn create_rejects_empty_required_fields_before_sending() {
+        // nothing listens here, so reaching the network would be an http error
+        let client = RemoteJobClient::new("http://127.0.0.1:1");
+        let cases = [
+            ("", "1.0.0", "main", "packageName"),
+            ("@oomol/pkg", "", "main", "packageVersion"),
+            ("@oomol/pkg", "1.0.0", " ", "blockName"),
+        ];
+
+        for (package_name, package_version, block_name, expected) in cases {
+            let payload = CreateTaskRequest::new(
+                package_name.to_string(),
+                package_version.to_string(),
+                block_name.to_string(),
+                None,
+            );
+            match client.create_remote_job(&payload).await {
+                Err(TaskClientError::InvalidRequest { field }) => assert_eq!(field, expected),
+                other => panic!("expected invalid request for {expected}, got {other:?}"),
+            }
+        }
     }
 
     #[test]
@@ -281,4 +579,260 @@ mod tests {
         let result: TaskResult = serde_json::from_value(raw).expect("result should deserialize");
         assert!(matches!(result, TaskResult::Pending));
     }
+
+    fn task_detail(status: TaskStatus, start: Option<f64>, end: Option<f64>) -> TaskDetail {
+        TaskDetail {
+            status,
+            progress: 0.0,
+            failed_message: None,
+            start_time: start,
+            end_time: end,
+        }
+    }
+
+    #[test]
+    fn task_detail_times_deserialize() {
+        let raw = serde_json::json!({
+            "status": "success",
+            "progress": 1.0,
+            "startTime": 100.0,
+            "endTime": 102.5
+        });
+        let detail: TaskDetail = serde_json::from_value(raw).expect("detail should deserialize");
+        assert_eq!(detail.elapsed(), Some(Duration::from_millis(2500)));
+        assert!(detail.is_terminal());
+    }
+
+    #[test]
+    fn task_detail_elapsed_edge_cases() {
+        let detail = task_detail(TaskStatus::Running, Some(100.0), None);
+        assert_eq!(detail.elapsed(), None);
+        assert!(!detail.is_terminal());
+
+        let detail = task_detail(TaskStatus::Queued, None, None);
+        assert_eq!(detail.elapsed(), None);
+
+        // clock skew
+        let detail = task_detail(TaskStatus::Failed, Some(100.0), Some(99.0));
+        assert_eq!(detail.elapsed(), Some(Duration::ZERO));
+        assert!(detail.is_terminal());
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn run_task_cancels_remote_task_when_token_fires() {
+        let server = crate::mock::start(23581);
+        let client = RemoteJobClient::new(server.url());
+        let payload = CreateTaskRequest::new(
+            "@oomol/pkg".to_string(),
+            "1.0.0".to_string(),
+            "main".to_string(),
+            None,
+        );
+
+        let cancel = CancellationToken::new();
+        let trigger = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            trigger.cancel();
+        });
+
+        let options = RunTaskOptions {
+            poll_interval: Duration::from_millis(50),
+            cancel: Some(cancel),
+            ..Default::default()
+        };
+        let task_id = match client.run_task(&payload, options).await {
+            Err(TaskClientError::Cancelled { task_id }) => task_id,
+            other => panic!("expected cancelled error, got {other:?}"),
+        };
+
+        let detail = client.get_task_detail(&task_id).await.unwrap();
+        assert_eq!(detail.status, TaskStatus::Failed);
+        assert_eq!(detail.failed_message.as_deref(), Some("cancelled"));
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn cancelled_run_stays_cancelled_when_the_cancel_request_fails() {
+        let server = crate::mock::start(23586);
+        server.fail_cancels();
+        let client = RemoteJobClient::new(server.url());
+        let receiver = webhook::WebhookReceiver::bind("127.0.0.1:0").await.unwrap();
+        let payload = CreateTaskRequest::new(
+            "@oomol/pkg".to_string(),
+            "1.0.0".to_string(),
+            "main".to_string(),
+            None,
+        );
+        let options = || {
+            let cancel = CancellationToken::new();
+            cancel.cancel();
+            RunTaskOptions {
+                poll_interval: Duration::from_secs(3600),
+                cancel: Some(cancel),
+                ..Default::default()
+            }
+        };
+
+        let polled = client.run_task(&payload, options()).await;
+        assert!(
+            matches!(polled, Err(TaskClientError::Cancelled { .. })),
+            "{polled:?}"
+        );
+        let webhook = client
+            .run_task_with_webhook(&payload, &receiver, Duration::from_secs(30), options())
+            .await;
+        assert!(
+            matches!(webhook, Err(TaskClientError::Cancelled { .. })),
+            "{webhook:?}"
+        );
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn run_task_reports_progress_changes() {
+        let server = crate::mock::start(23582);
+        let client = RemoteJobClient::new(server.url());
+        let payload = CreateTaskRequest::new(
+            "@oomol/pkg".to_string(),
+            "1.0.0".to_string(),
+            "main".to_string(),
+            None,
+        );
+
+        let seen = Arc::new(std::sync::Mutex::new(Vec::<f64>::new()));
+        let seen_clone = Arc::clone(&seen);
+        let options = RunTaskOptions {
+            poll_interval: Duration::from_millis(200),
+            on_progress: Some(Arc::new(move |progress| {
+                seen_clone.lock().unwrap().push(progress);
+            })),
+            ..Default::default()
+        };
+        let result = client.run_task(&payload, options).await.unwrap();
+        assert!(matches!(result, TaskResult::Success { .. }));
+
+        let seen = seen.lock().unwrap();
+        assert!(seen.len() > 1);
+        assert!(seen.windows(2).all(|w| w[0] != w[1]));
+        assert_eq!(seen.last(), Some(&1.0));
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn run_task_completes_via_webhook() {
+        let server = crate::mock::start(23585);
+        let client = RemoteJobClient::new(server.url());
+        let receiver = webhook::WebhookReceiver::bind("127.0.0.1:0").await.unwrap();
+        let payload = CreateTaskRequest::new(
+            "@oomol/pkg".to_string(),
+            "1.0.0".to_string(),
+            "main".to_string(),
+            None,
+        );
+
+        // a poll would only come after an hour
+        let options = RunTaskOptions {
+            poll_interval: Duration::from_secs(3600),
+            ..Default::default()
+        };
+        let result = client
+            .run_task_with_webhook(&payload, &receiver, Duration::from_secs(30), options)
+            .await
+            .unwrap();
+
+        assert!(matches!(result, TaskResult::Success { .. }));
+        // the webhook is confirmed once instead of polled for
+        assert_eq!(server.detail_requests(), 1);
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn webhook_receiver_drops_webhooks_nobody_waits_for() {
+        let receiver = webhook::WebhookReceiver::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_retention(Duration::from_millis(100));
+        let send = |task_id: &str| {
+            let body = serde_json::to_value(TaskWebhook {
+                task_id: task_id.to_string(),
+                status: TaskStatus::Success,
+                failed_message: None,
+            })
+            .unwrap();
+            let url = receiver.callback_url();
+            async move {
+                reqwest::Client::new()
+                    .post(url)
+                    .json(&body)
+                    .send()
+                    .await
+                    .unwrap();
+            }
+        };
+        let short = Duration::from_millis(10);
+
+        send("late").await;
+        assert!(receiver.wait("late", short).await.is_some());
+        // taken by the first wait
+        assert!(receiver.wait("late", short).await.is_none());
+
+        send("stale").await;
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        send("fresh").await;
+        assert!(receiver.wait("stale", short).await.is_none());
+        assert!(receiver.wait("fresh", short).await.is_some());
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn create_retries_with_the_same_idempotency_key() {
+        let server = crate::mock::start(23583);
+        let client = RemoteJobClient::new(server.url());
+        let payload = CreateTaskRequest::new(
+            "@oomol/pkg".to_string(),
+            "1.0.0".to_string(),
+            "main".to_string(),
+            None,
+        );
+
+        server.fail_next_creates(1);
+        client.create_remote_job(&payload).await.unwrap();
+
+        let keys = server.create_keys();
+        assert_eq!(keys.len(), 2);
+        assert!(keys[0].is_some());
+        assert_eq!(keys[0], keys[1]);
+        assert_eq!(server.task_count(), 1);
+
+        // every call gets its own key
+        client.create_remote_job(&payload).await.unwrap();
+        assert_ne!(server.create_keys()[2], keys[0]);
+        assert_eq!(server.task_count(), 2);
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn create_sends_caller_idempotency_key() {
+        let server = crate::mock::start(23584);
+        let client = RemoteJobClient::new(server.url());
+        let payload = CreateTaskRequest::new(
+            "@oomol/pkg".to_string(),
+            "1.0.0".to_string(),
+            "main".to_string(),
+            None,
+        )
+        .with_idempotency_key("job-1");
+
+        let first = client.create_remote_job(&payload).await.unwrap();
+        let second = client.create_remote_job(&payload).await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(
+            server.create_keys(),
+            vec![Some("job-1".to_string()), Some("job-1".to_string())]
+        );
+        assert_eq!(server.task_count(), 1);
+    }
 }
@@ -10,7 +10,7 @@ use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use axum::extract::{Path, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
 use axum::routing::{get, post};
 use axum::{Json, Router};
@@ -24,15 +24,34 @@ struct Task {
     #[allow(dead_code)]
     input_values: Option<JsonMap>,
     created_at: Instant,
+    cancelled: bool,
 }
 
-type Tasks = Arc<Mutex<HashMap<String, Task>>>;
+#[derive(Default)]
+struct MockState {
+    tasks: HashMap<String, Task>,
+    /// Idempotency key -> task id, a repeated key returns the task created first.
+    idempotency_keys: HashMap<String, String>,
+    /// `Idempotency-Key` header of every create request, in arrival order.
+    create_keys: Vec<Option<String>>,
+    /// Create requests that still create the task but answer 503, like a lost response.
+    failing_creates: usize,
+    /// Task detail requests received so far, i.e. polls.
+    detail_requests: usize,
+    /// Cancel requests answer 503 and leave the task running.
+    failing_cancels: bool,
+}
+
+type Tasks = Arc<Mutex<MockState>>;
 
 // Thresholds for automatic state progression (seconds since creation).
 const RUNNING_AFTER: u64 = 2;
 const SUCCESS_AFTER: u64 = 4;
 
 fn current_status(task: &Task) -> &'static str {
+    if task.cancelled {
+        return "failed";
+    }
     let elapsed = task.created_at.elapsed().as_secs();
     if elapsed >= SUCCESS_AFTER {
         "success"
@@ -56,6 +75,8 @@ struct CreateTaskRequest {
     task_type: String,
     #[serde(rename = "inputValues")]
     input_values: Option<JsonMap>,
+    #[serde(rename = "callbackUrl")]
+    callback_url: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -84,30 +105,104 @@ struct TaskDetailResponse {
 
 async fn create_task(
     State(tasks): State<Tasks>,
+    headers: HeaderMap,
     Json(body): Json<CreateTaskRequest>,
 ) -> impl IntoResponse {
-    let task_id = uuid::Uuid::new_v4().to_string();
+    let key = headers
+        .get("idempotency-key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_owned);
+
+    let mut guard = tasks.lock().unwrap();
+    guard.create_keys.push(key.clone());
+
+    let existing = key
+        .as_ref()
+        .and_then(|key| guard.idempotency_keys.get(key))
+        .cloned();
+    let task_id = match existing {
+        Some(task_id) => {
+            eprintln!("[mock] POST /tasks -> {task_id} (idempotent replay)");
+            task_id
+        }
+        None => {
+            let task_id = uuid::Uuid::new_v4().to_string();
+            eprintln!("[mock] POST /tasks -> {task_id} (type={})", body.task_type);
+            guard.tasks.insert(
+                task_id.clone(),
+                Task {
+                    task_type: body.task_type,
+                    input_values: body.input_values,
+                    created_at: Instant::now(),
+                    cancelled: false,
+                },
+            );
+            if let Some(key) = key {
+                guard.idempotency_keys.insert(key, task_id.clone());
+            }
+            if let Some(callback_url) = body.callback_url {
+                tokio::spawn(send_webhook(
+                    Arc::clone(&tasks),
+                    task_id.clone(),
+                    callback_url,
+                ));
+            }
+            task_id
+        }
+    };
+
+    if guard.failing_creates > 0 {
+        guard.failing_creates -= 1;
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "message": "simulated lost response" })),
+        );
+    }
 
-    eprintln!("[mock] POST /tasks -> {task_id} (type={})", body.task_type);
+    (
+        StatusCode::OK,
+        Json(serde_json::to_value(CreateTaskResponse { task_id }).unwrap()),
+    )
+}
 
-    tasks.lock().unwrap().insert(
-        task_id.clone(),
-        Task {
-            task_type: body.task_type,
-            input_values: body.input_values,
-            created_at: Instant::now(),
-        },
-    );
+/// Post the completion webhook once the task finished, like the server does for a task created
+/// with a `callbackUrl`.
+async fn send_webhook(tasks: Tasks, task_id: String, callback_url: String) {
+    loop {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let status = match tasks.lock().unwrap().tasks.get(&task_id) {
+            Some(task) => current_status(task),
+            None => return,
+        };
+        if status != "success" && status != "failed" {
+            continue;
+        }
 
-    (StatusCode::OK, Json(CreateTaskResponse { task_id }))
+        eprintln!("[mock] POST {callback_url} -> task {task_id} status={status}");
+        let body = serde_json::json!({
+            "taskID": &task_id,
+            "status": status,
+            "failedMessage": (status == "failed").then_some("cancelled"),
+        });
+        let sent = reqwest::Client::new()
+            .post(&callback_url)
+            .json(&body)
+            .send()
+            .await;
+        if let Err(e) = sent {
+            eprintln!("[mock] webhook for task {task_id} failed: {e}");
+        }
+        return;
+    }
 }
 
 async fn get_task_detail(
     State(tasks): State<Tasks>,
     Path(task_id): Path<String>,
 ) -> impl IntoResponse {
-    let guard = tasks.lock().unwrap();
-    let Some(task) = guard.get(&task_id) else {
+    let mut guard = tasks.lock().unwrap();
+    guard.detail_requests += 1;
+    let Some(task) = guard.tasks.get(&task_id) else {
         return (
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({ "message": format!("task {task_id} not found") })),
@@ -125,18 +220,41 @@ async fn get_task_detail(
         created_at: 0.0,
         start_time: if status != "queued" { Some(0.0) } else { None },
         end_time: if status == "success" { Some(0.0) } else { None },
-        failed_message: None,
+        failed_message: task.cancelled.then(|| "cancelled".to_owned()),
     };
 
     (StatusCode::OK, Json(serde_json::to_value(resp).unwrap()))
 }
 
+async fn cancel_task(State(tasks): State<Tasks>, Path(task_id): Path<String>) -> impl IntoResponse {
+    let mut guard = tasks.lock().unwrap();
+    let failing = guard.failing_cancels;
+    let Some(task) = guard.tasks.get_mut(&task_id) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "message": format!("task {task_id} not found") })),
+        );
+    };
+
+    if failing {
+        eprintln!("[mock] DELETE /tasks/{task_id} -> 503 (failing)");
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(serde_json::json!({ "message": "simulated failed cancel" })),
+        );
+    }
+    eprintln!("[mock] DELETE /tasks/{task_id}");
+    task.cancelled = true;
+
+    (StatusCode::OK, Json(serde_json::json!({})))
+}
+
 async fn get_task_result(
     State(tasks): State<Tasks>,
     Path(task_id): Path<String>,
 ) -> impl IntoResponse {
     let guard = tasks.lock().unwrap();
-    let Some(task) = guard.get(&task_id) else {
+    let Some(task) = guard.tasks.get(&task_id) else {
         return (
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({ "message": format!("task {task_id} not found") })),
@@ -174,7 +292,7 @@ async fn get_task_logs(
     axum::extract::Query(params): axum::extract::Query<HashMap<String, String>>,
 ) -> impl IntoResponse {
     let guard = tasks.lock().unwrap();
-    let Some(task) = guard.get(&task_id) else {
+    let Some(task) = guard.tasks.get(&task_id) else {
         return (
             StatusCode::NOT_FOUND,
             Json(serde_json::json!({ "logs": [] })),
@@ -230,6 +348,7 @@ async fn get_task_logs(
 pub struct MockServer {
     shutdown: Option<tokio::sync::oneshot::Sender<()>>,
     thread: Option<std::thread::JoinHandle<()>>,
+    state: Tasks,
     pub port: u16,
 }
 
@@ -237,6 +356,31 @@ impl MockServer {
     pub fn url(&self) -> String {
         format!("http://127.0.0.1:{}", self.port)
     }
+
+    /// Make the next `count` create requests answer 503 after the task is created,
+    /// as if the response got lost on the way back.
+    pub fn fail_next_creates(&self, count: usize) {
+        self.state.lock().unwrap().failing_creates = count;
+    }
+
+    /// Make cancel requests answer 503 without cancelling the task.
+    pub fn fail_cancels(&self) {
+        self.state.lock().unwrap().failing_cancels = true;
+    }
+
+    /// `Idempotency-Key` header of every create request received so far.
+    pub fn create_keys(&self) -> Vec<Option<String>> {
+        self.state.lock().unwrap().create_keys.clone()
+    }
+
+    pub fn task_count(&self) -> usize {
+        self.state.lock().unwrap().tasks.len()
+    }
+
+    /// Task detail requests received so far, i.e. how often tasks were polled.
+    pub fn detail_requests(&self) -> usize {
+        self.state.lock().unwrap().detail_requests
+    }
 }
 
 impl Drop for MockServer {
@@ -258,15 +402,18 @@ impl Drop for MockServer {
 /// Panics if the server does not become ready within 10 seconds.
 pub fn start(port: u16) -> MockServer {
     let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    let state: Tasks = Arc::default();
+    let tasks = Arc::clone(&state);
 
     let thread = std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().expect("failed to create tokio runtime");
         rt.block_on(async move {
-            let tasks: Tasks = Arc::new(Mutex::new(HashMap::new()));
-
             let app = Router::new()
                 .route("/v3/users/me/tasks", post(create_task))
-                .route("/v3/users/me/tasks/{task_id}", get(get_task_detail))
+                .route(
+                    "/v3/users/me/tasks/{task_id}",
+                    get(get_task_detail).delete(cancel_task),
+                )
                 .route("/v3/users/me/tasks/{task_id}/result", get(get_task_result))
                 .route("/v3/users/me/tasks/{task_id}/logs", get(get_task_logs))
                 .with_state(tasks);
@@ -300,6 +447,7 @@ pub fn start(port: u16) -> MockServer {
     MockServer {
         shutdown: Some(shutdown_tx),
         thread: Some(thread),
+        state,
         port,
     }
 }
@@ -0,0 +1,136 @@
+//! Receiver for the completion webhooks of tasks created with a callback URL.
+//!
+//! Enabled by the `webhook` feature. Serves an axum HTTP endpoint on the current tokio runtime,
+//! see [`RemoteJobClient::run_task_with_webhook`](crate::RemoteJobClient::run_task_with_webhook).
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::routing::post;
+use axum::{Json, Router};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use tokio::sync::{oneshot, watch};
+
+use crate::TaskWebhook;
+
+const WEBHOOK_PATH: &str = "/webhook";
+const DEFAULT_RETENTION: Duration = Duration::from_secs(600);
+
+/// Task id -> when the webhook of the task arrived and the webhook, kept until someone waits for
+/// it or it's older than the retention.
+type Events = Arc<watch::Sender<HashMap<String, (Instant, TaskWebhook)>>>;
+
+/// Listens for task webhooks until dropped.
+pub struct WebhookReceiver {
+    local_addr: SocketAddr,
+    public_url: Option<String>,
+    events: Events,
+    retention: Arc<Mutex<Duration>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+#[derive(Clone)]
+struct ReceiverState {
+    events: Events,
+    retention: Arc<Mutex<Duration>>,
+}
+
+async fn receive_webhook(
+    State(state): State<ReceiverState>,
+    Json(event): Json<TaskWebhook>,
+) -> StatusCode {
+    let retention = *state.retention.lock().unwrap();
+    state.events.send_modify(|events| {
+        // webhooks of tasks nobody waited for
+        events.retain(|_, (received, _)| received.elapsed() < retention);
+        events.insert(event.task_id.clone(), (Instant::now(), event));
+    });
+    StatusCode::OK
+}
+
+impl WebhookReceiver {
+    /// Listen on `addr`, port 0 picks a free port.
+    pub async fn bind(addr: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let events: Events = Arc::new(watch::Sender::new(HashMap::new()));
+        let retention = Arc::new(Mutex::new(DEFAULT_RETENTION));
+
+        let app = Router::new()
+            .route(WEBHOOK_PATH, post(receive_webhook))
+            .with_state(ReceiverState {
+                events: Arc::clone(&events),
+                retention: Arc::clone(&retention),
+            });
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    let _ = shutdown_rx.await;
+                })
+                .await;
+        });
+
+        Ok(Self {
+            local_addr,
+            public_url: None,
+            events,
+            retention,
+            shutdown: Some(shutdown_tx),
+        })
+    }
+
+    /// Base URL the server reaches this receiver at, when it isn't the address it listens on,
+    /// e.g. behind a proxy.
+    pub fn with_public_url(mut self, url: impl Into<String>) -> Self {
+        self.public_url = Some(url.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    /// How long the webhook of a task nobody waits for is kept, 10 minutes by default.
+    pub fn with_retention(self, retention: Duration) -> Self {
+        *self.retention.lock().unwrap() = retention;
+        self
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// URL to create tasks with, see [`CreateTaskRequest::callback_url`](crate::CreateTaskRequest::callback_url).
+    pub fn callback_url(&self) -> String {
+        match &self.public_url {
+            Some(url) => format!("{url}{WEBHOOK_PATH}"),
+            None => format!("http://{}{WEBHOOK_PATH}", self.local_addr),
+        }
+    }
+
+    /// Wait for the webhook of `task_id`, `None` when it doesn't arrive within `timeout`. A
+    /// webhook that arrived before the call is returned right away. Either way the webhook of
+    /// `task_id` is no longer kept.
+    pub async fn wait(&self, task_id: &str, timeout: Duration) -> Option<TaskWebhook> {
+        let mut rx = self.events.subscribe();
+        let _ =
+            tokio::time::timeout(timeout, rx.wait_for(|events| events.contains_key(task_id))).await;
+        drop(rx);
+
+        let mut event = None;
+        self.events.send_if_modified(|events| {
+            event = events.remove(task_id);
+            false
+        });
+        event.map(|(_, event)| event)
+    }
+}
+
+impl Drop for WebhookReceiver {
+    fn drop(&mut self) {
+        if let Some(tx) = self.shutdown.take() {
+            let _ = tx.send(());
+        }
+    }
+}
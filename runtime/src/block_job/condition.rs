@@ -52,8 +52,8 @@ pub fn execute_condition_job(params: ConditionJobParameters) -> Option<BlockJobH
 
     let output_handle = condition_block.evaluate(&inputs_values);
     if output_handle.is_none() {
-        block_status.finish(job_id.clone(), None, None, None);
-        reporter.finished(None, None);
+        block_status.finish(job_id.clone(), None, None, None, None);
+        reporter.finished(None, None, None);
         return None;
     }
     let output_handle = output_handle.unwrap();
@@ -70,16 +70,16 @@ pub fn execute_condition_job(params: ConditionJobParameters) -> Option<BlockJobH
 
     if let Some(output) = output_value {
         let result = HashMap::from([(output_handle, output)]);
-        block_status.finish(job_id.clone(), Some(result.clone()), None, None);
+        block_status.finish(job_id.clone(), Some(result.clone()), None, None, None);
 
         let result_map = result
             .iter()
             .map(|(k, v)| (k.to_string(), v.value.clone()))
             .collect();
-        reporter.finished(Some(result_map), None);
+        reporter.finished(Some(result_map), None, None);
     } else {
-        block_status.finish(job_id.clone(), None, None, None);
-        reporter.finished(None, None);
+        block_status.finish(job_id.clone(), None, None, None, None);
+        reporter.finished(None, None, None);
     }
 
     Some(BlockJobHandle::new(ConditionJobHandle {}))
@@ -3,7 +3,7 @@ use std::{collections::HashMap, sync::Arc};
 use job::{BlockInputs, BlockJobStacks, JobId, RuntimeScope};
 use mainframe::{
     reporter::BlockReporterTx,
-    scheduler::{self, ExecutorParams, SchedulerTx, ServiceParams},
+    scheduler::{self, ErrorKind, ExecutorParams, SchedulerTx, ServiceParams},
 };
 use manifest_meta::{
     HandleName, InjectionStore, InputDefPatchMap, InputHandles, OutputHandles,
@@ -16,6 +16,7 @@ use utils::output::{
 };
 
 use crate::block_status::BlockStatusTx;
+use crate::output_cache::OutputCacheEntry;
 
 #[derive(Debug)]
 pub struct ServiceExecutorPayload {
@@ -41,9 +42,11 @@ pub struct ListenerParameters {
     pub scope: RuntimeScope,
     pub injection_store: Option<InjectionStore>,
     pub flow_path: Option<String>,
+    /// Where the outputs of a successful run are saved, `None` when they aren't cached.
+    pub output_cache: Option<OutputCacheEntry>,
 }
 
-fn is_json_serializable(
+pub(crate) fn is_json_serializable(
     handle: &HandleName,
     value: &Value,
     outputs_def: &Option<OutputHandles>,
@@ -78,6 +81,25 @@ fn is_json_serializable(
         .unwrap_or(true)
 }
 
+/// Outputs that only live in the executor (var, bin, secret) can't be cached, one of them drops
+/// the whole run from the cache.
+fn record_outputs(
+    emitted: &mut Option<HashMap<HandleName, Value>>,
+    outputs: HashMap<HandleName, Value>,
+    outputs_def: &Option<OutputHandles>,
+) {
+    let Some(map) = emitted else {
+        return;
+    };
+    for (handle, value) in outputs {
+        if !is_json_serializable(&handle, &value, outputs_def) {
+            *emitted = None;
+            return;
+        }
+        map.insert(handle, value);
+    }
+}
+
 pub fn listen_to_worker(params: ListenerParameters) -> tokio::task::JoinHandle<()> {
     let block_scope = params.scheduler_tx.calculate_scope(&params.scope);
     let (job_tx, job_rx) = flume::unbounded::<scheduler::ReceiveMessage>();
@@ -110,6 +132,7 @@ fn listen_to_worker_messages(
         scope,
         injection_store,
         flow_path,
+        output_cache,
     } = params;
 
     tokio::spawn(async move {
@@ -142,6 +165,9 @@ fn listen_to_worker_messages(
             }
         };
         let mut has_executor_response = false;
+        // outputs emitted so far, saved to the output cache when the block succeeds
+        let mut emitted_outputs: Option<HashMap<HandleName, Value>> =
+            output_cache.as_ref().map(|_| HashMap::new());
         while let Ok(message) = job_rx.recv_async().await {
             match message {
                 scheduler::ReceiveMessage::ExecutorReady {
@@ -201,6 +227,15 @@ fn listen_to_worker_messages(
                 } => {
                     debug!("executor {executor_name} exited with code {code}, reason: {reason:?}");
                 }
+                scheduler::ReceiveMessage::ExecutorRestart {
+                    executor_name,
+                    code,
+                    attempt,
+                    ..
+                } => {
+                    has_executor_response = false;
+                    reporter.executor_restarted(&executor_name, code, attempt);
+                }
                 scheduler::ReceiveMessage::ExecutorTimeout {
                     executor_name,
                     package,
@@ -221,7 +256,7 @@ fn listen_to_worker_messages(
                     }
 
                     debug!(
-                        "Executor {executor_name} identifier {identifier:?} for package {package:?} timeout after 5s"
+                        "Executor {executor_name} identifier {identifier:?} for package {package:?} timed out before it was ready"
                     );
                 }
                 scheduler::ReceiveMessage::BlockReady { job_id, .. } => {
@@ -230,7 +265,12 @@ fn listen_to_worker_messages(
                         job_id: job_id.to_owned(),
                         block_path: block_path.clone(),
                         stacks: stacks.vec().clone(),
-                        inputs: inputs.take().clone(),
+                        // kept when a restarted executor may ask for the inputs again
+                        inputs: if scheduler_tx.restarts_executors() {
+                            inputs.clone()
+                        } else {
+                            inputs.take()
+                        },
                         inputs_def: inputs_def.clone(),
                         inputs_def_patch: inputs_def_patch.clone(),
                     });
@@ -242,7 +282,7 @@ fn listen_to_worker_messages(
                         continue;
                     }
                     warn!(
-                        "listener wait timeout 3s. job_id: {msg_job_id}. try to run block again, executor will filter duplicate job_id"
+                        "listener wait timeout. job_id: {msg_job_id}. try to run block again, executor will filter duplicate job_id"
                     );
                     run_block(executor.as_ref(), service.as_ref());
                 }
@@ -267,6 +307,7 @@ fn listen_to_worker_messages(
                     }
                     block_status.outputs(job_id, output_map);
                     reporter.outputs(&reporter_map);
+                    record_outputs(&mut emitted_outputs, outputs, &outputs_def);
                 }
                 scheduler::ReceiveMessage::BlockOutput {
                     output: value,
@@ -278,6 +319,11 @@ fn listen_to_worker_messages(
                     reporter.output(&value, &handle);
 
                     let cacheable = is_json_serializable(&handle, &value, &outputs_def);
+                    record_outputs(
+                        &mut emitted_outputs,
+                        HashMap::from([(handle.clone(), value.clone())]),
+                        &outputs_def,
+                    );
 
                     block_status.output(
                         job_id,
@@ -289,15 +335,50 @@ fn listen_to_worker_messages(
                         options,
                     );
                 }
+                scheduler::ReceiveMessage::BlockOutputChunk {
+                    job_id,
+                    handle,
+                    chunk_index,
+                    chunk_total,
+                    data,
+                    options,
+                    ..
+                } => {
+                    // the pieces are only put back together in the flow, a chunked output drops
+                    // the run from the cache
+                    emitted_outputs = None;
+                    block_status.output_chunk(
+                        job_id,
+                        handle,
+                        chunk_index,
+                        chunk_total,
+                        data,
+                        options,
+                    );
+                }
                 scheduler::ReceiveMessage::BlockFinished {
                     result,
                     error,
+                    error_kind,
+                    exit_code,
                     job_id,
                     ..
                 } => {
+                    if error.is_none() {
+                        if let Some(result) = &result {
+                            record_outputs(&mut emitted_outputs, result.clone(), &outputs_def);
+                        }
+                        if let (Some(entry), Some(outputs)) =
+                            (&output_cache, emitted_outputs.take())
+                        {
+                            entry.store(outputs);
+                        }
+                    }
+
                     if let Some(error) = error {
-                        block_status.finish(job_id, None, Some(error.clone()), None);
-                        reporter.finished(None, Some(error));
+                        let error_kind = Some(error_kind.unwrap_or(ErrorKind::UserError));
+                        block_status.finish(job_id, None, Some(error.clone()), error_kind, None);
+                        reporter.finished_with_exit_code(None, Some(error), error_kind, exit_code);
                         continue;
                     }
 
@@ -318,15 +399,26 @@ fn listen_to_worker_messages(
                             );
                             reporter_map.insert(key.to_string(), value.clone());
                         }
-                        reporter.finished(Some(reporter_map), None);
-                        block_status.finish(job_id, Some(output_map), None, None);
+                        reporter.finished(Some(reporter_map), None, None);
+                        block_status.finish(job_id, Some(output_map), None, None, None);
                     } else {
-                        reporter.finished(None, None);
-                        block_status.finish(job_id, None, None, None);
+                        reporter.finished(None, None, None);
+                        block_status.finish(job_id, None, None, None, None);
                     }
                 }
-                scheduler::ReceiveMessage::BlockError { error, .. } => {
-                    reporter.error(&error);
+                scheduler::ReceiveMessage::BlockError {
+                    error, error_kind, ..
+                } => {
+                    reporter.error(&error, error_kind.unwrap_or(ErrorKind::UserError));
+                }
+                scheduler::ReceiveMessage::AbortJob { job_id, .. } => {
+                    warn!("job {job_id} aborted by controller");
+                    reporter.finished(
+                        None,
+                        Some("Job aborted".to_owned()),
+                        Some(ErrorKind::Cancelled),
+                    );
+                    block_status.abort_job(job_id);
                 }
                 scheduler::ReceiveMessage::BlockRequest(request) => {
                     // Handle block preview request
@@ -385,6 +477,8 @@ mod tests {
 
         async fn run_service_block(&self, _executor_name: &str, _data: MessageData) {}
 
+        async fn soft_cancel(&self, _executor_name: &str, _data: MessageData) {}
+
         async fn disconnect(&self) {}
     }
 
@@ -427,6 +521,7 @@ mod tests {
             node_id: None,
             is_inject: false,
             enable_layer: false,
+            data_dir_readonly: false,
         }
     }
 
@@ -438,9 +533,20 @@ mod tests {
             pass_through_env_keys: vec![],
             bind_paths: vec![],
             env_file: None,
+            env_vars: Default::default(),
+            secrets_file: None,
             tmp_dir: std::env::temp_dir(),
             debug: false,
             wait_for_client: false,
+            max_log_line_bytes: scheduler::DEFAULT_MAX_LOG_LINE_BYTES,
+            max_log_bytes: None,
+            per_executor_log_dir: None,
+            executor_log_reporter: None,
+            spawn_timeout: mainframe::scheduler::DEFAULT_EXECUTOR_SPAWN_TIMEOUT,
+            ready_timeout: mainframe::scheduler::DEFAULT_EXECUTOR_READY_TIMEOUT,
+            listener_timeout: mainframe::scheduler::DEFAULT_LISTENER_TIMEOUT,
+            restart_policy: Default::default(),
+            extra_args: vec![],
         }
     }
 
@@ -499,11 +605,13 @@ mod tests {
                 job_id: done_job_id,
                 result,
                 error,
+                error_kind,
                 error_detail,
             } => {
                 assert_eq!(done_job_id, *job_id);
                 assert!(result.is_none());
                 assert_eq!(error.as_deref(), Some(expected_error));
+                assert_eq!(error_kind, Some(ErrorKind::ExecutorExit));
                 assert!(error_detail.is_none());
             }
             _ => panic!("expected done error status"),
@@ -547,6 +655,7 @@ mod tests {
             scope,
             injection_store: None,
             flow_path: None,
+            output_cache: None,
         });
 
         wait_for_listener_subscription(&worker_tx, &block_status_rx, &session_id, &job_id).await;
@@ -558,6 +667,8 @@ mod tests {
                 job_id: job_id.clone(),
                 result: None,
                 error: Some("Executor python-executor exit with code 1".to_string()),
+                error_kind: Some(ErrorKind::ExecutorExit),
+                exit_code: None,
             },
         )
         .await;
@@ -574,6 +685,72 @@ mod tests {
         listener_handle.await.unwrap();
     }
 
+    #[tokio::test]
+    async fn listener_forwards_abort_job_from_scheduler() {
+        let session_id = SessionId::random();
+        let job_id = JobId::random();
+        let scope = test_scope(session_id.clone());
+        let (worker_tx, worker_rx) = flume::unbounded();
+        let (scheduler_tx, scheduler_rx) = scheduler::create(
+            NoopSchedulerTx,
+            ChannelSchedulerRx { rx: worker_rx },
+            None,
+            None,
+            test_executor_payload(session_id.clone()),
+            scope.data_dir.clone(),
+        );
+        let scheduler_handle = scheduler_rx.event_loop();
+
+        let (reporter_tx, _reporter_rx) =
+            reporter::create::<NoopReporterTx, NoopReporterRx>(session_id.clone(), None, None);
+        let reporter = Arc::new(reporter_tx.block(job_id.clone(), None, BlockJobStacks::new()));
+        let (block_status_tx, block_status_rx) = crate::block_status::create();
+        let listener_handle = listen_to_worker(ListenerParameters {
+            job_id: job_id.clone(),
+            block_path: None,
+            stacks: BlockJobStacks::new(),
+            scheduler_tx: scheduler_tx.clone(),
+            inputs: None,
+            outputs_def: None,
+            inputs_def: None,
+            inputs_def_patch: None,
+            block_status: block_status_tx,
+            reporter,
+            executor: None,
+            service: None,
+            block_dir: scope.data_dir.clone(),
+            scope,
+            injection_store: None,
+            flow_path: None,
+            output_cache: None,
+        });
+
+        wait_for_listener_subscription(&worker_tx, &block_status_rx, &session_id, &job_id).await;
+
+        send_worker_message(
+            &worker_tx,
+            scheduler::ReceiveMessage::AbortJob {
+                session_id: session_id.clone(),
+                job_id: job_id.clone(),
+            },
+        )
+        .await;
+
+        match timeout(Duration::from_secs(1), block_status_rx.recv())
+            .await
+            .expect("listener should emit block status")
+        {
+            Some(crate::block_status::Status::AbortJob {
+                job_id: aborted_job_id,
+            }) => assert_eq!(aborted_job_id, job_id),
+            _ => panic!("expected abort job status"),
+        }
+
+        scheduler_tx.abort();
+        scheduler_handle.await.unwrap();
+        listener_handle.await.unwrap();
+    }
+
     #[tokio::test]
     async fn listener_waits_for_block_finished_after_executor_lifecycle_messages() {
         let session_id = SessionId::random();
@@ -613,6 +790,7 @@ mod tests {
                 scope: scope.clone(),
                 injection_store: None,
                 flow_path: None,
+                output_cache: None,
             },
             block_scope,
             job_rx,
@@ -650,6 +828,8 @@ mod tests {
                 job_id: job_id.clone(),
                 result: None,
                 error: Some("Executor python-executor exit with code 1".to_string()),
+                error_kind: Some(ErrorKind::ExecutorExit),
+                exit_code: None,
             })
             .await
             .unwrap();
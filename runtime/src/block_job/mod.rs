@@ -9,6 +9,7 @@ mod task_job;
 pub use condition::{ConditionJobParameters, execute_condition_job};
 pub use input::{fulfill_nullable_and_default, validate_inputs};
 pub use job_handle::BlockJobHandle;
+pub(crate) use listener::is_json_serializable;
 pub use remote_block_job::{RemoteBlockJobParameters, execute_remote_block_job};
 pub use service_job::{ServiceJobParameters, execute_service_job};
 pub use task_job::{TaskJobParameters, block_dir, execute_task_job};
@@ -2,9 +2,9 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use job::{BlockInputs, BlockJobStacks, JobId};
-use mainframe::reporter::BlockReporterTx;
+use mainframe::{reporter::BlockReporterTx, scheduler::ErrorKind};
 use manifest_meta::{HandleName, TaskBlock};
-use remote_job_client::{CreateTaskRequest, RemoteJobClient, TaskStatus};
+use remote_job_client::{CancellationToken, CreateTaskRequest, RemoteJobClient, TaskStatus};
 use tracing::warn;
 use utils::output::OutputValue;
 
@@ -23,12 +23,19 @@ pub struct RemoteBlockJobParameters {
 }
 
 struct RemoteBlockJobHandle {
-    spawn_handle: tokio::task::JoinHandle<()>,
+    shared: Arc<Shared>,
+    cancel: CancellationToken,
+    spawn_handle: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl Drop for RemoteBlockJobHandle {
     fn drop(&mut self) {
-        self.spawn_handle.abort();
+        // the spawned task sends the remote cancel on its way out before the delayed abort,
+        // otherwise the remote compute keeps running
+        self.cancel.cancel();
+        if let Some(handle) = self.spawn_handle.take() {
+            self.shared.delay_abort_tx.send(vec![handle]);
+        }
     }
 }
 
@@ -95,6 +102,7 @@ async fn poll_logs(
                     // side triggers session teardown (exit(0)).
                     let mut should_finish = false;
                     let mut finish_error: Option<String> = None;
+                    let mut finish_error_kind: Option<ErrorKind> = None;
                     let mut finish_result: Option<HashMap<HandleName, Arc<OutputValue>>> = None;
                     if !has_remote_stacks {
                         match msg_type {
@@ -131,6 +139,12 @@ async fn poll_logs(
                                         .get("error")
                                         .and_then(|e| e.as_str())
                                         .map(|s| s.to_owned());
+                                    // a remote error without a kind is the block's own failure
+                                    finish_error_kind = finish_error.as_ref().map(|_| {
+                                        item.get("error_kind")
+                                            .and_then(|k| serde_json::from_value(k.clone()).ok())
+                                            .unwrap_or(ErrorKind::UserError)
+                                    });
                                     finish_result =
                                         item.get("result").and_then(|r| r.as_object()).map(|obj| {
                                             obj.iter()
@@ -165,6 +179,7 @@ async fn poll_logs(
                             ctx.job_id.clone(),
                             finish_result,
                             finish_error,
+                            finish_error_kind,
                             None,
                         );
                         *finished = true;
@@ -247,6 +262,7 @@ pub fn execute_remote_block_job(params: RemoteBlockJobParameters) -> Option<Bloc
                      Set --remote-block-url or OOCANA_REMOTE_BLOCK_URL."
                         .to_owned(),
                 ),
+                Some(ErrorKind::SchedulerError),
                 None,
             );
             return None;
@@ -264,8 +280,14 @@ pub fn execute_remote_block_job(params: RemoteBlockJobParameters) -> Option<Bloc
     let (package_name, package_version, block_name) = match infer_remote_params(&task_block) {
         Ok(p) => p,
         Err(err) => {
-            reporter.finished(None, Some(err.clone()));
-            block_status.finish(job_id, None, Some(err), None);
+            reporter.finished(None, Some(err.clone()), Some(ErrorKind::SchedulerError));
+            block_status.finish(
+                job_id,
+                None,
+                Some(err),
+                Some(ErrorKind::SchedulerError),
+                None,
+            );
             return None;
         }
     };
@@ -299,6 +321,8 @@ pub fn execute_remote_block_job(params: RemoteBlockJobParameters) -> Option<Bloc
     let reporter_clone = Arc::clone(&reporter);
     let block_status_clone = block_status;
     let job_id_clone = job_id;
+    let cancel = CancellationToken::new();
+    let cancel_clone = cancel.clone();
 
     let spawn_handle = tokio::spawn(async move {
         // 1. Create remote task
@@ -306,8 +330,14 @@ pub fn execute_remote_block_job(params: RemoteBlockJobParameters) -> Option<Bloc
             Ok(id) => id,
             Err(e) => {
                 let msg = format!("Failed to create remote task: {e}");
-                reporter_clone.finished(None, Some(msg.clone()));
-                block_status_clone.finish(job_id_clone, None, Some(msg), None);
+                reporter_clone.finished(None, Some(msg.clone()), Some(ErrorKind::SchedulerError));
+                block_status_clone.finish(
+                    job_id_clone,
+                    None,
+                    Some(msg),
+                    Some(ErrorKind::SchedulerError),
+                    None,
+                );
                 return;
             }
         };
@@ -331,19 +361,38 @@ pub fn execute_remote_block_job(params: RemoteBlockJobParameters) -> Option<Bloc
         let mut is_first_poll = true;
 
         loop {
-            tokio::time::sleep(if is_first_poll {
+            let delay = if is_first_poll {
                 is_first_poll = false;
                 first_poll_delay
             } else {
                 poll_interval
-            })
-            .await;
+            };
+            tokio::select! {
+                _ = cancel_clone.cancelled() => {
+                    // not part of this task, the delayed abort would cut the request short
+                    if !finished {
+                        tokio::spawn(async move {
+                            if let Err(e) = client.cancel_task(&task_id).await {
+                                warn!("Failed to cancel remote task {task_id}: {e}");
+                            }
+                        });
+                    }
+                    return;
+                }
+                _ = tokio::time::sleep(delay) => {}
+            }
 
             if let Some(dl) = deadline {
                 if tokio::time::Instant::now() >= dl {
                     let msg = format!("Remote task {task_id} timed out after {timeout_secs}s");
-                    reporter_clone.finished(None, Some(msg.clone()));
-                    block_status_clone.finish(job_id_clone, None, Some(msg), None);
+                    reporter_clone.finished(None, Some(msg.clone()), Some(ErrorKind::Timeout));
+                    block_status_clone.finish(
+                        job_id_clone,
+                        None,
+                        Some(msg),
+                        Some(ErrorKind::Timeout),
+                        None,
+                    );
                     return;
                 }
             }
@@ -366,8 +415,18 @@ pub fn execute_remote_block_job(params: RemoteBlockJobParameters) -> Option<Bloc
                             "Remote task {task_id} polling failed after \
                              {MAX_CONSECUTIVE_POLL_ERRORS} consecutive errors: {e}"
                         );
-                        reporter_clone.finished(None, Some(msg.clone()));
-                        block_status_clone.finish(job_id_clone, None, Some(msg), None);
+                        reporter_clone.finished(
+                            None,
+                            Some(msg.clone()),
+                            Some(ErrorKind::SchedulerError),
+                        );
+                        block_status_clone.finish(
+                            job_id_clone,
+                            None,
+                            Some(msg),
+                            Some(ErrorKind::SchedulerError),
+                            None,
+                        );
                         return;
                     }
                     continue;
@@ -437,8 +496,9 @@ pub fn execute_remote_block_job(params: RemoteBlockJobParameters) -> Option<Bloc
                             _ => None,
                         };
 
-                        reporter_clone.finished(None, error.clone());
-                        block_status_clone.finish(job_id_clone, result, error, None);
+                        let error_kind = error.as_ref().map(|_| ErrorKind::UserError);
+                        reporter_clone.finished(None, error.clone(), error_kind);
+                        block_status_clone.finish(job_id_clone, result, error, error_kind, None);
                     }
                     return;
                 }
@@ -447,7 +507,11 @@ pub fn execute_remote_block_job(params: RemoteBlockJobParameters) -> Option<Bloc
         }
     });
 
-    Some(BlockJobHandle::new(RemoteBlockJobHandle { spawn_handle }))
+    Some(BlockJobHandle::new(RemoteBlockJobHandle {
+        shared,
+        cancel,
+        spawn_handle: Some(spawn_handle),
+    }))
 }
 
 /// Infer package_name, package_version, block_name from the TaskBlock's path metadata.
@@ -107,6 +107,7 @@ pub fn execute_service_job(params: ServiceJobParameters) -> Option<BlockJobHandl
             .as_ref()
             .map(|f| f.read().unwrap().path_str.clone()),
         inputs_def_patch,
+        output_cache: None,
     });
 
     send_to_service(
@@ -146,6 +147,7 @@ fn service_executor_options(service_block: &ServiceBlock) -> ServiceExecutorOpti
         start_at: executor.start_at.clone(),
         stop_at: executor.stop_at.clone(),
         keep_alive: executor.keep_alive,
+        health_check: executor.health_check.clone(),
     }
 }
 
@@ -1,5 +1,5 @@
 use mainframe::reporter::BlockReporterTx;
-use mainframe::scheduler::{self, ExecutorParams, SchedulerTx};
+use mainframe::scheduler::{self, ErrorKind, ExecutorParams, SchedulerTx};
 use manifest_meta::{
     HandleName, InputDefPatchMap, InputHandles, OutputHandles, SubflowBlock, TaskBlock,
     TaskBlockExecutor,
@@ -19,6 +19,7 @@ use crate::shared::Shared;
 
 use job::{BlockInputs, BlockJobStacks, JobId, RuntimeScope, SessionId};
 use utils::error::Result;
+use utils::output::OutputValue;
 use utils::path::to_absolute;
 
 use super::job_handle::BlockJobHandle;
@@ -80,6 +81,8 @@ pub struct TaskJobParameters {
     pub flow_path: Option<String>,
     pub dir: String,
     pub inputs_def_patch: Option<InputDefPatchMap>,
+    /// The block's `cacheable` flag, the output cache is only used when it is set.
+    pub cacheable: bool,
 }
 
 pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
@@ -99,6 +102,7 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
         scope,
         timeout,
         inputs_def_patch,
+        cacheable,
     } = params;
     let reporter = Arc::new(shared.reporter.block(
         job_id.to_owned(),
@@ -108,6 +112,38 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
 
     reporter.started(&inputs);
 
+    let output_cache = shared
+        .output_cache
+        .as_ref()
+        .filter(|_| cacheable)
+        .and_then(|cache| {
+            cache.entry(
+                block_path.as_deref().unwrap_or(&block_dir),
+                Path::new(&block_dir),
+                &executor,
+                &inputs,
+            )
+        });
+    if let Some(outputs) = output_cache.as_ref().and_then(|entry| entry.load()) {
+        tracing::info!("block {block_path:?} outputs are served from the output cache");
+        let reporter_map = outputs
+            .iter()
+            .map(|(handle, value)| (handle.to_string(), value.clone()))
+            .collect();
+        let output_map = outputs
+            .into_iter()
+            .map(|(handle, value)| (handle, Arc::new(OutputValue::new(value, true))))
+            .collect();
+        reporter.finished(Some(reporter_map), None, None);
+        block_status.finish(job_id.to_owned(), Some(output_map), None, None, None);
+        return Some(BlockJobHandle::new(TaskJobHandle {
+            job_id,
+            shared,
+            child: None,
+            spawn_handles: vec![],
+        }));
+    }
+
     let mut spawn_handles: Vec<tokio::task::JoinHandle<()>> = Vec::new();
 
     if let Some(timeout_value) = timeout {
@@ -138,6 +174,7 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
         injection_store: injection_store.clone(),
         flow_path: flow_path.clone(),
         inputs_def_patch,
+        output_cache,
     });
 
     match executor.as_ref() {
@@ -164,7 +201,7 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
                 }
                 Err(e) => {
                     worker_listener_handle.abort();
-                    reporter.finished(None, Some(e.to_string()));
+                    reporter.finished(None, Some(e.to_string()), Some(ErrorKind::SchedulerError));
                     Some(BlockJobHandle::new(TaskJobHandle {
                         job_id,
                         shared,
@@ -209,6 +246,8 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
                                     job_id: job_id_clone.clone(),
                                     result: None,
                                     error: Some(msg),
+                                    error_kind: Some(ErrorKind::UserError),
+                                    exit_code: None,
                                 },
                             );
                         } else {
@@ -218,6 +257,8 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
                                     job_id: job_id_clone.clone(),
                                     result: None,
                                     error: None,
+                                    error_kind: None,
+                                    exit_code: None,
                                 },
                             );
                         }
@@ -239,6 +280,8 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
                             job_id: job_id.clone(),
                             result: None,
                             error: Some("Failed to spawn shell".to_owned()),
+                            error_kind: Some(ErrorKind::SchedulerError),
+                            exit_code: None,
                         },
                     );
                     Some(BlockJobHandle::new(TaskJobHandle {
@@ -280,6 +323,8 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
                             job_id: job_id_clone,
                             result: Some(outputs),
                             error: None,
+                            error_kind: None,
+                            exit_code: None,
                         });
                     }
                     Err(error) => {
@@ -288,6 +333,8 @@ pub fn execute_task_job(params: TaskJobParameters) -> Option<BlockJobHandle> {
                             job_id: job_id_clone,
                             result: None,
                             error: Some(error.to_string()),
+                            error_kind: Some(ErrorKind::UserError),
+                            exit_code: None,
                         });
                     }
                 }
@@ -883,8 +930,16 @@ pub fn timeout_abort(
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         tokio::time::sleep(timeout).await;
-        reporter.error(&format!("{job_id} timeout after {timeout:?}"));
-        block_status.finish(job_id, None, Some("Timeout".to_owned()), None);
+        let error = format!("{job_id} timeout after {timeout:?}");
+        reporter.error(&error, ErrorKind::Timeout);
+        reporter.finished(None, Some(error), Some(ErrorKind::Timeout));
+        block_status.finish(
+            job_id,
+            None,
+            Some("Timeout".to_owned()),
+            Some(ErrorKind::Timeout),
+            None,
+        );
     })
 }
 
@@ -1248,6 +1303,7 @@ mod tests {
                     json_schema: None,
                     kind: None,
                     nullable: None,
+                    deprecated: None,
                     is_additional: false,
                     _serialize_for_cache: false,
                 },
@@ -1279,6 +1335,7 @@ mod tests {
                     json_schema: None,
                     kind: None,
                     nullable: None,
+                    deprecated: None,
                     is_additional: false,
                     _serialize_for_cache: false,
                 },
@@ -1316,6 +1373,7 @@ mod tests {
                     json_schema: None,
                     kind: None,
                     nullable: None,
+                    deprecated: None,
                     is_additional: false,
                     _serialize_for_cache: false,
                 },
@@ -0,0 +1,137 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use job::BlockInputs;
+use manifest_meta::{Block, NodeId, TaskBlock};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{info, warn};
+use utils::error::Result;
+
+/// Resolved inputs of one node run, written by `--record-block` and run again by `--replay-block`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BlockRecord {
+    /// Flow the node was in when it was recorded.
+    pub flow: String,
+    pub node_id: NodeId,
+    pub inputs: BTreeMap<String, Value>,
+}
+
+impl BlockRecord {
+    pub fn read(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read block record {}: {e}", path.display()))?;
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid block record {}: {e}", path.display()).into())
+    }
+
+    /// The task block of the recorded node in `block`, the flow it was recorded in. Only task
+    /// blocks can be replayed.
+    pub(crate) fn task_block(&self, block: &Block) -> Result<Arc<TaskBlock>> {
+        let Block::Flow(flow) = block else {
+            return Err(format!(
+                "Recorded node {} can only be replayed in a flow",
+                self.node_id
+            )
+            .into());
+        };
+        let flow = flow.read().unwrap();
+        let node = flow.nodes.get(&self.node_id).ok_or_else(|| {
+            format!(
+                "Recorded node {} is not in flow {}",
+                self.node_id, flow.path_str
+            )
+        })?;
+        match node.block() {
+            Block::Task(task_block) => Ok(task_block),
+            _ => Err(format!(
+                "Recorded node {} is not a task node, only task blocks can be replayed",
+                self.node_id
+            )
+            .into()),
+        }
+    }
+}
+
+enum RecorderCommand {
+    Write(BlockRecord),
+    Flush(oneshot::Sender<()>),
+}
+
+/// Writes the inputs of every run of a node to a file, the last run wins. The node is looked up
+/// by id in the flow and all of its subflows. The file is written off the flow loop, by a task
+/// spawned on the current runtime.
+#[derive(Debug, Clone)]
+pub struct BlockRecorder {
+    node_id: NodeId,
+    tx: mpsc::UnboundedSender<RecorderCommand>,
+}
+
+impl BlockRecorder {
+    pub fn new(node_id: NodeId, path: PathBuf) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Some(command) = rx.recv().await {
+                match command {
+                    RecorderCommand::Write(record) => write_record(&path, &record).await,
+                    RecorderCommand::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+        Self { node_id, tx }
+    }
+
+    /// Wait until the records of the runs so far are written.
+    pub async fn flush(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.tx.send(RecorderCommand::Flush(done_tx)).is_ok() {
+            let _ = done_rx.await;
+        }
+    }
+
+    pub(crate) fn record(&self, flow: &str, node_id: &NodeId, inputs: &Option<BlockInputs>) {
+        if node_id != &self.node_id {
+            return;
+        }
+        let mut values = BTreeMap::new();
+        for (handle, input) in inputs.iter().flatten() {
+            if !input.is_json_serializable {
+                warn!(
+                    "input {handle} of node {node_id} only lives in its executor, a replay won't have its value"
+                );
+            }
+            values.insert(handle.to_string(), input.value.clone());
+        }
+        let record = BlockRecord {
+            flow: flow.to_owned(),
+            node_id: node_id.to_owned(),
+            inputs: values,
+        };
+        if self.tx.send(RecorderCommand::Write(record)).is_err() {
+            warn!("failed to record inputs of node {node_id}, the recorder stopped");
+        }
+    }
+}
+
+async fn write_record(path: &Path, record: &BlockRecord) {
+    let node_id = &record.node_id;
+    let result = match serde_json::to_vec_pretty(record) {
+        Ok(data) => tokio::fs::write(path, data)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    match result {
+        Ok(()) => info!("recorded inputs of node {node_id} to {}", path.display()),
+        Err(e) => warn!(
+            "failed to record inputs of node {node_id} to {}: {e}",
+            path.display()
+        ),
+    }
+}
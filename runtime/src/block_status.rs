@@ -4,8 +4,9 @@ use flume::{Receiver, Sender};
 use job::JobId;
 use mainframe::{
     reporter::ErrorDetail,
-    scheduler::{BlockRequest, OutputOptions},
+    scheduler::{BlockRequest, ErrorKind, OutputOptions},
 };
+use tokio::sync::OwnedSemaphorePermit;
 use tracing::warn;
 use utils::output::OutputValue;
 
@@ -18,6 +19,15 @@ pub enum Status {
         handle: HandleName,
         options: Option<OutputOptions>,
     },
+    /// A piece of a large output, the flow puts the pieces back together.
+    OutputChunk {
+        job_id: JobId,
+        handle: HandleName,
+        chunk_index: usize,
+        chunk_total: usize,
+        data: String,
+        options: Option<OutputOptions>,
+    },
     Outputs {
         job_id: JobId,
         outputs: HashMap<HandleName, Arc<OutputValue>>,
@@ -31,10 +41,28 @@ pub enum Status {
         job_id: JobId,
         result: Option<HashMap<HandleName, Arc<OutputValue>>>,
         error: Option<String>,
+        error_kind: Option<ErrorKind>,
         error_detail: Option<ErrorDetail>,
     },
+    /// A permit of the session concurrency limit, requested by a flow with queued nodes.
+    Permit {
+        permit: OwnedSemaphorePermit,
+    },
+    /// Graceful shutdown requested, stop starting nodes and let the running ones finish.
+    Drain,
+    /// The retry delay of a failed node is over, run the node again.
+    Retry {
+        job_id: JobId,
+    },
+    /// Time to save the flow's node input values, see `Shared::checkpoint_interval`.
+    Checkpoint,
+    /// A controller aborted the job, its flow drops it and goes on without the node.
+    AbortJob {
+        job_id: JobId,
+    },
     Error {
         error: String,
+        error_kind: Option<ErrorKind>,
     },
 }
 
@@ -60,6 +88,26 @@ impl BlockStatusTx {
             warn!("BlockStatus send output failed: {e}");
         }
     }
+    pub fn output_chunk(
+        &self,
+        job_id: JobId,
+        handle: HandleName,
+        chunk_index: usize,
+        chunk_total: usize,
+        data: String,
+        options: Option<OutputOptions>,
+    ) {
+        if let Err(e) = self.tx.send(Status::OutputChunk {
+            job_id,
+            handle,
+            chunk_index,
+            chunk_total,
+            data,
+            options,
+        }) {
+            warn!("BlockStatus send output chunk failed: {e}");
+        }
+    }
     pub fn outputs(&self, job_id: JobId, outputs: HashMap<HandleName, Arc<OutputValue>>) {
         if let Err(e) = self.tx.send(Status::Outputs { job_id, outputs }) {
             warn!("BlockStatus send outputs failed: {e}");
@@ -80,21 +128,53 @@ impl BlockStatusTx {
         job_id: JobId,
         result: Option<HashMap<HandleName, Arc<OutputValue>>>,
         error: Option<String>,
+        error_kind: Option<ErrorKind>,
         error_detail: Option<ErrorDetail>,
     ) {
         if let Err(e) = self.tx.send(Status::Done {
             job_id,
             result,
             error,
+            error_kind,
             error_detail,
         }) {
             warn!("BlockStatus send done failed: {e}");
         }
     }
 
+    pub fn permit(&self, permit: OwnedSemaphorePermit) {
+        if let Err(e) = self.tx.send(Status::Permit { permit }) {
+            warn!("BlockStatus send permit failed: {e}");
+        }
+    }
+
+    pub fn retry(&self, job_id: JobId) {
+        if let Err(e) = self.tx.send(Status::Retry { job_id }) {
+            warn!("BlockStatus send retry failed: {e}");
+        }
+    }
+
+    pub fn abort_job(&self, job_id: JobId) {
+        if let Err(e) = self.tx.send(Status::AbortJob { job_id }) {
+            warn!("BlockStatus send abort job failed: {e}");
+        }
+    }
+
+    pub fn checkpoint(&self) {
+        if let Err(e) = self.tx.send(Status::Checkpoint) {
+            warn!("BlockStatus send checkpoint failed: {e}");
+        }
+    }
+
+    pub fn drain(&self) {
+        if let Err(e) = self.tx.send(Status::Drain) {
+            warn!("BlockStatus send drain failed: {e}");
+        }
+    }
+
     // error function don't have job_id, it is a global error not related to a specific job. Currently code architecture only doesn't support handle global error, so we use this function to send global error.
-    pub fn error(&self, error: String) {
-        if let Err(e) = self.tx.send(Status::Error { error }) {
+    pub fn error(&self, error: String, error_kind: Option<ErrorKind>) {
+        if let Err(e) = self.tx.send(Status::Error { error, error_kind }) {
             warn!("BlockStatus send error failed: {e}");
         }
     }
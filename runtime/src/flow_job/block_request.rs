@@ -209,6 +209,7 @@ pub fn parse_run_block_request(
                             task_block.hide_source,
                             Some(pkg_name.as_str()),
                         ),
+                        data_dir_readonly: scope.data_dir_readonly,
                     }
                 }
                 _ => scope.clone(),
@@ -243,6 +244,7 @@ pub fn parse_run_block_request(
                         subflow_guard.hide_source,
                         Some(pkg_name.as_str()),
                     ),
+                    data_dir_readonly: scope.data_dir_readonly,
                 },
                 _ => scope.clone(),
             };
@@ -39,3 +39,96 @@ pub(crate) fn save_flow_cache(node_input_values: &NodeInputValues, flow: &str) {
         }
     }
 }
+
+/// Cache root of the test process, set for every test that runs a flow or reads the cache. The
+/// root is global to the process, tests agreeing on it can run in parallel.
+#[cfg(test)]
+pub(crate) fn test_cache_root() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("oocana-cache-root-{}", std::process::id()));
+    utils::config::set_cache_root(root.to_str().unwrap());
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use manifest_meta::{HandleName, NodeId};
+    use utils::output::OutputValue;
+
+    use super::*;
+
+    #[test]
+    fn flow_cache_round_trips_under_custom_cache_root() {
+        let root = test_cache_root();
+        let flow = "/flows/cache-root/flow.oo.yaml";
+
+        let mut values = NodeInputValues::new(true).with_fingerprint("flow-v1".to_owned());
+        values.update_serializable_cache_value(
+            &NodeId::from("node".to_string()),
+            &HandleName::new("input".to_string()),
+            Arc::new(OutputValue::new(serde_json::json!(42), true)),
+        );
+        save_flow_cache(&values, flow);
+
+        let cache_path = get_flow_cache_path(flow).expect("cache meta should record the flow");
+        assert!(cache_path.starts_with(root.join("cache")), "{cache_path:?}");
+        assert!(root.join(utils::cache::CACHE_META_FILE).exists());
+
+        // save what was recovered again, it must be the same cache
+        let recovered = NodeInputValues::recover_from(cache_path.clone(), true, "flow-v1");
+        let resaved = root.join("resaved.json");
+        recovered.save_cache(resaved.clone()).unwrap();
+        let read_json = |path: &PathBuf| -> serde_json::Value {
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+        };
+        assert_eq!(read_json(&resaved), read_json(&cache_path));
+        assert_eq!(
+            read_json(&resaved)["node"]["input"][0],
+            serde_json::json!(42)
+        );
+
+        let _ = std::fs::remove_file(&cache_path);
+        let _ = std::fs::remove_file(&resaved);
+    }
+
+    #[test]
+    fn flow_cache_is_discarded_when_fingerprint_changes() {
+        let dir = std::env::temp_dir().join(format!("oocana-cache-fp-{}", std::process::id()));
+        let cache_path = dir.join("cache.json");
+
+        let mut values = NodeInputValues::new(true).with_fingerprint("flow-v1".to_owned());
+        values.update_serializable_cache_value(
+            &NodeId::from("node".to_string()),
+            &HandleName::new("input".to_string()),
+            Arc::new(OutputValue::new(serde_json::json!(42), true)),
+        );
+        values.save_cache(cache_path.clone()).unwrap();
+
+        let cached_node_count = |values: NodeInputValues| {
+            let resaved = dir.join("resaved.json");
+            values.save_cache(resaved.clone()).unwrap();
+            let json: serde_json::Value =
+                serde_json::from_str(&std::fs::read_to_string(resaved).unwrap()).unwrap();
+            json.as_object().unwrap().len()
+        };
+        assert_eq!(
+            cached_node_count(NodeInputValues::recover_from(
+                cache_path.clone(),
+                true,
+                "flow-v1"
+            )),
+            1
+        );
+        assert_eq!(
+            cached_node_count(NodeInputValues::recover_from(
+                cache_path.clone(),
+                true,
+                "flow-v2"
+            )),
+            0
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -2,10 +2,11 @@ use std::{
     collections::{HashMap, HashSet},
     default,
     sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use crate::{
-    block_job::{self, BlockJobHandle, TaskJobParameters, execute_task_job},
+    block_job::{self, BlockJobHandle, TaskJobParameters, execute_task_job, is_json_serializable},
     block_status::{self, BlockStatusTx},
     flow_job::{
         block_request::{
@@ -14,23 +15,27 @@ use crate::{
         },
         cache::save_flow_cache,
         find_upstream_nodes, parse_oauth_request,
+        run_to_node::RunToNode,
     },
+    output_chunks::OutputChunks,
     run::{CommonJobParameters, JobParams, run_job},
     shared::Shared,
 };
 use mainframe::{
     reporter::{ErrorDetail, FlowReporterTx},
     scheduler::{
-        self, BlockRequest, BlockResponseParams, OutputOptions, QueryBlockRequest, ToFlowOutput,
-        ToNodeInput,
+        self, BlockRequest, BlockResponseParams, ErrorKind, OutputOptions, QueryBlockRequest,
+        ToFlowOutput,
     },
 };
+use tokio::sync::OwnedSemaphorePermit;
 use tracing::warn;
 use utils::output::OutputValue;
 
-use job::{BlockInputs, BlockJobStacks, JobId, RuntimeScope};
+use job::{BlockInputs, BlockJobStackLevel, BlockJobStacks, JobId, RuntimeScope};
 use manifest_meta::{
-    Block, BlockResolver, BlockScope, HandleTo, InputHandle, Node, NodeId, Slot, SubflowBlock,
+    Block, BlockResolver, BlockScope, HandleName, HandleTo, InputHandle, Node, NodeId, Slot,
+    SubflowBlock,
 };
 
 use super::node_input_values;
@@ -38,17 +43,48 @@ use node_input_values::NodeInputValues;
 
 pub struct FlowJobHandle {
     spawn_handle: tokio::task::JoinHandle<()>,
+    timeout_handle: Option<tokio::task::JoinHandle<()>>,
+    checkpoint_handle: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl Drop for FlowJobHandle {
     fn drop(&mut self) {
         self.spawn_handle.abort();
+        if let Some(timeout_handle) = &self.timeout_handle {
+            timeout_handle.abort();
+        }
+        if let Some(checkpoint_handle) = &self.checkpoint_handle {
+            checkpoint_handle.abort();
+        }
     }
 }
 
 struct BlockInFlowJobHandle {
     node_id: NodeId,
     _job: BlockJobHandle,
+    // taken as soon as the job is done, so a node queued by the concurrency limit can run
+    permit: Option<OwnedSemaphorePermit>,
+    started_at: Instant,
+    // kept so a retry runs with the same inputs, only for nodes with a retry policy
+    inputs: Option<BlockInputs>,
+    attempt: u32,
+    // the failure the node waits to be retried after
+    failure: Option<NodeFailure>,
+}
+
+/// A failed node run again by its retry policy.
+struct NodeRetry {
+    inputs: Option<BlockInputs>,
+    attempt: u32,
+}
+
+/// The failure of a node waiting for its retry, the flow fails with it when a drain skips the
+/// retry.
+#[derive(Clone)]
+struct NodeFailure {
+    error: String,
+    error_kind: Option<ErrorKind>,
+    error_detail: Option<ErrorDetail>,
 }
 
 struct FlowShared {
@@ -69,6 +105,16 @@ struct RunFlowContext {
     jobs: HashMap<JobId, BlockInFlowJobHandle>,
     block_status: BlockStatusTx,
     node_queue_pool: HashMap<NodeId, NodeQueue>,
+    // some nodes wait in queue for a permit of the session concurrency limit
+    waiting_permit: bool,
+    // values of `merge: collect` flow outputs, emitted as one array when the flow succeeds
+    collected_outputs: HashMap<HandleName, Vec<Arc<OutputValue>>>,
+    // nodes with at least one successful run, `wait_all` nodes wait for all their upstream nodes
+    finished_nodes: HashSet<NodeId>,
+    // `wait_all` nodes already started, they only run once
+    fired_barriers: HashSet<NodeId>,
+    // nodes with a job aborted by a controller, the flow goes on but doesn't succeed
+    aborted_nodes: HashSet<NodeId>,
 }
 
 #[derive(Default)]
@@ -89,6 +135,8 @@ pub struct FlowJobParameters {
     pub parent_scope: RuntimeScope,
     pub scope: RuntimeScope,
     pub slot_blocks: HashMap<NodeId, Slot>,
+    /// Timeout of the subflow node in seconds, the flow fails once it's over.
+    pub timeout: Option<u64>,
     pub path_finder: manifest_reader::path_finder::BlockPathFinder,
     pub vault_client: Arc<Option<vault::VaultClient>>,
 }
@@ -104,6 +152,7 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
         node_value_store,
         ref mut nodes,
         slot_blocks,
+        timeout,
         scope,
         parent_scope,
         path_finder,
@@ -179,6 +228,11 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
         jobs: HashMap::new(),
         block_status: block_status_tx,
         node_queue_pool: HashMap::new(),
+        waiting_permit: false,
+        collected_outputs: HashMap::new(),
+        finished_nodes: HashSet::new(),
+        fired_barriers: HashSet::new(),
+        aborted_nodes: HashSet::new(),
     };
 
     let flow_shared = FlowShared {
@@ -194,23 +248,58 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
     };
 
     if let Some(ref origin_nodes) = nodes {
-        let (runnable_nodes, pending_nodes, upstream_nodes) = {
+        let (runnable_nodes, pending_nodes, upstream_nodes, dependencies) = {
             let flow_guard = flow_shared.flow_block.read().unwrap();
-            find_upstream_nodes(
+            // what the nodes depend on without any cached input
+            let dependencies: HashSet<NodeId> = origin_nodes
+                .iter()
+                .flat_map(|node_id| {
+                    RunToNode::new(&flow_guard, Some(node_id.to_owned()), None)
+                        .should_run_nodes
+                        .unwrap_or_default()
+                })
+                .collect();
+            let (runnable_nodes, pending_nodes, upstream_nodes) = find_upstream_nodes(
                 origin_nodes,
                 &flow_guard,
                 &mut run_flow_ctx.node_input_values,
+                false,
+            );
+            (runnable_nodes, pending_nodes, upstream_nodes, dependencies)
+        };
+
+        let end_nodes = origin_nodes
+            .iter()
+            .map(|node| node.to_string())
+            .collect::<Vec<String>>();
+        let descriptions = {
+            let flow_guard = flow_shared.flow_block.read().unwrap();
+            node_descriptions(
+                &flow_guard,
+                runnable_nodes
+                    .iter()
+                    .chain(pending_nodes.iter())
+                    .chain(end_nodes.iter()),
             )
         };
+        reporter.will_run_nodes(&runnable_nodes, &pending_nodes, &end_nodes, &descriptions);
 
-        reporter.will_run_nodes(
-            &runnable_nodes,
-            &pending_nodes,
-            &origin_nodes
-                .iter()
-                .map(|node| node.to_string())
-                .collect::<Vec<String>>(),
-        );
+        let mut cache_hits: Vec<&NodeId> = dependencies
+            .iter()
+            .filter(|node_id| {
+                !origin_nodes.contains(*node_id)
+                    && ![&runnable_nodes, &pending_nodes, &upstream_nodes]
+                        .iter()
+                        .any(|list| list.iter().any(|id| id == node_id.as_str()))
+            })
+            .collect();
+        cache_hits.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        for node_id in cache_hits {
+            reporter.node_cache_hit(node_id);
+            if flow_shared.shared.replay_cached_outputs {
+                replay_cached_outputs(node_id, &flow_shared, &run_flow_ctx);
+            }
+        }
 
         for node in runnable_nodes {
             let node_opt = {
@@ -231,7 +320,7 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
     } else {
         let mut runnable_nodes: Vec<String> = Vec::new();
         let mut pending_nodes: Vec<String> = Vec::new();
-        {
+        let descriptions = {
             let flow_guard = flow_shared.flow_block.read().unwrap();
             for node in flow_guard.nodes.values() {
                 if run_flow_ctx.node_input_values.is_node_fulfill(node) {
@@ -240,11 +329,15 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                     pending_nodes.push(node.node_id().to_string());
                 }
             }
-        }
+            node_descriptions(
+                &flow_guard,
+                runnable_nodes.iter().chain(pending_nodes.iter()),
+            )
+        };
 
         // 直接把可直接运行之外的节点，都当做中间节点（可以考虑把没有 output 连线的节点当做终点）
         // 目前 UI 只会区分可直接运行的节点，和其他节点（mid 和 end）
-        reporter.will_run_nodes(&runnable_nodes, &pending_nodes, &Vec::new());
+        reporter.will_run_nodes(&runnable_nodes, &pending_nodes, &Vec::new(), &descriptions);
 
         for node in runnable_nodes {
             let node_opt = {
@@ -279,7 +372,7 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
     }
 
     if is_finish(&run_flow_ctx) {
-        flow_success(&flow_shared, &run_flow_ctx, &reporter);
+        flow_success(&flow_shared, &run_flow_ctx, &limit_nodes, &reporter);
         return None;
     }
 
@@ -302,6 +395,21 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
         }
     }
 
+    let timeout_handle = timeout.map(|secs| {
+        flow_timeout(
+            flow_job_id.to_owned(),
+            Duration::from_secs(secs),
+            run_flow_ctx.parent_block_status.clone(),
+            Arc::clone(&reporter),
+            flow_shared.stacks.vec().to_owned(),
+        )
+    });
+
+    let checkpoint_handle = flow_shared
+        .shared
+        .checkpoint_interval
+        .map(|interval| flow_checkpoints(interval, run_flow_ctx.block_status.clone()));
+
     let scheduler_tx = flow_shared.shared.scheduler_tx.clone();
     let mut block_resolver = BlockResolver::new();
     let mut flow_path_finder = flow_shared.path_finder.clone();
@@ -353,6 +461,8 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
             Some(estimation_flow_progress.clamp(0.0, 95.0))
         }
 
+        let mut output_chunks = OutputChunks::default();
+
         while let Some(status) = block_status_rx.recv().await {
             match status {
                 block_status::Status::Output {
@@ -361,29 +471,67 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                     handle,
                     options,
                 } => {
-                    if let Some(job) = run_flow_ctx.jobs.get(&job_id) {
-                        let node_opt = {
+                    node_output(
+                        &job_id,
+                        &result,
+                        &handle,
+                        &options,
+                        &flow_shared,
+                        &mut run_flow_ctx,
+                        &limit_nodes,
+                        &reporter,
+                    );
+                }
+                block_status::Status::OutputChunk {
+                    job_id,
+                    handle,
+                    chunk_index,
+                    chunk_total,
+                    data,
+                    options,
+                } => match output_chunks.add(&job_id, &handle, chunk_index, chunk_total, data) {
+                    Some(Ok(value)) => {
+                        let outputs_def = run_flow_ctx.jobs.get(&job_id).and_then(|job| {
                             let flow_guard = flow_shared.flow_block.read().unwrap();
-                            flow_guard.nodes.get(&job.node_id).cloned()
-                        };
-                        if let Some(node) = node_opt {
-                            if let Some(tos) = node.to() {
-                                if let Some(handle_tos) = tos.get(&handle) {
-                                    produce_new_value(
-                                        &result,
-                                        handle_tos,
-                                        &flow_shared,
-                                        &mut run_flow_ctx,
-                                        true,
-                                        &limit_nodes,
-                                        &reporter,
-                                        &options,
-                                    );
-                                }
-                            }
-                        }
+                            let node = flow_guard.nodes.get(&job.node_id)?;
+                            // the block's listener only saw the pieces
+                            flow_shared
+                                .shared
+                                .reporter
+                                .block(
+                                    job_id.to_owned(),
+                                    node.block().path_str(),
+                                    flow_shared.stacks.stack(
+                                        flow_shared.job_id.to_owned(),
+                                        flow_guard.path_str.to_owned(),
+                                        job.node_id.to_owned(),
+                                    ),
+                                )
+                                .output(&value, &handle);
+                            node.outputs_def()
+                        });
+                        let result = Arc::new(OutputValue {
+                            is_json_serializable: is_json_serializable(
+                                &handle,
+                                &value,
+                                &outputs_def,
+                            ),
+                            value,
+                        });
+                        node_output(
+                            &job_id,
+                            &result,
+                            &handle,
+                            &options,
+                            &flow_shared,
+                            &mut run_flow_ctx,
+                            &limit_nodes,
+                            &reporter,
+                        );
                     }
-                }
+                    Some(Err(e)) => warn!("Dropped output of job {job_id}: {e}"),
+                    None => {}
+                },
                 block_status::Status::Progress { job_id, progress } => {
                     if let Some(job) = run_flow_ctx.jobs.get(&job_id) {
                         let contains_key = {
@@ -493,12 +641,18 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                                         scope,
                                         timeout: None,
                                         inputs_def_patch: None,
+                                        cacheable: task_block.cacheable,
                                     }) {
                                         run_flow_ctx.jobs.insert(
                                             job_id.to_owned(),
                                             BlockInFlowJobHandle {
                                                 node_id,
                                                 _job: handle,
+                                                permit: None,
+                                                started_at: Instant::now(),
+                                                inputs: None,
+                                                attempt: 1,
+                                                failure: None,
                                             },
                                         );
                                     }
@@ -531,6 +685,7 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                                         parent_scope: flow_shared.scope.clone(),
                                         scope,
                                         slot_blocks: default::Default::default(),
+                                        timeout: None,
                                         path_finder: flow_shared.path_finder.clone(),
                                         vault_client: flow_shared.vault_client.clone(),
                                     }) {
@@ -539,6 +694,11 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                                             BlockInFlowJobHandle {
                                                 node_id,
                                                 _job: handle,
+                                                permit: None,
+                                                started_at: Instant::now(),
+                                                inputs: None,
+                                                attempt: 1,
+                                                failure: None,
                                             },
                                         );
                                     }
@@ -778,12 +938,53 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                     job_id,
                     result,
                     error,
+                    error_kind,
                     error_detail,
                 } => {
+                    output_chunks.discard(&job_id);
+                    let retrying = error.is_some()
+                        && error_kind != Some(ErrorKind::Cancelled)
+                        && schedule_retry(&job_id, &flow_shared, &run_flow_ctx);
+
+                    if let Some(job) = run_flow_ctx.jobs.get_mut(&job_id) {
+                        // a retried node keeps its permit until the retry starts
+                        if !retrying {
+                            job.permit.take();
+                        }
+                        // a failure sent again after a drain skipped its retry is recorded already
+                        if job.failure.is_none() {
+                            flow_shared.shared.node_timings.record(
+                                &job.node_id,
+                                &flow_path_str,
+                                job.started_at.elapsed(),
+                                error.is_none(),
+                            );
+                            flow_shared
+                                .shared
+                                .time_budgets
+                                .record(&job.node_id, job.started_at.elapsed());
+                        }
+                        if retrying {
+                            job.failure = Some(NodeFailure {
+                                error: error.clone().unwrap_or_default(),
+                                error_kind,
+                                error_detail: error_detail.clone(),
+                            });
+                        }
+                    }
+                    if retrying {
+                        continue;
+                    }
                     run_pending_node(job_id.to_owned(), &flow_shared, &mut run_flow_ctx);
 
                     let success_done = error.is_none();
 
+                    if success_done {
+                        if let Some(job) = run_flow_ctx.jobs.get(&job_id) {
+                            run_flow_ctx.finished_nodes.insert(job.node_id.to_owned());
+                        }
+                    }
+
                     if let Some(job) = run_flow_ctx.jobs.get(&job_id) {
                         let flow_guard = flow_shared.flow_block.read().unwrap();
                         if let Some(node) = flow_guard.nodes.get(&job.node_id) {
@@ -826,6 +1027,11 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                         }
                     }
 
+                    if success_done {
+                        record_progress(&job_id, &flow_shared, &run_flow_ctx);
+                        run_ready_barriers(&flow_shared, &mut run_flow_ctx);
+                    }
+
                     // error already handled in the block report
                     if let Some(err) = error {
                         let flow_path_str = flow_shared.flow_block.read().unwrap().path_str.clone();
@@ -857,6 +1063,9 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                                 .unwrap_or_else(|| format!("job_id: {job_id}")),
                         );
 
+                        // the flow fails the way its node failed
+                        let error_kind = error_kind.unwrap_or(ErrorKind::UserError);
+
                         let error_stack = if let Some(detail) = &error_detail {
                             [flow_shared.stacks.vec().clone(), detail.stack.to_owned()].concat()
                         } else {
@@ -873,6 +1082,7 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                             &Some(ErrorDetail {
                                 message: None, // hide message here to avoid log duplication
                                 stack: error_stack.clone(),
+                                kind: Some(error_kind),
                             }),
                         );
 
@@ -881,9 +1091,11 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                                 flow_shared.job_id.to_owned(),
                                 None,
                                 Some(format!("flow {flow_path_str} failed.")),
+                                Some(error_kind),
                                 Some(ErrorDetail {
                                     message: Some(err),
                                     stack: error_stack,
+                                    kind: Some(error_kind),
                                 }),
                             );
                         } else {
@@ -891,31 +1103,170 @@ pub fn execute_flow_job(mut params: FlowJobParameters) -> Option<BlockJobHandle>
                                 flow_shared.job_id.to_owned(),
                                 None,
                                 Some(err.clone()),
+                                Some(error_kind),
                                 Some(ErrorDetail {
                                     message: Some(err),
                                     stack: error_stack,
+                                    kind: Some(error_kind),
                                 }),
                             );
                         }
                         break;
                     } else if remove_job_and_is_finished(&job_id, &mut run_flow_ctx) {
-                        flow_success(&flow_shared, &run_flow_ctx, &reporter);
+                        flow_success(&flow_shared, &run_flow_ctx, &limit_nodes, &reporter);
+                        break;
+                    }
+                }
+                block_status::Status::Permit { permit } => {
+                    run_flow_ctx.waiting_permit = false;
+                    run_queued_node(permit, &flow_shared, &mut run_flow_ctx);
+                    // a drain skips the queued node, so the last job may already be done
+                    if flow_shared.shared.drain.is_draining() && is_finish(&run_flow_ctx) {
+                        flow_success(&flow_shared, &run_flow_ctx, &limit_nodes, &reporter);
                         break;
                     }
                 }
-                block_status::Status::Error { error } => {
+                block_status::Status::Retry { job_id } => {
+                    if flow_shared.shared.drain.is_draining() {
+                        // no retry while draining, the node fails the flow the way it failed last
+                        let failure = run_flow_ctx
+                            .jobs
+                            .get(&job_id)
+                            .and_then(|job| job.failure.clone());
+                        if let Some(failure) = failure {
+                            run_flow_ctx.block_status.finish(
+                                job_id,
+                                None,
+                                Some(failure.error),
+                                failure.error_kind,
+                                failure.error_detail,
+                            );
+                            continue;
+                        }
+                    }
+                    if let Some(job) = run_flow_ctx.jobs.remove(&job_id) {
+                        if let Some(queue) = run_flow_ctx.node_queue_pool.get_mut(&job.node_id) {
+                            queue.jobs.remove(&job_id);
+                        }
+                        let flow_guard = flow_shared.flow_block.read().unwrap();
+                        if let Some(node) = flow_guard.nodes.get(&job.node_id) {
+                            start_node_job(
+                                node,
+                                node_block(node, &flow_shared),
+                                &flow_shared,
+                                &mut run_flow_ctx,
+                                job.permit,
+                                Some(NodeRetry {
+                                    inputs: job.inputs,
+                                    attempt: job.attempt + 1,
+                                }),
+                            );
+                        }
+                    }
+                    // the retry may not start, so the last job may already be done
+                    if is_finish(&run_flow_ctx) {
+                        flow_success(&flow_shared, &run_flow_ctx, &limit_nodes, &reporter);
+                        break;
+                    }
+                }
+                block_status::Status::AbortJob { job_id } => {
+                    // dropping the handle aborts the job. the rest of the flow goes on, the flow
+                    // fails once it's done
+                    let Some(job) = run_flow_ctx.jobs.remove(&job_id) else {
+                        continue;
+                    };
+                    if let Some(queue) = run_flow_ctx.node_queue_pool.get_mut(&job.node_id) {
+                        queue.jobs.remove(&job_id);
+                        queue.pending.clear();
+                    }
+                    warn!("node {} aborted, job {job_id}", job.node_id);
+                    run_flow_ctx.aborted_nodes.insert(job.node_id.to_owned());
+                    drop(job);
+                    if is_finish(&run_flow_ctx) {
+                        flow_success(&flow_shared, &run_flow_ctx, &limit_nodes, &reporter);
+                        break;
+                    }
+                }
+                // only the session root handles drain, flows see it through `Shared::drain`
+                block_status::Status::Drain => {}
+                block_status::Status::Checkpoint => {
+                    let flow_path_str = flow_shared.flow_block.read().unwrap().path_str.clone();
+                    save_flow_cache(&run_flow_ctx.node_input_values, &flow_path_str);
+                }
+                block_status::Status::Error { error, error_kind } => {
                     let flow_path_str = flow_shared.flow_block.read().unwrap().path_str.clone();
                     save_flow_cache(&run_flow_ctx.node_input_values, &flow_path_str);
 
                     run_flow_ctx.jobs.clear();
-                    run_flow_ctx.parent_block_status.error(error);
+                    run_flow_ctx.parent_block_status.error(error, error_kind);
                     break;
                 }
             };
         }
     });
 
-    Some(BlockJobHandle::new(FlowJobHandle { spawn_handle }))
+    Some(BlockJobHandle::new(FlowJobHandle {
+        spawn_handle,
+        timeout_handle,
+        checkpoint_handle,
+    }))
+}
+
+/// Fail the flow once its timeout is over. The parent flow drops the job on the failure, which
+/// aborts the nodes still running in it.
+fn flow_timeout(
+    job_id: JobId,
+    timeout: Duration,
+    parent_block_status: BlockStatusTx,
+    reporter: Arc<FlowReporterTx>,
+    stack: Vec<BlockJobStackLevel>,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        tokio::time::sleep(timeout).await;
+        reporter.done(
+            &Some(format!("{job_id} timeout after {timeout:?}")),
+            &Some(ErrorDetail {
+                message: None,
+                stack,
+                kind: Some(ErrorKind::Timeout),
+            }),
+        );
+        parent_block_status.finish(
+            job_id,
+            None,
+            Some("Timeout".to_owned()),
+            Some(ErrorKind::Timeout),
+            None,
+        );
+    })
+}
+
+/// Ask the flow to save its node input values every `interval` until it's dropped.
+fn flow_checkpoints(
+    interval: Duration,
+    block_status: BlockStatusTx,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+        loop {
+            ticks.tick().await;
+            block_status.checkpoint();
+        }
+    })
+}
+
+/// Collect descriptions of the given nodes, nodes without a description are skipped.
+fn node_descriptions<'a>(
+    flow: &SubflowBlock,
+    node_ids: impl Iterator<Item = &'a String>,
+) -> HashMap<NodeId, String> {
+    node_ids
+        .filter_map(|id| {
+            let node_id = NodeId::from(id.clone());
+            let description = flow.nodes.get(&node_id)?.description()?;
+            Some((node_id, description))
+        })
+        .collect()
 }
 
 fn remove_job_and_is_finished(job_id: &JobId, run_flow_ctx: &mut RunFlowContext) -> bool {
@@ -923,14 +1274,128 @@ fn remove_job_and_is_finished(job_id: &JobId, run_flow_ctx: &mut RunFlowContext)
     is_finish(run_flow_ctx)
 }
 
-fn flow_success(shared: &FlowShared, ctx: &RunFlowContext, reporter: &FlowReporterTx) {
+/// Schedule another run of a failed node when its retry policy allows one. The failed job stays
+/// in `jobs` until the retry starts, so the flow doesn't finish in between.
+fn schedule_retry(job_id: &JobId, shared: &FlowShared, ctx: &RunFlowContext) -> bool {
+    let Some(job) = ctx.jobs.get(job_id) else {
+        return false;
+    };
+    // errors of run_block requests are handled by the requester
+    // a node that spent its time budget isn't dispatched again
+    if job.node_id.starts_with("run_block::")
+        || shared.shared.drain.is_draining()
+        || shared.shared.time_budgets.is_exhausted(&job.node_id)
+    {
+        return false;
+    }
+    let flow_guard = shared.flow_block.read().unwrap();
+    let Some(policy) = flow_guard.nodes.get(&job.node_id).and_then(|n| n.retry()) else {
+        return false;
+    };
+    if job.attempt >= policy.max_attempts {
+        return false;
+    }
+
+    tracing::info!(
+        "node {} failed on attempt {}/{}, retry in {}ms",
+        job.node_id,
+        job.attempt,
+        policy.max_attempts,
+        policy.delay
+    );
+    let block_status = ctx.block_status.clone();
+    let job_id = job_id.to_owned();
+    let delay = Duration::from_millis(policy.delay);
+    let shared = shared.shared.clone();
+    tokio::spawn(async move {
+        // a drain ends the wait, the retry then fails the flow with the node's failure
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shared.drain.started() => {}
+        }
+        block_status.retry(job_id);
+    });
+    true
+}
+
+/// Finish a flow whose nodes are all done. It fails when a node was aborted.
+fn flow_success(
+    shared: &FlowShared,
+    ctx: &RunFlowContext,
+    limit_nodes: &Option<HashSet<NodeId>>,
+    reporter: &FlowReporterTx,
+) {
+    if !ctx.aborted_nodes.is_empty() {
+        flow_aborted(shared, ctx, reporter);
+        return;
+    }
+    report_skipped_barriers(shared, ctx, limit_nodes, reporter);
+    report_held_nodes(shared, ctx, reporter);
+    for (handle, values) in &ctx.collected_outputs {
+        let value = Arc::new(OutputValue::new(
+            serde_json::Value::Array(values.iter().map(|v| v.value.clone()).collect()),
+            values.iter().all(|v| v.is_json_serializable),
+        ));
+        reporter.output(Arc::clone(&value), handle);
+        ctx.parent_block_status
+            .output(shared.job_id.to_owned(), value, handle.to_owned(), None);
+    }
     reporter.done(&None, &None);
     ctx.parent_block_status
-        .finish(shared.job_id.to_owned(), None, None, None);
+        .finish(shared.job_id.to_owned(), None, None, None, None);
     let flow_path_str = shared.flow_block.read().unwrap().path_str.clone();
     save_flow_cache(&ctx.node_input_values, &flow_path_str);
 }
 
+fn flow_aborted(shared: &FlowShared, ctx: &RunFlowContext, reporter: &FlowReporterTx) {
+    let flow_path_str = shared.flow_block.read().unwrap().path_str.clone();
+    save_flow_cache(&ctx.node_input_values, &flow_path_str);
+
+    let mut nodes = ctx
+        .aborted_nodes
+        .iter()
+        .map(|node_id| node_id.to_string())
+        .collect::<Vec<_>>();
+    nodes.sort();
+    let nodes = nodes.join(", ");
+    let error = format!("flow {flow_path_str} aborted nodes: {nodes}");
+    let error_detail = ErrorDetail {
+        message: Some(error.clone()),
+        stack: shared.stacks.vec().clone(),
+        kind: Some(ErrorKind::Cancelled),
+    };
+    reporter.done(&Some(error.clone()), &Some(error_detail.clone()));
+    ctx.parent_block_status.finish(
+        shared.job_id.to_owned(),
+        None,
+        Some(error),
+        Some(ErrorKind::Cancelled),
+        Some(error_detail),
+    );
+}
+
+/// Keep the root flow nodes that completed along with the values they produced, for `--resume`.
+fn record_progress(job_id: &JobId, shared: &FlowShared, ctx: &RunFlowContext) {
+    let Some(progress) = &shared.shared.session_progress else {
+        return;
+    };
+    if shared.stacks.depth() > 0 {
+        return;
+    }
+    let (Some(job), Some(fingerprint)) =
+        (ctx.jobs.get(job_id), ctx.node_input_values.fingerprint())
+    else {
+        return;
+    };
+    let flow_path_str = shared.flow_block.read().unwrap().path_str.clone();
+    progress.node_completed(
+        &flow_path_str,
+        fingerprint,
+        &job.node_id,
+        &ctx.node_input_values,
+    );
+}
+
 fn run_pending_node(job_id: JobId, flow_shared: &FlowShared, run_flow_ctx: &mut RunFlowContext) {
     if let Some(job_handle) = run_flow_ctx.jobs.get(&job_id) {
         let node_id = job_handle.node_id.to_owned();
@@ -956,6 +1421,43 @@ fn run_pending_node(job_id: JobId, flow_shared: &FlowShared, run_flow_ctx: &mut
     }
 }
 
+/// Pass an output of a node's job to the nodes and flow outputs connected to the handle.
+#[allow(clippy::too_many_arguments)]
+fn node_output(
+    job_id: &JobId,
+    result: &Arc<OutputValue>,
+    handle: &HandleName,
+    options: &Option<OutputOptions>,
+    shared: &FlowShared,
+    ctx: &mut RunFlowContext,
+    limit_nodes: &Option<HashSet<NodeId>>,
+    reporter: &FlowReporterTx,
+) {
+    let Some(job) = ctx.jobs.get(job_id) else {
+        return;
+    };
+    let node_opt = {
+        let flow_guard = shared.flow_block.read().unwrap();
+        flow_guard.nodes.get(&job.node_id).cloned()
+    };
+    if let Some(handle_tos) = node_opt
+        .as_ref()
+        .and_then(|node| node.to())
+        .and_then(|tos| tos.get(handle))
+    {
+        produce_new_value(
+            result,
+            handle_tos,
+            shared,
+            ctx,
+            true,
+            limit_nodes,
+            reporter,
+            options,
+        );
+    }
+}
+
 // TODO: refactor to reduce arguments, consider using a struct
 #[allow(clippy::too_many_arguments)]
 fn produce_new_value(
@@ -968,6 +1470,29 @@ fn produce_new_value(
     reporter: &FlowReporterTx,
     options: &Option<OutputOptions>,
 ) {
+    // patterns are expanded against the node inputs connected to the output
+    if let Some(patterns) = options
+        .as_ref()
+        .and_then(|op| op.target.as_ref())
+        .and_then(|t| t.to_node_pattern.as_ref())
+    {
+        for pattern in patterns {
+            let matched = handle_tos.iter().any(|handle_to| match handle_to {
+                HandleTo::ToNodeInput {
+                    node_id,
+                    input_handle,
+                } => pattern.matches(node_id, input_handle),
+                _ => false,
+            });
+            if !matched {
+                warn!(
+                    "output target pattern {} ({}) matches no node input connected to the output, the value isn't sent to it",
+                    pattern.node_id_glob, pattern.input_handle
+                );
+            }
+        }
+    }
+
     for handle_to in handle_tos {
         match handle_to {
             HandleTo::ToNodeInput {
@@ -975,19 +1500,14 @@ fn produce_new_value(
                 input_handle,
             } => {
                 // only handle when options is some:
-                // - if to_node_inputs is not in options(None), skip this handle_to processing
-                // - if to_node_inputs is in options: check whether this handle_to is contained in to_node_inputs.
-                //      if handle_to is in to_node_input, continue processing
+                // - if the target lists no node inputs (to_node and to_node_pattern are None), skip this handle_to processing
+                // - otherwise check whether this handle_to is listed in to_node or matched by to_node_pattern.
+                //      if so, continue processing
                 //      if not, skip this handle_to processing
                 if options.as_ref().is_some_and(|op| {
-                    op.target.as_ref().is_some_and(|t| {
-                        t.to_node.as_ref().is_none_or(|to_nodes| {
-                            !to_nodes.contains(&ToNodeInput {
-                                node_id: node_id.to_owned(),
-                                input_handle: input_handle.to_owned(),
-                            })
-                        })
-                    })
+                    op.target
+                        .as_ref()
+                        .is_some_and(|t| !t.targets_node(node_id, input_handle))
                 }) {
                     continue;
                 }
@@ -1019,7 +1539,11 @@ fn produce_new_value(
 
                 if run_next_node {
                     if let Some(node) = flow_guard.nodes.get(node_id) {
-                        if ctx.node_input_values.is_node_fulfill(node) {
+                        if node.wait_all() {
+                            if barrier_ready(node, ctx) {
+                                run_barrier(node, shared, ctx);
+                            }
+                        } else if ctx.node_input_values.is_node_fulfill(node) {
                             let node_queue =
                                 ctx.node_queue_pool.entry(node_id.to_owned()).or_default();
                             if node_queue.jobs.len() < node.concurrency() as usize {
@@ -1065,6 +1589,20 @@ fn produce_new_value(
                     continue;
                 }
 
+                let collected = shared
+                    .flow_block
+                    .read()
+                    .unwrap()
+                    .collected_outputs
+                    .contains(flow_output_handle);
+                if collected {
+                    ctx.collected_outputs
+                        .entry(flow_output_handle.to_owned())
+                        .or_default()
+                        .push(Arc::clone(value));
+                    continue;
+                }
+
                 reporter.output(value.clone(), flow_output_handle);
                 ctx.parent_block_status.output(
                     shared.job_id.to_owned(),
@@ -1077,24 +1615,211 @@ fn produce_new_value(
     }
 }
 
-fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
-    let job_id = JobId::random();
-    ctx.node_queue_pool
-        .entry(node.node_id().to_owned())
-        .or_default()
-        .jobs
-        .insert(job_id.to_owned());
+/// A `wait_all` node runs once, when every upstream node finished and its inputs are fulfilled.
+fn barrier_ready(node: &Node, ctx: &RunFlowContext) -> bool {
+    !ctx.fired_barriers.contains(node.node_id())
+        && node
+            .upstream_nodes()
+            .iter()
+            .all(|upstream| ctx.finished_nodes.contains(*upstream))
+        && ctx.node_input_values.is_node_fulfill(node)
+}
 
-    let block = if matches!(node, Node::Slot(_)) {
+/// Run a ready `wait_all` node with the latest value of each input.
+fn run_barrier(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
+    ctx.node_input_values.keep_latest(node.node_id());
+    run_node(node, shared, ctx);
+}
+
+/// Report the `wait_all` nodes that never ran because some of their upstream nodes didn't finish.
+fn report_skipped_barriers(
+    shared: &FlowShared,
+    ctx: &RunFlowContext,
+    limit_nodes: &Option<HashSet<NodeId>>,
+    reporter: &FlowReporterTx,
+) {
+    let flow_guard = shared.flow_block.read().unwrap();
+    for node in flow_guard.nodes.values() {
         let node_id = node.node_id();
+        if !node.wait_all()
+            || ctx.fired_barriers.contains(node_id)
+            || limit_nodes
+                .as_ref()
+                .is_some_and(|nodes| !nodes.contains(node_id))
+        {
+            continue;
+        }
+        let mut unfinished: Vec<NodeId> = node
+            .upstream_nodes()
+            .into_iter()
+            .filter(|upstream| !ctx.finished_nodes.contains(*upstream))
+            .cloned()
+            .collect();
+        unfinished.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        warn!("wait_all node {node_id} won't run, upstream nodes didn't finish: {unfinished:?}");
+        reporter.wait_all_node_skipped(node_id, &unfinished);
+    }
+}
+
+/// Report the nodes whose runs were still held by a spent time budget when the flow finished.
+fn report_held_nodes(shared: &FlowShared, ctx: &RunFlowContext, reporter: &FlowReporterTx) {
+    let mut held: Vec<(&NodeId, usize)> = ctx
+        .node_queue_pool
+        .iter()
+        .filter(|(node_id, queue)| {
+            !queue.pending.is_empty() && shared.shared.time_budgets.is_exhausted(node_id)
+        })
+        .map(|(node_id, queue)| (node_id, queue.pending.len()))
+        .collect();
+    held.sort_by(|a, b| a.0.as_str().cmp(b.0.as_str()));
+    for (node_id, runs) in held {
+        warn!("node {node_id} spent its time budget, {runs} run(s) never started");
+        reporter.node_held_by_time_budget(node_id, runs);
+    }
+}
+
+fn run_ready_barriers(shared: &FlowShared, ctx: &mut RunFlowContext) {
+    let flow_guard = shared.flow_block.read().unwrap();
+    for node in flow_guard.nodes.values() {
+        if node.wait_all() && barrier_ready(node, ctx) {
+            run_barrier(node, shared, ctx);
+        }
+    }
+}
+
+fn node_block(node: &Node, shared: &FlowShared) -> Block {
+    if matches!(node, Node::Slot(_)) {
         shared
             .slot_blocks
-            .get(node_id)
+            .get(node.node_id())
             .map(|slot| slot.block())
             .unwrap_or_else(|| node.block())
     } else {
         node.block()
+    }
+}
+
+/// Run the node now, or queue it as pending when the session concurrency limit is reached or the
+/// node spent its time budget.
+fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
+    if node.wait_all() {
+        ctx.fired_barriers.insert(node.node_id().to_owned());
+    }
+
+    if shared.shared.time_budgets.is_exhausted(node.node_id()) {
+        ctx.node_queue_pool
+            .entry(node.node_id().to_owned())
+            .or_default()
+            .pending
+            .insert(JobId::random());
+        warn!(
+            "node ({}) spent its time budget, further runs are deferred",
+            node.node_id()
+        );
+        return;
+    }
+
+    let block = node_block(node, shared);
+    // flow and slot jobs only wait for their own nodes, a permit held by them could never be released
+    let limit = match block {
+        Block::Flow(_) | Block::Slot(_) => None,
+        _ => shared.shared.concurrency_limit.as_ref(),
+    };
+
+    let permit = match limit.map(|limit| Arc::clone(limit).try_acquire_owned()) {
+        None => None,
+        Some(Ok(permit)) => Some(permit),
+        Some(Err(_)) => {
+            ctx.node_queue_pool
+                .entry(node.node_id().to_owned())
+                .or_default()
+                .pending
+                .insert(JobId::random());
+            tracing::info!(
+                "max concurrency reached, node ({}) waits in queue",
+                node.node_id()
+            );
+            wait_for_permit(shared, ctx);
+            return;
+        }
+    };
+
+    start_node_job(node, block, shared, ctx, permit, None);
+}
+
+fn wait_for_permit(shared: &FlowShared, ctx: &mut RunFlowContext) {
+    let Some(limit) = shared.shared.concurrency_limit.clone() else {
+        return;
+    };
+    if ctx.waiting_permit {
+        return;
+    }
+    ctx.waiting_permit = true;
+
+    let block_status = ctx.block_status.clone();
+    tokio::spawn(async move {
+        if let Ok(permit) = limit.acquire_owned().await {
+            block_status.permit(permit);
+        }
+    });
+}
+
+/// Hand a released permit to a node queued by the concurrency limit.
+fn run_queued_node(permit: OwnedSemaphorePermit, shared: &FlowShared, ctx: &mut RunFlowContext) {
+    let flow_guard = shared.flow_block.read().unwrap();
+    let runnable = |ctx: &RunFlowContext| {
+        ctx.node_queue_pool.iter().find_map(|(node_id, queue)| {
+            let node = flow_guard.nodes.get(node_id)?;
+            (!queue.pending.is_empty()
+                && queue.jobs.len() < node.concurrency() as usize
+                && !shared.shared.time_budgets.is_exhausted(node_id))
+            .then_some(node)
+        })
+    };
+
+    // the permit is dropped if no queued node can run right now
+    let Some(node) = runnable(ctx) else {
+        return;
     };
+    if let Some(queue) = ctx.node_queue_pool.get_mut(node.node_id()) {
+        if let Some(job_id) = queue.pending.iter().next().cloned() {
+            queue.pending.remove(&job_id);
+        }
+    }
+    start_node_job(
+        node,
+        node_block(node, shared),
+        shared,
+        ctx,
+        Some(permit),
+        None,
+    );
+
+    if runnable(ctx).is_some() {
+        wait_for_permit(shared, ctx);
+    }
+}
+
+fn start_node_job(
+    node: &Node,
+    block: Block,
+    shared: &FlowShared,
+    ctx: &mut RunFlowContext,
+    permit: Option<OwnedSemaphorePermit>,
+    retry: Option<NodeRetry>,
+) {
+    if shared.shared.drain.is_draining() {
+        tracing::info!("session is draining, skip node {}", node.node_id());
+        shared.shared.drain.skip_node();
+        return;
+    }
+
+    let job_id = JobId::random();
+    ctx.node_queue_pool
+        .entry(node.node_id().to_owned())
+        .or_default()
+        .jobs
+        .insert(job_id.to_owned());
 
     let block_scope = if matches!(node, Node::Slot(_)) {
         shared
@@ -1129,6 +1854,7 @@ fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
                 Some(name.as_str()),
             ),
             is_inject: node.scope().is_inject(),
+            data_dir_readonly: shared.scope.data_dir_readonly,
         },
         BlockScope::Flow { node_id, .. } => RuntimeScope {
             session_id: shared.scope.session_id.clone(),
@@ -1139,6 +1865,7 @@ fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
             node_id: node_id.clone(),
             enable_layer: shared.scope.need_layer(),
             is_inject: node.scope().is_inject(),
+            data_dir_readonly: shared.scope.data_dir_readonly,
         },
         BlockScope::Slot { .. } => RuntimeScope {
             session_id: shared.scope.session_id.clone(),
@@ -1149,9 +1876,24 @@ fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
             node_id: None,
             enable_layer: shared.parent_scope.need_layer(),
             is_inject: node.scope().is_inject(),
+            data_dir_readonly: shared.parent_scope.data_dir_readonly,
         },
     };
 
+    // a retry reuses the inputs of the failed run, a new run takes the queued ones
+    let (inputs, attempt) = match retry {
+        Some(retry) => (retry.inputs, retry.attempt),
+        None => (ctx.node_input_values.take(node), 1),
+    };
+    if let Some(recorder) = &shared.shared.block_recorder {
+        recorder.record(
+            &shared.flow_block.read().unwrap().path_str,
+            node.node_id(),
+            &inputs,
+        );
+    }
+
+    let retry_inputs = node.retry().and(inputs.clone());
     let common_job_params = CommonJobParameters {
         shared: shared.shared.clone(),
         stacks: shared.stacks.stack(
@@ -1160,7 +1902,7 @@ fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
             node.node_id().to_owned(),
         ),
         job_id: job_id.clone(),
-        inputs: ctx.node_input_values.take(node),
+        inputs,
         block_status: ctx.block_status.clone(),
         scope: runtime_scope,
     };
@@ -1184,6 +1926,7 @@ fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
                 Node::Flow(n) => n.slots.clone(),
                 _ => None,
             },
+            timeout: node.timeout(),
             path_finder: shared.path_finder.clone(),
             common: common_job_params,
             vault_client: shared.vault_client.clone(),
@@ -1218,6 +1961,11 @@ fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
             BlockInFlowJobHandle {
                 node_id: node.node_id().to_owned(),
                 _job: handle,
+                permit,
+                started_at: Instant::now(),
+                inputs: retry_inputs,
+                attempt,
+                failure: None,
             },
         );
     } else {
@@ -1225,6 +1973,50 @@ fn run_node(node: &Node, shared: &FlowShared, ctx: &mut RunFlowContext) {
     }
 }
 
+/// Report the outputs a node skipped for the cache produced last time, read back from the cached
+/// inputs of the nodes it feeds. Outputs that only went to flow outputs weren't cached.
+fn replay_cached_outputs(node_id: &NodeId, shared: &FlowShared, ctx: &RunFlowContext) {
+    let flow_guard = shared.flow_block.read().unwrap();
+    let Some(node) = flow_guard.nodes.get(node_id) else {
+        return;
+    };
+
+    let mut outputs = vec![];
+    for (handle, targets) in node.to().into_iter().flatten() {
+        let value = targets.iter().find_map(|target| match target {
+            HandleTo::ToNodeInput {
+                node_id,
+                input_handle,
+            } => ctx.node_input_values.peek(node_id, input_handle),
+            HandleTo::ToFlowOutput { .. } => None,
+        });
+        if let Some(value) = value {
+            outputs.push((handle, value));
+        }
+    }
+    outputs.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+    let job_id = JobId::random();
+    let block_path = node.block().path_str();
+    let stacks = shared.stacks.stack(
+        shared.job_id.to_owned(),
+        flow_guard.path_str.to_owned(),
+        node_id.to_owned(),
+    );
+    let reporter = &shared.shared.reporter;
+    if matches!(node.block(), Block::Flow(_)) {
+        let flow_reporter = reporter.flow(job_id, block_path, stacks);
+        for (handle, value) in outputs {
+            flow_reporter.cached_output(value, handle);
+        }
+    } else {
+        let block_reporter = reporter.block(job_id, block_path, stacks);
+        for (handle, value) in outputs {
+            block_reporter.cached_output(&value.value, handle);
+        }
+    }
+}
+
 fn is_finish(ctx: &RunFlowContext) -> bool {
-    ctx.jobs.is_empty()
+    ctx.jobs.is_empty() && !ctx.waiting_permit
 }
@@ -9,7 +9,10 @@ pub use block_request::{
     parse_run_block_request,
 };
 pub use cache::get_flow_cache_path;
+#[cfg(test)]
+pub(crate) use cache::test_cache_root;
 pub use flow::{FlowJobParameters, execute_flow_job};
 pub use node_input_values::NodeInputValues;
+pub(crate) use node_input_values::fingerprint_path;
 pub(crate) use upstream::find_upstream_nodes;
-pub use upstream::{UpstreamParameters, find_upstream};
+pub use upstream::{NodePlan, UpstreamParameters, find_upstream, plan_nodes};
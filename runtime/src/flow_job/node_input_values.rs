@@ -3,7 +3,7 @@ use std::{
     collections::{HashMap, HashSet, VecDeque},
     fs::File,
     io::Write,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
@@ -25,6 +25,8 @@ pub struct NodeInputValues {
     // used to store last values for each node input when `remember` is true
     memory_store: NodeInputStore,
     cache_value_store: Option<NodeInputStore>,
+    // fingerprint of the flow the values belong to, saved next to the cache
+    fingerprint: Option<String>,
 }
 
 impl NodeInputValues {
@@ -37,16 +39,39 @@ impl NodeInputValues {
             } else {
                 None
             },
+            fingerprint: None,
         }
     }
 
-    pub fn recover_from(path: PathBuf, save_cache: bool) -> Self {
+    pub fn with_fingerprint(mut self, fingerprint: String) -> Self {
+        self.fingerprint = Some(fingerprint);
+        self
+    }
+
+    pub fn fingerprint(&self) -> Option<&str> {
+        self.fingerprint.as_deref()
+    }
+
+    /// Load the values cached at `path`. The cache is discarded when it was saved for a flow
+    /// with another `fingerprint`, its values may not fit the flow's nodes anymore.
+    pub fn recover_from(path: PathBuf, save_cache: bool, fingerprint: &str) -> Self {
         let last_values = if save_cache {
             Some(HashMap::new())
         } else {
             None
         };
 
+        let cached_fingerprint = std::fs::read_to_string(fingerprint_path(&path)).ok();
+        if path.exists() && cached_fingerprint.as_deref() != Some(fingerprint) {
+            warn!(
+                "flow changed since cache {} was saved (fingerprint {} now {}), discard the cache",
+                path.display(),
+                cached_fingerprint.as_deref().unwrap_or("missing"),
+                fingerprint
+            );
+            return Self::new(save_cache).with_fingerprint(fingerprint.to_owned());
+        }
+
         if let Ok(file) = File::open(path) {
             let reader = std::io::BufReader::new(file);
 
@@ -77,6 +102,7 @@ impl NodeInputValues {
                         store: store.clone(),
                         memory_store: HashMap::new(),
                         cache_value_store: Some(store),
+                        fingerprint: Some(fingerprint.to_owned()),
                     }
                 }
                 Err(e) => {
@@ -85,6 +111,7 @@ impl NodeInputValues {
                         store: HashMap::new(),
                         memory_store: HashMap::new(),
                         cache_value_store: last_values,
+                        fingerprint: Some(fingerprint.to_owned()),
                     }
                 }
             }
@@ -93,6 +120,7 @@ impl NodeInputValues {
                 store: HashMap::new(),
                 memory_store: HashMap::new(),
                 cache_value_store: last_values,
+                fingerprint: Some(fingerprint.to_owned()),
             }
         }
     }
@@ -126,28 +154,50 @@ impl NodeInputValues {
             .push_back(value);
     }
 
-    pub fn is_node_fulfill(&self, node: &Node) -> bool {
-        for (handle, input) in node.inputs() {
-            if input.sources.as_ref().is_none_or(|f| f.is_empty()) && input.value.is_provided() {
-                continue;
+    /// The latest value queued for a node input, left in place.
+    pub fn peek(&self, node_id: &NodeId, handle_name: &HandleName) -> Option<Arc<OutputValue>> {
+        self.store
+            .get(node_id)
+            .and_then(|inputs| inputs.get(handle_name))
+            .and_then(|queue| queue.back())
+            .cloned()
+    }
+
+    /// Drop all but the latest value queued for each input of the node.
+    pub fn keep_latest(&mut self, node_id: &NodeId) {
+        if let Some(inputs) = self.store.get_mut(node_id) {
+            for queue in inputs.values_mut() {
+                let stale = queue.len().saturating_sub(1);
+                queue.drain(..stale);
             }
+        }
+    }
+
+    pub fn is_node_fulfill(&self, node: &Node) -> bool {
+        node.inputs()
+            .keys()
+            .all(|handle| self.is_input_fulfill(node, handle))
+    }
 
-            let no_handle_value = self
-                .store
-                .get(node.node_id())
-                .and_then(|m| m.get(handle))
-                .is_none_or(|v| v.is_empty());
-            let no_memory_value = self
-                .memory_store
-                .get(node.node_id())
-                .and_then(|m| m.get(handle))
-                .is_none_or(|v| v.is_empty());
-            if no_handle_value && no_memory_value {
-                return false;
+    /// Whether `handle` of `node` has the value the node needs to start, see [`Self::is_node_fulfill`].
+    pub fn is_input_fulfill(&self, node: &Node, handle: &HandleName) -> bool {
+        if let Some(input) = node.inputs().get(handle) {
+            if input.sources.as_ref().is_none_or(|f| f.is_empty()) && input.value.is_provided() {
+                return true;
             }
         }
 
-        true
+        let has_handle_value = self
+            .store
+            .get(node.node_id())
+            .and_then(|m| m.get(handle))
+            .is_some_and(|v| !v.is_empty());
+        let has_memory_value = self
+            .memory_store
+            .get(node.node_id())
+            .and_then(|m| m.get(handle))
+            .is_some_and(|v| !v.is_empty());
+        has_handle_value || has_memory_value
     }
 
     pub fn node_has_input(&self, node: &Node, handle_name: &HandleName) -> bool {
@@ -189,9 +239,12 @@ impl NodeInputValues {
                 std::fs::create_dir_all(parent).map_err(|e| format!("failed to create dir {e}"))?;
             }
 
-            let mut file = File::create(path).map_err(|e| format!("failed to create file {e}"))?;
-            file.write_all(json_string.as_bytes())
-                .map_err(|e| format!("failed to write file {e}"))?;
+            write_atomic(&path, json_string.as_bytes())?;
+
+            if let Some(fingerprint) = &self.fingerprint {
+                write_atomic(&fingerprint_path(&path), fingerprint.as_bytes())
+                    .map_err(|e| format!("failed to write fingerprint {e}"))?;
+            }
         }
         Ok(())
     }
@@ -292,6 +345,27 @@ impl NodeInputValues {
     }
 }
 
+/// Write to a temporary file next to `path` and rename it, a crash mid-write leaves the previous
+/// content in place instead of a truncated file.
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let temp_path = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let mut file = File::create(&temp_path).map_err(|e| format!("failed to create file {e}"))?;
+    file.write_all(data)
+        .and_then(|()| file.sync_all())
+        .map_err(|e| format!("failed to write file {e}"))?;
+    std::fs::rename(&temp_path, path).map_err(|e| {
+        let _ = std::fs::remove_file(&temp_path);
+        format!("failed to rename file {e}")
+    })
+}
+
+/// The flow fingerprint is kept in a sidecar file, the cache itself stays a plain value map.
+pub(crate) fn fingerprint_path(cache_path: &Path) -> PathBuf {
+    let mut path = cache_path.as_os_str().to_owned();
+    path.push(".fingerprint");
+    PathBuf::from(path)
+}
+
 fn need_remember_value(node: &Node, handle: &HandleName) -> bool {
     if let Some(inputs_def) = node.inputs_def() {
         if let Some(def) = inputs_def.get(handle) {
@@ -1,6 +1,7 @@
 use std::collections::HashSet;
+use std::path::PathBuf;
 
-use manifest_meta::{Node, NodeId, SubflowBlock};
+use manifest_meta::{HandleFrom, HandleName, HandleSource, Node, NodeId, SubflowBlock};
 
 use super::node_input_values::NodeInputValues;
 
@@ -28,6 +29,35 @@ impl RunToNode {
         }
     }
 
+    /// Like [`RunToNode::new`], but follows dependencies per (node, output handle). A subflow node
+    /// only pulls in the upstream of the inputs its consumed outputs are wired to, and of the
+    /// inputs it can't start without because they have no value yet.
+    pub fn by_output_handle(
+        flow: &SubflowBlock,
+        to_node: Option<NodeId>,
+        input: Option<&NodeInputValues>,
+    ) -> Self {
+        Self {
+            should_run_nodes: to_node.and_then(|to_node| {
+                let node = flow.nodes.get(&to_node)?;
+                let mut should_run_nodes = HashSet::new();
+                let mut visited = HashSet::new();
+
+                calc_handle_deps(
+                    node,
+                    None,
+                    flow,
+                    &mut should_run_nodes,
+                    &mut visited,
+                    &mut vec![flow.path.clone()],
+                    &input,
+                );
+
+                Some(should_run_nodes)
+            }),
+        }
+    }
+
     pub fn has_deps_in(&self, nodes: &HashSet<NodeId>) -> bool {
         match self.should_run_nodes {
             Some(ref should_run_nodes) => !should_run_nodes.is_disjoint(nodes),
@@ -77,3 +107,163 @@ fn calc_node_deps(
         }
     }
 }
+
+/// `needed_inputs` is `None` when every input of the node is needed. `flows` are the paths of the
+/// flows being walked, from the outermost one.
+fn calc_handle_deps(
+    node: &Node,
+    needed_inputs: Option<&HashSet<HandleName>>,
+    flow: &SubflowBlock,
+    should_run_nodes: &mut HashSet<NodeId>,
+    visited: &mut HashSet<(NodeId, Option<HandleName>)>,
+    flows: &mut Vec<PathBuf>,
+    node_input_values: &Option<&NodeInputValues>,
+) {
+    should_run_nodes.insert(node.node_id().to_owned());
+
+    if let Some(inputs_values) = node_input_values {
+        if inputs_values.is_node_fulfill(node) {
+            return;
+        }
+    }
+
+    for (handle_name, input) in node.inputs() {
+        // the node waits for every input, an input the consumed outputs don't depend on can only
+        // be left out when it already has a value
+        if needed_inputs.is_some_and(|needed| !needed.contains(handle_name))
+            && node_input_values
+                .as_ref()
+                .is_some_and(|values| values.is_input_fulfill(node, handle_name))
+        {
+            continue;
+        }
+        if node_input_values
+            .as_ref()
+            .is_some_and(|values| values.node_has_input(node, handle_name))
+        {
+            continue;
+        }
+
+        for node_source in input.sources.iter().flatten() {
+            if let HandleSource::NodeOutput {
+                node_id,
+                output_handle,
+            } = node_source
+            {
+                let Some(dependent_node) = flow.nodes.get(node_id) else {
+                    continue;
+                };
+                let needed = output_dependencies(dependent_node, output_handle, flows);
+                let key = (
+                    node_id.to_owned(),
+                    needed.is_some().then(|| output_handle.to_owned()),
+                );
+                if visited.insert(key) {
+                    calc_handle_deps(
+                        dependent_node,
+                        needed.as_ref(),
+                        flow,
+                        should_run_nodes,
+                        visited,
+                        flows,
+                        node_input_values,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Inputs of `node` that `output_handle` is wired to. Only subflow nodes expose that wiring,
+/// `None` means the output may depend on every input, which is also the answer for a subflow
+/// that is already in `flows`.
+fn output_dependencies(
+    node: &Node,
+    output_handle: &HandleName,
+    flows: &mut Vec<PathBuf>,
+) -> Option<HashSet<HandleName>> {
+    let Node::Flow(subflow_node) = node else {
+        return None;
+    };
+    // nothing writes the flows while they are walked, reading a recursive one again is fine
+    let subflow = subflow_node.flow.read().unwrap();
+    if flows.contains(&subflow.path) {
+        return None;
+    }
+    flows.push(subflow.path.clone());
+    let flow_inputs = subflow_inputs(&subflow, output_handle, flows);
+    flows.pop();
+    flow_inputs
+}
+
+fn subflow_inputs(
+    subflow: &SubflowBlock,
+    output_handle: &HandleName,
+    flows: &mut Vec<PathBuf>,
+) -> Option<HashSet<HandleName>> {
+    let mut flow_inputs = HashSet::new();
+    let mut visited = HashSet::new();
+    for from in subflow
+        .flow_outputs_froms
+        .get(output_handle)
+        .into_iter()
+        .flatten()
+    {
+        match from {
+            HandleFrom::FromFlowInput { input_handle } => {
+                flow_inputs.insert(input_handle.to_owned());
+            }
+            HandleFrom::FromNodeOutput {
+                node_id,
+                output_handle,
+            } => collect_flow_inputs(
+                subflow,
+                node_id,
+                output_handle,
+                &mut flow_inputs,
+                &mut visited,
+                flows,
+            )?,
+            HandleFrom::FromValue { .. } => {}
+        }
+    }
+    Some(flow_inputs)
+}
+
+fn collect_flow_inputs(
+    flow: &SubflowBlock,
+    node_id: &NodeId,
+    output_handle: &HandleName,
+    flow_inputs: &mut HashSet<HandleName>,
+    visited: &mut HashSet<(NodeId, HandleName)>,
+    flows: &mut Vec<PathBuf>,
+) -> Option<()> {
+    if !visited.insert((node_id.to_owned(), output_handle.to_owned())) {
+        return Some(());
+    }
+    let node = flow.nodes.get(node_id)?;
+    let needed = output_dependencies(node, output_handle, flows);
+
+    for (handle_name, input) in node.inputs() {
+        if needed
+            .as_ref()
+            .is_some_and(|needed| !needed.contains(handle_name))
+        {
+            continue;
+        }
+        for source in input.sources.iter().flatten() {
+            match source {
+                HandleSource::FlowInput { input_handle } => {
+                    flow_inputs.insert(input_handle.to_owned());
+                }
+                HandleSource::NodeOutput {
+                    node_id,
+                    output_handle,
+                } => {
+                    collect_flow_inputs(flow, node_id, output_handle, flow_inputs, visited, flows)?
+                }
+            }
+        }
+    }
+    Some(())
+}
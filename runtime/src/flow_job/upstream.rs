@@ -1,11 +1,12 @@
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, BTreeSet, HashSet},
     sync::{Arc, RwLock},
 };
 
+use serde::Serialize;
 use tracing::warn;
 
-use manifest_meta::{Node, NodeId, SubflowBlock};
+use manifest_meta::{HandleSource, Node, NodeId, SubflowBlock};
 
 use crate::flow_job::get_flow_cache_path;
 
@@ -17,6 +18,10 @@ pub struct UpstreamParameters {
     pub flow_block: Arc<RwLock<SubflowBlock>>,
     pub use_cache: bool,
     pub nodes: Option<HashSet<NodeId>>,
+    /// Follow dependencies per (node, output handle) instead of per node.
+    pub by_output_handle: bool,
+    /// Also list the value nodes feeding the nodes, they never run but provide inputs.
+    pub include_value_nodes: bool,
 }
 
 pub fn find_upstream(params: UpstreamParameters) -> (Vec<String>, Vec<String>, Vec<String>) {
@@ -24,26 +29,142 @@ pub fn find_upstream(params: UpstreamParameters) -> (Vec<String>, Vec<String>, V
         flow_block,
         use_cache,
         nodes,
+        by_output_handle,
+        include_value_nodes,
     } = params;
 
     let flow_guard = flow_block.read().unwrap();
-    let mut node_input_values = if let Some(cache_path) = use_cache
-        .then(|| get_flow_cache_path(&flow_guard.path_str))
-        .flatten()
-    {
-        NodeInputValues::recover_from(cache_path, false)
-    } else {
-        NodeInputValues::new(false)
-    };
+    let mut node_input_values = upstream_input_values(&flow_guard, use_cache);
+    let nodes = nodes.unwrap_or_default();
 
-    let (node_will_run, waiting_nodes, upstream_nodes) = find_upstream_nodes(
-        &nodes.unwrap_or_default(),
+    let (node_will_run, waiting_nodes, mut upstream_nodes) = find_upstream_nodes(
+        &nodes,
         &flow_guard,
         &mut node_input_values,
+        by_output_handle,
     );
+    if include_value_nodes {
+        let value_nodes: BTreeSet<String> = nodes
+            .iter()
+            .cloned()
+            .chain(upstream_nodes.iter().cloned().map(NodeId::from))
+            .filter_map(|node_id| flow_guard.value_node_sources.get(&node_id))
+            .flatten()
+            .map(|node_id| node_id.to_string())
+            .collect();
+        upstream_nodes.extend(value_nodes);
+    }
     (node_will_run, waiting_nodes, upstream_nodes)
 }
 
+/// What a run limited to some nodes executes, the same split `FlowNodesWillRun` reports.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NodePlan {
+    /// Nodes that run right away.
+    pub start: Vec<String>,
+    /// Nodes that run once their upstream nodes are done.
+    pub mid: Vec<String>,
+    /// The requested nodes.
+    pub end: Vec<String>,
+    /// Every node that runs, upstream nodes before the nodes they feed.
+    pub order: Vec<String>,
+}
+
+/// Plan a run limited to `nodes` without running anything.
+pub fn plan_nodes(params: UpstreamParameters) -> NodePlan {
+    let UpstreamParameters {
+        flow_block,
+        use_cache,
+        nodes,
+        by_output_handle,
+        include_value_nodes: _,
+    } = params;
+
+    let flow_guard = flow_block.read().unwrap();
+    let mut node_input_values = upstream_input_values(&flow_guard, use_cache);
+    let nodes = nodes.unwrap_or_default();
+
+    let (mut start, mut mid, _) = find_upstream_nodes(
+        &nodes,
+        &flow_guard,
+        &mut node_input_values,
+        by_output_handle,
+    );
+    let mut end: Vec<String> = nodes.iter().map(|node| node.to_string()).collect();
+    start.sort();
+    start.dedup();
+    mid.sort();
+    end.sort();
+
+    let will_run: HashSet<&str> = start
+        .iter()
+        .chain(mid.iter())
+        .chain(end.iter())
+        .map(String::as_str)
+        .collect();
+    let order = topological_order(&flow_guard, &will_run);
+
+    NodePlan {
+        start,
+        mid,
+        end,
+        order,
+    }
+}
+
+fn upstream_input_values(flow: &SubflowBlock, use_cache: bool) -> NodeInputValues {
+    if let Some(cache_path) = use_cache
+        .then(|| get_flow_cache_path(&flow.path_str))
+        .flatten()
+    {
+        NodeInputValues::recover_from(cache_path, false, &flow.fingerprint())
+    } else {
+        NodeInputValues::new(false)
+    }
+}
+
+/// Nodes with no pending upstream come first, ties in name order. Nodes in a cycle are appended
+/// in name order.
+fn topological_order(flow: &SubflowBlock, nodes: &HashSet<&str>) -> Vec<String> {
+    let mut remaining: BTreeMap<String, HashSet<String>> = BTreeMap::new();
+    for node_id in nodes {
+        let Some(node) = flow.nodes.get(&NodeId::from(node_id.to_string())) else {
+            continue;
+        };
+        let upstream = node
+            .inputs()
+            .values()
+            .flat_map(|input| input.sources.iter().flatten())
+            .filter_map(|source| match source {
+                HandleSource::NodeOutput { node_id, .. } => Some(node_id.to_string()),
+                HandleSource::FlowInput { .. } => None,
+            })
+            .filter(|upstream| upstream != node_id && nodes.contains(upstream.as_str()))
+            .collect();
+        remaining.insert(node_id.to_string(), upstream);
+    }
+
+    let mut order = Vec::new();
+    while !remaining.is_empty() {
+        let mut ready: Vec<String> = remaining
+            .iter()
+            .filter(|(_, upstream)| upstream.is_empty())
+            .map(|(node_id, _)| node_id.to_owned())
+            .collect();
+        if ready.is_empty() {
+            ready = remaining.keys().cloned().collect();
+        }
+        for node_id in &ready {
+            remaining.remove(node_id);
+        }
+        for upstream in remaining.values_mut() {
+            upstream.retain(|node_id| !ready.contains(node_id));
+        }
+        order.extend(ready);
+    }
+    order
+}
+
 /// 第一个是可以直接 run 的节点(会包含部分可以直接跑的 origin_nodes）
 /// 第二个是等待的节点 nodes（不包含 origin_nodes）
 /// 第三个是所有的上游 nodes（不包含 origin_nodes）
@@ -51,9 +172,14 @@ pub(crate) fn find_upstream_nodes(
     origin_nodes: &HashSet<NodeId>,
     flow_block: &SubflowBlock,
     node_input_values: &mut NodeInputValues,
+    by_output_handle: bool,
 ) -> (Vec<String>, Vec<String>, Vec<String>) {
-    let (node_not_found, out_of_side_nodes, node_can_run_directly) =
-        calc_nodes(origin_nodes, flow_block, node_input_values);
+    let (node_not_found, out_of_side_nodes, node_can_run_directly) = calc_nodes(
+        origin_nodes,
+        flow_block,
+        node_input_values,
+        by_output_handle,
+    );
     // 两部分：
     // 1. nodes 中可以直接 run 的
     // 2. nodes 中的依赖节点中可以直接 run 的节点
@@ -91,6 +217,7 @@ fn calc_nodes<'a>(
     nodes: &HashSet<NodeId>,
     flow_block: &'a SubflowBlock,
     node_input_values: &mut NodeInputValues,
+    by_output_handle: bool,
 ) -> (HashSet<NodeId>, HashSet<NodeId>, Vec<&'a Node>) {
     let mut node_id_not_found = HashSet::new();
     let mut dep_node_id_outside_list = HashSet::new();
@@ -98,11 +225,19 @@ fn calc_nodes<'a>(
 
     for node_id in nodes {
         if let Some(node) = flow_block.nodes.get(node_id) {
-            let n = RunToNode::new(
-                flow_block,
-                Some(node_id.to_owned()),
-                Some(node_input_values),
-            );
+            let n = if by_output_handle {
+                RunToNode::by_output_handle(
+                    flow_block,
+                    Some(node_id.to_owned()),
+                    Some(node_input_values),
+                )
+            } else {
+                RunToNode::new(
+                    flow_block,
+                    Some(node_id.to_owned()),
+                    Some(node_input_values),
+                )
+            };
 
             let nodes_without_self = nodes
                 .iter()
@@ -0,0 +1,131 @@
+use std::{fs, path::Path};
+
+use serde_json::{Map, Value};
+use utils::error::Result;
+
+const REF_KEY: &str = "$ref";
+
+/// Resolve handle values of the form `{"$ref": "file.json#/a/b"}` by loading `file.json` and
+/// applying the JSON pointer `/a/b`. Relative files are resolved against `base_dir`, refs found
+/// in a referenced file are resolved against that file's directory.
+pub fn resolve_input_refs(inputs: Value, base_dir: &Path) -> Result<Value> {
+    let Value::Object(map) = inputs else {
+        return Ok(inputs);
+    };
+
+    map.into_iter()
+        .map(|(handle, value)| {
+            let value = resolve_value(value, base_dir, &mut vec![])
+                .map_err(|e| format!("Failed to resolve input {handle}: {e}"))?;
+            Ok((handle, value))
+        })
+        .collect::<Result<Map<String, Value>>>()
+        .map(Value::Object)
+}
+
+fn ref_target(value: &Value) -> Option<&str> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 => obj.get(REF_KEY)?.as_str(),
+        _ => None,
+    }
+}
+
+fn resolve_value(value: Value, base_dir: &Path, visiting: &mut Vec<String>) -> Result<Value> {
+    let Some(reference) = ref_target(&value) else {
+        return Ok(value);
+    };
+
+    let (file, pointer) = reference.split_once('#').unwrap_or((reference, ""));
+    let path = base_dir.join(file);
+    let content = fs::read_to_string(&path)
+        .map_err(|e| format!("cannot read $ref file {}: {e}", path.display()))?;
+    let path = path.canonicalize().unwrap_or(path);
+
+    let key = format!("{}#{pointer}", path.display());
+    if visiting.contains(&key) {
+        visiting.push(key);
+        return Err(format!("cyclic $ref: {}", visiting.join(" -> ")).into());
+    }
+
+    let doc: Value = serde_json::from_str(&content)
+        .map_err(|e| format!("invalid JSON in $ref file {}: {e}", path.display()))?;
+    let target = doc
+        .pointer(pointer)
+        .cloned()
+        .ok_or_else(|| format!("$ref pointer {pointer:?} not found in {}", path.display()))?;
+
+    visiting.push(key);
+    let dir = path.parent().unwrap_or(base_dir).to_path_buf();
+    resolve_value(target, &dir, visiting)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fixture_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("oocana-input-ref-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn ref_resolves_to_pointed_sub_value() {
+        let dir = fixture_dir("resolve");
+        fs::write(
+            dir.join("config.json"),
+            json!({ "model": { "name": "gpt", "params": [1, 2] } }).to_string(),
+        )
+        .unwrap();
+
+        let inputs = json!({
+            "name": { "$ref": "config.json#/model/name" },
+            "params": { "$ref": "config.json#/model/params" },
+            "plain": 1,
+        });
+        let resolved = resolve_input_refs(inputs, &dir).unwrap();
+        assert_eq!(
+            resolved,
+            json!({ "name": "gpt", "params": [1, 2], "plain": 1 })
+        );
+    }
+
+    #[test]
+    fn missing_ref_errors() {
+        let dir = fixture_dir("missing");
+        fs::write(dir.join("config.json"), json!({ "a": 1 }).to_string()).unwrap();
+
+        let err = resolve_input_refs(json!({ "x": { "$ref": "config.json#/b" } }), &dir)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Failed to resolve input x"), "{err}");
+        assert!(err.contains("not found"), "{err}");
+
+        let err = resolve_input_refs(json!({ "x": { "$ref": "nope.json#/a" } }), &dir)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cannot read $ref file"), "{err}");
+    }
+
+    #[test]
+    fn cyclic_ref_errors() {
+        let dir = fixture_dir("cyclic");
+        fs::write(
+            dir.join("a.json"),
+            json!({ "x": { "$ref": "b.json#/y" } }).to_string(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("b.json"),
+            json!({ "y": { "$ref": "a.json#/x" } }).to_string(),
+        )
+        .unwrap();
+
+        let err = resolve_input_refs(json!({ "v": { "$ref": "a.json#/x" } }), &dir)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("cyclic $ref"), "{err}");
+    }
+}
@@ -1,18 +1,29 @@
 mod block_job;
+pub mod block_record;
 pub mod block_status;
 pub mod delay_abort;
 mod flow_job;
+mod input_ref;
+pub mod node_timings;
+pub mod output_cache;
+mod output_chunks;
 pub mod remote_task_config;
+pub mod resolve;
 mod run;
+pub mod session_progress;
 pub mod shared;
+pub mod time_budget;
 use mainframe::reporter::ErrorDetail;
-use mainframe::scheduler::{BlockRequest, BlockResponseParams, QueryBlockRequest};
+use mainframe::scheduler::{BlockRequest, BlockResponseParams, ErrorKind, QueryBlockRequest};
 use manifest_reader::path_finder::BlockPathFinder;
+use remote_job_client::CancellationToken;
 use std::{
     collections::{HashMap, HashSet},
     env::current_dir,
+    io::Write,
     path::PathBuf,
     sync::Arc,
+    time::Duration,
 };
 use tokio::signal::unix::{SignalKind, signal};
 use vault::VaultClient;
@@ -20,20 +31,25 @@ use vault::VaultClient;
 use tracing::{error as log_error, info, warn};
 
 use job::{BlockJobStacks, JobId, RuntimeScope};
-use manifest_meta::{Block, BlockResolver, MergeInputsValue, NodeId, read_flow_or_block};
-use utils::error::Result;
+use manifest_meta::{
+    Block, BlockResolver, HandleName, MergeInputsValue, Node, NodeId, Slot, read_flow_or_block,
+};
+use utils::{error::Result, output::OutputValue};
 
 use crate::{
     block_job::{TaskJobParameters, execute_task_job},
+    block_record::BlockRecord,
     flow_job::{
         FlowJobParameters, NodeInputValues, RunBlockSuccessResponse, execute_flow_job,
         get_flow_cache_path, parse_node_downstream, parse_oauth_request, parse_query_block_request,
         parse_run_block_request,
     },
     run::{CommonJobParameters, JobParams, run_job},
+    session_progress::ResumedSession,
 };
 
 const SESSION_CANCEL_INFO: &str = "Cancelled";
+const BROKER_DISCONNECTED_INFO: &str = "Broker disconnected";
 
 #[cfg(test)]
 pub(crate) static CONNECTOR_ENV_LOCK: std::sync::OnceLock<std::sync::Mutex<()>> =
@@ -46,13 +62,43 @@ pub struct RunArgs<'a> {
     pub path_finder: BlockPathFinder,
     pub job_id: Option<JobId>,
     pub nodes: Option<HashSet<String>>,
+    /// Also run the flow nodes whose id matches, along with their upstream nodes like `nodes`.
+    pub node_filter: Option<regex::Regex>,
+    /// Run only the recorded node's task block, with the recorded inputs instead of `inputs`.
+    pub replay_block: Option<BlockRecord>,
+    /// Continue an interrupted session: the root flow nodes it completed are skipped and their
+    /// outputs come from the values it saved. A flow changed since then runs from the start.
+    pub resume: Option<ResumedSession>,
     pub inputs: Option<String>,
+    /// Fail the session when a required input of the block has no value, see
+    /// [`missing_required_inputs`].
+    pub inputs_required_check: bool,
     pub nodes_inputs: Option<String>,
     pub default_package_path: Option<PathBuf>,
     pub project_data: &'a PathBuf,
     pub pkg_data_root: &'a PathBuf,
     pub in_layer: bool,
     pub vault_client: Option<VaultClient>,
+    /// Cancels the session the same way SIGINT/SIGTERM do.
+    pub cancel: Option<CancellationToken>,
+    /// After a cancel, keep delivering outputs that blocks already emitted for this long
+    /// before the jobs are torn down. Running blocks are soft cancelled at the start of it and
+    /// no node starts in it.
+    pub abort_grace_outputs: Option<Duration>,
+    /// Write the block's outputs to this file as a JSON object when the session finishes.
+    pub output_file: Option<PathBuf>,
+    /// Only these output handles are written to `output_file`, all handles when `None`.
+    pub output_only: Option<HashSet<HandleName>>,
+    /// Write every output of the block to this as a JSON line `{"handle":..,"value":..}` as soon
+    /// as it's produced, in the order they come.
+    pub output_stream: Option<Box<dyn std::io::Write + Send>>,
+    /// Mount package data directories read-only for blocks running in a layer.
+    pub readonly_package_data: bool,
+    /// Drains the session the same way SIGTERM does with `shutdown_grace`.
+    pub drain: Option<CancellationToken>,
+    /// When set, SIGTERM drains the session instead of cancelling it: no new node starts and
+    /// running jobs get this long to finish before the session is cancelled.
+    pub shutdown_grace: Option<Duration>,
 }
 
 pub async fn run(args: RunArgs<'_>) -> Result<()> {
@@ -63,18 +109,31 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
         mut path_finder,
         job_id: param_job_id,
         nodes,
+        node_filter,
+        replay_block,
+        resume,
         inputs,
+        inputs_required_check,
         nodes_inputs,
         default_package_path,
         project_data,
         pkg_data_root,
         in_layer,
         vault_client,
+        cancel,
+        abort_grace_outputs,
+        output_file,
+        output_only,
+        mut output_stream,
+        readonly_package_data,
+        drain,
+        shutdown_grace,
     } = args;
     let (block_status_tx, block_status_rx) = block_status::create();
     let root_job_id = param_job_id.unwrap_or_else(JobId::random);
     let stacks = BlockJobStacks::new();
-    let partial = nodes.is_some();
+    let partial =
+        nodes.is_some() || node_filter.is_some() || replay_block.is_some() || resume.is_some();
     let cache = shared.use_cache;
 
     let vault_client = Arc::new(vault_client);
@@ -95,12 +154,63 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
                 partial,
                 cache,
             );
+            shared.reporter.flush().await;
             return Err(err);
         }
     };
 
     let block_path = block.path_str().unwrap_or_else(|| block_name.to_string());
 
+    let nodes = match &node_filter {
+        Some(filter) => match filter_nodes(&block, filter, nodes) {
+            Ok(nodes) => Some(nodes),
+            Err(err) => {
+                log_error!("{}", err);
+                shared.reporter.session_started(&block_path, partial, cache);
+                shared.reporter.session_finished(
+                    &block_path,
+                    &Some(err.to_string()),
+                    &None,
+                    partial,
+                    cache,
+                );
+                shared.reporter.flush().await;
+                return Err(err);
+            }
+        },
+        None => nodes,
+    };
+
+    let (block, inputs) = match replay_block {
+        // a node recorded in a subflow is looked up in that subflow
+        Some(record) => match if block.path_str().as_deref() == Some(record.flow.as_str()) {
+            record.task_block(&block)
+        } else {
+            read_flow_or_block(&record.flow, &mut block_reader, &mut path_finder)
+                .and_then(|flow| record.task_block(&flow))
+        } {
+            Ok(task_block) => {
+                info!("replay node {} with its recorded inputs", record.node_id);
+                let inputs = serde_json::to_string(&record.inputs).ok();
+                (Block::Task(task_block), inputs)
+            }
+            Err(err) => {
+                log_error!("{}", err);
+                shared.reporter.session_started(&block_path, partial, cache);
+                shared.reporter.session_finished(
+                    &block_path,
+                    &Some(err.to_string()),
+                    &None,
+                    partial,
+                    cache,
+                );
+                shared.reporter.flush().await;
+                return Err(err);
+            }
+        },
+        None => (block, inputs),
+    };
+
     shared.reporter.session_started(&block_path, partial, cache);
 
     let nodes = nodes.map(|nodes| nodes.into_iter().map(NodeId::new).collect());
@@ -128,12 +238,25 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
     }
 
     let mut inputs = if let Some(block_values) = inputs {
-        serde_json::from_str::<job::BlockInputs>(&block_values)
+        let values = serde_json::from_str::<serde_json::Value>(&block_values)
             .map_err(|e| {
                 log_error!("Failed to parse block values: {}", e);
                 format!("Invalid block values: {e}")
             })
-            .ok()
+            .ok();
+        match values {
+            Some(values) => {
+                let values =
+                    input_ref::resolve_input_refs(values, &current_dir().unwrap_or_default())?;
+                serde_json::from_value::<job::BlockInputs>(values)
+                    .map_err(|e| {
+                        log_error!("Failed to parse block values: {}", e);
+                        format!("Invalid block values: {e}")
+                    })
+                    .ok()
+            }
+            None => None,
+        }
     } else {
         None
     };
@@ -183,6 +306,23 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
         inputs = Some(pass_through_inputs);
     }
 
+    if inputs_required_check {
+        let missing = missing_required_inputs(&block, &inputs);
+        if !missing.is_empty() {
+            let err = format!("Missing required inputs: {}", missing.join(", "));
+            log_error!("{}", err);
+            shared.reporter.session_finished(
+                &block_path,
+                &Some(err.clone()),
+                &None,
+                partial,
+                cache,
+            );
+            shared.reporter.flush().await;
+            return Err(err.into());
+        }
+    }
+
     let root_scope = RuntimeScope {
         session_id: shared.session_id.clone(),
         pkg_name: None,
@@ -192,6 +332,7 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
         node_id: None,
         enable_layer: in_layer,
         is_inject: false,
+        data_dir_readonly: readonly_package_data,
     };
 
     let common_job_params = CommonJobParameters {
@@ -203,6 +344,10 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
         scope: root_scope.clone(),
     };
 
+    if resume.is_some() && !matches!(block, Block::Flow(_)) {
+        warn!("only flows can be resumed, run {block_path} from the start");
+    }
+
     let job_params = match block {
         Block::Task(task_block) => JobParams::Task {
             inputs_def: task_block.inputs_def.clone(),
@@ -214,19 +359,67 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
             common: common_job_params,
         },
         Block::Flow(flow_block) => {
-            let flow_cache_path = {
+            let (flow_cache_path, fingerprint, resumable) = {
                 let flow_guard = flow_block.read().unwrap();
-                get_flow_cache_path(&flow_guard.path_str)
+                let fingerprint = flow_guard.fingerprint();
+                let resumable = resume.filter(|resumed| {
+                    let unchanged = resumed.flow == flow_guard.path_str
+                        && resumed.fingerprint == fingerprint;
+                    if !unchanged {
+                        warn!(
+                            "flow {} changed since session {} was interrupted, run it from the start",
+                            flow_guard.path_str, resumed.session_id
+                        );
+                    }
+                    unchanged
+                });
+                let resumable = resumable.map(|resumed| {
+                    let remaining = flow_guard
+                        .nodes
+                        .keys()
+                        .filter(|node_id| !resumed.completed_nodes.contains(*node_id))
+                        .cloned()
+                        .collect::<HashSet<_>>();
+                    (resumed, remaining)
+                });
+                (
+                    get_flow_cache_path(&flow_guard.path_str),
+                    fingerprint,
+                    resumable,
+                )
+            };
+            let (nodes, node_value_store) = match resumable {
+                Some((resumed, remaining)) => {
+                    info!(
+                        "resume session {}, skip {} completed nodes",
+                        resumed.session_id,
+                        resumed.completed_nodes.len()
+                    );
+                    if let Some(progress) = &shared.session_progress {
+                        progress.resume(&resumed);
+                    }
+                    (
+                        Some(remaining),
+                        NodeInputValues::recover_from(resumed.values_path, true, &fingerprint),
+                    )
+                }
+                None => (
+                    nodes,
+                    match (shared.use_cache, flow_cache_path) {
+                        (true, Some(cache_path)) => {
+                            NodeInputValues::recover_from(cache_path, true, &fingerprint)
+                        }
+                        _ => NodeInputValues::new(true).with_fingerprint(fingerprint),
+                    },
+                ),
             };
             JobParams::Flow {
                 flow_block: flow_block.clone(),
                 nodes,
                 parent_scope: root_scope.clone(),
-                node_value_store: match (shared.use_cache, flow_cache_path) {
-                    (true, Some(cache_path)) => NodeInputValues::recover_from(cache_path, true),
-                    _ => NodeInputValues::new(true),
-                },
+                node_value_store,
                 slot_blocks: None,
+                timeout: None,
                 path_finder: path_finder.clone(),
                 common: common_job_params,
                 vault_client: vault_client.clone(),
@@ -251,29 +444,154 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
 
     let handle = run_job(job_params);
 
-    let block_status_tx_clone = block_status_tx.clone();
+    let cancel = cancel.unwrap_or_default();
+    let cancel_clone = cancel.clone();
+    let drain = drain.unwrap_or_default();
+    let drain_status_tx = block_status_tx.clone();
+    // blocks waiting on the broker would never finish, fail the session instead of hanging
+    let transport_watcher = tokio::task::spawn({
+        let scheduler_tx = shared.scheduler_tx.clone();
+        let block_status_tx = block_status_tx.clone();
+        async move {
+            scheduler_tx.transport_closed().await;
+            block_status_tx.error(
+                BROKER_DISCONNECTED_INFO.to_owned(),
+                Some(ErrorKind::BrokerDisconnected),
+            );
+        }
+    });
     let signal_handler = tokio::task::spawn(async move {
         let mut sigint = signal(SignalKind::interrupt()).unwrap();
         let mut sigterm = signal(SignalKind::terminate()).unwrap();
-        tokio::select! {
-            _ = sigint.recv() => {
-                log_error!("Received SIGINT");
-                block_status_tx_clone.error(SESSION_CANCEL_INFO.to_owned());
-            }
-            _ = sigterm.recv() => {
-                log_error!("Received SIGTERM");
-                block_status_tx_clone.error(SESSION_CANCEL_INFO.to_owned());
+        let mut draining = false;
+        // a signal received while draining cancels right away
+        loop {
+            tokio::select! {
+                _ = sigint.recv() => log_error!("Received SIGINT"),
+                _ = sigterm.recv() => {
+                    if shutdown_grace.is_some() && !draining {
+                        info!("Received SIGTERM, draining the session");
+                        draining = true;
+                        drain_status_tx.drain();
+                        continue;
+                    }
+                    log_error!("Received SIGTERM");
+                }
+                _ = drain.cancelled(), if !draining => {
+                    draining = true;
+                    drain_status_tx.drain();
+                    continue;
+                }
             }
+            break;
         }
+        cancel_clone.cancel();
     });
 
     let mut result_error: Option<String> = None;
     let mut result_error_detail: Option<ErrorDetail> = None;
     let mut addition_running_jobs = HashSet::new();
-    while let Some(status) = block_status_rx.recv().await {
+    let mut captured_outputs = serde_json::Map::new();
+    let mut root_output_chunks = output_chunks::OutputChunks::default();
+    let mut capture_output = |handle: &HandleName, value: &OutputValue| {
+        if output_file.is_some()
+            && output_only
+                .as_ref()
+                .is_none_or(|only| only.contains(handle))
+        {
+            captured_outputs.insert(handle.to_string(), value.value.clone());
+        }
+        if let Some(stream) = output_stream.as_mut() {
+            let line = serde_json::json!({ "handle": handle, "value": value.value });
+            // a reader that went away stops the stream, not the session
+            if let Err(e) = writeln!(stream, "{line}").and_then(|()| stream.flush()) {
+                warn!("Stopped streaming outputs: {e}");
+                output_stream = None;
+            }
+        }
+    };
+    // set once the session is cancelled, jobs keep running until then so queued outputs still get delivered
+    let mut grace_deadline: Option<tokio::time::Instant> = None;
+    // set by a drain with a shutdown grace, the session is cancelled once it passes
+    let mut drain_deadline: Option<tokio::time::Instant> = None;
+    loop {
+        let status = tokio::select! {
+            status = block_status_rx.recv() => status,
+            _ = cancel.cancelled(), if grace_deadline.is_none() => {
+                result_error = Some(SESSION_CANCEL_INFO.to_owned());
+                result_error_detail = Some(cancelled_error_detail());
+                // blocks get a chance to clean up, the hard abort follows after the grace or,
+                // without one, once the dropped jobs go through delay_abort
+                shared.scheduler_tx.soft_cancel_running_blocks();
+                match abort_grace_outputs.filter(|grace| !grace.is_zero()) {
+                    Some(grace) => {
+                        info!("session cancelled, delivering pending outputs for {grace:?} before abort");
+                        // the outputs only go to the nodes and the flow outputs, no new node starts
+                        shared.drain.start();
+                        grace_deadline = Some(tokio::time::Instant::now() + grace);
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+            _ = tokio::time::sleep_until(grace_deadline.unwrap_or_else(tokio::time::Instant::now)), if grace_deadline.is_some() => break,
+            _ = tokio::time::sleep_until(drain_deadline.unwrap_or_else(tokio::time::Instant::now)), if drain_deadline.is_some() => {
+                warn!("running jobs didn't finish within the shutdown grace, cancelling the session");
+                drain_deadline = None;
+                cancel.cancel();
+                continue;
+            }
+        };
+        let Some(status) = status else {
+            break;
+        };
+
         match status {
-            block_status::Status::Outputs { .. } => {}
-            block_status::Status::Output { .. } => {}
+            block_status::Status::Outputs { job_id, outputs } => {
+                if job_id == root_job_id {
+                    for (handle, value) in &outputs {
+                        capture_output(handle, value);
+                    }
+                }
+            }
+            block_status::Status::Output {
+                job_id,
+                result,
+                handle,
+                ..
+            } => {
+                if job_id == root_job_id {
+                    capture_output(&handle, &result);
+                }
+            }
+            block_status::Status::OutputChunk {
+                job_id,
+                handle,
+                chunk_index,
+                chunk_total,
+                data,
+                ..
+            } => {
+                if job_id == root_job_id {
+                    match root_output_chunks.add(&job_id, &handle, chunk_index, chunk_total, data) {
+                        Some(Ok(value)) => {
+                            shared
+                                .reporter
+                                .block(job_id, Some(block_path.clone()), stacks.clone())
+                                .output(&value, &handle);
+                            capture_output(
+                                &handle,
+                                &OutputValue {
+                                    value,
+                                    is_json_serializable: true,
+                                },
+                            );
+                        }
+                        Some(Err(e)) => warn!("Dropped output of job {job_id}: {e}"),
+                        None => {}
+                    }
+                }
+            }
             block_status::Status::Request(request) => match request {
                 BlockRequest::RunBlock(request) => {
                     let res = parse_run_block_request(
@@ -315,6 +633,7 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
                                     scope,
                                     timeout: None,
                                     inputs_def_patch: None,
+                                    cacheable: task_block.cacheable,
                                 })
                                 .is_some()
                                 {
@@ -346,6 +665,7 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
                                     parent_scope: root_scope.clone(),
                                     scope,
                                     slot_blocks: Default::default(),
+                                    timeout: None,
                                     path_finder: path_finder.clone(),
                                 })
                                 .is_some()
@@ -493,35 +813,107 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
                 BlockRequest::UpdateNodeWeight { .. } => {}
             },
             block_status::Status::Progress { .. } => {}
+            // only flows wait for permits and retry nodes
+            block_status::Status::Permit { .. }
+            | block_status::Status::Retry { .. }
+            | block_status::Status::Checkpoint => {}
+            block_status::Status::AbortJob { job_id } => {
+                // a task block run as the session has no flow to go on without it
+                if job_id == root_job_id {
+                    result_error = Some(SESSION_CANCEL_INFO.to_owned());
+                    result_error_detail = Some(cancelled_error_detail());
+                    break;
+                }
+            }
+            block_status::Status::Drain => {
+                if !shared.drain.is_draining() {
+                    shared.drain.start();
+                    drain_deadline =
+                        shutdown_grace.map(|grace| tokio::time::Instant::now() + grace);
+                    info!("session draining, waiting for running jobs to finish");
+                }
+            }
             block_status::Status::Done {
                 error,
                 job_id,
+                error_kind,
                 error_detail,
-                ..
+                result,
             } => {
                 if job_id != root_job_id && addition_running_jobs.remove(&job_id) {
                     continue;
                 }
+                if job_id == root_job_id {
+                    for (handle, value) in result.iter().flatten() {
+                        capture_output(handle, value);
+                    }
+                    // the flow stopped early, it didn't really succeed
+                    if error.is_none() && shared.drain.skipped_nodes() {
+                        result_error = Some(SESSION_CANCEL_INFO.to_owned());
+                        result_error_detail = Some(cancelled_error_detail());
+                    }
+                }
 
                 if let Some(err) = error {
-                    result_error = Some(err);
-                    result_error_detail = error_detail;
+                    // a cancelled session reports the cancel, not the errors of the jobs it tears down
+                    if grace_deadline.is_none() {
+                        result_error = Some(err);
+                        // a task block run as the session has no detail, only its kind
+                        result_error_detail = error_detail.or_else(|| {
+                            error_kind.map(|kind| ErrorDetail {
+                                message: None,
+                                stack: vec![],
+                                kind: Some(kind),
+                            })
+                        });
+                    }
                     break;
                 }
                 if addition_running_jobs.is_empty() {
                     break;
                 }
             }
-            block_status::Status::Error { error } => {
-                // it should never happen now
-                tracing::warn!("this should never happen: {}", error);
+            block_status::Status::Error { error, error_kind } => {
+                if error_kind.is_none() {
+                    // it should never happen now
+                    tracing::warn!("this should never happen: {}", error);
+                }
                 result_error = Some(error);
+                result_error_detail = error_kind.map(|kind| ErrorDetail {
+                    message: None,
+                    stack: vec![],
+                    kind: Some(kind),
+                });
                 break;
             }
         };
     }
 
     signal_handler.abort();
+    transport_watcher.abort();
+
+    if let Some(output_file) = &output_file {
+        let written = serde_json::to_string_pretty(&captured_outputs)
+            .map_err(|e| e.to_string())
+            .and_then(|json| std::fs::write(output_file, json).map_err(|e| e.to_string()));
+        if let Err(err) = written {
+            log_error!(
+                "Failed to write outputs to {}: {}",
+                output_file.display(),
+                err
+            );
+            if result_error.is_none() {
+                result_error = Some(format!(
+                    "Failed to write outputs to {}: {err}",
+                    output_file.display()
+                ));
+            }
+        }
+    }
+
+    if let Some(recorder) = &shared.block_recorder {
+        recorder.flush().await;
+    }
     shared.reporter.session_finished(
         &block_path,
         &result_error,
@@ -529,6 +921,8 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
         partial,
         cache,
     );
+    // SessionFinished is the terminal event, make sure it reaches the transport before returning.
+    shared.reporter.flush().await;
     info!(
         "session finished: {}. error: {:?}",
         block_path, result_error
@@ -540,9 +934,21 @@ pub async fn run(args: RunArgs<'_>) -> Result<()> {
         return Err(utils::error::Error::new(&err));
     }
 
+    if let Some(progress) = &shared.session_progress {
+        progress.discard();
+    }
+
     Ok(())
 }
 
+fn cancelled_error_detail() -> ErrorDetail {
+    ErrorDetail {
+        message: None,
+        stack: vec![],
+        kind: Some(ErrorKind::Cancelled),
+    }
+}
+
 pub struct GetPackageArgs<'a> {
     pub block: &'a str,
     pub block_reader: BlockResolver,
@@ -588,21 +994,115 @@ pub fn get_packages(args: GetPackageArgs<'_>) -> Result<HashMap<PathBuf, String>
         }
     };
 
-    let mut package_layers = HashMap::new();
-    packages.iter().for_each(|package| {
-        if manifest_reader::reader::should_skip_package_layer_handling_for_path(package) {
-            package_layers.insert(package.clone(), "true".to_owned());
-            return;
+    Ok(with_layer_status(packages))
+}
+
+/// Like [`get_packages`], but also the packages of the nodes inside subflows and of the blocks
+/// their slots are given, at any depth. `nodes` only filters the nodes of the flow itself. Each
+/// flow is walked once, so recursive subflows end.
+pub fn get_packages_recursive(args: GetPackageArgs<'_>) -> Result<HashMap<PathBuf, String>> {
+    let GetPackageArgs {
+        block,
+        mut block_reader,
+        mut path_finder,
+        nodes,
+    } = args;
+    let filter_nodes = nodes.unwrap_or_default();
+
+    let flow = match read_flow_or_block(block, &mut block_reader, &mut path_finder) {
+        Ok(Block::Flow(flow)) => flow,
+        Ok(_) => return Err("wrong block type. except flow get others".into()),
+        Err(err) => {
+            log_error!("Failed to read block: {}", err);
+            return Err(err);
         }
-        let layers = layer::package_layer_status(package);
-        if let Ok(layer::PackageLayerStatus::Exist) = layers {
-            package_layers.insert(package.clone(), "true".to_owned());
-        } else {
-            package_layers.insert(package.clone(), "false".to_owned());
+    };
+
+    let root_path = flow.read().unwrap().path.clone();
+    let mut packages: HashSet<PathBuf> = HashSet::new();
+    resolve::walk_flows(flow, &filter_nodes, |flow, walked| {
+        if flow.path != root_path {
+            packages.extend(flow.package_path.clone());
+        }
+        for (_, node) in flow.nodes.iter().filter(|(node_id, _)| walked(node_id)) {
+            let Node::Flow(subflow_node) = node else {
+                packages.extend(node.package_path());
+                continue;
+            };
+            // slot subflows are walked, only task slots have a package of their own
+            for slot in subflow_node.slots.iter().flat_map(|slots| slots.values()) {
+                if let Slot::Task(task_slot) = slot {
+                    packages.extend(task_slot.task.package_path.clone());
+                }
+            }
         }
     });
 
-    Ok(package_layers)
+    Ok(with_layer_status(packages))
+}
+
+fn with_layer_status(packages: impl IntoIterator<Item = PathBuf>) -> HashMap<PathBuf, String> {
+    packages
+        .into_iter()
+        .map(|package| {
+            let layer = resolve::package_layer_exists(&package);
+            (package, layer.to_string())
+        })
+        .collect()
+}
+
+/// Input handles of `block` still without a value once default and nullable inputs are filled
+/// in. A flow input that isn't connected to any node is never required.
+fn missing_required_inputs(block: &Block, inputs: &Option<job::BlockInputs>) -> Vec<String> {
+    let Some(inputs_def) = block.inputs_def() else {
+        return vec![];
+    };
+    let connected = match block {
+        Block::Flow(flow) => Some(flow.read().unwrap().flow_inputs_tos.clone()),
+        _ => None,
+    };
+    let mut missing: Vec<String> = inputs_def
+        .keys()
+        .filter(|handle| {
+            inputs
+                .as_ref()
+                .is_none_or(|inputs| !inputs.contains_key(*handle))
+        })
+        .filter(|handle| {
+            connected
+                .as_ref()
+                .is_none_or(|tos| tos.get(*handle).is_some_and(|tos| !tos.is_empty()))
+        })
+        .map(|handle| handle.to_string())
+        .collect();
+    missing.sort();
+    missing
+}
+
+/// `nodes` plus the ids of the flow nodes matching `filter`.
+fn filter_nodes(
+    block: &Block,
+    filter: &regex::Regex,
+    nodes: Option<HashSet<String>>,
+) -> Result<HashSet<String>> {
+    let Block::Flow(flow) = block else {
+        return Err(format!("node filter {filter} only applies to flows").into());
+    };
+    let flow = flow.read().unwrap();
+    let matched: Vec<String> = flow
+        .nodes
+        .keys()
+        .filter(|node_id| filter.is_match(node_id.as_str()))
+        .map(|node_id| node_id.to_string())
+        .collect();
+    if matched.is_empty() {
+        return Err(format!("no node in {} matches node filter {filter}", flow.path_str).into());
+    }
+    info!("node filter {filter} selects nodes: {}", matched.join(","));
+
+    let mut nodes = nodes.unwrap_or_default();
+    nodes.extend(matched);
+    Ok(nodes)
 }
 
 pub struct FindUpstreamArgs<'a> {
@@ -611,17 +1111,30 @@ pub struct FindUpstreamArgs<'a> {
     pub path_finder: BlockPathFinder,
     pub use_cache: bool,
     pub nodes: Option<HashSet<String>>,
+    pub by_output_handle: bool,
+    pub include_value_nodes: bool,
 }
 
 pub fn find_upstream(
     args: FindUpstreamArgs<'_>,
 ) -> Result<(Vec<String>, Vec<String>, Vec<String>)> {
+    Ok(flow_job::find_upstream(upstream_parameters(args)?))
+}
+
+/// Which nodes a run with `nodes` executes and in what order, without running anything.
+pub fn plan_nodes(args: FindUpstreamArgs<'_>) -> Result<flow_job::NodePlan> {
+    Ok(flow_job::plan_nodes(upstream_parameters(args)?))
+}
+
+fn upstream_parameters(args: FindUpstreamArgs<'_>) -> Result<flow_job::UpstreamParameters> {
     let FindUpstreamArgs {
         block_name,
         mut block_reader,
         use_cache,
         mut path_finder,
         nodes,
+        by_output_handle,
+        include_value_nodes,
     } = args;
 
     let block = match read_flow_or_block(block_name, &mut block_reader, &mut path_finder) {
@@ -635,15 +1148,13 @@ pub fn find_upstream(
     let block_path = block.path_str().unwrap_or_else(|| block_name.to_string());
 
     match block {
-        Block::Flow(flow) => {
-            let args = flow_job::UpstreamParameters {
-                flow_block: flow,
-                use_cache,
-                nodes: nodes.map(|nodes| nodes.into_iter().map(NodeId::new).collect()),
-            };
-
-            Ok(flow_job::find_upstream(args))
-        }
+        Block::Flow(flow) => Ok(flow_job::UpstreamParameters {
+            flow_block: flow,
+            use_cache,
+            nodes: nodes.map(|nodes| nodes.into_iter().map(NodeId::new).collect()),
+            by_output_handle,
+            include_value_nodes,
+        }),
         _ => {
             log_error!("Block is not a flow block: {}", block_path);
             Err("wrong block type. except flow get others".into())
@@ -654,6 +1165,10 @@ pub fn find_upstream(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::block_record::BlockRecorder;
+    use crate::output_cache::OutputCache;
+    use crate::session_progress::{ResumedSession, SessionProgress};
+    use crate::time_budget::TimeBudgets;
     use async_trait::async_trait;
     use flume::{Receiver, Sender};
     use mainframe::{
@@ -662,7 +1177,7 @@ mod tests {
         scheduler::{self, ExecutorParameters, SchedulerRxImpl, SchedulerTxImpl},
     };
     use std::{
-        path::PathBuf,
+        path::{Path, PathBuf},
         sync::{Arc, Mutex},
     };
     use wiremock::{
@@ -694,6 +1209,8 @@ mod tests {
 
         async fn run_service_block(&self, _executor_name: &str, _data: MessageData) {}
 
+        async fn soft_cancel(&self, _executor_name: &str, _data: MessageData) {}
+
         async fn disconnect(&self) {}
     }
 
@@ -737,12 +1254,107 @@ mod tests {
         reporter_handle: tokio::task::JoinHandle<()>,
         delay_abort_handle: tokio::task::JoinHandle<()>,
         reporter_rx: Receiver<serde_json::Value>,
+        /// Delivers messages to the scheduler as if an executor sent them. Weak, the transport
+        /// still closes once the scheduler stops.
+        executor_tx: flume::WeakSender<MessageData>,
+    }
+
+    #[derive(Default)]
+    struct TestRuntimeOptions {
+        concurrency_limit: Option<Arc<tokio::sync::Semaphore>>,
+        output_cache: Option<OutputCache>,
+        time_budgets: TimeBudgets,
+        replay_cached_outputs: bool,
+        block_recorder: Option<BlockRecorder>,
+        session_progress_dir: Option<PathBuf>,
+        use_cache: bool,
+        checkpoint_interval: Option<Duration>,
     }
 
     impl TestRuntime {
-        fn new(project_root: &PathBuf) -> Self {
+        fn new(project_root: &Path) -> Self {
+            Self::with_concurrency_limit(project_root, None)
+        }
+
+        fn with_concurrency_limit(
+            project_root: &Path,
+            concurrency_limit: Option<Arc<tokio::sync::Semaphore>>,
+        ) -> Self {
+            Self::with_options(
+                project_root,
+                TestRuntimeOptions {
+                    concurrency_limit,
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn with_output_cache(project_root: &Path, output_cache: OutputCache) -> Self {
+            Self::with_options(
+                project_root,
+                TestRuntimeOptions {
+                    output_cache: Some(output_cache),
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn with_time_budgets(project_root: &Path, time_budgets: TimeBudgets) -> Self {
+            Self::with_options(
+                project_root,
+                TestRuntimeOptions {
+                    time_budgets,
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn with_replayed_cached_outputs(project_root: &Path) -> Self {
+            Self::with_options(
+                project_root,
+                TestRuntimeOptions {
+                    replay_cached_outputs: true,
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn with_block_recorder(project_root: &Path, block_recorder: BlockRecorder) -> Self {
+            Self::with_options(
+                project_root,
+                TestRuntimeOptions {
+                    block_recorder: Some(block_recorder),
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn with_session_progress(project_root: &Path, progress_dir: &Path) -> Self {
+            Self::with_options(
+                project_root,
+                TestRuntimeOptions {
+                    session_progress_dir: Some(progress_dir.to_owned()),
+                    ..Default::default()
+                },
+            )
+        }
+
+        fn with_options(project_root: &Path, options: TestRuntimeOptions) -> Self {
+            let TestRuntimeOptions {
+                concurrency_limit,
+                output_cache,
+                time_budgets,
+                replay_cached_outputs,
+                block_recorder,
+                session_progress_dir,
+                use_cache,
+                checkpoint_interval,
+            } = options;
+            // flows save their cache on the way out, every test agrees on the root from the start
+            crate::flow_job::test_cache_root();
             let session_id = job::SessionId::random();
             let (scheduler_impl_tx, scheduler_impl_rx) = flume::unbounded();
+            let executor_tx = scheduler_impl_tx.downgrade();
             let (scheduler_tx, scheduler_rx) = scheduler::create(
                 LoopbackSchedulerTx {
                     tx: scheduler_impl_tx,
@@ -759,9 +1371,20 @@ mod tests {
                     pass_through_env_keys: vec![],
                     bind_paths: vec![],
                     env_file: None,
+                    env_vars: Default::default(),
+                    secrets_file: None,
                     tmp_dir: std::env::temp_dir(),
                     debug: false,
                     wait_for_client: false,
+                    max_log_line_bytes: scheduler::DEFAULT_MAX_LOG_LINE_BYTES,
+                    max_log_bytes: None,
+                    per_executor_log_dir: None,
+                    executor_log_reporter: None,
+                    spawn_timeout: mainframe::scheduler::DEFAULT_EXECUTOR_SPAWN_TIMEOUT,
+                    ready_timeout: mainframe::scheduler::DEFAULT_EXECUTOR_READY_TIMEOUT,
+                    listener_timeout: mainframe::scheduler::DEFAULT_LISTENER_TIMEOUT,
+                    restart_policy: Default::default(),
+                    extra_args: vec![],
                 },
                 project_root.display().to_string(),
             );
@@ -774,6 +1397,8 @@ mod tests {
             );
 
             let (delay_abort_tx, delay_abort_rx) = crate::delay_abort::delay_abort();
+            let session_progress =
+                session_progress_dir.map(|dir| SessionProgress::new(&dir, &session_id));
 
             Self {
                 shared: Arc::new(shared::Shared {
@@ -784,13 +1409,24 @@ mod tests {
                     scheduler_tx,
                     delay_abort_tx,
                     reporter,
-                    use_cache: false,
+                    use_cache,
+                    replay_cached_outputs,
+                    output_cache,
                     remote_task_config: None,
+                    concurrency_limit,
+                    node_timings: Default::default(),
+                    time_budgets,
+                    drain: Default::default(),
+                    max_flow_depth: shared::DEFAULT_MAX_FLOW_DEPTH,
+                    block_recorder,
+                    checkpoint_interval,
+                    session_progress,
                 }),
                 scheduler_handle: scheduler_rx.event_loop(),
                 reporter_handle: reporter_loop.event_loop(),
                 delay_abort_handle: delay_abort_rx.run(),
                 reporter_rx,
+                executor_tx,
             }
         }
 
@@ -813,6 +1449,42 @@ mod tests {
             .unwrap()
     }
 
+    /// Run args of `flow_path` on `runtime` with everything else off, tests override the fields
+    /// they need.
+    fn test_run_args<'a>(
+        runtime: &TestRuntime,
+        root: &'a PathBuf,
+        flow_path: &'a str,
+    ) -> RunArgs<'a> {
+        RunArgs {
+            shared: runtime.shared.clone(),
+            block_name: flow_path,
+            block_reader: BlockResolver::new(),
+            path_finder: BlockPathFinder::new(root.clone(), None),
+            job_id: None,
+            nodes: None,
+            node_filter: None,
+            replay_block: None,
+            resume: None,
+            inputs: None,
+            inputs_required_check: false,
+            nodes_inputs: None,
+            default_package_path: None,
+            project_data: root,
+            pkg_data_root: root,
+            in_layer: false,
+            vault_client: None,
+            cancel: None,
+            abort_grace_outputs: None,
+            output_file: None,
+            output_only: None,
+            output_stream: None,
+            readonly_package_data: false,
+            drain: None,
+            shutdown_grace: None,
+        }
+    }
+
     fn find_block_finished_result(
         messages: &[serde_json::Value],
         node_id: &str,
@@ -857,6 +1529,113 @@ mod tests {
         assert_eq!(packages.get(&connector_package), Some(&"true".to_string()));
     }
 
+    #[test]
+    fn get_packages_recursive_walks_subflows_and_slots() {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/query-nested-packages");
+        let search_path = root.join("tests/fixtures");
+        let args = || GetPackageArgs {
+            block: flow_path.to_str().unwrap(),
+            block_reader: BlockResolver::new(),
+            path_finder: BlockPathFinder::new(root.clone(), Some(vec![search_path.clone()])),
+            nodes: None,
+        };
+        let package = |name: &str| {
+            search_path
+                .join("@connector")
+                .join(name)
+                .canonicalize()
+                .unwrap()
+        };
+
+        let direct = get_packages(args()).expect("query package should succeed");
+        assert!(!direct.contains_key(&package("demo")));
+        assert!(!direct.contains_key(&package("slotted")));
+
+        // demo is behind two subflows, the inner one recursive, slotted only fills a slot
+        let packages = get_packages_recursive(args()).expect("query package should succeed");
+        assert_eq!(packages.get(&package("demo")), Some(&"true".to_string()));
+        assert_eq!(packages.get(&package("slotted")), Some(&"true".to_string()));
+    }
+
+    #[test]
+    fn find_upstream_by_output_handle_keeps_inputs_the_node_waits_for() {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/split-outputs");
+        let upstream = |node: &str, by_output_handle| {
+            let (_, _, mut whole) = find_upstream(FindUpstreamArgs {
+                block_name: flow_path.to_str().unwrap(),
+                block_reader: BlockResolver::new(),
+                path_finder: BlockPathFinder::new(root.clone(), None),
+                use_cache: false,
+                nodes: Some(HashSet::from([node.to_string()])),
+                by_output_handle,
+                include_value_nodes: false,
+            })
+            .expect("find upstream should succeed");
+            whole.sort();
+            whole
+        };
+
+        assert_eq!(
+            upstream("left-consumer", false),
+            vec!["source-a", "source-b", "split"]
+        );
+        // split.left only comes from split.a, but split doesn't start before b has a value
+        assert_eq!(
+            upstream("left-consumer", true),
+            vec!["source-a", "source-b", "split"]
+        );
+        // loop.out comes from a subflow node of the loop flow itself
+        assert_eq!(upstream("loop-consumer", true), vec!["loop", "source-a"]);
+    }
+
+    #[test]
+    fn find_upstream_lists_value_nodes_when_asked() {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/connector-flow.oo.yaml");
+        let upstream = |include_value_nodes| {
+            let (_, _, mut whole) = find_upstream(FindUpstreamArgs {
+                block_name: flow_path.to_str().unwrap(),
+                block_reader: BlockResolver::new(),
+                path_finder: BlockPathFinder::new(root.clone(), None),
+                use_cache: false,
+                nodes: Some(HashSet::from(["after-connector".to_string()])),
+                by_output_handle: false,
+                include_value_nodes,
+            })
+            .expect("find upstream should succeed");
+            whole.sort();
+            whole
+        };
+
+        assert_eq!(upstream(false), vec!["connector"]);
+        assert_eq!(upstream(true), vec!["before-connector", "connector"]);
+    }
+
+    #[test]
+    fn plan_nodes_orders_upstream_before_requested_nodes() {
+        let root = project_root();
+        let plan = plan_nodes(FindUpstreamArgs {
+            block_name: root.join("tests/fixtures/split-outputs").to_str().unwrap(),
+            block_reader: BlockResolver::new(),
+            path_finder: BlockPathFinder::new(root.clone(), None),
+            use_cache: false,
+            nodes: Some(HashSet::from(["left-consumer".to_string()])),
+            by_output_handle: false,
+            include_value_nodes: false,
+        })
+        .expect("plan should succeed");
+
+        assert_eq!(plan.start, vec!["source-a", "source-b"]);
+        assert_eq!(plan.mid, vec!["split"]);
+        assert_eq!(plan.end, vec!["left-consumer"]);
+        assert_eq!(
+            plan.order,
+            vec!["source-a", "source-b", "split", "left-consumer"]
+        );
+    }
+
     #[tokio::test]
     async fn connector_executor_runs_inside_a_flow_chain() {
         let _env_guard = CONNECTOR_ENV_LOCK
@@ -924,22 +1703,7 @@ mod tests {
             std::env::set_var("OOMOL_TOKEN", "test-token");
         }
 
-        let run_result = run(RunArgs {
-            shared: runtime.shared.clone(),
-            block_name: flow_path.to_str().unwrap(),
-            block_reader: BlockResolver::new(),
-            path_finder: BlockPathFinder::new(root.clone(), None),
-            job_id: None,
-            nodes: None,
-            inputs: None,
-            nodes_inputs: None,
-            default_package_path: None,
-            project_data: &root,
-            pkg_data_root: &root,
-            in_layer: false,
-            vault_client: None,
-        })
-        .await;
+        let run_result = run(test_run_args(&runtime, &root, flow_path.to_str().unwrap())).await;
 
         unsafe {
             if let Some(value) = previous_connector_base_url {
@@ -975,4 +1739,1205 @@ mod tests {
             Some(&serde_json::json!("connector-ok"))
         );
     }
+
+    #[tokio::test]
+    async fn will_run_message_includes_node_descriptions() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/described-nodes-flow.oo.yaml");
+
+        let run_result = run(test_run_args(&runtime, &root, flow_path.to_str().unwrap())).await;
+
+        let reporter_rx = runtime.shutdown().await;
+        let messages: Vec<_> = reporter_rx.try_iter().collect();
+
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+
+        let will_run = messages
+            .iter()
+            .find(|message| message["type"] == "FlowNodesWillRun")
+            .expect("flow should report nodes that will run");
+        assert_eq!(
+            will_run["node_descriptions"],
+            serde_json::json!({
+                "threshold": "Check whether the value is above the threshold"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn concurrency_limit_queues_ready_nodes_until_a_permit_is_free() {
+        let root = project_root();
+        let limit = Arc::new(tokio::sync::Semaphore::new(1));
+        // hold the only permit so every node has to wait in queue
+        let held = Arc::clone(&limit).try_acquire_owned().unwrap();
+        let runtime = TestRuntime::with_concurrency_limit(&root, Some(Arc::clone(&limit)));
+        let flow_path = root.join("tests/fixtures/concurrency-limit-flow.oo.yaml");
+
+        let run_future = run(test_run_args(&runtime, &root, flow_path.to_str().unwrap()));
+        tokio::pin!(run_future);
+
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_millis(200), &mut run_future)
+                .await
+                .is_err(),
+            "flow must not finish while its nodes wait for a permit"
+        );
+        assert_eq!(
+            runtime
+                .reporter_rx
+                .try_iter()
+                .filter(|message| message["type"] == "BlockStarted")
+                .count(),
+            0
+        );
+
+        drop(held);
+        let run_result = run_future.await;
+
+        let reporter_rx = runtime.shutdown().await;
+        let messages: Vec<_> = reporter_rx.try_iter().collect();
+
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+        for node_id in ["check-a", "check-b", "check-c"] {
+            assert!(
+                find_block_finished_result(&messages, node_id).is_some(),
+                "{node_id} should finish after the permit is released"
+            );
+        }
+        assert_eq!(limit.available_permits(), 1);
+    }
+
+    /// Runs the abort grace fixture, cancelled once the producer started. Returns the run
+    /// result, the flow outputs written on the way out and the reporter messages.
+    async fn run_cancelled_after_start(
+        abort_grace_outputs: Option<Duration>,
+    ) -> (Result<()>, serde_json::Value, Vec<serde_json::Value>) {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/abort-grace-outputs-flow.oo.yaml");
+        let cancel = CancellationToken::new();
+        let output_path = std::env::temp_dir().join(format!(
+            "oocana-abort-grace-output-{}.json",
+            job::JobId::random()
+        ));
+
+        let run_future = run(RunArgs {
+            cancel: Some(cancel.clone()),
+            abort_grace_outputs,
+            output_file: Some(output_path.clone()),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        });
+        tokio::pin!(run_future);
+
+        // cancel as soon as the producer runs, its output is only emitted afterwards
+        let mut messages = vec![];
+        tokio::select! {
+            result = &mut run_future => panic!("flow finished before cancel: {result:?}"),
+            _ = async {
+                while let Ok(message) = runtime.reporter_rx.recv_async().await {
+                    let started = message["type"] == "BlockStarted";
+                    messages.push(message);
+                    if started {
+                        break;
+                    }
+                }
+            } => {}
+        }
+        cancel.cancel();
+        let run_result = run_future.await;
+
+        let reporter_rx = runtime.shutdown().await;
+        messages.extend(reporter_rx.try_iter());
+
+        let outputs = std::fs::read_to_string(&output_path)
+            .map(|outputs| serde_json::from_str(&outputs).unwrap())
+            .unwrap_or_default();
+        let _ = std::fs::remove_file(&output_path);
+        (run_result, outputs, messages)
+    }
+
+    #[tokio::test]
+    async fn abort_grace_outputs_delivers_outputs_after_cancel() {
+        let (run_result, outputs, messages) =
+            run_cancelled_after_start(Some(Duration::from_secs(2))).await;
+
+        assert_eq!(
+            run_result.map_err(|e| e.to_string()),
+            Err(SESSION_CANCEL_INFO.to_owned())
+        );
+        assert_eq!(
+            outputs,
+            serde_json::json!({ "stdout": "ready" }),
+            "the output emitted during the grace window should be delivered"
+        );
+        assert!(
+            find_block_finished_result(&messages, "consumer").is_none(),
+            "no node should start after the cancel"
+        );
+    }
+
+    #[tokio::test]
+    async fn cancel_without_abort_grace_outputs_aborts_right_away() {
+        // the grace is opt-in, without it or with zero the producer is aborted before its output
+        for abort_grace_outputs in [None, Some(Duration::ZERO)] {
+            let (run_result, outputs, _) = run_cancelled_after_start(abort_grace_outputs).await;
+            assert_eq!(
+                run_result.map_err(|e| e.to_string()),
+                Err(SESSION_CANCEL_INFO.to_owned())
+            );
+            assert!(outputs.get("stdout").is_none(), "{abort_grace_outputs:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn drain_lets_running_nodes_finish_without_starting_new_ones() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/drain-flow.oo.yaml");
+        let drain = CancellationToken::new();
+
+        let run_future = run(RunArgs {
+            drain: Some(drain.clone()),
+            shutdown_grace: Some(std::time::Duration::from_secs(10)),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        });
+        tokio::pin!(run_future);
+
+        let mut messages = vec![];
+        tokio::select! {
+            result = &mut run_future => panic!("flow finished before drain: {result:?}"),
+            _ = async {
+                while let Ok(message) = runtime.reporter_rx.recv_async().await {
+                    let started = message["type"] == "BlockStarted";
+                    messages.push(message);
+                    if started {
+                        break;
+                    }
+                }
+            } => {}
+        }
+        drain.cancel();
+        let started = std::time::Instant::now();
+        let run_result = run_future.await;
+
+        let reporter_rx = runtime.shutdown().await;
+        messages.extend(reporter_rx.try_iter());
+
+        assert_eq!(
+            run_result.map_err(|e| e.to_string()),
+            Err(SESSION_CANCEL_INFO.to_owned())
+        );
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        let finished = |node_id: &str| {
+            messages.iter().any(|message| {
+                message["type"] == "BlockFinished"
+                    && message["error"].is_null()
+                    && message["stacks"]
+                        .as_array()
+                        .and_then(|s| s.last())
+                        .is_some_and(|level| level["node_id"] == node_id)
+            })
+        };
+        assert!(
+            finished("producer"),
+            "the running producer should finish during the drain"
+        );
+        assert!(!finished("consumer"), "no node should start while draining");
+    }
+
+    #[tokio::test]
+    async fn failed_node_reports_its_error_kind_up_to_the_session() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/failing-flow.oo.yaml");
+
+        let run_result = run(test_run_args(&runtime, &root, flow_path.to_str().unwrap())).await;
+        let messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+
+        assert!(run_result.is_err());
+        let message = |ty: &str| {
+            messages
+                .iter()
+                .find(|message| message["type"] == ty)
+                .unwrap_or_else(|| panic!("no {ty} message"))
+        };
+        assert_eq!(message("BlockFinished")["error_kind"], "UserError");
+        assert_eq!(message("FlowFinished")["_error"]["kind"], "UserError");
+        assert_eq!(message("SessionFinished")["_error"]["kind"], "UserError");
+    }
+
+    async fn run_timeout_fixture(fixture: &str) -> (Result<()>, Vec<serde_json::Value>) {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join(fixture);
+
+        let run_result = run(test_run_args(&runtime, &root, flow_path.to_str().unwrap())).await;
+        (run_result, runtime.shutdown().await.try_iter().collect())
+    }
+
+    fn find_message<'a>(messages: &'a [serde_json::Value], ty: &str) -> &'a serde_json::Value {
+        messages
+            .iter()
+            .find(|message| message["type"] == ty)
+            .unwrap_or_else(|| panic!("no {ty} message"))
+    }
+
+    #[tokio::test]
+    async fn self_referencing_subflow_stops_at_max_depth() {
+        let (run_result, messages) =
+            run_timeout_fixture("tests/fixtures/recursive-flow/subflow.oo.yaml").await;
+
+        assert!(run_result.is_err());
+        let error = find_message(&messages, "BlockError");
+        assert_eq!(error["error_kind"], "SchedulerError");
+        let error = error["error"].as_str().unwrap();
+        assert!(
+            error.contains(&format!(
+                "Maximum recursion depth exceeded: {} (limit: {})",
+                shared::DEFAULT_MAX_FLOW_DEPTH,
+                shared::DEFAULT_MAX_FLOW_DEPTH
+            )),
+            "{error}"
+        );
+        // the cycle, not the whole stack
+        assert_eq!(error.matches("(recurse)").count(), 1, "{error}");
+        assert!(find_message(&messages, "SessionFinished")["error"].is_string());
+    }
+
+    #[tokio::test]
+    async fn aborted_job_fails_the_flow() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/chunked-output-flow.oo.yaml");
+        let session_id = runtime.shared.session_id.clone();
+        let reporter_rx = runtime.reporter_rx.clone();
+        let executor_tx = runtime.executor_tx.upgrade().unwrap();
+
+        // stands in for a controller aborting `big`, the only node of the flow
+        let controller = async move {
+            let job_id = loop {
+                let message = reporter_rx.recv_async().await.unwrap();
+                if message["type"] == "BlockStarted" {
+                    break job::JobId::new(message["job_id"].as_str().unwrap().to_owned());
+                }
+            };
+            // the listener may subscribe after the job started
+            for _ in 0..20 {
+                let message = scheduler::ReceiveMessage::AbortJob {
+                    session_id: session_id.clone(),
+                    job_id: job_id.clone(),
+                };
+                executor_tx
+                    .send(serde_json::to_vec(&message).unwrap())
+                    .unwrap();
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                if reporter_rx
+                    .try_iter()
+                    .any(|message| message["type"] == "BlockFinished")
+                {
+                    break;
+                }
+            }
+        };
+
+        let started = tokio::time::Instant::now();
+        let (run_result, _) = tokio::join!(
+            run(test_run_args(&runtime, &root, flow_path.to_str().unwrap())),
+            controller
+        );
+        runtime.shutdown().await;
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(10));
+        let error = run_result.expect_err("a flow with an aborted node should fail");
+        assert!(error.to_string().contains("aborted nodes: big"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn chunked_output_is_reported_and_reaches_the_flow_output() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/chunked-output-flow.oo.yaml");
+        let output_path = std::env::temp_dir().join(format!(
+            "oocana-chunked-output-{}.json",
+            job::JobId::random()
+        ));
+        let session_id = runtime.shared.session_id.clone();
+        let reporter_rx = runtime.reporter_rx.clone();
+        let executor_tx = runtime.executor_tx.upgrade().unwrap();
+
+        // stands in for the executor of `big`, the output arrives in three pieces
+        let executor = async move {
+            let job_id = loop {
+                let message = reporter_rx.recv_async().await.unwrap();
+                if message["type"] == "BlockStarted" {
+                    break job::JobId::new(message["job_id"].as_str().unwrap().to_owned());
+                }
+            };
+            let send = |message: scheduler::ReceiveMessage| {
+                executor_tx
+                    .send(serde_json::to_vec(&message).unwrap())
+                    .unwrap();
+            };
+            // the listener may subscribe after the job started, pieces that arrive twice are
+            // only kept once
+            let mut reported = None;
+            for _ in 0..20 {
+                for (chunk_index, data) in ["\"hel", "lo wo", "rld\""].into_iter().enumerate() {
+                    send(scheduler::ReceiveMessage::BlockOutputChunk {
+                        session_id: session_id.clone(),
+                        job_id: job_id.clone(),
+                        handle: HandleName::new("stdout".to_owned()),
+                        chunk_index,
+                        chunk_total: 3,
+                        data: data.to_owned(),
+                        options: None,
+                    });
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                reported = reporter_rx
+                    .try_iter()
+                    .find(|message| message["type"] == "BlockOutput");
+                if reported.is_some() {
+                    break;
+                }
+            }
+            send(scheduler::ReceiveMessage::BlockFinished {
+                session_id: session_id.clone(),
+                job_id,
+                result: None,
+                error: None,
+                error_kind: None,
+                exit_code: None,
+            });
+            reported
+        };
+
+        let (run_result, reported) = tokio::join!(
+            run(RunArgs {
+                output_file: Some(output_path.clone()),
+                ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+            }),
+            executor
+        );
+        runtime.shutdown().await;
+
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+        let reported = reported.expect("the joined output should be reported");
+        assert_eq!(reported["handle"], "stdout");
+        assert_eq!(reported["output"], "hello world");
+        assert_eq!(reported["stacks"][0]["node_id"], "big");
+        let outputs: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&output_path).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&output_path);
+        assert_eq!(outputs, serde_json::json!({ "stdout": "hello world" }));
+    }
+
+    #[tokio::test]
+    async fn task_node_past_its_timeout_fails_the_flow() {
+        let started = std::time::Instant::now();
+        let (run_result, messages) =
+            run_timeout_fixture("tests/fixtures/timeout-flow.oo.yaml").await;
+
+        assert!(run_result.is_err());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(
+            find_message(&messages, "BlockError")["error_kind"],
+            "Timeout"
+        );
+        assert_eq!(
+            find_message(&messages, "BlockFinished")["error_kind"],
+            "Timeout"
+        );
+        assert_eq!(
+            find_message(&messages, "FlowFinished")["_error"]["kind"],
+            "Timeout"
+        );
+    }
+
+    #[tokio::test]
+    async fn subflow_node_past_its_timeout_fails_the_flow() {
+        let started = std::time::Instant::now();
+        let (run_result, messages) = run_timeout_fixture("tests/fixtures/timeout-subflow").await;
+
+        assert!(run_result.is_err());
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(
+            find_message(&messages, "SubflowBlockFinished")["_error"]["kind"],
+            "Timeout"
+        );
+        assert_eq!(
+            find_message(&messages, "FlowFinished")["_error"]["kind"],
+            "Timeout"
+        );
+    }
+
+    #[tokio::test]
+    async fn wait_all_node_runs_once_after_all_upstream_nodes() {
+        let (run_result, messages) =
+            run_timeout_fixture("tests/fixtures/wait-all-flow.oo.yaml").await;
+        assert!(run_result.is_ok());
+
+        let positions = |ty: &str, node_id: &str| -> Vec<usize> {
+            messages
+                .iter()
+                .enumerate()
+                .filter(|(_, message)| {
+                    message["type"] == ty
+                        && message["stacks"]
+                            .as_array()
+                            .and_then(|stacks| stacks.last())
+                            .is_some_and(|level| level["node_id"] == node_id)
+                })
+                .map(|(position, _)| position)
+                .collect()
+        };
+
+        let join_started = positions("BlockStarted", "join");
+        assert_eq!(join_started.len(), 1, "join should run exactly once");
+        for upstream in ["fast", "slow"] {
+            let finished = positions("BlockFinished", upstream);
+            assert_eq!(finished.len(), 1);
+            assert!(
+                finished[0] < join_started[0],
+                "{upstream} finished after join started"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn wait_all_node_is_reported_when_an_upstream_node_never_finishes() {
+        let (run_result, messages) =
+            run_timeout_fixture("tests/fixtures/wait-all-skipped-flow.oo.yaml").await;
+        assert!(run_result.is_ok());
+
+        assert!(!messages.iter().any(|message| {
+            message["type"] == "BlockStarted"
+                && message["stacks"]
+                    .as_array()
+                    .and_then(|stacks| stacks.last())
+                    .is_some_and(|level| level["node_id"] == "join")
+        }));
+        let skipped = find_message(&messages, "WaitAllNodeSkipped");
+        assert_eq!(skipped["node_id"], "join");
+        assert_eq!(skipped["unfinished_nodes"], serde_json::json!(["never"]));
+    }
+
+    #[tokio::test]
+    async fn inputs_required_check_fails_on_missing_inputs() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/required-inputs-flow.oo.yaml");
+
+        let run_result = run(RunArgs {
+            inputs_required_check: true,
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        let messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+
+        // `label` is nullable and `unused` feeds no node, only `threshold` is required
+        let err = run_result.unwrap_err().to_string();
+        assert_eq!(err, "Missing required inputs: threshold");
+        let finished = find_message(&messages, "SessionFinished");
+        assert_eq!(finished["error"], err);
+        assert!(
+            !messages
+                .iter()
+                .any(|message| message["type"] == "BlockStarted"),
+            "no node should run"
+        );
+
+        let runtime = TestRuntime::new(&root);
+        let run_result = run(RunArgs {
+            inputs: Some(r#"{"threshold": 10}"#.to_owned()),
+            inputs_required_check: true,
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        runtime.shutdown().await;
+        // only the connected but nullable `label` is missing
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+    }
+
+    #[tokio::test]
+    async fn node_filter_runs_matching_nodes_and_their_upstream() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/node-filter-flow.oo.yaml");
+
+        let run_result = run(RunArgs {
+            node_filter: Some(regex::Regex::new("^extract_").unwrap()),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        let messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+
+        let will_run = find_message(&messages, "FlowNodesWillRun");
+        let node_ids = |key: &str| -> Vec<String> {
+            let mut node_ids: Vec<String> = will_run[key]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|node_id| node_id.as_str().unwrap().to_owned())
+                .collect();
+            node_ids.sort();
+            node_ids
+        };
+        assert_eq!(node_ids("end_nodes"), vec!["extract_a", "extract_b"]);
+
+        let mut selected = [node_ids("start_nodes"), node_ids("mid_nodes")].concat();
+        selected.sort();
+        selected.dedup();
+        assert_eq!(selected, vec!["extract_b", "source"]);
+    }
+
+    #[tokio::test]
+    async fn failed_node_is_retried_with_the_same_inputs() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/retrying-flow.oo.yaml");
+        let marker =
+            std::env::temp_dir().join(format!("oocana-retry-marker-{}", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        // fails the first time only
+        let command = format!("test -f {0} || {{ touch {0}; exit 3; }}", marker.display());
+        let nodes_inputs = serde_json::json!({ "flaky": { "command": command } }).to_string();
+
+        let run_result = run(RunArgs {
+            nodes_inputs: Some(nodes_inputs),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        let timings = runtime.shared.node_timings.snapshot();
+        runtime.shutdown().await;
+        let _ = std::fs::remove_file(&marker);
+
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+        let attempts: Vec<_> = timings.iter().map(|timing| timing.success).collect();
+        assert_eq!(attempts, vec![false, true]);
+    }
+
+    #[tokio::test]
+    async fn node_fails_the_flow_once_retries_are_used_up() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/retrying-flow.oo.yaml");
+
+        let run_result = run(test_run_args(&runtime, &root, flow_path.to_str().unwrap())).await;
+        let timings = runtime.shared.node_timings.snapshot();
+        runtime.shutdown().await;
+
+        assert!(run_result.is_err());
+        let attempts: Vec<_> = timings.iter().map(|timing| timing.success).collect();
+        assert_eq!(attempts, vec![false, false]);
+    }
+
+    #[tokio::test]
+    async fn drain_during_the_retry_delay_fails_with_the_last_failure() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/retry-delay-flow.oo.yaml");
+        let drain = CancellationToken::new();
+
+        let run_future = run(RunArgs {
+            drain: Some(drain.clone()),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        });
+        tokio::pin!(run_future);
+
+        let mut messages = vec![];
+        tokio::select! {
+            result = &mut run_future => panic!("flow finished before drain: {result:?}"),
+            _ = async {
+                while let Ok(message) = runtime.reporter_rx.recv_async().await {
+                    let finished = message["type"] == "BlockFinished";
+                    messages.push(message);
+                    if finished {
+                        break;
+                    }
+                }
+            } => {}
+        }
+        drain.cancel();
+        let started = std::time::Instant::now();
+        let run_result = run_future.await;
+        let timings = runtime.shared.node_timings.snapshot();
+
+        let reporter_rx = runtime.shutdown().await;
+        messages.extend(reporter_rx.try_iter());
+
+        assert!(started.elapsed() < std::time::Duration::from_secs(30));
+        assert!(
+            run_result
+                .as_ref()
+                .is_err_and(|e| e.to_string() != SESSION_CANCEL_INFO),
+            "{run_result:?}"
+        );
+        let attempts: Vec<_> = timings.iter().map(|timing| timing.success).collect();
+        assert_eq!(attempts, vec![false]);
+        assert_eq!(
+            find_message(&messages, "FlowFinished")["_error"]["kind"],
+            "UserError"
+        );
+        assert_eq!(
+            find_message(&messages, "SessionFinished")["_error"]["kind"],
+            "UserError"
+        );
+    }
+
+    #[tokio::test]
+    async fn resumed_session_skips_completed_nodes() {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/resume-flow.oo.yaml");
+        let dir = std::env::temp_dir().join(format!("oocana-resume-{}", JobId::random()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_runs = dir.join("source.runs");
+        // source counts its runs, gate fails until the marker exists
+        let nodes_inputs = |gate: &str| {
+            serde_json::json!({
+                "source": { "command": format!("echo run >> {}; printf GREETING=hello", source_runs.display()) },
+                "gate": { "command": gate },
+            })
+            .to_string()
+        };
+
+        let runtime = TestRuntime::with_session_progress(&root, &dir);
+        let session_id = runtime.shared.session_id.clone();
+        let run_result = run(RunArgs {
+            nodes_inputs: Some(nodes_inputs("exit 3")),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        runtime.shutdown().await;
+        assert!(run_result.is_err());
+
+        let resumed = ResumedSession::read(&dir, &session_id).unwrap();
+        assert_eq!(
+            resumed.completed_nodes,
+            HashSet::from([NodeId::new("source".to_owned())])
+        );
+
+        let runtime = TestRuntime::with_session_progress(&root, &dir);
+        let resumed_id = runtime.shared.session_id.clone();
+        let run_result = run(RunArgs {
+            resume: Some(resumed),
+            nodes_inputs: Some(nodes_inputs("echo $GREETING")),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        let messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+        let source_run_count = std::fs::read_to_string(&source_runs)
+            .unwrap_or_default()
+            .lines()
+            .count();
+        let progress_left = ResumedSession::read(&dir, &resumed_id).is_ok();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(run_result.is_ok(), "resumed run failed: {run_result:?}");
+        assert_eq!(source_run_count, 1);
+        let gate_output = messages.iter().find(|message| {
+            message["type"] == "BlockOutput"
+                && message["handle"] == "stdout"
+                && message["stacks"][0]["node_id"] == "gate"
+        });
+        assert_eq!(gate_output.unwrap()["output"], "hello");
+        assert!(!progress_left, "a finished session keeps no progress");
+    }
+
+    #[tokio::test]
+    async fn broker_disconnect_fails_the_session() {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/checkpoint-flow.oo.yaml");
+        let runtime = TestRuntime::new(&root);
+        // stopping the scheduler closes the loopback transport while slow still runs
+        let scheduler_tx = runtime.shared.scheduler_tx.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            scheduler_tx.abort();
+        });
+
+        let started = tokio::time::Instant::now();
+        let run_result = run(RunArgs {
+            nodes_inputs: Some(
+                serde_json::json!({ "slow": { "command": "sleep 30" } }).to_string(),
+            ),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        let messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "session hung after the broker went away"
+        );
+        let err = run_result.unwrap_err().to_string();
+        assert!(err.contains(BROKER_DISCONNECTED_INFO), "{err}");
+        let finished = find_message(&messages, "SessionFinished");
+        assert_eq!(finished["_error"]["kind"], "BrokerDisconnected");
+    }
+
+    #[tokio::test]
+    async fn checkpoint_lets_a_crashed_flow_go_on_from_the_cache() {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/checkpoint-flow.oo.yaml");
+        let dir = std::env::temp_dir().join(format!("oocana-checkpoint-{}", JobId::random()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source_runs = dir.join("source.runs");
+        let nodes_inputs = |slow: &str| {
+            serde_json::json!({
+                "source": { "command": format!("echo run >> {}; printf GREETING=hello", source_runs.display()) },
+                "slow": { "command": slow },
+            })
+            .to_string()
+        };
+        let checkpointed = |flow: &str| {
+            get_flow_cache_path(flow)
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .is_some_and(|cache| cache.contains("GREETING=hello"))
+        };
+
+        let runtime = TestRuntime::with_options(
+            &root,
+            TestRuntimeOptions {
+                checkpoint_interval: Some(Duration::from_millis(100)),
+                ..Default::default()
+            },
+        );
+        let cancel = CancellationToken::new();
+        // crash while slow still runs, only a checkpoint saves the cache by then
+        let crash = tokio::spawn({
+            let cancel = cancel.clone();
+            let flow = flow_path.to_str().unwrap().to_owned();
+            async move {
+                let started = tokio::time::Instant::now();
+                while !checkpointed(&flow) && started.elapsed() < Duration::from_secs(5) {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+                cancel.cancel();
+                checkpointed(&flow)
+            }
+        });
+        let run_result = run(RunArgs {
+            nodes_inputs: Some(nodes_inputs("sleep 10")),
+            cancel: Some(cancel),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        runtime.shutdown().await;
+        assert!(run_result.is_err());
+        assert!(crash.await.unwrap(), "no checkpoint before the crash");
+
+        let runtime = TestRuntime::with_options(
+            &root,
+            TestRuntimeOptions {
+                use_cache: true,
+                ..Default::default()
+            },
+        );
+        let run_result = run(RunArgs {
+            nodes: Some(HashSet::from(["slow".to_owned()])),
+            nodes_inputs: Some(nodes_inputs("echo $GREETING")),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        let messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+        let source_run_count = std::fs::read_to_string(&source_runs)
+            .unwrap_or_default()
+            .lines()
+            .count();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert!(run_result.is_ok(), "resumed run failed: {run_result:?}");
+        assert_eq!(source_run_count, 1);
+        let slow_output = messages.iter().find(|message| {
+            message["type"] == "BlockOutput"
+                && message["handle"] == "stdout"
+                && message["stacks"][0]["node_id"] == "slow"
+        });
+        assert_eq!(slow_output.unwrap()["output"], "hello");
+    }
+
+    #[tokio::test]
+    async fn output_cache_serves_repeated_runs_of_cacheable_blocks() {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/output-cache-flow.oo.yaml");
+        let dir = std::env::temp_dir().join(format!("oocana-output-cache-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = |node: &str| dir.join(format!("{node}.runs"));
+        let nodes_inputs = serde_json::json!({
+            "cached": { "command": format!("echo run >> {}; echo cached", marker("cached").display()) },
+            "uncached": { "command": format!("echo run >> {}; echo uncached", marker("uncached").display()) },
+        })
+        .to_string();
+
+        let mut results = vec![];
+        for _ in 0..2 {
+            let runtime = TestRuntime::with_output_cache(&root, OutputCache::new(&dir));
+            let run_result = run(RunArgs {
+                nodes_inputs: Some(nodes_inputs.clone()),
+                ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+            })
+            .await;
+            assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+            let messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+            results.push(find_block_finished_result(&messages, "cached"));
+        }
+        let runs = |node: &str| {
+            std::fs::read_to_string(marker(node))
+                .unwrap_or_default()
+                .lines()
+                .count()
+        };
+        let (cached_runs, uncached_runs) = (runs("cached"), runs("uncached"));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(cached_runs, 1);
+        assert_eq!(uncached_runs, 2);
+        assert_eq!(
+            results[1]
+                .as_ref()
+                .and_then(|result| result.get("stdout").cloned()),
+            Some(serde_json::json!("cached"))
+        );
+    }
+
+    #[tokio::test]
+    async fn node_over_its_time_budget_is_held() {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/output-cache-flow.oo.yaml");
+        let budgets = TimeBudgets::new(HashMap::from([(
+            NodeId::new("cached".to_owned()),
+            Duration::from_millis(10),
+        )]));
+        let nodes_inputs = serde_json::json!({
+            "cached": { "command": "sleep 0.05; echo cached" },
+        })
+        .to_string();
+
+        let mut runs = vec![];
+        for _ in 0..2 {
+            let runtime = TestRuntime::with_time_budgets(&root, budgets.clone());
+            let run_result = run(RunArgs {
+                nodes_inputs: Some(nodes_inputs.clone()),
+                ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+            })
+            .await;
+            assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+            runs.push(runtime.shutdown().await.try_iter().collect::<Vec<_>>());
+        }
+        let started = |messages: &[serde_json::Value], node_id: &str| {
+            messages.iter().any(|message| {
+                message["type"] == "BlockStarted"
+                    && message["stacks"]
+                        .as_array()
+                        .and_then(|stacks| stacks.last())
+                        .is_some_and(|level| level["node_id"] == node_id)
+            })
+        };
+
+        assert!(budgets.is_exhausted(&NodeId::new("cached".to_owned())));
+        assert!(started(&runs[0], "cached"));
+        assert!(
+            !runs[0]
+                .iter()
+                .any(|message| message["type"] == "NodeHeldByTimeBudget")
+        );
+
+        // the budget is spent, the run is held and reported once the rest of the flow is done
+        assert!(!started(&runs[1], "cached"));
+        assert!(started(&runs[1], "uncached"));
+        let held = find_message(&runs[1], "NodeHeldByTimeBudget");
+        assert_eq!(held["node_id"], "cached");
+        assert_eq!(held["held_runs"], 1);
+    }
+
+    #[tokio::test]
+    async fn output_only_writes_selected_handles_to_output_file() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/multi-outputs-flow.oo.yaml");
+        let output_file =
+            std::env::temp_dir().join(format!("oocana-output-only-{}.json", std::process::id()));
+
+        let run_result = run(RunArgs {
+            output_file: Some(output_file.clone()),
+            output_only: Some(HashSet::from([HandleName::new("right".to_owned())])),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        runtime.shutdown().await;
+
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+        let outputs: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&output_file).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&output_file);
+        assert_eq!(outputs, serde_json::json!({ "right": 20 }));
+    }
+
+    #[tokio::test]
+    async fn output_stream_writes_a_line_per_output_in_order() {
+        #[derive(Clone, Default)]
+        struct Captured(Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/stream-outputs-flow.oo.yaml");
+        let captured = Captured::default();
+
+        let run_result = run(RunArgs {
+            output_stream: Some(Box::new(captured.clone())),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        runtime.shutdown().await;
+
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+        let stdout = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        let lines = stdout
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::json!({ "handle": "first", "value": 10 }),
+                serde_json::json!({ "handle": "second", "value": 10 }),
+                serde_json::json!({ "handle": "third", "value": 10 }),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn recorded_block_replays_to_the_same_output() {
+        record_and_replay("tests/fixtures/record-flow.oo.yaml").await;
+    }
+
+    #[tokio::test]
+    async fn block_recorded_in_a_subflow_replays_from_the_root_flow() {
+        record_and_replay("tests/fixtures/record-subflow/flow.oo.yaml").await;
+    }
+
+    /// Record `shout` while running `fixture`, then replay it with `fixture` as the block.
+    async fn record_and_replay(fixture: &str) {
+        let root = project_root();
+        let flow_path = root.join(fixture);
+        let dir = std::env::temp_dir().join(format!("oocana-block-record-{}", JobId::random()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let record_path = dir.join("shout.record.json");
+        let output_file = dir.join("outputs.json");
+
+        let node_output = |messages: &[serde_json::Value], node_id: &str| {
+            messages
+                .iter()
+                .find(|message| {
+                    message["type"] == "BlockOutput"
+                        && message["handle"] == "stdout"
+                        && message["stacks"]
+                            .as_array()
+                            .and_then(|stacks| stacks.last())
+                            .is_some_and(|level| level["node_id"] == node_id)
+                })
+                .map(|message| message["output"].clone())
+                .unwrap_or_else(|| panic!("no output of node {node_id}"))
+        };
+
+        let runtime = TestRuntime::with_block_recorder(
+            &root,
+            BlockRecorder::new(NodeId::new("shout".to_owned()), record_path.clone()),
+        );
+        let run_result = run(test_run_args(&runtime, &root, flow_path.to_str().unwrap())).await;
+        let messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+
+        let record = BlockRecord::read(&record_path).unwrap();
+        assert_eq!(record.node_id, NodeId::new("shout".to_owned()));
+        assert_eq!(record.inputs["command"], node_output(&messages, "source"));
+
+        let runtime = TestRuntime::new(&root);
+        let run_result = run(RunArgs {
+            replay_block: Some(record),
+            output_file: Some(output_file.clone()),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        let replay_messages: Vec<_> = runtime.shutdown().await.try_iter().collect();
+        assert!(run_result.is_ok(), "replay failed: {run_result:?}");
+
+        // only the recorded block ran
+        let started = replay_messages
+            .iter()
+            .filter(|message| message["type"] == "BlockStarted")
+            .count();
+        assert_eq!(started, 1);
+        let outputs: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&output_file).unwrap()).unwrap();
+        assert_eq!(outputs["stdout"], node_output(&messages, "shout"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn collect_merge_emits_all_aliased_values_as_one_flow_output() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/collect-outputs-flow.oo.yaml");
+        let output_file = std::env::temp_dir().join(format!(
+            "oocana-collect-outputs-{}.json",
+            std::process::id()
+        ));
+
+        let run_result = run(RunArgs {
+            output_file: Some(output_file.clone()),
+            ..test_run_args(&runtime, &root, flow_path.to_str().unwrap())
+        })
+        .await;
+        runtime.shutdown().await;
+
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+        let outputs: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&output_file).unwrap()).unwrap();
+        let _ = std::fs::remove_file(&output_file);
+        let mut values = outputs["values"].as_array().cloned().unwrap_or_default();
+        values.sort_by_key(|v| v.as_i64());
+        assert_eq!(values, vec![serde_json::json!(10), serde_json::json!(20)]);
+    }
+
+    /// Run cached-flow.oo.yaml up to `sink`, `source` is skipped since sink's input is cached.
+    async fn run_sink_with_cached_source(runtime: TestRuntime) -> Vec<serde_json::Value> {
+        let root = project_root();
+        let flow_path = root.join("tests/fixtures/cached-flow.oo.yaml");
+        let mut path_finder = BlockPathFinder::new(root.clone(), None);
+        let flow_block = BlockResolver::new()
+            .resolve_flow_block(flow_path.to_str().unwrap(), &mut path_finder)
+            .unwrap();
+
+        // what a previous run left in the cache for the sink node
+        let mut cached = NodeInputValues::new(false);
+        cached.insert(
+            &NodeId::from("sink".to_string()),
+            &HandleName::new("value".to_string()),
+            Arc::new(OutputValue::new(serde_json::json!(10), true)),
+        );
+        let scope = RuntimeScope {
+            session_id: runtime.shared.session_id.clone(),
+            pkg_name: None,
+            path: root.clone(),
+            data_dir: root.to_string_lossy().to_string(),
+            pkg_root: root.clone(),
+            node_id: None,
+            enable_layer: false,
+            is_inject: false,
+            data_dir_readonly: false,
+        };
+        let (block_status_tx, block_status_rx) = block_status::create();
+        let job_id = JobId::random();
+
+        let _handle = execute_flow_job(FlowJobParameters {
+            flow_block,
+            shared: runtime.shared.clone(),
+            stacks: BlockJobStacks::new(),
+            flow_job_id: job_id.clone(),
+            inputs: None,
+            node_value_store: cached,
+            parent_block_status: block_status_tx,
+            nodes: Some(HashSet::from([NodeId::from("sink".to_string())])),
+            parent_scope: scope.clone(),
+            scope,
+            slot_blocks: HashMap::new(),
+            timeout: None,
+            path_finder,
+            vault_client: Arc::new(None),
+        });
+        loop {
+            match block_status_rx.recv().await {
+                Some(block_status::Status::Done {
+                    job_id: done,
+                    error,
+                    ..
+                }) if done == job_id => {
+                    assert!(error.is_none(), "flow failed: {error:?}");
+                    break;
+                }
+                Some(_) => {}
+                None => panic!("flow job stopped without finishing"),
+            }
+        }
+        runtime.shutdown().await.try_iter().collect()
+    }
+
+    #[tokio::test]
+    async fn nodes_skipped_for_cached_inputs_report_cache_hits() {
+        let root = project_root();
+        let messages = run_sink_with_cached_source(TestRuntime::new(&root)).await;
+
+        let cache_hits: Vec<_> = messages
+            .iter()
+            .filter(|message| message["type"] == "NodeCacheHit")
+            .map(|message| message["node_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(cache_hits, vec!["source"]);
+    }
+
+    #[tokio::test]
+    async fn replay_reports_outputs_of_cached_nodes_from_cache() {
+        let root = project_root();
+        let messages =
+            run_sink_with_cached_source(TestRuntime::with_replayed_cached_outputs(&root)).await;
+
+        let source_outputs: Vec<_> = messages
+            .iter()
+            .filter(|message| {
+                message["type"] == "BlockOutput" && message["stacks"][0]["node_id"] == "source"
+            })
+            .collect();
+        assert_eq!(source_outputs.len(), 1, "messages: {messages:?}");
+        assert_eq!(source_outputs[0]["handle"], "above");
+        assert_eq!(source_outputs[0]["output"], 10);
+        assert_eq!(source_outputs[0]["from_cache"], true);
+
+        // sink did run, nothing of it is marked
+        assert!(
+            messages
+                .iter()
+                .filter(|message| message["from_cache"] == true)
+                .all(|message| message["stacks"][0]["node_id"] == "source")
+        );
+    }
+
+    #[tokio::test]
+    async fn run_records_node_timings() {
+        let root = project_root();
+        let runtime = TestRuntime::new(&root);
+        let flow_path = root.join("tests/fixtures/multi-outputs-flow.oo.yaml");
+
+        let run_result = run(test_run_args(&runtime, &root, flow_path.to_str().unwrap())).await;
+        let timings = runtime.shared.node_timings.snapshot();
+        runtime.shutdown().await;
+
+        assert!(run_result.is_ok(), "flow run failed: {run_result:?}");
+        let mut nodes: Vec<_> = timings
+            .iter()
+            .map(|timing| (timing.node_id.as_str(), timing.success))
+            .collect();
+        nodes.sort();
+        assert_eq!(nodes, vec![("source-a", true), ("source-b", true)]);
+        assert!(
+            timings
+                .iter()
+                .all(|timing| timing.flow == flow_path.to_str().unwrap())
+        );
+    }
 }
@@ -0,0 +1,54 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use manifest_meta::NodeId;
+use serde::{Deserialize, Serialize};
+use utils::error::Result;
+
+/// Sidecar file written next to the session log.
+pub const NODE_TIMINGS_FILE: &str = "node-timings.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeTiming {
+    pub node_id: NodeId,
+    /// Path of the flow the node belongs to, node ids are only unique inside one flow.
+    pub flow: String,
+    pub duration_ms: u64,
+    pub success: bool,
+}
+
+/// Wall-clock duration of every node run in a session, subflow nodes included.
+#[derive(Debug, Clone, Default)]
+pub struct NodeTimings {
+    timings: Arc<Mutex<Vec<NodeTiming>>>,
+}
+
+impl NodeTimings {
+    pub fn record(&self, node_id: &NodeId, flow: &str, duration: Duration, success: bool) {
+        self.timings.lock().unwrap().push(NodeTiming {
+            node_id: node_id.to_owned(),
+            flow: flow.to_owned(),
+            duration_ms: duration.as_millis() as u64,
+            success,
+        });
+    }
+
+    pub fn snapshot(&self) -> Vec<NodeTiming> {
+        self.timings.lock().unwrap().clone()
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(&self.snapshot())?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+pub fn read_node_timings(path: &Path) -> Result<Vec<NodeTiming>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read node timings {}: {e}", path.display()))?;
+    Ok(serde_json::from_str(&content)?)
+}
@@ -0,0 +1,182 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use job::BlockInputs;
+use manifest_meta::{HandleName, TaskBlockExecutor};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::warn;
+
+/// Directory under `pkg_data_root` that holds the output cache entries.
+pub const OUTPUT_CACHE_DIR: &str = ".output-cache";
+
+/// Entries not used for this long are removed by [`OutputCache::prune`].
+pub const OUTPUT_CACHE_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Outputs of task blocks keyed by what they ran with, shared across sessions. A task block that
+/// runs again with the same block, entry file, executor and inputs gets its outputs from here
+/// instead.
+#[derive(Debug, Clone)]
+pub struct OutputCache {
+    dir: PathBuf,
+}
+
+impl OutputCache {
+    pub fn new(pkg_data_root: &Path) -> Self {
+        Self {
+            dir: pkg_data_root.join(OUTPUT_CACHE_DIR),
+        }
+    }
+
+    /// `None` when an input only lives in an executor's memory, a hash of it means nothing in
+    /// another session. The entry file of the executor is read from `block_dir`, an edited
+    /// block doesn't get the outputs of its old code.
+    pub(crate) fn entry(
+        &self,
+        block: &str,
+        block_dir: &Path,
+        executor: &TaskBlockExecutor,
+        inputs: &Option<BlockInputs>,
+    ) -> Option<OutputCacheEntry> {
+        let mut values = BTreeMap::new();
+        for (handle, input) in inputs.iter().flatten() {
+            if !input.is_json_serializable {
+                return None;
+            }
+            values.insert(handle.as_str(), &input.value);
+        }
+        let entry_hash = executor
+            .entry()
+            .and_then(|entry| std::fs::read(block_dir.join(entry)).ok())
+            .map(|code| utils::calculate_short_hash(&String::from_utf8_lossy(&code), 64));
+
+        let key = serde_json::to_string(&(block, entry_hash, executor, values)).ok()?;
+        let hash = utils::calculate_short_hash(&key, 64);
+        Some(OutputCacheEntry {
+            path: self.dir.join(format!("{hash}.json")),
+        })
+    }
+
+    /// Remove the entries last used more than `max_age` ago.
+    pub fn prune(&self, max_age: Duration) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        let now = SystemTime::now();
+        for entry in entries.flatten() {
+            let expired = entry
+                .metadata()
+                .and_then(|metadata| metadata.modified())
+                .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > max_age));
+            if expired {
+                if let Err(e) = std::fs::remove_file(entry.path()) {
+                    warn!(
+                        "failed to remove output cache {}: {e}",
+                        entry.path().display()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedOutputs {
+    outputs: HashMap<HandleName, Value>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct OutputCacheEntry {
+    path: PathBuf,
+}
+
+impl OutputCacheEntry {
+    pub fn load(&self) -> Option<HashMap<HandleName, Value>> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        match serde_json::from_str::<CachedOutputs>(&content) {
+            Ok(cached) => {
+                // a used entry is kept by the prune
+                let _ = std::fs::File::options()
+                    .write(true)
+                    .open(&self.path)
+                    .and_then(|file| file.set_modified(SystemTime::now()));
+                Some(cached.outputs)
+            }
+            Err(e) => {
+                warn!("ignore broken output cache {}: {e}", self.path.display());
+                None
+            }
+        }
+    }
+
+    pub fn store(&self, outputs: HashMap<HandleName, Value>) {
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| {
+                let json = serde_json::to_string(&CachedOutputs { outputs })?;
+                std::fs::write(&self.path, json)
+            });
+        if let Err(e) = result {
+            warn!("failed to write output cache {}: {e}", self.path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_changes_with_the_block_entry_file() {
+        let dir =
+            std::env::temp_dir().join(format!("oocana-output-cache-{}", job::JobId::random()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cache = OutputCache::new(&dir);
+        let executor: TaskBlockExecutor = serde_json::from_value(serde_json::json!({
+            "name": "python",
+            "options": { "entry": "main.py" }
+        }))
+        .unwrap();
+        let entry_path = |code: &str| {
+            std::fs::write(dir.join("main.py"), code).unwrap();
+            cache.entry("block", &dir, &executor, &None).unwrap().path
+        };
+
+        let first = entry_path("print(1)");
+        assert_eq!(entry_path("print(1)"), first);
+        assert_ne!(entry_path("print(2)"), first);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_recently_used_entries() {
+        let dir =
+            std::env::temp_dir().join(format!("oocana-output-cache-{}", job::JobId::random()));
+        let cache = OutputCache::new(&dir);
+        let executor: TaskBlockExecutor =
+            serde_json::from_value(serde_json::json!({ "name": "shell" })).unwrap();
+        let old = cache.entry("old", &dir, &executor, &None).unwrap();
+        let used = cache.entry("used", &dir, &executor, &None).unwrap();
+        for entry in [&old, &used] {
+            entry.store(HashMap::new());
+            std::fs::File::options()
+                .write(true)
+                .open(&entry.path)
+                .unwrap()
+                .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+                .unwrap();
+        }
+
+        assert!(used.load().is_some());
+        cache.prune(Duration::from_secs(60 * 60));
+
+        assert!(old.load().is_none());
+        assert!(used.load().is_some());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,103 @@
+//! Puts the outputs a block sent in pieces back together, see `BlockOutputChunk`.
+
+use std::collections::HashMap;
+
+use job::JobId;
+use manifest_meta::{HandleName, JsonValue};
+use utils::error::Result;
+
+struct PendingOutput {
+    chunks: Vec<Option<String>>,
+    received: usize,
+}
+
+#[derive(Default)]
+pub(crate) struct OutputChunks {
+    pending: HashMap<(JobId, HandleName), PendingOutput>,
+}
+
+impl OutputChunks {
+    /// Keep one piece, returns the output once its last piece arrived. A piece that doesn't fit
+    /// the ones before drops the output.
+    pub fn add(
+        &mut self,
+        job_id: &JobId,
+        handle: &HandleName,
+        chunk_index: usize,
+        chunk_total: usize,
+        data: String,
+    ) -> Option<Result<JsonValue>> {
+        let key = (job_id.to_owned(), handle.to_owned());
+        let pending = self
+            .pending
+            .entry(key.clone())
+            .or_insert_with(|| PendingOutput {
+                chunks: vec![None; chunk_total],
+                received: 0,
+            });
+
+        if pending.chunks.len() != chunk_total || chunk_index >= chunk_total {
+            let expected = pending.chunks.len();
+            self.pending.remove(&key);
+            return Some(Err(format!(
+                "Output chunk {chunk_index} of {chunk_total} for handle {handle} doesn't match the {expected} chunks expected"
+            )
+            .into()));
+        }
+
+        let slot = &mut pending.chunks[chunk_index];
+        if slot.is_none() {
+            pending.received += 1;
+        }
+        *slot = Some(data);
+        if pending.received < chunk_total {
+            return None;
+        }
+
+        let pending = self.pending.remove(&key)?;
+        let data = pending.chunks.into_iter().flatten().collect::<String>();
+        Some(serde_json::from_str(&data).map_err(|e| {
+            format!("Output chunks for handle {handle} are not valid JSON: {e}").into()
+        }))
+    }
+
+    /// Drop the pieces of a job that finished before sending them all.
+    pub fn discard(&mut self, job_id: &JobId) {
+        self.pending.retain(|(id, _), _| id != job_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn chunks_are_joined_in_index_order() {
+        let mut chunks = OutputChunks::default();
+        let job_id = JobId::new("job".to_owned());
+        let handle = HandleName::new("out".to_owned());
+
+        assert!(chunks.add(&job_id, &handle, 2, 3, "3]".into()).is_none());
+        assert!(chunks.add(&job_id, &handle, 0, 3, "[1,".into()).is_none());
+        let output = chunks.add(&job_id, &handle, 1, 3, "2,".into());
+        assert_eq!(output.unwrap().unwrap(), json!([1, 2, 3]));
+        assert!(chunks.pending.is_empty());
+    }
+
+    #[test]
+    fn mismatched_chunk_drops_the_output() {
+        let mut chunks = OutputChunks::default();
+        let job_id = JobId::new("job".to_owned());
+        let handle = HandleName::new("out".to_owned());
+
+        assert!(chunks.add(&job_id, &handle, 0, 2, "[1,".into()).is_none());
+        let err = chunks.add(&job_id, &handle, 1, 3, "2]".into()).unwrap();
+        assert!(err.unwrap_err().to_string().contains("doesn't match"));
+        assert!(chunks.pending.is_empty());
+
+        assert!(chunks.add(&job_id, &handle, 0, 2, "[1,".into()).is_none());
+        chunks.discard(&job_id);
+        assert!(chunks.pending.is_empty());
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use manifest_meta::{Block, InjectionTarget, Node, NodeId, Slot, SubflowBlock, read_flow_or_block};
+use manifest_reader::reader::{read_package_identity, should_skip_package_layer_handling_for_path};
+use serde::Serialize;
+use utils::error::Result;
+
+use crate::GetPackageArgs;
+
+/// What a node's block resolved to, for auditing which files and packages a flow runs.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolvedNode {
+    /// Node ids of subflow nodes are prefixed with the ids of the nodes they are nested in, joined
+    /// with `/`.
+    pub node_id: String,
+    pub kind: &'static str,
+    /// Absolute path of the block manifest, `None` for blocks defined inline in the flow.
+    pub manifest: Option<String>,
+    pub package: Option<ResolvedPackage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolvedPackage {
+    pub path: PathBuf,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    /// Whether the package layer exists, or the package doesn't need one.
+    pub layer: bool,
+}
+
+/// Resolve every node of a flow, subflow nodes included, sorted by node id.
+pub fn resolve_nodes(args: GetPackageArgs<'_>) -> Result<Vec<ResolvedNode>> {
+    let GetPackageArgs {
+        block,
+        mut block_reader,
+        mut path_finder,
+        nodes,
+    } = args;
+    let filter_nodes = nodes.unwrap_or_default();
+
+    let Block::Flow(flow) = read_flow_or_block(block, &mut block_reader, &mut path_finder)? else {
+        return Err("wrong block type. except flow get others".into());
+    };
+
+    let mut resolved = vec![];
+    let flow = flow.read().unwrap();
+    for (node_id, node) in &flow.nodes {
+        if filter_nodes.is_empty() || filter_nodes.contains(node_id.as_str()) {
+            push_resolved(&mut resolved, node_id.to_string(), node);
+        }
+    }
+    resolved.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+    Ok(resolved)
+}
+
+fn push_resolved(resolved: &mut Vec<ResolvedNode>, node_id: String, node: &Node) {
+    let kind = match node {
+        Node::Task(_) => "task",
+        Node::Flow(_) => "subflow",
+        Node::Slot(_) => "slot",
+        Node::Service(_) => "service",
+        Node::Condition(_) => "condition",
+    };
+    let package = node.package_path().map(|path| {
+        let identity = read_package_identity(&path);
+        ResolvedPackage {
+            layer: package_layer_exists(&path),
+            name: identity.as_ref().and_then(|identity| identity.name.clone()),
+            version: identity.and_then(|identity| identity.version),
+            path: absolute(path),
+        }
+    });
+    resolved.push(ResolvedNode {
+        node_id: node_id.clone(),
+        kind,
+        manifest: node
+            .block()
+            .path_str()
+            .map(|path| absolute(PathBuf::from(path)).to_string_lossy().to_string()),
+        package,
+    });
+
+    if let Node::Flow(subflow) = node {
+        for (inner_id, inner) in &subflow.flow.read().unwrap().nodes {
+            push_resolved(resolved, format!("{node_id}/{inner_id}"), inner);
+        }
+    }
+}
+
+/// A package nodes of a flow are injected into, one entry of the flow's `InjectionStore`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolvedInjection {
+    /// Flow the injected nodes are in.
+    pub flow: PathBuf,
+    pub package: PathBuf,
+    pub package_version: String,
+    /// Sorted by node id.
+    pub nodes: Vec<ResolvedInjectionNode>,
+    pub scripts: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolvedInjectionNode {
+    pub node_id: String,
+    pub absolute_entry: PathBuf,
+    /// Path of the entry inside the package once it's injected.
+    pub relative_entry: PathBuf,
+}
+
+/// The injection stores of a flow and of the subflows it runs, slot subflows included, sorted by
+/// flow and package. `nodes` only filters the injected nodes of the flow itself.
+pub fn resolve_injections(args: GetPackageArgs<'_>) -> Result<Vec<ResolvedInjection>> {
+    let GetPackageArgs {
+        block,
+        mut block_reader,
+        mut path_finder,
+        nodes,
+    } = args;
+    let filter_nodes = nodes.unwrap_or_default();
+
+    let Block::Flow(flow) = read_flow_or_block(block, &mut block_reader, &mut path_finder)? else {
+        return Err("wrong block type. except flow get others".into());
+    };
+
+    let mut injections = vec![];
+    walk_flows(flow, &filter_nodes, |flow, walked| {
+        for (target, meta) in flow.injection_store.iter().flatten() {
+            let InjectionTarget::Package(package) = target;
+            let mut nodes: Vec<ResolvedInjectionNode> = meta
+                .nodes
+                .iter()
+                .filter(|node| walked(&node.node_id))
+                .map(|node| ResolvedInjectionNode {
+                    node_id: node.node_id.to_string(),
+                    absolute_entry: absolute(node.absolute_entry.clone()),
+                    relative_entry: node.relative_entry.clone(),
+                })
+                .collect();
+            nodes.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+            injections.push(ResolvedInjection {
+                flow: absolute(flow.path.clone()),
+                package: absolute(package.clone()),
+                package_version: meta.package_version.clone(),
+                nodes,
+                scripts: meta.scripts.clone().unwrap_or_default(),
+            });
+        }
+    });
+
+    injections.sort_by(|a, b| (&a.flow, &a.package).cmp(&(&b.flow, &b.package)));
+    Ok(injections)
+}
+
+/// Visit `flow` and the flows it runs, subflows and slot subflows at any depth, each once so
+/// recursive subflows end. `nodes` only filters the nodes of `flow` itself, `visit` is given
+/// whether a node of the flow is walked.
+pub(crate) fn walk_flows(
+    flow: Arc<RwLock<SubflowBlock>>,
+    nodes: &HashSet<String>,
+    mut visit: impl FnMut(&SubflowBlock, &dyn Fn(&NodeId) -> bool),
+) {
+    let mut visited: HashSet<PathBuf> = HashSet::new();
+    let mut pending: Vec<Arc<RwLock<SubflowBlock>>> = vec![flow];
+    let mut is_root = true;
+    // no flow guard is held while the next one is locked, a recursive subflow is the same lock
+    while let Some(flow) = pending.pop() {
+        let flow = flow.read().unwrap();
+        if !visited.insert(flow.path.clone()) {
+            continue;
+        }
+        let walked =
+            |node_id: &NodeId| !is_root || nodes.is_empty() || nodes.contains(node_id.as_str());
+
+        for (node_id, node) in &flow.nodes {
+            let Node::Flow(subflow) = node else {
+                continue;
+            };
+            if !walked(node_id) {
+                continue;
+            }
+            pending.push(Arc::clone(&subflow.flow));
+            for slot in subflow.slots.iter().flat_map(|slots| slots.values()) {
+                if let Slot::Subflow(slot) = slot {
+                    pending.push(Arc::clone(&slot.subflow));
+                }
+            }
+        }
+
+        visit(&flow, &walked);
+        is_root = false;
+    }
+}
+
+/// Blocks keep the path they were found by, which is relative when the flow path was.
+fn absolute(path: PathBuf) -> PathBuf {
+    path.canonicalize().unwrap_or(path)
+}
+
+pub(crate) fn package_layer_exists(package: &Path) -> bool {
+    should_skip_package_layer_handling_for_path(package)
+        || matches!(
+            layer::package_layer_status(package),
+            Ok(layer::PackageLayerStatus::Exist)
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use manifest_meta::BlockResolver;
+    use manifest_reader::path_finder::BlockPathFinder;
+
+    use super::*;
+
+    #[test]
+    fn resolve_nodes_reports_every_node_manifest() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .canonicalize()
+            .unwrap();
+        let fixtures = root.join("tests/fixtures");
+        let flow_path = fixtures.join("resolve-blocks");
+
+        let resolved = resolve_nodes(GetPackageArgs {
+            block: flow_path.to_str().unwrap(),
+            block_reader: BlockResolver::new(),
+            path_finder: BlockPathFinder::new(root.clone(), Some(vec![fixtures.clone()])),
+            nodes: None,
+        })
+        .expect("resolve should succeed");
+
+        let connector_package = fixtures.join("@connector/demo");
+        let connector_task = connector_package
+            .join("tasks/echo/task.oo.yaml")
+            .to_string_lossy()
+            .to_string();
+        let package = Some(ResolvedPackage {
+            path: connector_package,
+            name: Some("@connector/demo".to_owned()),
+            version: Some("0.1.0".to_owned()),
+            layer: true,
+        });
+        // the package path is taken from the manifest location (`<pkg>/tasks/<name>/`), for local
+        // blocks that is a directory without a package file
+        let local_package = Some(ResolvedPackage {
+            path: fixtures.clone(),
+            name: None,
+            version: None,
+            layer: false,
+        });
+        assert_eq!(
+            resolved,
+            vec![
+                ResolvedNode {
+                    node_id: "connector-task".to_owned(),
+                    kind: "task",
+                    manifest: Some(connector_task.clone()),
+                    package: package.clone(),
+                },
+                ResolvedNode {
+                    node_id: "local-task".to_owned(),
+                    kind: "task",
+                    manifest: Some(
+                        flow_path
+                            .join("echo/task.oo.yaml")
+                            .to_string_lossy()
+                            .to_string()
+                    ),
+                    package: local_package.clone(),
+                },
+                ResolvedNode {
+                    node_id: "nested".to_owned(),
+                    kind: "subflow",
+                    manifest: Some(
+                        flow_path
+                            .join("nested/subflow.oo.yaml")
+                            .to_string_lossy()
+                            .to_string()
+                    ),
+                    package: local_package.clone(),
+                },
+                ResolvedNode {
+                    node_id: "nested/inner-task".to_owned(),
+                    kind: "task",
+                    manifest: Some(connector_task),
+                    package,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn resolve_injections_reports_the_injected_nodes() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .canonicalize()
+            .unwrap();
+        let fixtures = root.join("tests/fixtures");
+        let flow_path = fixtures.join("injection-flow");
+
+        let resolve = |nodes: Option<&str>| {
+            resolve_injections(GetPackageArgs {
+                block: flow_path.to_str().unwrap(),
+                block_reader: BlockResolver::new(),
+                path_finder: BlockPathFinder::new(root.clone(), Some(vec![fixtures.clone()])),
+                nodes: nodes.map(|node| HashSet::from([node.to_owned()])),
+            })
+            .expect("resolve should succeed")
+        };
+        let injections = resolve(None);
+
+        assert_eq!(
+            injections,
+            vec![ResolvedInjection {
+                flow: flow_path.join("flow.oo.yaml"),
+                package: fixtures.join("@connector/demo"),
+                package_version: "0.1.0".to_owned(),
+                nodes: vec![ResolvedInjectionNode {
+                    node_id: "injected".to_owned(),
+                    absolute_entry: flow_path.join("scriptlets/injected.py"),
+                    relative_entry: PathBuf::from("scriptlets/injected.py"),
+                }],
+                scripts: vec!["import os".to_owned()],
+            }]
+        );
+
+        let dumped = serde_json::to_value(&injections).unwrap();
+        assert_eq!(dumped[0]["nodes"][0]["node_id"], "injected");
+        assert_eq!(dumped[0]["package_version"], "0.1.0");
+
+        assert!(resolve(Some("plain"))[0].nodes.is_empty());
+    }
+}
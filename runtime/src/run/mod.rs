@@ -3,8 +3,11 @@ use std::{
     sync::{Arc, RwLock},
 };
 
-use job::{BlockInputs, BlockJobStacks, JobId, RuntimeScope};
-use mainframe::reporter::ReporterMessage;
+use job::{BlockInputs, BlockJobStackLevel, BlockJobStacks, JobId, RuntimeScope};
+use mainframe::{
+    reporter::{ErrorDetail, ReporterMessage},
+    scheduler::ErrorKind,
+};
 use manifest_meta::{
     InputDefPatchMap, InputHandles, NodeId, OutputHandles, ServiceBlock, Slot, SlotBlock,
     SubflowBlock, TaskBlock,
@@ -26,10 +29,6 @@ pub struct CommonJobParameters {
     pub scope: RuntimeScope,
 }
 
-/// Maximum allowed recursion depth for job execution.
-/// Exceeding this limit will cause the job to fail with an error.
-pub const MAX_RECURSION_DEPTH: usize = 50;
-
 // TODO: consider boxing large fields to reduce enum size
 #[allow(clippy::large_enum_variant)]
 pub enum JobParams {
@@ -39,6 +38,7 @@ pub enum JobParams {
         parent_scope: RuntimeScope,
         node_value_store: NodeInputValues,
         slot_blocks: Option<HashMap<NodeId, Slot>>,
+        timeout: Option<u64>,
         path_finder: manifest_reader::path_finder::BlockPathFinder,
         common: CommonJobParameters,
         vault_client: Arc<Option<vault::VaultClient>>,
@@ -80,24 +80,45 @@ impl JobParams {
             JobParams::Condition { common, .. } => common,
         }
     }
+
+    fn flow_path(&self) -> Option<String> {
+        match self {
+            JobParams::Flow { flow_block, .. } => Some(flow_block.read().unwrap().path_str.clone()),
+            _ => None,
+        }
+    }
 }
 
 pub fn run_job(params: JobParams) -> Option<BlockJobHandle> {
-    let depth = params.common().stacks.depth();
-    if depth >= MAX_RECURSION_DEPTH {
-        let common = params.common();
-        common.shared.reporter.send(ReporterMessage::BlockFinished {
-            session_id: &common.shared.session_id,
-            job_id: &common.job_id,
-            block_path: &None,
-            stacks: common.stacks.vec(),
-            error: Some(format!(
-                "Maximum recursion depth exceeded: {depth} (limit: {MAX_RECURSION_DEPTH})"
-            )),
-            result: None,
-            finish_at: ReporterMessage::now(),
-        });
-        return None;
+    let common = params.common();
+    let depth = common.stacks.depth();
+    if depth >= common.shared.max_flow_depth {
+        let flow_path = params.flow_path();
+        let mut error = format!(
+            "Maximum recursion depth exceeded: {depth} (limit: {})",
+            common.shared.max_flow_depth
+        );
+        if let Some(flow) = &flow_path {
+            error = format!("{error}: {}", nesting_path(common.stacks.vec(), flow));
+        }
+        common
+            .shared
+            .reporter
+            .block(common.job_id.to_owned(), flow_path, common.stacks.clone())
+            .error(&error, ErrorKind::SchedulerError);
+        // the parent flow handles the failure once it holds the job
+        common.block_status.finish(
+            common.job_id.to_owned(),
+            None,
+            Some(error.clone()),
+            Some(ErrorKind::SchedulerError),
+            Some(ErrorDetail {
+                message: Some(error),
+                stack: common.stacks.vec().to_owned(),
+                kind: Some(ErrorKind::SchedulerError),
+            }),
+        );
+        return Some(BlockJobHandle::new(()));
     }
 
     match params {
@@ -107,6 +128,7 @@ pub fn run_job(params: JobParams) -> Option<BlockJobHandle> {
             parent_scope,
             node_value_store,
             slot_blocks,
+            timeout,
             path_finder,
             common,
             vault_client,
@@ -122,6 +144,7 @@ pub fn run_job(params: JobParams) -> Option<BlockJobHandle> {
             node_value_store,
             scope: common.scope,
             slot_blocks: slot_blocks.unwrap_or_default(),
+            timeout,
             path_finder,
             vault_client,
         }),
@@ -170,6 +193,7 @@ pub fn run_job(params: JobParams) -> Option<BlockJobHandle> {
                     scope: common.scope,
                     timeout,
                     inputs_def_patch,
+                    cacheable: task_block.cacheable,
                 })
             }
         }
@@ -202,6 +226,8 @@ pub fn run_job(params: JobParams) -> Option<BlockJobHandle> {
                     block_path: &None,
                     stacks: shared.stacks.vec(),
                     error: Some("Cannot run Slot Block directly".to_string()),
+                    error_kind: Some(ErrorKind::SchedulerError),
+                    exit_code: None,
                     result: None,
                     finish_at: ReporterMessage::now(),
                 });
@@ -223,3 +249,17 @@ pub fn run_job(params: JobParams) -> Option<BlockJobHandle> {
         }),
     }
 }
+
+/// The flows that led to `flow`. When it runs itself, only the cycle since it was last entered.
+fn nesting_path(stacks: &[BlockJobStackLevel], flow: &str) -> String {
+    let start = stacks
+        .iter()
+        .rposition(|level| level.flow == flow)
+        .unwrap_or(0);
+    stacks[start..]
+        .iter()
+        .map(|level| format!("{} ({})", level.flow, level.node_id))
+        .chain(std::iter::once(flow.to_owned()))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
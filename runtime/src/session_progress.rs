@@ -0,0 +1,194 @@
+//! Nodes a session completed, written while it runs so `--resume` can continue an interrupted
+//! session. Only the nodes of the root flow are tracked, a subflow node that didn't complete
+//! runs again from its start.
+
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{Duration, SystemTime},
+};
+
+use job::SessionId;
+use manifest_meta::NodeId;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utils::error::Result;
+
+use crate::flow_job::{NodeInputValues, fingerprint_path};
+
+/// Directory holding the progress of the sessions of a host.
+pub fn session_progress_dir() -> PathBuf {
+    utils::config::cache_root()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("sessions")
+}
+
+/// Progress of a session not touched for this long can't be resumed anymore.
+pub const SESSION_PROGRESS_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Remove the progress files in `dir` last written more than `max_age` ago.
+pub fn prune_session_progress(dir: &Path, max_age: Duration) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let now = SystemTime::now();
+    for entry in entries.flatten() {
+        let expired = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .is_ok_and(|modified| now.duration_since(modified).is_ok_and(|age| age > max_age));
+        if expired {
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                warn!(
+                    "failed to remove session progress {}: {e}",
+                    entry.path().display()
+                );
+            }
+        }
+    }
+}
+
+fn record_path(dir: &Path, session_id: &SessionId) -> PathBuf {
+    dir.join(format!("{session_id}.json"))
+}
+
+fn values_path(dir: &Path, session_id: &SessionId) -> PathBuf {
+    dir.join(format!("{session_id}.values.json"))
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct ProgressRecord {
+    flow: String,
+    fingerprint: String,
+    completed_nodes: Vec<NodeId>,
+}
+
+/// Progress of an earlier session, read for `--resume`.
+#[derive(Debug, Clone)]
+pub struct ResumedSession {
+    pub session_id: SessionId,
+    /// Flow the session ran, with the fingerprint it had then.
+    pub flow: String,
+    pub fingerprint: String,
+    pub completed_nodes: HashSet<NodeId>,
+    /// Node input values cached when the last node completed.
+    pub values_path: PathBuf,
+}
+
+impl ResumedSession {
+    pub fn read(dir: &Path, session_id: &SessionId) -> Result<Self> {
+        let path = record_path(dir, session_id);
+        let content = std::fs::read_to_string(&path).map_err(|e| {
+            format!(
+                "No progress recorded for session {session_id} at {}: {e}",
+                path.display()
+            )
+        })?;
+        let record: ProgressRecord = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid session progress {}: {e}", path.display()))?;
+        Ok(Self {
+            session_id: session_id.to_owned(),
+            flow: record.flow,
+            fingerprint: record.fingerprint,
+            completed_nodes: record.completed_nodes.into_iter().collect(),
+            values_path: values_path(dir, session_id),
+        })
+    }
+}
+
+/// Writes the nodes of the root flow a session completed, with the values they produced.
+#[derive(Debug)]
+pub struct SessionProgress {
+    record_path: PathBuf,
+    values_path: PathBuf,
+    completed: Mutex<HashSet<NodeId>>,
+}
+
+impl SessionProgress {
+    pub fn new(dir: &Path, session_id: &SessionId) -> Self {
+        Self {
+            record_path: record_path(dir, session_id),
+            values_path: values_path(dir, session_id),
+            completed: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Keep the nodes a resumed session completed, the session can be resumed again.
+    pub(crate) fn resume(&self, resumed: &ResumedSession) {
+        self.completed
+            .lock()
+            .unwrap()
+            .extend(resumed.completed_nodes.iter().cloned());
+    }
+
+    pub(crate) fn node_completed(
+        &self,
+        flow: &str,
+        fingerprint: &str,
+        node_id: &NodeId,
+        values: &NodeInputValues,
+    ) {
+        let mut completed = self.completed.lock().unwrap();
+        completed.insert(node_id.to_owned());
+        let mut completed_nodes = completed.iter().cloned().collect::<Vec<_>>();
+        completed_nodes.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        let record = ProgressRecord {
+            flow: flow.to_owned(),
+            fingerprint: fingerprint.to_owned(),
+            completed_nodes,
+        };
+
+        // values first, a record never points at nodes whose outputs weren't saved
+        let result = values
+            .save_cache(self.values_path.clone())
+            .and_then(|()| serde_json::to_vec(&record).map_err(|e| e.to_string()))
+            .and_then(|data| std::fs::write(&self.record_path, data).map_err(|e| e.to_string()));
+        if let Err(e) = result {
+            warn!(
+                "failed to record session progress at {}: {e}",
+                self.record_path.display()
+            );
+        }
+    }
+
+    /// Drop the progress of a session that finished, there's nothing left to resume.
+    pub(crate) fn discard(&self) {
+        let fingerprint_path = fingerprint_path(&self.values_path);
+        for path in [&self.record_path, &self.values_path, &fingerprint_path] {
+            if let Err(e) = std::fs::remove_file(path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("failed to remove session progress {}: {e}", path.display());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_removes_only_old_progress() {
+        let dir =
+            std::env::temp_dir().join(format!("oocana-progress-prune-{}", SessionId::random()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = dir.join("old.json");
+        let recent = dir.join("recent.json");
+        std::fs::write(&old, "{}").unwrap();
+        std::fs::write(&recent, "{}").unwrap();
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(2 * 60 * 60))
+            .unwrap();
+
+        prune_session_progress(&dir, Duration::from_secs(60 * 60));
+
+        assert!(!old.exists());
+        assert!(recent.exists());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,26 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
+};
+
 use job::SessionId;
+use tokio::sync::{Notify, Semaphore};
 
 use mainframe::{reporter::ReporterTx, scheduler::SchedulerTx};
 
+use crate::block_record::BlockRecorder;
 use crate::delay_abort::DelayAbortTx;
+use crate::node_timings::NodeTimings;
+use crate::output_cache::OutputCache;
 use crate::remote_task_config::RemoteTaskConfig;
+use crate::session_progress::SessionProgress;
+use crate::time_budget::TimeBudgets;
+
+/// How deep jobs nest unless configured otherwise.
+pub const DEFAULT_MAX_FLOW_DEPTH: usize = 50;
 
 pub struct Shared {
     pub session_id: SessionId,
@@ -14,7 +31,65 @@ pub struct Shared {
     pub delay_abort_tx: DelayAbortTx,
     pub reporter: ReporterTx,
     pub use_cache: bool,
+    /// Report the cached outputs of nodes skipped for the cache as if they ran.
+    pub replay_cached_outputs: bool,
+    /// Serve task blocks that ran before with the same inputs from this cache.
+    pub output_cache: Option<OutputCache>,
     pub remote_task_config: Option<RemoteTaskConfig>,
+    /// Caps how many node blocks run at once across the flow and all of its subflows.
+    pub concurrency_limit: Option<Arc<Semaphore>>,
+    pub node_timings: NodeTimings,
+    pub time_budgets: TimeBudgets,
+    pub drain: Drain,
+    /// A job nested this deep fails instead of running, see [`DEFAULT_MAX_FLOW_DEPTH`].
+    pub max_flow_depth: usize,
+    /// Records the inputs a node runs with, for `--record-block`.
+    pub block_recorder: Option<BlockRecorder>,
+    /// Save the node input values of running flows to their cache this often, so a crashed
+    /// session can go on from the last checkpoint with `use_cache`.
+    pub checkpoint_interval: Option<Duration>,
+    /// Records the root flow nodes the session completed, for `--resume`.
+    pub session_progress: Option<SessionProgress>,
+}
+
+/// Graceful shutdown state. Once draining, flows stop starting nodes and let running ones finish.
+#[derive(Debug, Default)]
+pub struct Drain {
+    draining: AtomicBool,
+    skipped_nodes: AtomicBool,
+    started: Notify,
+}
+
+impl Drain {
+    pub fn start(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+        self.started.notify_waiters();
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the drain starts.
+    pub(crate) async fn started(&self) {
+        let notified = self.started.notified();
+        tokio::pin!(notified);
+        // registered before the check, so a drain starting in between isn't missed
+        notified.as_mut().enable();
+        if self.is_draining() {
+            return;
+        }
+        notified.await;
+    }
+
+    pub(crate) fn skip_node(&self) {
+        self.skipped_nodes.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether a node that was ready to run got dropped by the drain.
+    pub fn skipped_nodes(&self) -> bool {
+        self.skipped_nodes.load(Ordering::SeqCst)
+    }
 }
 
 pub(crate) fn should_enable_package_layer(
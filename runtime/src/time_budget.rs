@@ -0,0 +1,118 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use manifest_meta::NodeId;
+use utils::error::Result;
+
+/// Cap on the wall-time a node id may spend across the session, subflow nodes included. A node
+/// that used up its budget isn't dispatched again, its ready runs wait in the node queue and are
+/// reported as held when the flow finishes.
+#[derive(Debug, Clone, Default)]
+pub struct TimeBudgets {
+    budgets: HashMap<NodeId, Duration>,
+    spent: Arc<Mutex<HashMap<NodeId, Duration>>>,
+}
+
+impl TimeBudgets {
+    pub fn new(budgets: HashMap<NodeId, Duration>) -> Self {
+        Self {
+            budgets,
+            spent: Default::default(),
+        }
+    }
+
+    /// Parse `NODE=DURATION` values such as `fetch=120s`. The duration takes an `ms`, `s`, `m`
+    /// or `h` suffix, plain numbers are seconds.
+    pub fn parse(values: &[String]) -> Result<Self> {
+        let mut budgets = HashMap::new();
+        for value in values {
+            let (node_id, duration) = value
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid time budget {value:?}, expected NODE=DURATION"))?;
+            let duration = parse_duration(duration.trim())
+                .ok_or_else(|| format!("Invalid duration in time budget {value:?}"))?;
+            budgets.insert(NodeId::new(node_id.trim().to_owned()), duration);
+        }
+        Ok(Self::new(budgets))
+    }
+
+    pub fn record(&self, node_id: &NodeId, duration: Duration) {
+        if self.budgets.contains_key(node_id) {
+            *self
+                .spent
+                .lock()
+                .unwrap()
+                .entry(node_id.to_owned())
+                .or_default() += duration;
+        }
+    }
+
+    pub fn is_exhausted(&self, node_id: &NodeId) -> bool {
+        self.budgets.get(node_id).is_some_and(|budget| {
+            self.spent
+                .lock()
+                .unwrap()
+                .get(node_id)
+                .is_some_and(|spent| spent >= budget)
+        })
+    }
+}
+
+fn parse_duration(value: &str) -> Option<Duration> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    match unit {
+        "ms" => Some(Duration::from_millis(number)),
+        "" | "s" => Some(Duration::from_secs(number)),
+        "m" => Some(Duration::from_secs(number * 60)),
+        "h" => Some(Duration::from_secs(number * 3600)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_node_and_duration() {
+        let budgets = TimeBudgets::parse(&[
+            "fetch=120s".to_owned(),
+            "train=2m".to_owned(),
+            "poll=500ms".to_owned(),
+        ])
+        .unwrap();
+        assert_eq!(
+            budgets.budgets,
+            HashMap::from([
+                (NodeId::new("fetch".to_owned()), Duration::from_secs(120)),
+                (NodeId::new("train".to_owned()), Duration::from_secs(120)),
+                (NodeId::new("poll".to_owned()), Duration::from_millis(500)),
+            ])
+        );
+
+        assert!(TimeBudgets::parse(&["fetch".to_owned()]).is_err());
+        assert!(TimeBudgets::parse(&["fetch=2d".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn budget_is_exhausted_once_spent() {
+        let fetch = NodeId::new("fetch".to_owned());
+        let other = NodeId::new("other".to_owned());
+        let budgets = TimeBudgets::new(HashMap::from([(fetch.clone(), Duration::from_secs(2))]));
+
+        budgets.record(&fetch, Duration::from_secs(1));
+        budgets.record(&other, Duration::from_secs(10));
+        assert!(!budgets.is_exhausted(&fetch));
+        assert!(!budgets.is_exhausted(&other));
+
+        budgets.record(&fetch, Duration::from_secs(1));
+        assert!(budgets.is_exhausted(&fetch));
+    }
+}
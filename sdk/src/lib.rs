@@ -1,8 +1,13 @@
 mod args;
+#[doc(hidden)]
+pub mod outputs;
 mod sdk;
 
 pub use job::{BlockInputs, JobId, SessionId};
 pub use mainframe::JsonValue;
 pub use manifest_meta::HandleName;
+pub use outputs::OutputSink;
 pub use sdk::{OocanaSDK, connect};
+#[doc(hidden)]
+pub use serde_json;
 pub use serde_json::json;
@@ -0,0 +1,122 @@
+use mainframe::JsonValue;
+use serde::Serialize;
+
+use crate::OocanaSDK;
+
+/// Where typed outputs go. [`OocanaSDK`] sends them to the flow.
+pub trait OutputSink {
+    fn output(&self, output: &JsonValue, handle: &str, done: bool);
+}
+
+impl OutputSink for OocanaSDK {
+    fn output(&self, output: &JsonValue, handle: &str, done: bool) {
+        OocanaSDK::output(self, output, handle, done);
+    }
+}
+
+/// Serialize `value` and send it to `handle`. Used by the methods [`define_outputs!`] generates.
+#[doc(hidden)]
+pub fn emit<S, T>(sink: &S, handle: &str, value: &T) -> serde_json::Result<()>
+where
+    S: OutputSink + ?Sized,
+    T: Serialize + ?Sized,
+{
+    let value = serde_json::to_value(value)?;
+    sink.output(&value, handle, false);
+    Ok(())
+}
+
+/// Declare a block's output handles with their value types.
+///
+/// ```ignore
+/// oocana_sdk::define_outputs! {
+///     my_output: i64,
+///     label: String,
+/// }
+///
+/// use outputs::OutputsExt;
+/// sdk.outputs().my_output(&42)?;
+/// ```
+///
+/// This generates a module `outputs` with an `Outputs` struct holding one method per handle,
+/// named like the handle, and an `OutputsExt` trait adding `outputs()` to the SDK. A misspelled
+/// handle or a value of the wrong type no longer compiles. Outputs are sent with `done` false,
+/// finish the block with [`OocanaSDK::finish`].
+#[macro_export]
+macro_rules! define_outputs {
+    ($($handle:ident : $ty:ty),* $(,)?) => {
+        pub mod outputs {
+            #[allow(unused_imports)]
+            use super::*;
+
+            pub struct Outputs<'a, S: $crate::OutputSink + ?Sized>(&'a S);
+
+            impl<S: $crate::OutputSink + ?Sized> Outputs<'_, S> {
+                $(
+                    pub fn $handle(&self, value: &$ty) -> $crate::serde_json::Result<()> {
+                        $crate::outputs::emit(self.0, stringify!($handle), value)
+                    }
+                )*
+            }
+
+            pub trait OutputsExt: $crate::OutputSink {
+                fn outputs(&self) -> Outputs<'_, Self> {
+                    Outputs(self)
+                }
+            }
+
+            impl<S: $crate::OutputSink + ?Sized> OutputsExt for S {}
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct RecordingSink(Mutex<Vec<(String, JsonValue, bool)>>);
+
+    impl OutputSink for RecordingSink {
+        fn output(&self, output: &JsonValue, handle: &str, done: bool) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((handle.to_owned(), output.clone(), done));
+        }
+    }
+
+    #[derive(Serialize)]
+    pub struct Point {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    crate::define_outputs! {
+        my_output: i64,
+        point: Point,
+    }
+
+    #[test]
+    fn typed_outputs_emit_declared_handles() {
+        use outputs::OutputsExt;
+
+        let sink = RecordingSink::default();
+        sink.outputs().my_output(&42).unwrap();
+        sink.outputs().point(&Point { x: 1, y: 2 }).unwrap();
+
+        assert_eq!(
+            sink.0.into_inner().unwrap(),
+            vec![
+                ("my_output".to_owned(), serde_json::json!(42), false),
+                (
+                    "point".to_owned(),
+                    serde_json::json!({"x": 1, "y": 2}),
+                    false
+                ),
+            ]
+        );
+    }
+}
@@ -63,4 +63,14 @@ impl OocanaSDK {
     pub fn done(&self, error: Option<&str>) {
         self.tx.done(error);
     }
+
+    /// Finish the block successfully.
+    ///
+    /// Outputs emitted before this call are sent first, then a final `BlockFinished` without
+    /// error. After that the connection is closed and the `WorkerRxHandle` returned by
+    /// [`connect`] resolves its `wait()`, so a block can end with `sdk.finish(); event_loop.wait().await;`.
+    /// Messages sent after `finish` (or `done`) are dropped.
+    pub fn finish(&self) {
+        self.tx.done(None);
+    }
 }
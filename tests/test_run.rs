@@ -112,6 +112,23 @@ fn run_flow_with_absence_input() {
         .success();
 }
 
+#[test]
+fn run_service_block_one_shot() {
+    // a service block run directly is treated like a task: one request, then shutdown
+    oocana_cmd()
+        .args([
+            "run",
+            "echo_pkg::echo::echo",
+            "--search-paths",
+            "examples/service_run",
+            "--inputs",
+            r#"{"text": "hello service"}"#,
+        ])
+        .assert()
+        .stdout(contains("service echo: hello service"))
+        .success();
+}
+
 #[test]
 fn condition_test() {
     run_flow("examples/condition");
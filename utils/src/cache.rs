@@ -8,7 +8,7 @@ use std::path::PathBuf;
 pub const CACHE_META_FILE: &str = "cache_meta.json";
 
 pub fn cache_meta_file_path() -> Option<PathBuf> {
-    match config::oocana_dir() {
+    match config::cache_root() {
         Some(mut path) => {
             path.push(CACHE_META_FILE);
             Some(path)
@@ -18,7 +18,7 @@ pub fn cache_meta_file_path() -> Option<PathBuf> {
 }
 
 pub fn cache_dir() -> Option<PathBuf> {
-    match config::oocana_dir() {
+    match config::cache_root() {
         Some(mut path) => {
             path.push("cache");
             Some(path)
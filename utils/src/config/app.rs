@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 
@@ -10,7 +11,7 @@ use dirs::home_dir;
 use super::{global_config::GlobalConfig, run_config::RunConfig};
 use crate::path::expand_home;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct AppConfig {
     pub global: GlobalConfig,
     pub run: RunConfig,
@@ -23,7 +24,7 @@ lazy_static! {
     });
 }
 
-pub fn load_config<P: AsRef<Path>>(file: Option<P>) -> Result<AppConfig, String> {
+pub(super) fn resolve_config_path<P: AsRef<Path>>(file: Option<P>) -> Result<PathBuf, String> {
     let p: PathBuf = match file {
         Some(p) => p.as_ref().to_path_buf(),
         None => {
@@ -51,11 +52,20 @@ pub fn load_config<P: AsRef<Path>>(file: Option<P>) -> Result<AppConfig, String>
         }
     };
 
+    Ok(config_path)
+}
+
+pub(super) fn config_file_exists(config_path: &Path) -> bool {
+    config_path.with_extension("json").exists()
+        || config_path.with_extension("toml").exists()
+        || config_path.with_extension("json5").exists()
+}
+
+pub fn load_config<P: AsRef<Path>>(file: Option<P>) -> Result<AppConfig, String> {
+    let config_path = resolve_config_path(file)?;
+
     // TODO: use config set_default to set default value
-    if !config_path.with_extension("json").exists()
-        && !config_path.with_extension("toml").exists()
-        && !config_path.with_extension("json5").exists()
-    {
+    if !config_file_exists(&config_path) {
         let app_config = GLOBAL_CONFIG.lock().unwrap();
         tracing::info!(
             "No config file found at {:?}, return default config",
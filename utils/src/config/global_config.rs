@@ -1,7 +1,8 @@
 use crate::path::expand_home;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 struct TmpGlobalConfig {
     #[serde(default = "default_store_dir")]
     pub store_dir: String,
@@ -12,6 +13,7 @@ struct TmpGlobalConfig {
     pub env_file: Option<String>,
     pub bind_path_file: Option<String>,
     pub search_paths: Option<Vec<String>>,
+    pub cache_root: Option<String>,
 }
 
 fn default_store_dir() -> String {
@@ -35,6 +37,7 @@ impl Default for TmpGlobalConfig {
             env_file: None,
             bind_path_file: None,
             search_paths: None,
+            cache_root: None,
         }
     }
 }
@@ -46,6 +49,7 @@ impl From<TmpGlobalConfig> for GlobalConfig {
         let external_store_file = expand_home(&tmp.external_store_file);
         let env_file = tmp.env_file.map(|s| expand_home(&s));
         let bind_path_file = tmp.bind_path_file.map(|s| expand_home(&s));
+        let cache_root = tmp.cache_root.map(|s| expand_home(&s));
 
         GlobalConfig {
             store_dir,
@@ -59,11 +63,12 @@ impl From<TmpGlobalConfig> for GlobalConfig {
                     .map(|s| expand_home(&s))
                     .collect::<Vec<String>>()
             }),
+            cache_root,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 #[serde(from = "TmpGlobalConfig")]
 pub struct GlobalConfig {
     pub store_dir: String,
@@ -72,6 +77,8 @@ pub struct GlobalConfig {
     pub env_file: Option<String>,
     pub bind_path_file: Option<String>,
     pub search_paths: Option<Vec<String>>,
+    /// Where flow caches are stored, `oocana_dir` when unset.
+    pub cache_root: Option<String>,
 }
 
 impl Default for GlobalConfig {
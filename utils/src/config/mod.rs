@@ -1,10 +1,17 @@
 mod app;
 mod global_config;
 mod run_config;
+mod validate;
 pub use app::*;
+pub use validate::validate_config;
 
 use std::path::PathBuf;
 
+/// JSON Schema of the config file, for editors and validators.
+pub fn json_schema() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(AppConfig)).unwrap_or_default()
+}
+
 pub fn default_broker_port() -> u16 {
     47688
 }
@@ -20,6 +27,19 @@ pub fn oocana_dir() -> Option<PathBuf> {
     Some(PathBuf::from(global_config.global.oocana_dir.clone()))
 }
 
+pub fn cache_root() -> Option<PathBuf> {
+    let global_config = GLOBAL_CONFIG.lock().unwrap();
+    match &global_config.global.cache_root {
+        Some(root) => Some(PathBuf::from(root)),
+        None => Some(PathBuf::from(global_config.global.oocana_dir.clone())),
+    }
+}
+
+pub fn set_cache_root(root: &str) {
+    let mut global_config = GLOBAL_CONFIG.lock().unwrap();
+    global_config.global.cache_root = Some(crate::path::expand_home(root));
+}
+
 pub fn external_store_file() -> Option<PathBuf> {
     use crate::path::expand_home;
 
@@ -46,6 +66,11 @@ pub fn search_paths() -> Option<Vec<String>> {
     global_config.global.search_paths.clone()
 }
 
+pub fn exclude_packages() -> Option<Vec<String>> {
+    let global_config = GLOBAL_CONFIG.lock().unwrap();
+    global_config.run.exclude_packages.clone()
+}
+
 pub fn extra_search_path() -> Option<Vec<String>> {
     let global_config = GLOBAL_CONFIG.lock().unwrap();
     global_config
@@ -1,4 +1,5 @@
 use crate::path::expand_home;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 struct TmpRunExtraConfig {
@@ -18,12 +19,12 @@ impl From<TmpRunExtraConfig> for RunExtraConfig {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct RunExtraConfig {
     pub search_paths: Option<Vec<String>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 struct TmpRunConfig {
     #[serde(default = "default_broker")]
     pub broker: String,
@@ -77,7 +78,7 @@ fn default_broker() -> String {
     "127.0.0.1:47688".to_string()
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 #[serde(from = "TmpRunConfig")]
 pub struct RunConfig {
     pub broker: String,
@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use config::Config;
+use serde_json::Value;
+
+use super::app::{config_file_exists, resolve_config_path};
+
+/// Check a config file against [`super::json_schema`] and return every problem found, an empty
+/// list means the file is valid. Unlike [`super::load_config`], a missing file is a problem too.
+/// Unknown keys are only reported when `strict` is set, loading ignores them.
+pub fn validate_config<P: AsRef<Path>>(file: Option<P>, strict: bool) -> Vec<String> {
+    let config_path = match resolve_config_path(file) {
+        Ok(path) => path,
+        Err(e) => return vec![e],
+    };
+    if !config_file_exists(&config_path) {
+        return vec![format!(
+            "no config file found at {} (json, toml or json5)",
+            config_path.display()
+        )];
+    }
+
+    let value = Config::builder()
+        .add_source(config::File::with_name(&config_path.to_string_lossy()))
+        .build()
+        .and_then(|config| config.try_deserialize::<Value>());
+    let value = match value {
+        Ok(value) => value,
+        Err(e) => return vec![format!("failed to parse {}: {e}", config_path.display())],
+    };
+
+    let mut schema = super::json_schema();
+    if strict {
+        deny_unknown_keys(&mut schema);
+    }
+    let validator = match jsonschema::validator_for(&schema) {
+        Ok(validator) => validator,
+        Err(e) => return vec![format!("invalid config schema: {e}")],
+    };
+    let mut problems: Vec<String> = validator
+        .iter_errors(&value)
+        .map(|error| {
+            let path = error.instance_path.to_string();
+            let path = if path.is_empty() { "config" } else { &path };
+            format!("{path}: {error}")
+        })
+        .collect();
+    problems.sort();
+    problems
+}
+
+/// Forbid keys missing from `properties` in every object of the schema.
+fn deny_unknown_keys(schema: &mut Value) {
+    match schema {
+        Value::Object(map) => {
+            if map.contains_key("properties") && !map.contains_key("additionalProperties") {
+                map.insert("additionalProperties".to_owned(), Value::Bool(false));
+            }
+            map.values_mut().for_each(deny_unknown_keys);
+        }
+        Value::Array(items) => items.iter_mut().for_each(deny_unknown_keys),
+        _ => {}
+    }
+}
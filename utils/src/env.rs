@@ -1,4 +1,10 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::error::Result;
 
 pub fn load_env_from_file<P: AsRef<Path>>(file_path: &Option<P>) -> HashMap<String, String> {
     let Some(file_path) = file_path else {
@@ -26,4 +32,104 @@ pub fn load_env_from_file<P: AsRef<Path>>(file_path: &Option<P>) -> HashMap<Stri
     }
 }
 
+/// Add the `env_file` values to `envs`. Keys already in `envs` win: they are set by oocana or
+/// passed through from the process env on purpose, an env file doesn't replace them.
+pub fn merge_envs<P: AsRef<Path>>(envs: &mut HashMap<String, String>, env_file: &Option<P>) {
+    for (key, value) in load_env_from_file(env_file) {
+        match envs.entry(key) {
+            std::collections::hash_map::Entry::Occupied(entry) => {
+                tracing::warn!(
+                    "env key {} is already in envs, skip the env file value",
+                    entry.key()
+                );
+            }
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+        }
+    }
+}
+
 pub static OVMLAYER_LOG_ENV_KEY: &str = "OVMLAYER_LOG";
+
+/// Executors read their secrets from the JSON file at this path instead of from env vars.
+pub static SECRETS_FILE_ENV_KEY: &str = "OOCANA_SECRETS_FILE";
+
+/// Secret env values saved as a JSON object in a file only the current user can read, so they
+/// don't show up in `/proc/<pid>/environ`. The file is removed on drop.
+#[derive(Debug)]
+pub struct SecretsFile {
+    path: PathBuf,
+}
+
+/// A file only the current user can read and write.
+fn create_private_file(path: &Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+impl SecretsFile {
+    pub fn create(path: PathBuf, values: &HashMap<String, String>) -> Result<Self> {
+        let mut file = create_private_file(&path)
+            .map_err(|e| format!("Failed to create secrets file {}: {e}", path.display()))?;
+        file.write_all(serde_json::to_string(values)?.as_bytes())?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SecretsFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!("Failed to remove secrets file {}: {e}", self.path.display());
+        }
+    }
+}
+
+/// Env values saved as `KEY=VALUE` lines in a file only the current user can read, for commands
+/// that take an `--env-file`: values on a command line show up in `ps` for every user. The file is
+/// removed on drop.
+#[derive(Debug)]
+pub struct PrivateEnvFile {
+    path: PathBuf,
+}
+
+impl PrivateEnvFile {
+    /// Env files have no quoting, a value with a line break can't be a line of the file and
+    /// fails the creation.
+    pub fn create(path: PathBuf, envs: &HashMap<String, String>) -> Result<Self> {
+        let mut content = String::new();
+        for (key, value) in envs {
+            if value.contains(['\n', '\r']) {
+                return Err(format!(
+                    "env {key} has a line break, it can't be passed in an env file"
+                )
+                .into());
+            }
+            content.push_str(&format!("{key}={value}\n"));
+        }
+
+        let mut file = create_private_file(&path)
+            .map_err(|e| format!("Failed to create env file {}: {e}", path.display()))?;
+        file.write_all(content.as_bytes())?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for PrivateEnvFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!("Failed to remove env file {}: {e}", self.path.display());
+        }
+    }
+}
@@ -32,6 +32,6 @@ pub fn calculate_short_hash(input: &str, length: usize) -> String {
     // Convert the hash to a hexadecimal string
     let hex_result = encode(result);
 
-    // Return the first `length` characters of the hash
-    hex_result[..length].to_string()
+    // Return the first `length` characters of the hash, or the whole hash if it is shorter
+    hex_result[..length.min(hex_result.len())].to_string()
 }
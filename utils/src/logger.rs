@@ -1,9 +1,10 @@
+use serde_json::{Map, Value};
 use std::{
     fs::{self, File},
-    io,
+    io::{self, Write},
     path::{Path, PathBuf},
 };
-use tracing::info;
+use tracing::{Event, Level, Subscriber, field::Field, info};
 
 use crate::{config, env};
 
@@ -12,16 +13,30 @@ use super::error::Result;
 use std::sync::Mutex;
 use tracing::level_filters::LevelFilter;
 use tracing_appender::non_blocking;
-use tracing_subscriber::{EnvFilter, Layer, Registry, fmt, layer::SubscriberExt};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry,
+    fmt::{self, writer::BoxMakeWriter},
+    layer::{Context, SubscriberExt},
+};
 
 lazy_static::lazy_static! {
-    static ref LOGGER_DIR: Mutex<PathBuf> = Mutex::new(config::oocana_dir().unwrap_or(std::env::temp_dir()));
+    static ref LOGGER_DIR: Mutex<PathBuf> = Mutex::new(logger_root());
 }
 
 pub fn logger_dir() -> PathBuf {
     LOGGER_DIR.lock().unwrap().clone()
 }
 
+/// Directory [`LogParams::sub_dir`] is relative to.
+pub fn logger_root() -> PathBuf {
+    config::oocana_dir().unwrap_or_else(std::env::temp_dir)
+}
+
+/// [`LogParams::sub_dir`] of a run session, where its logs and node timings are written.
+pub fn session_sub_dir(session: &str) -> String {
+    format!("sessions/{session}")
+}
+
 pub const STDOUT_TARGET: &str = "stdout";
 pub const STDERR_TARGET: &str = "stderr";
 
@@ -31,6 +46,10 @@ pub struct LogParams<'a, P: AsRef<Path>> {
     pub output_to_console: bool,
     /// 捕获 target 为 STDOUT_TARGET 或者 STDERR_TARGET 的日志。
     pub capture_stdout_stderr_target: bool,
+    /// Write console logs to stderr, when stdout carries data for another program.
+    pub console_to_stderr: bool,
+    /// Also write WARN events to this file as NDJSON, see [`WarnJsonLayer`].
+    pub warn_json: Option<PathBuf>,
 }
 
 #[allow(unused_mut)]
@@ -40,9 +59,11 @@ pub fn setup_logging<P: AsRef<Path>>(params: LogParams<P>) -> Result<non_blockin
         log_name,
         mut output_to_console,
         capture_stdout_stderr_target,
+        console_to_stderr,
+        warn_json,
     } = params;
 
-    let mut logger_dir = config::oocana_dir().unwrap_or_else(std::env::temp_dir);
+    let mut logger_dir = logger_root();
 
     if let Some(sub_dir) = sub_dir {
         logger_dir.push(sub_dir);
@@ -72,7 +93,12 @@ pub fn setup_logging<P: AsRef<Path>>(params: LogParams<P>) -> Result<non_blockin
         .with_line_number(true)
         .with_filter(LevelFilter::TRACE);
 
-    let subscriber = Registry::default().with(file_layer);
+    let warn_json_layer = match warn_json {
+        Some(path) => Some(WarnJsonLayer::create(&path)?),
+        None => None,
+    };
+
+    let subscriber = Registry::default().with(file_layer).with(warn_json_layer);
 
     // 主动要求输出时，不使用 ansi color
     #[allow(unused_assignments, unused_mut)]
@@ -84,11 +110,19 @@ pub fn setup_logging<P: AsRef<Path>>(params: LogParams<P>) -> Result<non_blockin
         show_ansi_color = true;
     }
 
+    let console_writer = || {
+        if console_to_stderr {
+            BoxMakeWriter::new(std::io::stderr)
+        } else {
+            BoxMakeWriter::new(std::io::stdout)
+        }
+    };
+
     if output_to_console {
         // 使用 target 提取出原始内容更方便。如果用 span，需要使用自定义 format 来提取原始信息（不带 span 的）
         if capture_stdout_stderr_target {
             let stdout_layer = fmt::Layer::default()
-                .with_writer(std::io::stdout)
+                .with_writer(console_writer())
                 .with_target(false)
                 .with_ansi(show_ansi_color)
                 .with_level(false)
@@ -114,7 +148,7 @@ pub fn setup_logging<P: AsRef<Path>>(params: LogParams<P>) -> Result<non_blockin
                 .expect("Unable to set global subscriber");
         } else {
             let stdout_layer = fmt::Layer::default()
-                .with_writer(std::io::stdout)
+                .with_writer(console_writer())
                 .with_target(false)
                 .with_ansi(show_ansi_color);
 
@@ -134,6 +168,80 @@ pub fn setup_logging<P: AsRef<Path>>(params: LogParams<P>) -> Result<non_blockin
     Ok(guard)
 }
 
+/// Writes every WARN event as one JSON object per line: `level`, `target`, `message`, the
+/// event's other structured `fields`, and the `file`/`line` it was logged from.
+pub struct WarnJsonLayer {
+    file: Mutex<File>,
+}
+
+impl WarnJsonLayer {
+    /// Create (or truncate) `path`, creating missing parent directories.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(Self {
+            file: Mutex::new(File::create(path)?),
+        })
+    }
+}
+
+impl<S: Subscriber> Layer<S> for WarnJsonLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() != Level::WARN {
+            return;
+        }
+
+        let mut fields = JsonFields::default();
+        event.record(&mut fields);
+        let mut fields = fields.0;
+        let message = fields.remove("message").unwrap_or(Value::Null);
+
+        let mut line = Map::new();
+        line.insert("level".to_owned(), metadata.level().as_str().into());
+        line.insert("target".to_owned(), metadata.target().into());
+        line.insert("message".to_owned(), message);
+        line.insert("fields".to_owned(), Value::Object(fields));
+        line.insert("file".to_owned(), metadata.file().into());
+        line.insert("line".to_owned(), metadata.line().into());
+
+        let mut file = self.file.lock().unwrap();
+        // a failing warning sink must not take logging down with it
+        let _ = writeln!(file, "{}", Value::Object(line));
+    }
+}
+
+#[derive(Default)]
+struct JsonFields(Map<String, Value>);
+
+impl tracing::field::Visit for JsonFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{value:?}").into());
+    }
+}
+
 fn create_file_with_dirs<P: AsRef<Path>>(file_path: P) -> io::Result<File> {
     if let Some(parent) = file_path.as_ref().parent() {
         fs::create_dir_all(parent)?;
@@ -153,6 +261,8 @@ mod tests {
             log_name: "test",
             output_to_console: true,
             capture_stdout_stderr_target: true,
+            console_to_stderr: false,
+            warn_json: None,
         })
         .unwrap();
 
@@ -161,4 +271,39 @@ mod tests {
 
         drop(g);
     }
+
+    #[test]
+    fn warn_json_layer_writes_warnings_as_ndjson() {
+        let path = std::env::temp_dir()
+            .join(format!("oocana-warn-json-{}", std::process::id()))
+            .join("warnings.ndjson");
+        let layer = WarnJsonLayer::create(&path).unwrap();
+        let subscriber = Registry::default().with(layer);
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("not a warning");
+            tracing::warn!(
+                target: "manifest_meta",
+                node_id = "node-1",
+                attempts = 2,
+                "node({}) won't run",
+                "node-1"
+            );
+        });
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<Value> = content
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["level"], "WARN");
+        assert_eq!(lines[0]["target"], "manifest_meta");
+        assert_eq!(lines[0]["message"], "node(node-1) won't run");
+        assert_eq!(lines[0]["fields"]["node_id"], "node-1");
+        assert_eq!(lines[0]["fields"]["attempts"], 2);
+        assert!(lines[0]["line"].is_u64());
+
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
 }
@@ -1,6 +1,19 @@
 #[cfg(test)]
 mod tests {
-    use utils::{config::load_config, env::load_env_from_file};
+    use utils::{
+        calculate_short_hash,
+        config::{json_schema, load_config, validate_config},
+        env::{PrivateEnvFile, SecretsFile, load_env_from_file, merge_envs},
+    };
+
+    #[test]
+    fn test_short_hash_length_is_clamped() {
+        let full = calculate_short_hash("oocana", 64);
+        assert_eq!(full.len(), 64);
+        assert_eq!(calculate_short_hash("oocana", 100), full);
+        assert_eq!(calculate_short_hash("oocana", 0), "");
+        assert_eq!(calculate_short_hash("oocana", 8), full[..8]);
+    }
 
     #[test]
     fn test_default_config() {
@@ -47,6 +60,53 @@ mod tests {
         assert_eq!(extra.search_paths.unwrap().len(), 0);
     }
 
+    #[test]
+    fn test_config_json_schema() {
+        let schema = json_schema();
+        let defs = &schema["$defs"];
+
+        assert!(schema["properties"]["run"].is_object());
+        assert!(schema["properties"]["global"].is_object());
+        assert_eq!(
+            defs["RunConfig"]["properties"]["broker"]["default"],
+            "127.0.0.1:47688"
+        );
+        assert_eq!(
+            defs["GlobalConfig"]["properties"]["store_dir"]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn test_validate_valid_config() {
+        assert!(validate_config(Some("tests/sample.toml"), true).is_empty());
+    }
+
+    #[test]
+    fn test_validate_invalid_config() {
+        let problems = validate_config(Some("tests/invalid.toml"), false);
+        assert_eq!(
+            problems,
+            vec![
+                r#"/global/search_paths/1: 2 is not of type "string""#,
+                r#"/global/store_dir: 1 is not of type "string""#,
+                r#"/run/reporter: "yes" is not of types "boolean", "null""#,
+            ]
+        );
+
+        let strict = validate_config(Some("tests/invalid.toml"), true);
+        assert_eq!(strict.len(), problems.len() + 1);
+        assert!(
+            strict.iter().any(|problem| problem.starts_with("/global: ")
+                && problem.contains("'unknown_option' was unexpected")),
+            "{strict:?}"
+        );
+
+        let missing = validate_config(Some("tests/not-exist.toml"), false);
+        assert_eq!(missing.len(), 1);
+        assert!(missing[0].starts_with("no config file found"));
+    }
+
     #[test]
     fn test_load_env_from_file() {
         let file_path = Some("tests/test.env");
@@ -54,4 +114,67 @@ mod tests {
         assert!(!env_vars.is_empty());
         assert_eq!(env_vars.get("TEST_KEY"), Some(&"TEST_VALUE".to_string()));
     }
+
+    #[test]
+    fn test_merge_envs_keeps_existing_keys() {
+        let mut envs =
+            std::collections::HashMap::from([("TEST_KEY".to_string(), "FROM_PROCESS".to_string())]);
+        merge_envs(&mut envs, &Some("tests/test.env"));
+        assert_eq!(envs.get("TEST_KEY"), Some(&"FROM_PROCESS".to_string()));
+
+        let mut envs = std::collections::HashMap::new();
+        merge_envs(&mut envs, &Some("tests/test.env"));
+        assert_eq!(envs.get("TEST_KEY"), Some(&"TEST_VALUE".to_string()));
+    }
+
+    #[test]
+    fn test_secrets_file() {
+        let path = std::env::temp_dir().join(format!("oocana-secrets-{}.json", std::process::id()));
+        let secrets = load_env_from_file(&Some("tests/test.env"));
+        let file = SecretsFile::create(path.clone(), &secrets).unwrap();
+        assert_eq!(file.path(), path);
+
+        let content: std::collections::HashMap<String, String> =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(content, secrets);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_private_env_file() {
+        let path = std::env::temp_dir().join(format!("oocana-env-{}.env", std::process::id()));
+        let envs = std::collections::HashMap::from([("TOKEN".to_string(), "a=b".to_string())]);
+        let file = PrivateEnvFile::create(path.clone(), &envs).unwrap();
+
+        let content = load_env_from_file(&Some(file.path()));
+        assert_eq!(
+            content,
+            std::collections::HashMap::from([("TOKEN".to_string(), "a=b".to_string())])
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        drop(file);
+        assert!(!path.exists());
+
+        let envs = std::collections::HashMap::from([(
+            "MULTILINE".to_string(),
+            "line1\nline2".to_string(),
+        )]);
+        let err = PrivateEnvFile::create(path.clone(), &envs).unwrap_err();
+        assert!(err.to_string().contains("MULTILINE"), "{err}");
+        assert!(!path.exists());
+    }
 }
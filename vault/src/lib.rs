@@ -1,12 +1,18 @@
+use futures::{StreamExt, stream};
+use rand::Rng;
 use reqwest::Client;
 use serde::Deserialize;
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 use tracing::{debug, warn};
 use utils::error::{Error, Result};
 
 const DEFAULT_MAX_RETRIES: u32 = 2;
 const DEFAULT_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_FETCH_CONCURRENCY: usize = 8;
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
 const API_TOKEN_PREFIX: &str = "api-";
 const X_JWT_TOKEN_HEADER: &str = "x-jwt-token";
 const AUTHORIZATION_HEADER: &str = "Authorization";
@@ -20,15 +26,49 @@ struct VaultValueOnly {
 
 pub type VaultValue = HashMap<String, String>;
 
+/// Cached value for one vaultlet ID. The async mutex makes concurrent fetches
+/// of the same ID wait for a single in-flight request instead of all hitting
+/// the server.
+type CacheSlot = Arc<tokio::sync::Mutex<Option<(Instant, VaultValue)>>>;
+
+/// Callbacks for monitoring vault usage, e.g. to feed Prometheus or statsd. Every callback
+/// defaults to doing nothing.
+pub trait VaultMetrics: Send + Sync {
+    /// A request to the server starts, retries included.
+    fn on_fetch_start(&self, _id: &str) {}
+    /// The server returned the value, `latency` covers all attempts.
+    fn on_fetch_success(&self, _id: &str, _latency: Duration) {}
+    /// Attempt `attempt` failed and is retried.
+    fn on_retry(&self, _id: &str, _attempt: u32) {}
+    /// The fetch failed for good.
+    fn on_failure(&self, _id: &str, _error: &Error) {}
+    /// The value came from the cache, the server was not asked.
+    fn on_cache_hit(&self, _id: &str) {}
+}
+
+impl fmt::Debug for dyn VaultMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("VaultMetrics")
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VaultClient {
     pub domain: String,
     pub token: String,
     pub max_retries: u32,
     pub timeout_secs: u64,
+    pub cache_ttl: Option<Duration>,
+    pub fetch_concurrency: usize,
+    /// Status codes that are retried. `None` retries every 5xx status.
+    pub retryable_statuses: Option<Vec<u16>>,
+    /// Base delay of the exponential backoff between retries. `None` retries immediately.
+    pub retry_backoff: Option<Duration>,
+    pub metrics: Option<Arc<dyn VaultMetrics>>,
     client: Client,
     auth_header_name: &'static str,
     auth_header_value: String,
+    cache: Arc<Mutex<HashMap<String, CacheSlot>>>,
 }
 
 impl VaultClient {
@@ -43,9 +83,15 @@ impl VaultClient {
             token,
             max_retries: DEFAULT_MAX_RETRIES,
             timeout_secs: DEFAULT_TIMEOUT_SECS,
+            cache_ttl: None,
+            fetch_concurrency: DEFAULT_FETCH_CONCURRENCY,
+            retryable_statuses: None,
+            retry_backoff: None,
+            metrics: None,
             client,
             auth_header_name,
             auth_header_value,
+            cache: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
@@ -68,6 +114,46 @@ impl VaultClient {
         self
     }
 
+    /// Retry only these status codes (e.g. `vec![429, 503]`) instead of every 5xx.
+    pub fn with_retryable_statuses(mut self, statuses: Vec<u16>) -> Self {
+        self.retryable_statuses = Some(statuses);
+        self
+    }
+
+    /// Sleep between retries, doubling `base` on every attempt (capped at 30s) with random jitter.
+    pub fn with_retry_backoff(mut self, base: Duration) -> Self {
+        self.retry_backoff = Some(base);
+        self
+    }
+
+    /// Max number of requests `fetch_many` keeps in flight at once.
+    pub fn with_fetch_concurrency(mut self, fetch_concurrency: usize) -> Self {
+        self.fetch_concurrency = fetch_concurrency.max(1);
+        self
+    }
+
+    /// Report fetches, retries, failures and cache hits to `metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<dyn VaultMetrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Cache fetched values in memory for `ttl`. Clones of the client share the same cache.
+    pub fn with_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Drop the cached value for `id`, the next `fetch` will hit the server again.
+    pub fn invalidate(&self, id: &str) {
+        self.cache.lock().unwrap().remove(id);
+    }
+
+    /// Drop all cached values.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
     /// Fetch vaultlet value by ID
     ///
     /// # Arguments
@@ -98,7 +184,71 @@ impl VaultClient {
     /// }
     /// ```
     pub async fn fetch(&self, id: &str) -> Result<VaultValue> {
+        let Some(ttl) = self.cache_ttl else {
+            return self.fetch_remote(id).await;
+        };
+
+        let slot = self
+            .cache
+            .lock()
+            .unwrap()
+            .entry(id.to_string())
+            .or_default()
+            .clone();
+
+        let mut cached = slot.lock().await;
+        if let Some((_, value)) = cached
+            .as_ref()
+            .filter(|(fetched_at, _)| fetched_at.elapsed() < ttl)
+        {
+            debug!("Vault cache hit for ID '{}'", id);
+            if let Some(metrics) = &self.metrics {
+                metrics.on_cache_hit(id);
+            }
+            return Ok(value.clone());
+        }
+
+        let value = self.fetch_remote(id).await?;
+        *cached = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    /// Fetch several vaultlet values concurrently, at most `fetch_concurrency` requests at a time.
+    ///
+    /// Each ID is fetched with the same retry, timeout and cache behavior as `fetch`. The result
+    /// holds one entry per distinct ID, so one failing ID does not fail the whole batch.
+    pub async fn fetch_many(&self, ids: &[String]) -> HashMap<String, Result<VaultValue>> {
+        let mut unique_ids: Vec<&String> = Vec::with_capacity(ids.len());
+        for id in ids {
+            if !unique_ids.contains(&id) {
+                unique_ids.push(id);
+            }
+        }
+
+        stream::iter(unique_ids)
+            .map(|id| async move { (id.clone(), self.fetch(id).await) })
+            .buffer_unordered(self.fetch_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    async fn fetch_remote(&self, id: &str) -> Result<VaultValue> {
         let start_time = Instant::now();
+        if let Some(metrics) = &self.metrics {
+            metrics.on_fetch_start(id);
+        }
+
+        let result = self.request_with_retries(id, start_time).await;
+        if let Some(metrics) = &self.metrics {
+            match &result {
+                Ok(_) => metrics.on_fetch_success(id, start_time.elapsed()),
+                Err(e) => metrics.on_failure(id, e),
+            }
+        }
+        result
+    }
+
+    async fn request_with_retries(&self, id: &str, start_time: Instant) -> Result<VaultValue> {
         let url = format!("{}/v1/vaultlet/{}", self.domain, id);
 
         for attempt in 0..=self.max_retries {
@@ -117,13 +267,14 @@ impl VaultClient {
                     if status.is_success() {
                         log_request_duration(id, start_time, "completed");
                         return parse_vaultlet_response(resp).await;
-                    } else if should_retry(status, attempt, self.max_retries) {
+                    } else if attempt < self.max_retries && self.is_retryable(status) {
                         warn!(
                             "Request failed with status {}, retrying... (attempt {}/{})",
                             status,
                             attempt + 1,
                             self.max_retries + 1
                         );
+                        self.wait_before_retry(id, attempt).await;
                         continue;
                     } else {
                         log_request_duration(
@@ -142,6 +293,7 @@ impl VaultClient {
                             attempt + 1,
                             self.max_retries + 1
                         );
+                        self.wait_before_retry(id, attempt).await;
                         continue;
                     } else {
                         log_request_duration(id, start_time, &format!("failed with error: {e}"));
@@ -157,6 +309,22 @@ impl VaultClient {
         warn!("Vault request loop completed without returning - this should not happen");
         Err(Error::new("Request loop completed unexpectedly"))
     }
+
+    fn is_retryable(&self, status: reqwest::StatusCode) -> bool {
+        match &self.retryable_statuses {
+            Some(statuses) => statuses.contains(&status.as_u16()),
+            None => status.is_server_error(),
+        }
+    }
+
+    async fn wait_before_retry(&self, id: &str, attempt: u32) {
+        if let Some(metrics) = &self.metrics {
+            metrics.on_retry(id, attempt + 1);
+        }
+        if let Some(base) = self.retry_backoff {
+            tokio::time::sleep(backoff_delay(base, attempt)).await;
+        }
+    }
 }
 
 fn log_request_duration(id: &str, start_time: Instant, status: &str) {
@@ -185,8 +353,13 @@ async fn parse_vaultlet_response(resp: reqwest::Response) -> Result<VaultValue>
     Ok(vault_data.value)
 }
 
-fn should_retry(status: reqwest::StatusCode, attempt: u32, max_retries: u32) -> bool {
-    status.as_u16() >= 500 && attempt < max_retries
+/// `base * 2^attempt` capped at `MAX_RETRY_BACKOFF`, with the upper half randomized.
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let delay = base
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_RETRY_BACKOFF);
+    let half = delay / 2;
+    half + half.mul_f64(rand::thread_rng().r#gen::<f64>())
 }
 
 fn create_status_error(status: reqwest::StatusCode, max_retries: u32) -> Error {
@@ -426,4 +599,279 @@ mod tests {
         assert_eq!(api_no_dash_client.auth_header_name, X_JWT_TOKEN_HEADER);
         assert_eq!(api_no_dash_client.auth_header_value, "api");
     }
+
+    #[tokio::test]
+    async fn test_fetch_cached_within_ttl() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/cached"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"value": {"k": "v"}})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = VaultClient::new(mock_server.uri(), "api-test-key".to_string())
+            .with_cache_ttl(Duration::from_secs(60));
+
+        let first = client.fetch("cached").await.unwrap();
+        let second = client.clone().fetch("cached").await.unwrap();
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_cache_expired_or_invalidated() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/expired"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"value": {"k": "v"}})),
+            )
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let client = VaultClient::new(mock_server.uri(), "api-test-key".to_string())
+            .with_cache_ttl(Duration::from_millis(50));
+
+        client.fetch("expired").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.fetch("expired").await.unwrap();
+        client.invalidate("expired");
+        client.fetch("expired").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_fetch_cache_single_flight() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/shared"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"value": {"k": "shared"}}))
+                    .set_delay(Duration::from_millis(100)),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/other"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"value": {"k": "other"}})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = VaultClient::new(mock_server.uri(), "api-test-key".to_string())
+            .with_cache_ttl(Duration::from_secs(60));
+
+        let (a, b, c, other) = tokio::join!(
+            client.fetch("shared"),
+            client.fetch("shared"),
+            client.fetch("shared"),
+            client.fetch("other"),
+        );
+        assert_eq!(a.unwrap().get("k"), Some(&"shared".to_string()));
+        assert_eq!(b.unwrap().get("k"), Some(&"shared".to_string()));
+        assert_eq!(c.unwrap().get("k"), Some(&"shared".to_string()));
+        assert_eq!(other.unwrap().get("k"), Some(&"other".to_string()));
+
+        client.clear_cache();
+        assert!(client.cache.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_many_reports_per_id_results() {
+        let mock_server = MockServer::start().await;
+
+        for id in ["a", "b"] {
+            Mock::given(method("GET"))
+                .and(path(format!("/v1/vaultlet/{id}")))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_json(serde_json::json!({"value": {"id": id}})),
+                )
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = VaultClient::new(mock_server.uri(), "api-test-key".to_string())
+            .with_fetch_concurrency(2);
+        assert_eq!(client.fetch_concurrency, 2);
+
+        let ids = ["a", "b", "missing", "a"].map(String::from);
+        let results = client.fetch_many(&ids).await;
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results["a"].as_ref().unwrap().get("id"),
+            Some(&"a".to_string())
+        );
+        assert_eq!(
+            results["b"].as_ref().unwrap().get("id"),
+            Some(&"b".to_string())
+        );
+        assert!(
+            results["missing"]
+                .as_ref()
+                .unwrap_err()
+                .to_string()
+                .contains("Client error: 404")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_too_many_requests_not_retried_by_default() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/rate-limited"))
+            .respond_with(ResponseTemplate::new(429))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = VaultClient::new(mock_server.uri(), "api-test-key".to_string());
+
+        let result = client.fetch("rate-limited").await;
+        assert!(result.unwrap_err().to_string().contains("429"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_retries_configured_statuses_with_backoff() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/rate-limited"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/rate-limited"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"value": {"k": "v"}})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = VaultClient::new(mock_server.uri(), "api-test-key".to_string())
+            .with_retryable_statuses(vec![429, 503])
+            .with_retry_backoff(Duration::from_millis(20));
+
+        let start = Instant::now();
+        let result = client.fetch("rate-limited").await;
+        assert_eq!(result.unwrap().get("k"), Some(&"v".to_string()));
+        // two retries wait at least 10ms + 20ms
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_configured_statuses_replace_default() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/server-error"))
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let client = VaultClient::new(mock_server.uri(), "api-test-key".to_string())
+            .with_retryable_statuses(vec![429]);
+
+        assert!(client.fetch("server-error").await.is_err());
+    }
+
+    #[derive(Default)]
+    struct RecordingMetrics(Mutex<Vec<String>>);
+
+    impl VaultMetrics for RecordingMetrics {
+        fn on_fetch_start(&self, id: &str) {
+            self.0.lock().unwrap().push(format!("start {id}"));
+        }
+
+        fn on_fetch_success(&self, id: &str, _latency: Duration) {
+            self.0.lock().unwrap().push(format!("success {id}"));
+        }
+
+        fn on_retry(&self, id: &str, attempt: u32) {
+            self.0.lock().unwrap().push(format!("retry {id} {attempt}"));
+        }
+
+        fn on_failure(&self, id: &str, _error: &Error) {
+            self.0.lock().unwrap().push(format!("failure {id}"));
+        }
+
+        fn on_cache_hit(&self, id: &str) {
+            self.0.lock().unwrap().push(format!("cache hit {id}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_metrics_report_retried_then_successful_fetch() {
+        let mock_server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/observed"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path("/v1/vaultlet/observed"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!({"value": {"k": "v"}})),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let metrics = Arc::new(RecordingMetrics::default());
+        let client = VaultClient::new(mock_server.uri(), "api-test-key".to_string())
+            .with_cache_ttl(Duration::from_secs(60))
+            .with_metrics(metrics.clone());
+
+        client.fetch("observed").await.unwrap();
+        client.fetch("observed").await.unwrap();
+
+        assert_eq!(
+            *metrics.0.lock().unwrap(),
+            vec![
+                "start observed",
+                "retry observed 1",
+                "success observed",
+                "cache hit observed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_backoff_delay_grows_and_caps() {
+        let base = Duration::from_millis(100);
+        for attempt in 0..3 {
+            let delay = backoff_delay(base, attempt);
+            let full = base * 2u32.pow(attempt);
+            assert!(delay >= full / 2 && delay <= full);
+        }
+        assert!(backoff_delay(base, 20) <= MAX_RETRY_BACKOFF);
+    }
 }